@@ -1,3 +0,0 @@
-fn main() {
-    embuild::espidf::sysenv::output();
-}
@@ -0,0 +1,197 @@
+//! Arbitration between the sources that set the status.
+//!
+//! Each source keeps its latest claim. A claim holds for its source's
+//! `hold_mins` after it was made, and while any claims hold, the one from
+//! the source with the highest priority wins, so a calendar event cannot
+//! replace a Do Not Disturb set by hand a few minutes earlier. Once no claim
+//! holds, the most recent one wins, as if every change had been applied in
+//! order.
+//!
+//! A claim made with a lease, as automated sources such as a desktop agent
+//! do, ends with it unless renewed: once `until` passes it counts as
+//! withdrawn, so a source that stopped running does not keep its status.
+//!
+//! Times are seconds on a monotonic clock, such as the uptime.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{SourceConfig, SourcesConfig};
+use crate::status::Status;
+
+/// Who set a status.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The web interface, the API and the phone apps.
+    Manual,
+    /// Buttons, gestures, badges and remote controls on the device.
+    Button,
+    /// Timers such as the pomodoro.
+    Schedule,
+    Calendar,
+    /// Sensors such as the door contact.
+    Presence,
+    /// Smart home systems and paired devices.
+    #[default]
+    Integration,
+    /// The rules in the configuration, see [`crate::rules`].
+    Rules,
+}
+
+const SOURCE_COUNT: usize = 7;
+
+impl Source {
+    pub const ALL: [Source; SOURCE_COUNT] = [
+        Source::Manual,
+        Source::Button,
+        Source::Schedule,
+        Source::Calendar,
+        Source::Presence,
+        Source::Integration,
+        Source::Rules,
+    ];
+
+    /// Name used by the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::Manual => "manual",
+            Source::Button => "button",
+            Source::Schedule => "schedule",
+            Source::Calendar => "calendar",
+            Source::Presence => "presence",
+            Source::Integration => "integration",
+            Source::Rules => "rules",
+        }
+    }
+
+    /// Parses the source names used by the API.
+    pub fn parse(name: &str) -> Option<Source> {
+        match name {
+            "manual" => Some(Source::Manual),
+            "button" => Some(Source::Button),
+            "schedule" => Some(Source::Schedule),
+            "calendar" => Some(Source::Calendar),
+            "presence" => Some(Source::Presence),
+            "integration" => Some(Source::Integration),
+            "rules" => Some(Source::Rules),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl SourcesConfig {
+    /// Priority and hold time of a source.
+    pub fn get(&self, source: Source) -> &SourceConfig {
+        match source {
+            Source::Manual => &self.manual,
+            Source::Button => &self.button,
+            Source::Schedule => &self.schedule,
+            Source::Calendar => &self.calendar,
+            Source::Presence => &self.presence,
+            Source::Integration => &self.integration,
+            Source::Rules => &self.rules,
+        }
+    }
+}
+
+/// A status a source asked for. `T` is the "back at" time, if any, after
+/// which the claim turns into Free.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Claim<T> {
+    pub status: Status,
+    /// When the claim was made.
+    pub since: u64,
+    pub back_at: Option<T>,
+    /// The end of the claim's lease; None for a claim that lasts until the
+    /// source replaces or withdraws it.
+    pub until: Option<u64>,
+}
+
+impl<T> Claim<T> {
+    /// Whether the claim's lease has run out at `now`.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.until.is_some_and(|until| now >= until)
+    }
+}
+
+/// The latest claim of each source.
+#[derive(Clone, Debug)]
+pub struct Arbiter<T> {
+    claims: [Option<Claim<T>>; SOURCE_COUNT],
+}
+
+impl<T> Default for Arbiter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Arbiter<T> {
+    pub const fn new() -> Self {
+        Self {
+            claims: [None, None, None, None, None, None, None],
+        }
+    }
+
+    /// Replaces the source's claim.
+    pub fn claim(&mut self, source: Source, claim: Claim<T>) {
+        self.claims[source.index()] = Some(claim);
+    }
+
+    /// The source's latest claim.
+    pub fn get(&self, source: Source) -> Option<&Claim<T>> {
+        self.claims[source.index()].as_ref()
+    }
+
+    /// Forgets the source's claim.
+    pub fn withdraw(&mut self, source: Source) {
+        self.claims[source.index()] = None;
+    }
+
+    /// Forgets the claims whose lease has run out at `now`; returns their
+    /// sources.
+    pub fn expire(&mut self, now: u64) -> Vec<Source> {
+        let mut expired = Vec::new();
+        for source in Source::ALL {
+            let slot = &mut self.claims[source.index()];
+            if slot.as_ref().is_some_and(|claim| claim.is_expired(now)) {
+                *slot = None;
+                expired.push(source);
+            }
+        }
+        expired
+    }
+
+    /// Forgets every claim.
+    pub fn clear(&mut self) {
+        self.claims = Self::new().claims;
+    }
+
+    /// The claim that decides the status at `now`, and its source; None
+    /// before any claim and once every claim is withdrawn or expired.
+    pub fn winner(&self, config: &SourcesConfig, now: u64) -> Option<(Source, &Claim<T>)> {
+        let claims = Source::ALL
+            .into_iter()
+            .filter_map(|source| Some((source, self.claims[source.index()].as_ref()?)))
+            .filter(move |(_, claim)| !claim.is_expired(now));
+
+        let holds = |source: Source, claim: &Claim<T>| {
+            let hold = u64::from(config.get(source).hold_mins) * 60;
+            now < claim.since.saturating_add(hold)
+        };
+        let holding = claims
+            .clone()
+            .filter(|(source, claim)| holds(*source, claim))
+            .max_by_key(|(source, claim)| (config.get(*source).priority, claim.since));
+
+        holding.or_else(|| {
+            claims.max_by_key(|(source, claim)| (claim.since, config.get(*source).priority))
+        })
+    }
+}
@@ -0,0 +1,88 @@
+//! Log of the recent changes of the selected status, with who made them.
+//!
+//! Each entry names the source of the winning claim and, for changes made
+//! over HTTP, the client's address and the credential it used, so a change
+//! nobody remembers making can be traced. The log keeps the latest
+//! [`CAPACITY`] entries, dropping the oldest.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+
+use serde::{Deserialize, Serialize};
+
+use crate::arbiter::Source;
+use crate::status::Status;
+
+/// Entries kept at most.
+pub const CAPACITY: usize = 64;
+
+/// Who made a claim.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Origin {
+    /// Address of the HTTP client.
+    pub peer: Option<String>,
+    /// Name of the credential it used: the admin user, `client
+    /// certificate`, `set_token` or a webhook's name.
+    pub token: Option<String>,
+}
+
+/// Why the selected status changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cause {
+    /// A claim was made or withdrawn, or a hold ended and another claim won.
+    Claim,
+    /// The "back at" time passed, or the transition after it ended.
+    BackAt,
+    /// The lease of the winning claim ran out without another claim left.
+    LeaseExpired,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Unix time of the change; None before the clock was set.
+    pub time: Option<u64>,
+    /// Seconds since boot at the change.
+    pub uptime_secs: u64,
+    pub from: Status,
+    pub to: Status,
+    pub cause: Cause,
+    /// Source of the claim behind the new status; None without one.
+    pub source: Option<Source>,
+    /// Who made that claim, as far as known.
+    pub origin: Origin,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditLog {
+    entries: VecDeque<Entry>,
+}
+
+impl AuditLog {
+    pub const fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Adds an entry, dropping the oldest beyond [`CAPACITY`].
+    pub fn push(&mut self, entry: Entry) {
+        if self.entries.len() >= CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// The entries, the most recent first.
+    pub fn latest(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,188 @@
+//! Dev boards with an integrated display or display connector, all of them
+//! ESP32 boards.
+//!
+//! A profile says which display controller the board has and on which pins,
+//! and replaces the `pins` section with the board's own wiring: the I2C bus,
+//! the onboard button and LED, and free header pins for the buzzer, the relay
+//! and the door sensor.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::PinsConfig;
+use crate::pins::{self, Chip, PinError};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Board {
+    /// Separate modules, wired as set in `pins`.
+    #[default]
+    #[serde(rename = "custom")]
+    Custom,
+    /// Heltec WiFi Kit 32 (V2), 128x64 OLED.
+    #[serde(rename = "heltec-wifi-kit-32")]
+    HeltecWifiKit32,
+    /// LilyGO TTGO LoRa32 (V2.1), 128x64 OLED.
+    #[serde(rename = "ttgo-lora32")]
+    TtgoLora32,
+    /// LilyGO TTGO T-Display, 240x135 TFT.
+    #[serde(rename = "ttgo-t-display")]
+    TtgoTDisplay,
+    /// Waveshare e-Paper ESP32 Driver Board with a 2.13" 250x122 panel.
+    #[serde(rename = "waveshare-epaper-esp32")]
+    WaveshareEpaperEsp32,
+}
+
+/// Display controller of a board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Screen {
+    /// SSD1306 on the I2C bus, 32 or 64 rows high, with its reset line on a
+    /// pin on some boards.
+    Ssd1306 { height: u8, reset: Option<u8> },
+    /// ST7789 on SPI.
+    St7789 {
+        sclk: u8,
+        mosi: u8,
+        cs: u8,
+        dc: u8,
+        reset: u8,
+        backlight: u8,
+    },
+    /// SSD1680 e-paper on SPI, with a busy line from the panel.
+    Ssd1680 {
+        sclk: u8,
+        mosi: u8,
+        cs: u8,
+        dc: u8,
+        reset: u8,
+        busy: u8,
+    },
+}
+
+impl Screen {
+    /// Pins the display needs besides the I2C bus.
+    pub fn pins(&self) -> Vec<u8> {
+        match *self {
+            Self::Ssd1306 { reset, .. } => reset.into_iter().collect(),
+            Self::St7789 {
+                sclk,
+                mosi,
+                cs,
+                dc,
+                reset,
+                backlight,
+            } => vec![sclk, mosi, cs, dc, reset, backlight],
+            Self::Ssd1680 {
+                sclk,
+                mosi,
+                cs,
+                dc,
+                reset,
+                busy,
+            } => vec![sclk, mosi, cs, dc, reset, busy],
+        }
+    }
+}
+
+/// Pins and display of a board.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    pub pins: PinsConfig,
+    pub screen: Screen,
+}
+
+impl Profile {
+    /// Checks the display's pins and `pins` together, see
+    /// [`PinsConfig::validate`].
+    pub fn validate(&self, chip: Chip, reserved: &[u8]) -> Result<(), PinError> {
+        let mut taken = reserved.to_vec();
+        for pin in self.screen.pins() {
+            pins::check_io(chip, pin)?;
+            if taken.contains(&pin) {
+                return Err(PinError::Reserved(pin));
+            }
+            taken.push(pin);
+        }
+        self.pins.validate(chip, &taken)
+    }
+}
+
+impl Board {
+    /// The board's profile; None for `Custom`.
+    pub fn profile(self) -> Option<Profile> {
+        match self {
+            Self::Custom => None,
+            Self::HeltecWifiKit32 => Some(Profile {
+                pins: PinsConfig {
+                    sda: 4,
+                    scl: 15,
+                    button: 0,
+                    led: 25,
+                    buzzer: 13,
+                    relay: 32,
+                    door: 27,
+                },
+                screen: Screen::Ssd1306 {
+                    height: 64,
+                    reset: Some(16),
+                },
+            }),
+            Self::TtgoLora32 => Some(Profile {
+                pins: PinsConfig {
+                    sda: 21,
+                    scl: 22,
+                    button: 0,
+                    led: 25,
+                    buzzer: 13,
+                    relay: 32,
+                    door: 14,
+                },
+                screen: Screen::Ssd1306 {
+                    height: 64,
+                    reset: None,
+                },
+            }),
+            // No onboard LED; GPIO2 is on the header
+            Self::TtgoTDisplay => Some(Profile {
+                pins: PinsConfig {
+                    sda: 21,
+                    scl: 22,
+                    button: 0,
+                    led: 2,
+                    buzzer: 25,
+                    relay: 32,
+                    door: 27,
+                },
+                screen: Screen::St7789 {
+                    sclk: 18,
+                    mosi: 19,
+                    cs: 5,
+                    dc: 16,
+                    reset: 23,
+                    backlight: 4,
+                },
+            }),
+            // The panel connector takes the HSPI pins and 25-27
+            Self::WaveshareEpaperEsp32 => Some(Profile {
+                pins: PinsConfig {
+                    sda: 21,
+                    scl: 22,
+                    button: 0,
+                    led: 2,
+                    buzzer: 4,
+                    relay: 32,
+                    door: 33,
+                },
+                screen: Screen::Ssd1680 {
+                    sclk: 13,
+                    mosi: 14,
+                    cs: 15,
+                    dc: 27,
+                    reset: 26,
+                    busy: 25,
+                },
+            }),
+        }
+    }
+}
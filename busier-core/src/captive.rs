@@ -0,0 +1,94 @@
+//! Captive portal of the setup access point.
+//!
+//! While the device has no network it opens an access point of its own.
+//! Every name a client looks up resolves to the device, so the connectivity
+//! checks of phones and laptops (`/generate_204`, `/hotspot-detect.html`,
+//! `/connecttest.txt` and the like) reach its web server, which redirects
+//! them to the setup page and makes the OS pop it up. [`dns_answer`] is the
+//! DNS side; [`wifi_qr_payload`] is what the display shows to join.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Seconds clients may cache an answer; short, as the names only point at
+/// the device until it joins a network.
+pub const DNS_TTL: u32 = 60;
+
+const HEADER_LEN: usize = 12;
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+// Pointer to the name of the question, which follows the header
+const NAME_POINTER: u16 = 0xC000 | HEADER_LEN as u16;
+
+/// The response to a DNS query, answering the first question with `ip`
+/// if it asks for an IPv4 address and with no records otherwise. None for
+/// anything that is not a well-formed standard query.
+pub fn dns_answer(query: &[u8], ip: [u8; 4]) -> Option<Vec<u8>> {
+    let header = query.get(..HEADER_LEN)?;
+    let flags = u16::from_be_bytes([header[2], header[3]]);
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    // Only queries (QR clear) with the standard opcode and a question
+    if flags & 0xF800 != 0 || questions == 0 {
+        return None;
+    }
+
+    // The name's labels up to the root, then type and class
+    let mut end = HEADER_LEN;
+    loop {
+        let len = *query.get(end)? as usize;
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        end += 1 + len;
+        if len == 0 {
+            break;
+        }
+    }
+    let question = query.get(HEADER_LEN..end + 4)?;
+    let qtype = u16::from_be_bytes([query[end], query[end + 1]]);
+    let qclass = u16::from_be_bytes([query[end + 2], query[end + 3]]);
+    let answers = u16::from((qtype == TYPE_A || qtype == TYPE_ANY) && qclass == CLASS_IN);
+
+    let mut response = Vec::with_capacity(HEADER_LEN + question.len() + 16);
+    response.extend_from_slice(&header[..2]);
+    // Authoritative response, keeping "recursion desired"
+    response.extend_from_slice(&(0x8400 | (flags & 0x0100)).to_be_bytes());
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&answers.to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0]);
+    response.extend_from_slice(question);
+    if answers > 0 {
+        response.extend_from_slice(&NAME_POINTER.to_be_bytes());
+        response.extend_from_slice(&TYPE_A.to_be_bytes());
+        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&DNS_TTL.to_be_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&ip);
+    }
+    Some(response)
+}
+
+/// The `WIFI:` text of a QR code that phone cameras offer to join; an empty
+/// password means an open network.
+pub fn wifi_qr_payload(ssid: &str, password: &str) -> String {
+    let mut payload = String::from("WIFI:T:");
+    payload.push_str(if password.is_empty() { "nopass" } else { "WPA" });
+    payload.push_str(";S:");
+    push_escaped(&mut payload, ssid);
+    if !password.is_empty() {
+        payload.push_str(";P:");
+        push_escaped(&mut payload, password);
+    }
+    payload.push_str(";;");
+    payload
+}
+
+fn push_escaped(payload: &mut String, text: &str) {
+    for c in text.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            payload.push('\\');
+        }
+        payload.push(c);
+    }
+}
@@ -0,0 +1,1420 @@
+//! Runtime configuration and its JSON form.
+//!
+//! Every section has defaults, so a partial or older document still
+//! deserializes. Storing it is up to the firmware.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::arbiter::Source;
+use crate::board::Board;
+use crate::pins::{self, Chip, PinError};
+use crate::reminders::Reminder;
+use crate::rules::Rule;
+use crate::status::Status;
+use crate::transitions::Transition;
+
+// Shown instead of secrets when the configuration is read back
+pub const REDACTED: &str = "********";
+const DEFAULT_DEVICE_NAME: &str = "busier";
+
+/// Language of the web pages, the displays and notifications.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    De,
+    El,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Name shown to discovery clients; empty means "busier".
+    pub device_name: String,
+    /// Zone name, e.g. `Europe/Berlin`, or POSIX TZ string, e.g.
+    /// `CET-1CEST,M3.5.0,M10.5.0/3`; empty means the `TZ` given at build time.
+    pub timezone: String,
+    pub ntp: NtpConfig,
+    /// Language of the web pages, the displays and notifications.
+    pub language: Language,
+    /// People sharing the device, each with their own status.
+    pub users: Vec<String>,
+    pub admin: AdminConfig,
+    pub http: HttpConfig,
+    pub https: HttpsConfig,
+    /// Outbound HTTPS servers trusted by certificate rather than by CA.
+    pub cert_pins: Vec<CertPin>,
+    pub hooks: Vec<HookConfig>,
+    /// Priority and override duration of each status source.
+    pub sources: SourcesConfig,
+    pub calendar: CalendarConfig,
+    /// Steps shown when a timed status ends, e.g. "Wrapping up" before Free.
+    pub transitions: Vec<Transition>,
+    /// Checked in order; the first that applies wins.
+    pub rules: Vec<Rule>,
+    pub reminders: Vec<Reminder>,
+    pub working_hours: WorkingHoursConfig,
+    pub quiet_hours: QuietHoursConfig,
+    pub peer_sync: PeerSyncConfig,
+    pub remote_buttons: Vec<RemoteButtonConfig>,
+    pub coap: CoapConfig,
+    pub snmp: SnmpConfig,
+    pub modbus: ModbusConfig,
+    pub hue: HueConfig,
+    pub wled: WledConfig,
+    pub esphome: EsphomeConfig,
+    pub alexa: AlexaConfig,
+    pub rfid: RfidConfig,
+    pub ir: IrConfig,
+    pub cube: CubeConfig,
+    pub door: DoorConfig,
+    pub doorbell: DoorbellConfig,
+    pub battery: BatteryConfig,
+    pub sleep: SleepConfig,
+    pub door_sign: DoorSignConfig,
+    pub wifi_power: WifiPowerConfig,
+    pub cpu: CpuConfig,
+    pub memory: MemoryConfig,
+    pub temperature: TemperatureConfig,
+    pub servo: ServoConfig,
+    pub relay: RelayConfig,
+    /// How each status looks and sounds on the outputs.
+    pub styles: StylesConfig,
+    pub artnet: ArtnetConfig,
+    /// Attached panels; empty means a single detail panel at 0x3C.
+    pub displays: Vec<DisplayConfig>,
+    pub led_matrix: LedMatrixConfig,
+    pub lcd: LcdConfig,
+    pub hub75: Hub75Config,
+    pub chime: ChimeConfig,
+    pub buzzer: BuzzerConfig,
+    pub haptic: HapticConfig,
+    pub countdown: CountdownConfig,
+    /// Dev board whose profile replaces `pins`.
+    pub board: Board,
+    /// None for the chip's default pins.
+    pub pins: Option<PinsConfig>,
+}
+
+/// GPIO numbers of the I2C bus and the simple inputs and outputs, applied at
+/// startup. The defaults match the ESP32 wiring in the README.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PinsConfig {
+    pub sda: u8,
+    pub scl: u8,
+    pub button: u8,
+    pub led: u8,
+    pub buzzer: u8,
+    pub relay: u8,
+    pub door: u8,
+}
+
+impl Default for PinsConfig {
+    fn default() -> Self {
+        Self {
+            sda: 21,
+            scl: 22,
+            button: 0,
+            led: 2,
+            buzzer: 25,
+            relay: 32,
+            door: 27,
+        }
+    }
+}
+
+impl PinsConfig {
+    /// Checks every pin against the chip and `reserved`, the pins of drivers
+    /// that are not configurable, and that none is given twice.
+    pub fn validate(&self, chip: Chip, reserved: &[u8]) -> Result<(), PinError> {
+        let all = [
+            self.sda,
+            self.scl,
+            self.button,
+            self.led,
+            self.buzzer,
+            self.relay,
+            self.door,
+        ];
+        for (i, &pin) in all.iter().enumerate() {
+            pins::check_io(chip, pin)?;
+            if reserved.contains(&pin) {
+                return Err(PinError::Reserved(pin));
+            }
+            if all[..i].contains(&pin) {
+                return Err(PinError::Duplicate(pin));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Login for the admin page and the routes that change the device.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    pub username: String,
+    /// Empty leaves the admin page open to anyone on the network.
+    pub password: String,
+    /// Token for `GET /set`, for clients that can only send GET requests;
+    /// empty turns the route off.
+    pub set_token: String,
+    /// API tokens, each with its own rate limit and usage counters.
+    pub tokens: Vec<ApiToken>,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            username: "admin".to_string(),
+            password: String::new(),
+            set_token: String::new(),
+            tokens: Vec::new(),
+        }
+    }
+}
+
+/// A named API token, sent as `Authorization: Bearer <token>` or in a
+/// `token` query parameter. Requests with it count towards its usage, see
+/// [`crate::ratelimit`].
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiToken {
+    pub name: String,
+    pub token: String,
+    /// Requests a minute; 0 for no limit.
+    pub rate_per_min: u32,
+    /// Opens the admin routes, as the admin login does.
+    pub admin: bool,
+}
+
+/// Limits of the web server, for HTTP and HTTPS alike. Takes effect on
+/// restart.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Connections served at once, at most [`HttpConfig::MAX_OPEN_SOCKETS`].
+    pub max_open_sockets: u16,
+    /// An idle session is dropped after this long.
+    pub session_timeout_secs: u32,
+    /// Stack of the server task that runs the handlers, in bytes; parsing
+    /// JSON takes plenty.
+    pub stack_size: u32,
+    /// With every socket taken, a new client closes the least recently
+    /// used connection instead of being turned away.
+    pub lru_purge: bool,
+}
+
+impl HttpConfig {
+    /// What the firmware's network stack leaves for one server.
+    pub const MAX_OPEN_SOCKETS: u16 = 13;
+    pub const MIN_STACK_SIZE: u32 = 6144;
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            max_open_sockets: 10,
+            session_timeout_secs: 300,
+            stack_size: 10240,
+            lru_purge: true,
+        }
+    }
+}
+
+/// HTTPS listener next to plain HTTP. Takes effect on restart.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpsConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Only accept clients with a certificate signed by the stored CA.
+    pub client_certs: bool,
+}
+
+impl Default for HttpsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 443,
+            client_certs: false,
+        }
+    }
+}
+
+/// The certificate an outbound HTTPS server must present. A pinned server
+/// is trusted by this alone, so self-signed certificates work too.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CertPin {
+    /// Host name as in the URLs, without a port.
+    pub host: String,
+    /// SHA-256 fingerprint of the server's certificate in hex, with or
+    /// without colons, as printed by `openssl x509 -fingerprint -sha256`.
+    pub sha256: String,
+}
+
+impl CertPin {
+    /// The fingerprint as bytes; None unless it is 32 bytes of hex.
+    pub fn fingerprint(&self) -> Option<[u8; 32]> {
+        let digits: Vec<u8> = self.sha256.bytes().filter(|b| *b != b':').collect();
+        if digits.len() != 64 {
+            return None;
+        }
+        let mut fingerprint = [0; 32];
+        for (byte, pair) in fingerprint.iter_mut().zip(digits.chunks(2)) {
+            let pair = core::str::from_utf8(pair).ok()?;
+            *byte = u8::from_str_radix(pair, 16).ok()?;
+        }
+        Some(fingerprint)
+    }
+
+    /// The pin for the host of an `https` URL.
+    pub fn find<'a>(pins: &'a [CertPin], url: &str) -> Option<&'a CertPin> {
+        let rest = url.strip_prefix("https://")?;
+        let authority = rest.split(['/', '?', '#']).next()?;
+        let host = authority.rsplit('@').next()?;
+        // An IPv6 literal keeps its brackets, and only a port follows them
+        let host = match host.strip_prefix('[') {
+            Some(literal) => literal.split(']').next()?,
+            None => host.split(':').next()?,
+        };
+        pins.iter().find(|pin| pin.host.eq_ignore_ascii_case(host))
+    }
+}
+
+/// An SSD1306 panel on the I2C bus.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// I2C address, usually 0x3C (60) or 0x3D (61).
+    pub address: u8,
+    pub layout: DisplayLayout,
+    /// Turn the picture upside down.
+    pub rotate: bool,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            address: 0x3C,
+            layout: DisplayLayout::Detail,
+            rotate: false,
+        }
+    }
+}
+
+/// Chained MAX7219 8x8 LED modules on the HSPI pins.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LedMatrixConfig {
+    pub enabled: bool,
+    /// Number of modules in the chain, 4 for a 32x8 board.
+    pub modules: u8,
+    /// Brightness, 0 to 15.
+    pub intensity: u8,
+    /// Time per pixel when scrolling text that does not fit.
+    pub scroll_ms: u16,
+    pub layout: DisplayLayout,
+}
+
+impl Default for LedMatrixConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            modules: 4,
+            intensity: 2,
+            scroll_ms: 40,
+            layout: DisplayLayout::Status,
+        }
+    }
+}
+
+/// HD44780 character LCD behind a PCF8574 I2C backpack.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LcdConfig {
+    pub enabled: bool,
+    /// I2C address, usually 0x27 (39) or 0x3F (63).
+    pub address: u8,
+    /// 16 or 20.
+    pub columns: u8,
+    /// 2 or 4.
+    pub rows: u8,
+    pub layout: DisplayLayout,
+}
+
+impl Default for LcdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: 0x27,
+            columns: 16,
+            rows: 2,
+            layout: DisplayLayout::Detail,
+        }
+    }
+}
+
+/// Vibration motor behind a DRV2605L haptic driver.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HapticConfig {
+    pub enabled: bool,
+    /// Motor strength, 0 to 100 percent.
+    pub strength: u8,
+    /// Alternating on and off times in milliseconds, starting with on.
+    pub knock: Vec<u16>,
+    pub status_changed: Vec<u16>,
+    /// Skip the buzzer and chimes while the motor is in use.
+    pub replace_sound: bool,
+}
+
+impl Default for HapticConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 80,
+            knock: vec![200, 100, 200, 100, 200],
+            status_changed: vec![150],
+            replace_sound: true,
+        }
+    }
+}
+
+/// Buzzer ringtones in RTTTL, e.g. "Knock:d=8,o=6,b=180:c,p,c,p,c"; empty
+/// for the default beeps.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuzzerConfig {
+    pub knock: String,
+    pub status_changed: String,
+}
+
+/// Chimes through an I2S amplifier instead of buzzer beeps.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChimeConfig {
+    pub enabled: bool,
+    /// 0 to 100 percent.
+    pub volume: u8,
+    /// Sound names: "ding", "dingdong", "chirp" or "knock"; empty for none.
+    pub knock: String,
+    pub status_changed: String,
+}
+
+impl Default for ChimeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            volume: 50,
+            knock: "dingdong".to_string(),
+            status_changed: "ding".to_string(),
+        }
+    }
+}
+
+/// HUB75 RGB panel in builds with the `hub75` feature.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hub75Config {
+    /// Share of the time each row is lit, 0 to 100 percent.
+    pub brightness: u8,
+    pub layout: DisplayLayout,
+}
+
+impl Default for Hub75Config {
+    fn default() -> Self {
+        Self {
+            brightness: 50,
+            layout: DisplayLayout::Status,
+        }
+    }
+}
+
+/// TM1637 4-digit display showing the time left on the running timer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CountdownConfig {
+    pub enabled: bool,
+    /// Brightness, 0 to 7.
+    pub brightness: u8,
+}
+
+impl Default for CountdownConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brightness: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayLayout {
+    /// Network, status and timers, for the desk.
+    #[default]
+    Detail,
+    /// The status in large letters, for the door.
+    Status,
+}
+
+/// An inbound webhook served at `/api/hooks/<name>`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookConfig {
+    pub name: String,
+    /// Shared secret for the HMAC-SHA256 signature.
+    pub secret: String,
+    /// Header carrying the hex signature, optionally prefixed with `sha256=`.
+    pub signature_header: String,
+    /// Source the hook sets the status as, such as `calendar`.
+    pub source: Source,
+    /// JSON pointer (RFC 6901) to the field that selects the status.
+    pub pointer: String,
+    /// Field value to status name ("free" / "dnd" / "away").
+    pub mapping: BTreeMap<String, String>,
+}
+
+/// See [`crate::arbiter`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourcesConfig {
+    pub manual: SourceConfig,
+    pub button: SourceConfig,
+    pub schedule: SourceConfig,
+    pub calendar: SourceConfig,
+    pub presence: SourceConfig,
+    pub integration: SourceConfig,
+    pub rules: SourceConfig,
+}
+
+impl Default for SourcesConfig {
+    fn default() -> Self {
+        let source = |priority, hold_mins| SourceConfig {
+            priority,
+            hold_mins,
+        };
+        // Whatever a person sets by hand holds for two hours
+        Self {
+            manual: source(5, 120),
+            button: source(5, 120),
+            schedule: source(2, 0),
+            calendar: source(3, 0),
+            presence: source(4, 0),
+            integration: source(3, 0),
+            rules: source(4, 0),
+        }
+    }
+}
+
+/// Time servers, queried in turn. Takes effect on restart.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NtpConfig {
+    /// Up to [`MAX_NTP_SERVERS`] host names or addresses.
+    pub servers: Vec<String>,
+}
+
+/// Servers SNTP is built to hold.
+pub const MAX_NTP_SERVERS: usize = 3;
+
+impl Default for NtpConfig {
+    fn default() -> Self {
+        Self {
+            servers: vec![
+                "0.pool.ntp.org".to_string(),
+                "1.pool.ntp.org".to_string(),
+                "2.pool.ntp.org".to_string(),
+            ],
+        }
+    }
+}
+
+/// Events claimed by the `calendar` source, with their end as `back_at`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalendarConfig {
+    /// Minutes after an event's end before the status returns to Free.
+    pub grace_mins: u32,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self { grace_mins: 2 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceConfig {
+    /// Higher wins while claims hold.
+    pub priority: u8,
+    /// Minutes a claim holds against sources of lower priority; 0 for none.
+    pub hold_mins: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkingHoursConfig {
+    pub enabled: bool,
+    /// Hours per weekday, Monday first; `null` for days off.
+    pub days: [Option<TimeRange>; 7],
+}
+
+impl Default for WorkingHoursConfig {
+    fn default() -> Self {
+        let weekday = Some(TimeRange {
+            start: "09:00".to_string(),
+            end: "17:00".to_string(),
+        });
+        Self {
+            enabled: false,
+            days: [
+                weekday.clone(),
+                weekday.clone(),
+                weekday.clone(),
+                weekday.clone(),
+                weekday,
+                None,
+                None,
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietHoursConfig {
+    pub enabled: bool,
+    pub hours: TimeRange,
+}
+
+impl Default for QuietHoursConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hours: TimeRange {
+                start: "22:00".to_string(),
+                end: "07:00".to_string(),
+            },
+        }
+    }
+}
+
+/// Devices paired for ESP-NOW status sync.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerSyncConfig {
+    /// MAC addresses as "aa:bb:cc:dd:ee:ff".
+    pub peers: Vec<String>,
+}
+
+/// A paired ESP-NOW remote button.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteButtonConfig {
+    /// MAC address as "aa:bb:cc:dd:ee:ff".
+    pub mac: String,
+    /// Key shared with the button for signing packets.
+    pub key: String,
+    /// Last accepted packet counter, as firmware before the counters moved to
+    /// their own NVS namespace stored it; read once to carry it over.
+    #[serde(skip_serializing)]
+    pub counter: u32,
+}
+
+/// CoAP server. CoAP has no authentication, so the status can only be
+/// changed over it while `writable` is set.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoapConfig {
+    pub writable: bool,
+}
+
+/// Read-only SNMP v2c agent.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnmpConfig {
+    pub enabled: bool,
+    pub community: String,
+}
+
+impl Default for SnmpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            community: "public".to_string(),
+        }
+    }
+}
+
+/// Modbus TCP server.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModbusConfig {
+    pub enabled: bool,
+}
+
+/// Philips Hue light mirroring the status.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HueConfig {
+    pub enabled: bool,
+    /// Bridge host name or IP address.
+    pub bridge: String,
+    /// Application key ("username") issued by the bridge.
+    pub key: String,
+    /// Whether `id` names a group (room or zone) instead of a single light.
+    pub group: bool,
+    pub id: String,
+}
+
+/// WLED controller mirroring the status.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WledConfig {
+    pub enabled: bool,
+    /// Controller host name or IP address.
+    pub host: String,
+    /// Status name to WLED preset id; statuses without a preset get a
+    /// solid color.
+    pub presets: BTreeMap<String, u8>,
+}
+
+/// Art-Net output for DMX fixtures.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtnetConfig {
+    pub enabled: bool,
+    /// Node host name or IP address; empty broadcasts.
+    pub host: String,
+    /// 15-bit port address: net, sub-net and universe.
+    pub universe: u16,
+    /// First DMX channel, starting at 1.
+    pub channel: u16,
+    /// Status name to the values of consecutive channels.
+    pub values: BTreeMap<String, Vec<u8>>,
+}
+
+impl Default for ArtnetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            universe: 0,
+            channel: 1,
+            values: BTreeMap::from([
+                ("free".to_string(), vec![0, 255, 0]),
+                ("dnd".to_string(), vec![255, 0, 0]),
+                ("away".to_string(), vec![0, 0, 0]),
+            ]),
+        }
+    }
+}
+
+/// ESPHome native API for Home Assistant.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EsphomeConfig {
+    pub enabled: bool,
+    /// API password; empty accepts any client.
+    pub password: String,
+    /// Base64 of the 32-byte key for the encrypted transport, as Home
+    /// Assistant asks for it; empty for plaintext.
+    pub encryption_key: String,
+}
+
+/// Hue bridge emulation for Alexa.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlexaConfig {
+    pub enabled: bool,
+    /// Name of the emulated light; empty means "Do Not Disturb".
+    pub name: String,
+}
+
+/// Battery monitoring for portable builds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryConfig {
+    pub enabled: bool,
+    pub source: BatterySource,
+    /// Battery voltage divided by the voltage at the ADC pin.
+    pub divider: f32,
+    /// Charge in percent at which WiFi is turned off.
+    pub low_percent: u8,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: BatterySource::Adc,
+            divider: 2.0,
+            low_percent: 10,
+        }
+    }
+}
+
+/// Symbol drawn next to the status word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Icon {
+    #[default]
+    None,
+    Check,
+    Cross,
+    Dot,
+    Clock,
+}
+
+/// What the outputs show for a status.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusStyle {
+    /// "#RRGGBB" for the colour displays and the Hue and WLED lights.
+    pub color: String,
+    /// Whether the Hue and WLED lights are on.
+    pub light: bool,
+    /// Whether the status LED is lit.
+    pub led: bool,
+    /// Whether the relay is on; None to follow `relay.statuses`.
+    pub relay: Option<bool>,
+    /// RTTTL ringtone on changing to the status; empty for
+    /// `buzzer.status_changed`.
+    pub ringtone: String,
+    pub icon: Icon,
+}
+
+impl StatusStyle {
+    fn new(color: &str, light: bool, led: bool) -> Self {
+        Self {
+            color: color.to_string(),
+            light,
+            led,
+            relay: None,
+            ringtone: String::new(),
+            icon: Icon::None,
+        }
+    }
+
+    /// The color as red, green and blue; None unless it is "#RRGGBB".
+    pub fn rgb(&self) -> Option<[u8; 3]> {
+        let digits = self.color.strip_prefix('#')?;
+        if digits.len() != 6 || !digits.is_ascii() {
+            return None;
+        }
+        let mut rgb = [0; 3];
+        for (channel, i) in rgb.iter_mut().zip((0..6).step_by(2)) {
+            *channel = u8::from_str_radix(&digits[i..i + 2], 16).ok()?;
+        }
+        Some(rgb)
+    }
+}
+
+impl Default for StatusStyle {
+    fn default() -> Self {
+        Self::new("#ffffff", true, false)
+    }
+}
+
+/// The style of each status; the defaults are green for Free, red with the
+/// LED lit for Do Not Disturb, and yellow with the lights off for Away.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StylesConfig {
+    pub free: StatusStyle,
+    pub dnd: StatusStyle,
+    pub away: StatusStyle,
+}
+
+impl StylesConfig {
+    pub fn get(&self, status: Status) -> &StatusStyle {
+        match status {
+            Status::Free => &self.free,
+            Status::Dnd => &self.dnd,
+            Status::Away => &self.away,
+        }
+    }
+}
+
+impl Default for StylesConfig {
+    fn default() -> Self {
+        Self {
+            free: StatusStyle::new("#00ff00", true, false),
+            dnd: StatusStyle::new("#ff0000", true, true),
+            away: StatusStyle::new("#ffff00", false, false),
+        }
+    }
+}
+
+/// Relay or MOSFET output for an external busy light.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayConfig {
+    pub enabled: bool,
+    /// Statuses during which the light is on.
+    pub statuses: Vec<Status>,
+    /// Drive the pin low for "on".
+    pub inverted: bool,
+    /// Open-drain instead of push-pull; applied at startup.
+    pub open_drain: bool,
+    /// Pulse for this long on every change instead of holding the level.
+    pub pulse_ms: Option<u16>,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            statuses: vec![Status::Dnd],
+            inverted: false,
+            open_drain: false,
+            pulse_ms: None,
+        }
+    }
+}
+
+/// Servo raising a physical flag.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServoConfig {
+    pub enabled: bool,
+    /// Angles in degrees, 0 to 180, for each status.
+    pub free_angle: u8,
+    pub dnd_angle: u8,
+    pub away_angle: u8,
+    /// Sweep speed between angles.
+    pub degrees_per_sec: f32,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            free_angle: 0,
+            dnd_angle: 90,
+            away_angle: 0,
+            degrees_per_sec: 45.0,
+        }
+    }
+}
+
+/// CPU frequency scaling and automatic light sleep.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CpuConfig {
+    /// Lowest frequency when idle: 80, 160 or 240.
+    pub min_mhz: u16,
+    /// Highest frequency under load: 80, 160 or 240.
+    pub max_mhz: u16,
+    pub light_sleep: bool,
+}
+
+impl Default for CpuConfig {
+    fn default() -> Self {
+        Self {
+            min_mhz: 80,
+            max_mhz: 240,
+            light_sleep: false,
+        }
+    }
+}
+
+/// Warnings when the heap or a task's stack runs low.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// Least free heap in bytes since startup before warning.
+    pub low_heap: u32,
+    /// Least unused stack of a task in bytes before warning.
+    pub low_stack: u32,
+    /// Broker to publish the warnings to, e.g. `mqtt://192.168.1.2`; empty
+    /// means none.
+    pub mqtt_url: String,
+    pub mqtt_username: String,
+    pub mqtt_password: String,
+    pub mqtt_topic: String,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            low_heap: 16384,
+            low_stack: 512,
+            mqtt_url: String::new(),
+            mqtt_username: String::new(),
+            mqtt_password: String::new(),
+            mqtt_topic: "busier/alert".to_string(),
+        }
+    }
+}
+
+/// Warning when the chip runs hot, on the chips with a temperature sensor.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemperatureConfig {
+    /// Chip temperature in degrees Celsius from which to warn.
+    pub high_celsius: f32,
+}
+
+impl Default for TemperatureConfig {
+    fn default() -> Self {
+        Self { high_celsius: 75.0 }
+    }
+}
+
+/// WiFi modem sleep, applied when WiFi connects.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WifiPowerConfig {
+    pub mode: WifiPowerSave,
+    /// Beacon intervals between wake-ups in `max` mode.
+    pub listen_interval: u8,
+}
+
+impl Default for WifiPowerConfig {
+    fn default() -> Self {
+        Self {
+            mode: WifiPowerSave::Min,
+            listen_interval: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WifiPowerSave {
+    /// Radio always on: lowest latency, highest current.
+    None,
+    /// Wake for every DTIM beacon, the ESP-IDF default.
+    #[default]
+    Min,
+    /// Wake every `listen_interval` beacons.
+    Max,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatterySource {
+    /// Voltage divider on GPIO35.
+    #[default]
+    Adc,
+    /// MAX17048 fuel gauge on the I2C bus.
+    Max17048,
+}
+
+/// Deep sleep between status refreshes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SleepConfig {
+    pub enabled: bool,
+    /// Seconds to stay awake after each wake-up.
+    pub awake_secs: u32,
+    /// Seconds between timer wake-ups.
+    pub interval_secs: u32,
+    /// Touch pad (0-9) that wakes the device, if any.
+    pub touch_pad: Option<u8>,
+    /// Touch readings below this count as a touch.
+    pub touch_threshold: u16,
+}
+
+impl Default for SleepConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            awake_secs: 60,
+            interval_secs: 900,
+            touch_pad: None,
+            touch_threshold: 400,
+        }
+    }
+}
+
+/// Battery door sign that mirrors another device's status.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DoorSignConfig {
+    pub enabled: bool,
+    /// Fetched on every wake-up, e.g. `http://busier-desk.local/api/status`;
+    /// answers with a JSON `status` field or a plain status name.
+    pub source: String,
+}
+
+/// Magnetic door sensor.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DoorConfig {
+    pub enabled: bool,
+    /// Select Do Not Disturb while the door is closed during working hours.
+    pub auto_dnd: bool,
+}
+
+/// Doorbell button between a pin and ground.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DoorbellConfig {
+    /// None without a doorbell. Any pin that `pins` and the board leave free.
+    pub pin: Option<u8>,
+    /// Presses within a few seconds that ring even during Do Not Disturb;
+    /// 0 never does.
+    pub override_presses: u8,
+}
+
+impl Default for DoorbellConfig {
+    fn default() -> Self {
+        Self {
+            pin: None,
+            override_presses: 3,
+        }
+    }
+}
+
+/// Orientation-based status from an MPU6050.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CubeConfig {
+    pub enabled: bool,
+    /// Status set when the enclosure lies face up.
+    pub up: Status,
+    /// Status set when the enclosure lies face down.
+    pub down: Status,
+    /// Status set when the enclosure stands on any side.
+    pub side: Status,
+    /// Raw accelerometer offset, written by calibration.
+    pub offset: [i16; 3],
+}
+
+impl Default for CubeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            up: Status::Free,
+            down: Status::Dnd,
+            side: Status::Away,
+            offset: [0; 3],
+        }
+    }
+}
+
+/// IR remote buttons that toggle Do Not Disturb.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IrConfig {
+    /// NEC frames as 8 hex digits, as shown in the web interface.
+    pub codes: Vec<String>,
+}
+
+/// Badges known to the RFID reader.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RfidConfig {
+    pub cards: Vec<RfidCard>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RfidCard {
+    /// Card UID as lowercase hex, e.g. "04a1b2c3d4e5f6".
+    pub uid: String,
+    pub action: RfidAction,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RfidAction {
+    /// Switch between Do Not Disturb and Free.
+    #[default]
+    Toggle,
+    /// Knock, as from the web interface.
+    Knock,
+    Free,
+    Dnd,
+    Away,
+}
+
+/// Daily time window in local "HH:MM" notation. Wraps past midnight when
+/// `end` is before `start`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: String,
+    pub end: String,
+}
+
+impl TimeRange {
+    /// Whether the given minute of the day falls inside the window.
+    /// Unparsable windows never match.
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        let (Some(start), Some(end)) = (parse_hhmm(&self.start), parse_hhmm(&self.end)) else {
+            return false;
+        };
+        if start <= end {
+            (start..end).contains(&minute_of_day)
+        } else {
+            minute_of_day >= start || minute_of_day < end
+        }
+    }
+}
+
+/// Parses "HH:MM" into minutes since midnight.
+pub fn parse_hhmm(text: &str) -> Option<u16> {
+    let (hours, minutes) = text.split_once(':')?;
+    let hours: u16 = hours.parse().ok()?;
+    let minutes: u16 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl Config {
+    pub fn device_name(&self) -> &str {
+        if self.device_name.is_empty() {
+            DEFAULT_DEVICE_NAME
+        } else {
+            &self.device_name
+        }
+    }
+
+    pub fn displays(&self) -> Vec<DisplayConfig> {
+        if self.displays.is_empty() {
+            vec![DisplayConfig::default()]
+        } else {
+            self.displays.clone()
+        }
+    }
+
+    /// Applies a partial update: top-level sections present in `patch`
+    /// replace the current ones, everything else is kept.
+    pub fn merged(
+        &self,
+        patch: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Config, serde_json::Error> {
+        let mut merged = serde_json::to_value(self)?;
+        if let serde_json::Value::Object(merged) = &mut merged {
+            merged.extend(patch);
+        }
+        serde_json::from_value(merged)
+    }
+
+    /// Copy that is safe to hand out over the API.
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        for (_, secret) in config.secrets_mut() {
+            *secret = REDACTED.to_string();
+        }
+        config
+    }
+
+    /// Moves the secrets out, keyed by where they belong, so that the rest
+    /// can be stored in plain text. Empty secrets are left out.
+    pub fn take_secrets(&mut self) -> BTreeMap<String, String> {
+        self.secrets_mut()
+            .into_iter()
+            .filter(|(_, secret)| !secret.is_empty())
+            .map(|(key, secret)| (key, core::mem::take(secret)))
+            .collect()
+    }
+
+    /// Puts back secrets moved out with [`Config::take_secrets`].
+    pub fn put_secrets(&mut self, secrets: &BTreeMap<String, String>) {
+        for (key, secret) in self.secrets_mut() {
+            if let Some(value) = secrets.get(&key) {
+                *secret = value.clone();
+            }
+        }
+    }
+
+    // Every secret with a key that stays the same while its entry exists
+    fn secrets_mut(&mut self) -> Vec<(String, &mut String)> {
+        let mut secrets = vec![
+            ("admin.password".to_string(), &mut self.admin.password),
+            ("admin.set_token".to_string(), &mut self.admin.set_token),
+            ("snmp.community".to_string(), &mut self.snmp.community),
+            ("hue.key".to_string(), &mut self.hue.key),
+            ("esphome.password".to_string(), &mut self.esphome.password),
+            (
+                "esphome.encryption_key".to_string(),
+                &mut self.esphome.encryption_key,
+            ),
+            (
+                "memory.mqtt_password".to_string(),
+                &mut self.memory.mqtt_password,
+            ),
+        ];
+        for token in &mut self.admin.tokens {
+            secrets.push((format!("admin.tokens.{}", token.name), &mut token.token));
+        }
+        for hook in &mut self.hooks {
+            secrets.push((format!("hooks.{}", hook.name), &mut hook.secret));
+        }
+        for button in &mut self.remote_buttons {
+            secrets.push((format!("remote_buttons.{}", button.mac), &mut button.key));
+        }
+        secrets
+    }
+
+    /// Restores secrets that were sent back in redacted form.
+    pub fn restore_secrets(&mut self, current: &Config) {
+        if self.admin.password == REDACTED {
+            self.admin.password = current.admin.password.clone();
+        }
+        if self.admin.set_token == REDACTED {
+            self.admin.set_token = current.admin.set_token.clone();
+        }
+        for token in &mut self.admin.tokens {
+            if token.token == REDACTED {
+                token.token = current
+                    .admin
+                    .tokens
+                    .iter()
+                    .find(|t| t.name == token.name)
+                    .map(|t| t.token.clone())
+                    .unwrap_or_default();
+            }
+        }
+        for hook in &mut self.hooks {
+            if hook.secret == REDACTED {
+                hook.secret = current
+                    .hooks
+                    .iter()
+                    .find(|h| h.name == hook.name)
+                    .map(|h| h.secret.clone())
+                    .unwrap_or_default();
+            }
+        }
+        for button in &mut self.remote_buttons {
+            if button.key == REDACTED {
+                button.key = current
+                    .remote_buttons
+                    .iter()
+                    .find(|b| b.mac == button.mac)
+                    .map(|b| b.key.clone())
+                    .unwrap_or_default();
+            }
+        }
+        if self.snmp.community == REDACTED {
+            self.snmp.community = current.snmp.community.clone();
+        }
+        if self.hue.key == REDACTED {
+            self.hue.key = current.hue.key.clone();
+        }
+        if self.esphome.password == REDACTED {
+            self.esphome.password = current.esphome.password.clone();
+        }
+        if self.esphome.encryption_key == REDACTED {
+            self.esphome.encryption_key = current.esphome.encryption_key.clone();
+        }
+        if self.memory.mqtt_password == REDACTED {
+            self.memory.mqtt_password = current.memory.mqtt_password.clone();
+        }
+    }
+}
+
+// The sections holding secrets print them redacted, so that logging the
+// configuration does not leak them
+
+pub(crate) fn redact(secret: &str) -> &str {
+    if secret.is_empty() {
+        ""
+    } else {
+        REDACTED
+    }
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("username", &self.username)
+            .field("password", &redact(&self.password))
+            .field("set_token", &redact(&self.set_token))
+            .field("tokens", &self.tokens)
+            .finish()
+    }
+}
+
+impl fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiToken")
+            .field("name", &self.name)
+            .field("token", &redact(&self.token))
+            .field("rate_per_min", &self.rate_per_min)
+            .field("admin", &self.admin)
+            .finish()
+    }
+}
+
+impl fmt::Debug for HookConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HookConfig")
+            .field("name", &self.name)
+            .field("secret", &redact(&self.secret))
+            .field("signature_header", &self.signature_header)
+            .field("source", &self.source)
+            .field("pointer", &self.pointer)
+            .field("mapping", &self.mapping)
+            .finish()
+    }
+}
+
+impl fmt::Debug for RemoteButtonConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteButtonConfig")
+            .field("mac", &self.mac)
+            .field("key", &redact(&self.key))
+            .field("counter", &self.counter)
+            .finish()
+    }
+}
+
+impl fmt::Debug for SnmpConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnmpConfig")
+            .field("enabled", &self.enabled)
+            .field("community", &redact(&self.community))
+            .finish()
+    }
+}
+
+impl fmt::Debug for HueConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HueConfig")
+            .field("enabled", &self.enabled)
+            .field("bridge", &self.bridge)
+            .field("key", &redact(&self.key))
+            .field("group", &self.group)
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl fmt::Debug for EsphomeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EsphomeConfig")
+            .field("enabled", &self.enabled)
+            .field("password", &redact(&self.password))
+            .field("encryption_key", &redact(&self.encryption_key))
+            .finish()
+    }
+}
+
+impl fmt::Debug for MemoryConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryConfig")
+            .field("low_heap", &self.low_heap)
+            .field("low_stack", &self.low_stack)
+            .field("mqtt_url", &self.mqtt_url)
+            .field("mqtt_username", &self.mqtt_username)
+            .field("mqtt_password", &redact(&self.mqtt_password))
+            .field("mqtt_topic", &self.mqtt_topic)
+            .finish()
+    }
+}
@@ -0,0 +1,44 @@
+//! Hardware abstraction for the device logic.
+//!
+//! The status state machine, the schedules and the request handlers read
+//! the time and the network through these traits rather than calling the
+//! platform, so they can be driven by mocks: a clock pinned to a Monday at
+//! 08:59, a network without signal. The firmware implements them for the
+//! real hardware.
+
+use core::net::Ipv4Addr;
+
+/// Broken-down local time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalTime {
+    /// Days since Monday (0-6).
+    pub weekday: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl LocalTime {
+    pub fn minute_of_day(&self) -> u16 {
+        self.hour as u16 * 60 + self.minute as u16
+    }
+}
+
+/// Source of the wall-clock time.
+pub trait Clock {
+    /// Current local time; None until the clock has been set.
+    fn local_now(&self) -> Option<LocalTime>;
+
+    /// Next time the local clock shows the given minute of the day, as Unix
+    /// seconds; None until the clock has been set.
+    fn next_occurrence(&self, minute_of_day: u16) -> Option<u64>;
+}
+
+/// State of the network connection.
+pub trait NetworkInfo {
+    /// Address of the station interface, if connected.
+    fn ip(&self) -> Option<Ipv4Addr>;
+
+    /// Signal strength of the access point in dBm, if connected.
+    fn rssi(&self) -> Option<i8>;
+}
@@ -0,0 +1,83 @@
+//! Histograms of how long the request handlers take.
+//!
+//! Durations are counted in fixed buckets, so a histogram takes the same
+//! few bytes however many requests it saw. Percentiles are read off the
+//! buckets as the upper bound of the one they fall in, and never above the
+//! longest duration seen, which is kept exactly.
+
+/// Upper bounds of the buckets in microseconds; a last one takes the rest.
+pub const BOUNDS_US: [u32; 14] = [
+    250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000,
+    2_500_000, 5_000_000,
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [u32; BOUNDS_US.len() + 1],
+    sum_us: u64,
+    max_us: u32,
+}
+
+impl Histogram {
+    pub const fn new() -> Self {
+        Self {
+            counts: [0; BOUNDS_US.len() + 1],
+            sum_us: 0,
+            max_us: 0,
+        }
+    }
+
+    /// Counts a duration.
+    pub fn record(&mut self, us: u32) {
+        let bucket = BOUNDS_US
+            .iter()
+            .position(|bound| us <= *bound)
+            .unwrap_or(BOUNDS_US.len());
+        self.counts[bucket] = self.counts[bucket].saturating_add(1);
+        self.sum_us = self.sum_us.saturating_add(u64::from(us));
+        self.max_us = self.max_us.max(us);
+    }
+
+    /// Durations counted.
+    pub fn count(&self) -> u32 {
+        self.counts.iter().fold(0, |sum, count| sum.saturating_add(*count))
+    }
+
+    pub fn sum_us(&self) -> u64 {
+        self.sum_us
+    }
+
+    pub fn max_us(&self) -> u32 {
+        self.max_us
+    }
+
+    /// The duration `percent` of the counted ones took at most, to the
+    /// bucket; 0 before any.
+    pub fn percentile(&self, percent: u8) -> u32 {
+        let count = u64::from(self.count());
+        if count == 0 {
+            return 0;
+        }
+        // The rank of the duration, counting from 1
+        let rank = (count * u64::from(percent.min(100))).div_ceil(100).max(1);
+        let mut seen = 0;
+        for (bucket, bucket_count) in self.counts.iter().enumerate() {
+            seen += u64::from(*bucket_count);
+            if seen >= rank {
+                let bound = BOUNDS_US.get(bucket).copied().unwrap_or(u32::MAX);
+                return bound.min(self.max_us);
+            }
+        }
+        self.max_us
+    }
+
+    /// Each bucket's upper bound, None for the last, with the durations up
+    /// to it, as in a Prometheus histogram.
+    pub fn cumulative(&self) -> impl Iterator<Item = (Option<u32>, u32)> + '_ {
+        let mut seen = 0u32;
+        self.counts.iter().enumerate().map(move |(bucket, count)| {
+            seen = seen.saturating_add(*count);
+            (BOUNDS_US.get(bucket).copied(), seen)
+        })
+    }
+}
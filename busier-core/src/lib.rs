@@ -0,0 +1,33 @@
+//! Hardware-independent core of busier.
+//!
+//! The status model, the schedules, the rules, the reminders and the
+//! configuration with its JSON form, without any ESP-IDF dependency, so the
+//! firmware for other chips can reuse them and they can be tested on the
+//! host. Time and network state come in through the traits in [`hal`].
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod arbiter;
+pub mod audit;
+pub mod board;
+pub mod captive;
+pub mod config;
+pub mod hal;
+pub mod latency;
+pub mod multipart;
+pub mod outbox;
+pub mod pins;
+pub mod provision;
+pub mod ratelimit;
+pub mod reminders;
+pub mod reset;
+pub mod rtc;
+pub mod rules;
+pub mod schedule;
+pub mod stats;
+pub mod status;
+pub mod template;
+pub mod transitions;
+pub mod watermark;
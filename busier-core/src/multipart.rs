@@ -0,0 +1,230 @@
+//! `multipart/form-data` bodies, as browsers send a `<form>` with a file
+//! input.
+//!
+//! The [`Parser`] takes the body in pieces as they arrive and hands out each
+//! part's headers and data as soon as it has them, holding back no more than
+//! a boundary's length, so an upload never has to fit in RAM whole.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+// Longest header block of a part
+const MAX_HEADERS_LEN: usize = 1024;
+
+/// The boundary from a `Content-Type` header, if it is multipart.
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let (kind, params) = content_type.split_once(';')?;
+    if !kind.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params
+        .split(';')
+        .find_map(|param| param_value(param, "boundary"))
+        .filter(|boundary| !boundary.is_empty())
+}
+
+/// The headers of a part.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Part {
+    /// The form field.
+    pub name: String,
+    /// The name of the uploaded file, for file inputs.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+}
+
+/// What the [`Parser`] found in a piece of the body.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// A part begins; its data follows.
+    Part(Part),
+    /// Data of the current part, in as many pieces as it takes.
+    Data(&'a [u8]),
+}
+
+/// Why a body cannot be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MultipartError {
+    HeadersTooLong,
+    /// No `Content-Disposition` with a name.
+    MissingName,
+    /// The body ended before the closing boundary.
+    Truncated,
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HeadersTooLong => write!(f, "part headers over {} bytes", MAX_HEADERS_LEN),
+            Self::MissingName => write!(f, "part without a field name"),
+            Self::Truncated => write!(f, "body ends before the closing boundary"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Before the first boundary.
+    Preamble,
+    /// Right after a boundary: a line break or the closing `--`.
+    Boundary,
+    Headers,
+    Data,
+    /// After the closing boundary; the rest is ignored.
+    Done,
+}
+
+pub struct Parser {
+    /// The line break, the dashes and the boundary between parts.
+    delimiter: Vec<u8>,
+    state: State,
+    pending: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new(boundary: &str) -> Self {
+        let mut delimiter = b"\r\n--".to_vec();
+        delimiter.extend_from_slice(boundary.as_bytes());
+        Self {
+            delimiter,
+            state: State::Preamble,
+            // The first boundary needs no line break before it
+            pending: b"\r\n".to_vec(),
+        }
+    }
+
+    /// Parses the next piece of the body, reporting what it holds.
+    pub fn feed(
+        &mut self,
+        piece: &[u8],
+        mut on_event: impl FnMut(Event),
+    ) -> Result<(), MultipartError> {
+        if self.state == State::Done {
+            return Ok(());
+        }
+        self.pending.extend_from_slice(piece);
+
+        let mut consumed = 0;
+        loop {
+            let rest = &self.pending[consumed..];
+            match self.state {
+                State::Preamble => match find(rest, &self.delimiter) {
+                    Some(at) => {
+                        consumed += at + self.delimiter.len();
+                        self.state = State::Boundary;
+                    }
+                    None => {
+                        consumed += rest.len().saturating_sub(self.delimiter.len() - 1);
+                        break;
+                    }
+                },
+                State::Boundary => {
+                    if rest.len() < 2 {
+                        break;
+                    }
+                    if rest.starts_with(b"--") {
+                        self.state = State::Done;
+                        consumed = self.pending.len();
+                        break;
+                    }
+                    // Transport padding may come before the line break
+                    let Some(end) = find(rest, b"\r\n") else {
+                        break;
+                    };
+                    consumed += end + 2;
+                    self.state = State::Headers;
+                }
+                State::Headers => {
+                    let Some(end) = find(rest, b"\r\n\r\n") else {
+                        if rest.len() > MAX_HEADERS_LEN {
+                            return Err(MultipartError::HeadersTooLong);
+                        }
+                        break;
+                    };
+                    let part = parse_headers(&rest[..end])?;
+                    consumed += end + 4;
+                    self.state = State::Data;
+                    on_event(Event::Part(part));
+                }
+                State::Data => match find(rest, &self.delimiter) {
+                    Some(at) => {
+                        if at > 0 {
+                            on_event(Event::Data(&rest[..at]));
+                        }
+                        consumed += at + self.delimiter.len();
+                        self.state = State::Boundary;
+                    }
+                    None => {
+                        // Hold back what may be the start of a boundary
+                        let safe = rest.len().saturating_sub(self.delimiter.len() - 1);
+                        if safe > 0 {
+                            on_event(Event::Data(&rest[..safe]));
+                        }
+                        consumed += safe;
+                        break;
+                    }
+                },
+                State::Done => break,
+            }
+        }
+        self.pending.drain(..consumed);
+        Ok(())
+    }
+
+    /// Checks that the body ended with the closing boundary.
+    pub fn finish(&self) -> Result<(), MultipartError> {
+        match self.state {
+            State::Done => Ok(()),
+            _ => Err(MultipartError::Truncated),
+        }
+    }
+}
+
+fn parse_headers(block: &[u8]) -> Result<Part, MultipartError> {
+    let block = String::from_utf8_lossy(block);
+    let mut part = Part::default();
+    let mut named = false;
+    for line in block.split("\r\n") {
+        let Some((header, value)) = line.split_once(':') else {
+            continue;
+        };
+        if header.trim().eq_ignore_ascii_case("content-disposition") {
+            for param in value.split(';').skip(1) {
+                if let Some(name) = param_value(param, "name") {
+                    part.name = name.to_string();
+                    named = true;
+                } else if let Some(filename) = param_value(param, "filename") {
+                    part.filename = Some(filename.to_string());
+                }
+            }
+        } else if header.trim().eq_ignore_ascii_case("content-type") {
+            part.content_type = Some(value.trim().to_string());
+        }
+    }
+    if !named {
+        return Err(MultipartError::MissingName);
+    }
+    Ok(part)
+}
+
+// The value of `key=value` or `key="value"`, if the key matches
+fn param_value<'a>(param: &'a str, key: &str) -> Option<&'a str> {
+    let (name, value) = param.split_once('=')?;
+    if !name.trim().eq_ignore_ascii_case(key) {
+        return None;
+    }
+    let value = value.trim();
+    Some(
+        value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value),
+    )
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
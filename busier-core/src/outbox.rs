@@ -0,0 +1,106 @@
+//! Queue of outbound notifications waiting for delivery.
+//!
+//! Messages are delivered in order, the oldest first. A failed delivery is
+//! retried after an exponential backoff, starting at [`FIRST_RETRY_SECS`]
+//! and capped at [`MAX_RETRY_SECS`], until it succeeds; nothing is given up
+//! on except the oldest message when the queue is full. The queue serializes
+//! to JSON so the firmware can keep it across reboots.
+//!
+//! Each message keeps the transaction id it was queued with, so a retry of a
+//! message the chat server did take, but did not confirm, is not posted twice.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+
+use serde::{Deserialize, Serialize};
+
+/// Messages kept at most.
+pub const CAPACITY: usize = 32;
+pub const FIRST_RETRY_SECS: u32 = 5;
+pub const MAX_RETRY_SECS: u32 = 600;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub message: String,
+    /// Sent with every attempt; empty in queues saved by older firmware.
+    #[serde(default)]
+    pub txn_id: String,
+    /// Failed attempts so far.
+    pub attempts: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outbox {
+    queue: VecDeque<Delivery>,
+}
+
+impl Outbox {
+    pub const fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Queues a message; returns false if the oldest one was dropped to
+    /// make room.
+    pub fn push(&mut self, message: String, txn_id: String) -> bool {
+        let dropped = self.queue.len() >= CAPACITY;
+        if dropped {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(Delivery {
+            message,
+            txn_id,
+            attempts: 0,
+        });
+        !dropped
+    }
+
+    /// Gives the messages of an older queue their transaction id; returns
+    /// false if they all had one.
+    pub fn assign_txn_ids(&mut self, mut txn_id: impl FnMut() -> String) -> bool {
+        let mut assigned = false;
+        for delivery in &mut self.queue {
+            if delivery.txn_id.is_empty() {
+                delivery.txn_id = txn_id();
+                assigned = true;
+            }
+        }
+        assigned
+    }
+
+    /// The message to deliver next.
+    pub fn front(&self) -> Option<&Delivery> {
+        self.queue.front()
+    }
+
+    /// Removes the message after a successful delivery.
+    pub fn delivered(&mut self) {
+        self.queue.pop_front();
+    }
+
+    /// Counts a failed attempt; returns the seconds to wait before the next.
+    pub fn failed(&mut self) -> u32 {
+        let Some(delivery) = self.queue.front_mut() else {
+            return 0;
+        };
+        delivery.attempts = delivery.attempts.saturating_add(1);
+        retry_secs(delivery.attempts)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Backoff after `attempts` failed attempts.
+pub fn retry_secs(attempts: u32) -> u32 {
+    let doublings = attempts.saturating_sub(1).min(31);
+    FIRST_RETRY_SECS
+        .saturating_mul(1 << doublings)
+        .min(MAX_RETRY_SECS)
+}
@@ -0,0 +1,91 @@
+//! GPIO capabilities of the supported chips, used to check pins set in the
+//! configuration before a driver is given them.
+//!
+//! On the ESP32, GPIO20, 24 and 28-31 are not bonded out, 6-11 are wired to
+//! the SPI flash and 34-39 are inputs without internal pull-ups, which none
+//! of the configurable pins can use. The C3 and S3 have no input-only pins,
+//! but give theirs to the flash (and the S3's octal PSRAM) and to the
+//! USB-Serial-JTAG console.
+
+use core::fmt;
+
+use crate::config::PinsConfig;
+
+/// Chip the firmware runs on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip {
+    Esp32,
+    Esp32C3,
+    Esp32S3,
+}
+
+impl Chip {
+    /// Pins used when the configuration has none.
+    pub fn default_pins(self) -> PinsConfig {
+        match self {
+            Self::Esp32 => PinsConfig::default(),
+            // The BOOT button and the LED of the C3 SuperMini and DevKitM
+            Self::Esp32C3 => PinsConfig {
+                sda: 4,
+                scl: 5,
+                button: 9,
+                led: 8,
+                buzzer: 6,
+                relay: 7,
+                door: 10,
+            },
+            // The DevKitC's LED is addressable, so the LED goes on GPIO2
+            Self::Esp32S3 => PinsConfig {
+                sda: 8,
+                scl: 9,
+                button: 0,
+                led: 2,
+                buzzer: 4,
+                relay: 5,
+                door: 6,
+            },
+        }
+    }
+}
+
+/// Why a configured pin cannot be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinError {
+    Missing(u8),
+    Flash(u8),
+    InputOnly(u8),
+    Usb(u8),
+    /// Given for more than one function.
+    Duplicate(u8),
+    /// Taken by a driver with fixed pins in this build.
+    Reserved(u8),
+}
+
+impl fmt::Display for PinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(pin) => write!(f, "GPIO{} does not exist", pin),
+            Self::Flash(pin) => write!(f, "GPIO{} is used by the flash or PSRAM", pin),
+            Self::InputOnly(pin) => write!(f, "GPIO{} is input only", pin),
+            Self::Usb(pin) => write!(f, "GPIO{} is used by the USB console", pin),
+            Self::Duplicate(pin) => write!(f, "GPIO{} is given more than once", pin),
+            Self::Reserved(pin) => write!(f, "GPIO{} is used by another driver", pin),
+        }
+    }
+}
+
+/// Checks that a pin can be driven and pulled up.
+pub fn check_io(chip: Chip, pin: u8) -> Result<(), PinError> {
+    match (chip, pin) {
+        (Chip::Esp32, 20 | 24 | 28..=31 | 40..) => Err(PinError::Missing(pin)),
+        (Chip::Esp32, 6..=11) => Err(PinError::Flash(pin)),
+        (Chip::Esp32, 34..=39) => Err(PinError::InputOnly(pin)),
+        (Chip::Esp32C3, 22..) => Err(PinError::Missing(pin)),
+        (Chip::Esp32C3, 11..=17) => Err(PinError::Flash(pin)),
+        (Chip::Esp32C3, 18 | 19) => Err(PinError::Usb(pin)),
+        (Chip::Esp32S3, 22..=25 | 49..) => Err(PinError::Missing(pin)),
+        (Chip::Esp32S3, 26..=37) => Err(PinError::Flash(pin)),
+        (Chip::Esp32S3, 19 | 20) => Err(PinError::Usb(pin)),
+        _ => Ok(()),
+    }
+}
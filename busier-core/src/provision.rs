@@ -0,0 +1,92 @@
+//! Provisioning documents.
+//!
+//! A new device can be set up without its web pages from one JSON document
+//! sent over the serial console: the WiFi network to join and the
+//! configuration, with the device name, the admin login and the API tokens.
+//! Either part may be left out, but a configuration ends the setup wizard,
+//! so it must come with an admin password.
+
+use alloc::string::String;
+use core::fmt;
+
+use serde::Deserialize;
+
+use crate::config::{self, Config};
+
+/// WiFi credentials, as sent to the device.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct WifiCredentials {
+    pub ssid: String,
+    /// Empty for an open network.
+    pub password: String,
+}
+
+impl WifiCredentials {
+    pub fn validate(&self) -> Result<(), ProvisionError> {
+        if !(1..=32).contains(&self.ssid.len()) {
+            return Err(ProvisionError::Ssid);
+        }
+        if !self.password.is_empty() && !(8..=64).contains(&self.password.len()) {
+            return Err(ProvisionError::Password);
+        }
+        Ok(())
+    }
+}
+
+// The password stays out of logs
+impl fmt::Debug for WifiCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WifiCredentials")
+            .field("ssid", &self.ssid)
+            .field("password", &config::redact(&self.password))
+            .finish()
+    }
+}
+
+/// Everything a provisioning document sets up.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Provisioning {
+    pub wifi: Option<WifiCredentials>,
+    /// Replaces the defaults; sections left out keep them.
+    pub config: Option<Config>,
+}
+
+impl Provisioning {
+    pub fn validate(&self) -> Result<(), ProvisionError> {
+        if self.wifi.is_none() && self.config.is_none() {
+            return Err(ProvisionError::Empty);
+        }
+        if let Some(wifi) = &self.wifi {
+            wifi.validate()?;
+        }
+        if let Some(config) = &self.config {
+            if config.admin.password.is_empty() {
+                return Err(ProvisionError::AdminPassword);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a provisioning document cannot be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProvisionError {
+    /// Neither `wifi` nor `config`.
+    Empty,
+    Ssid,
+    Password,
+    AdminPassword,
+}
+
+impl fmt::Display for ProvisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "needs `wifi` or `config`"),
+            Self::Ssid => write!(f, "the network name must have 1 to 32 characters"),
+            Self::Password => write!(f, "the WiFi password must have 8 to 64 characters"),
+            Self::AdminPassword => write!(f, "the configuration needs `admin.password`"),
+        }
+    }
+}
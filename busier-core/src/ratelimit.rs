@@ -0,0 +1,83 @@
+//! Request rate limits and usage counters of API clients.
+//!
+//! Each client has a bucket that holds up to a minute's worth of requests
+//! and refills at its rate, so a client may burst up to its limit and then
+//! keeps to the rate on average. Times are milliseconds on a monotonic
+//! clock, such as the uptime.
+
+use serde::{Deserialize, Serialize};
+
+// Thousandths of a request, so the bucket refills between requests
+const REQUEST: u64 = 1000;
+
+/// What a client did since startup.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Requests let through.
+    pub requests: u32,
+    /// Requests refused for going over the limit.
+    pub throttled: u32,
+    /// When the last request came, let through or not.
+    pub last_ms: Option<u64>,
+}
+
+/// The bucket and usage of one client.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limiter {
+    // Thousandths of a request left, as of `updated_ms`; full before the
+    // first request
+    level: u64,
+    updated_ms: Option<u64>,
+    usage: Usage,
+}
+
+impl Limiter {
+    pub const fn new() -> Self {
+        Self {
+            level: 0,
+            updated_ms: None,
+            usage: Usage {
+                requests: 0,
+                throttled: 0,
+                last_ms: None,
+            },
+        }
+    }
+
+    /// Counts a request at `now_ms` against `rate_per_min`, 0 for no limit.
+    /// Returns the milliseconds until the next request would be let
+    /// through if this one is over the limit.
+    pub fn check(&mut self, rate_per_min: u32, now_ms: u64) -> Result<(), u64> {
+        self.usage.last_ms = Some(now_ms);
+        if rate_per_min == 0 {
+            self.usage.requests = self.usage.requests.saturating_add(1);
+            return Ok(());
+        }
+
+        // A minute's worth of requests, refilled at `rate_per_min` thousandths
+        // of a request every 60 ms
+        let rate = u64::from(rate_per_min);
+        let capacity = rate * REQUEST;
+        let level = match self.updated_ms {
+            Some(updated_ms) => {
+                let refill = now_ms.saturating_sub(updated_ms).saturating_mul(rate) / 60;
+                self.level.saturating_add(refill).min(capacity)
+            }
+            None => capacity,
+        };
+        self.updated_ms = Some(now_ms);
+
+        if level < REQUEST {
+            self.level = level;
+            self.usage.throttled = self.usage.throttled.saturating_add(1);
+            return Err((REQUEST - level) * 60 / rate + 1);
+        }
+        self.level = level - REQUEST;
+        self.usage.requests = self.usage.requests.saturating_add(1);
+        Ok(())
+    }
+
+    pub fn usage(&self) -> &Usage {
+        &self.usage
+    }
+}
@@ -0,0 +1,79 @@
+//! Reminders that beep, flash and show a message, such as a daily standup
+//! or a break every 50 minutes during working hours:
+//!
+//! ```json
+//! {
+//!   "message": "Stretch",
+//!   "every_mins": 50,
+//!   "when": [{ "working_hours": true }]
+//! }
+//! ```
+//!
+//! A reminder fires at a local time each day (`at`) or after every so many
+//! minutes (`every_mins`), and only while its conditions, the same as a
+//! rule's, all hold. An interval starts over while they do not, so the first
+//! break comes 50 minutes into the working day. A `once` reminder is removed
+//! after it fired.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::parse_hhmm;
+use crate::rules::{Condition, Facts, RuleError};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reminder {
+    /// Shown on the display.
+    pub message: String,
+    /// Local time it fires each day, "HH:MM".
+    pub at: Option<String>,
+    /// Minutes between firings, when there is no `at`.
+    pub every_mins: Option<u32>,
+    /// All of them must hold; an empty list always does.
+    pub when: Vec<Condition>,
+    /// Removed after it fired.
+    pub once: bool,
+}
+
+impl Reminder {
+    /// Checks the time, the interval and the conditions.
+    pub fn validate(&self) -> Result<(), RuleError> {
+        match (&self.at, self.every_mins) {
+            (Some(at), None) if parse_hhmm(at).is_none() => {
+                return Err(RuleError::Time(at.clone()));
+            }
+            (Some(_), None) | (None, Some(1..)) => {}
+            _ => return Err(RuleError::Trigger),
+        }
+        self.when.iter().try_for_each(Condition::validate)
+    }
+
+    /// Whether the reminder fires at `now`, in seconds of any monotonic
+    /// clock. `since` is when it last fired or was set up, and is moved to
+    /// `now` when it fires or while an interval waits for its conditions.
+    pub fn check(&self, facts: &Facts, now: u64, since: &mut u64) -> bool {
+        let applies = self.when.iter().all(|condition| condition.holds(facts));
+        let elapsed = now.saturating_sub(*since);
+
+        let due = match (&self.at, self.every_mins) {
+            (Some(at), _) => {
+                // A firing lasts a minute; only the first check in it counts
+                let minute = facts.now.map(|local| local.minute_of_day());
+                applies && minute.is_some() && minute == parse_hhmm(at) && elapsed >= 60
+            }
+            (None, Some(mins)) if applies => elapsed >= u64::from(mins) * 60,
+            (None, Some(_)) => {
+                *since = now;
+                false
+            }
+            (None, None) => false,
+        };
+        if due {
+            *since = now;
+        }
+        due
+    }
+}
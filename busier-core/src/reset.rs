@@ -0,0 +1,52 @@
+//! Reset reasons and the crash counter behind safe mode.
+//!
+//! Each start counts as a crash when the chip was reset by a panic or a
+//! watchdog, and the count goes on across resets until the device starts
+//! otherwise or runs for [`STABLE_SECS`]. After [`SAFE_MODE_CRASHES`] in a
+//! row the firmware starts in safe mode, with only what it takes to fix the
+//! configuration, so a bad setting cannot keep the device from starting.
+
+use serde::Serialize;
+
+/// Crashes in a row after which the device starts in safe mode.
+pub const SAFE_MODE_CRASHES: u8 = 3;
+/// Seconds of running after which the crashes are forgotten.
+pub const STABLE_SECS: u64 = 300;
+
+/// Why the chip last started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetReason {
+    PowerOn,
+    /// The reset pin or the USB serial port.
+    External,
+    /// A restart from the firmware, e.g. after an update.
+    Software,
+    Panic,
+    /// The interrupt, task or RTC watchdog.
+    Watchdog,
+    DeepSleep,
+    Brownout,
+    Other,
+}
+
+impl ResetReason {
+    /// Whether the firmware crashed rather than being restarted.
+    pub fn is_crash(self) -> bool {
+        matches!(self, ResetReason::Panic | ResetReason::Watchdog)
+    }
+}
+
+/// The crashes in a row, counting this start.
+pub fn crashes(previous: u8, reason: ResetReason) -> u8 {
+    if reason.is_crash() {
+        previous.saturating_add(1)
+    } else {
+        0
+    }
+}
+
+/// Whether to start in safe mode after `crashes` in a row.
+pub fn is_safe_mode(crashes: u8) -> bool {
+    crashes >= SAFE_MODE_CRASHES
+}
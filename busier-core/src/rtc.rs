@@ -0,0 +1,110 @@
+//! Time registers of the DS3231 real-time clock.
+//!
+//! The chip counts the date and time in BCD in registers 0x00-0x06. The
+//! firmware keeps it in UTC in 24-hour mode, so converting to and from Unix
+//! seconds needs no time zone. The century bit of the month register covers
+//! the years 2000-2199.
+
+/// Registers 0x00-0x06: seconds, minutes, hours, weekday, date, month with
+/// the century bit, and year.
+pub type Registers = [u8; 7];
+
+const CENTURY: u8 = 0x80;
+const TWELVE_HOUR: u8 = 0x40;
+
+/// Encodes Unix seconds; None outside the years the chip can count.
+pub fn encode(unix: u64) -> Option<Registers> {
+    let days = unix / 86_400;
+    let secs = unix % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    if !(2000..2200).contains(&year) {
+        return None;
+    }
+    // 1970-01-01 was a Thursday; the chip counts 1-7, Monday first here
+    let weekday = ((days + 3) % 7 + 1) as u8;
+    let century = if year >= 2100 { CENTURY } else { 0 };
+    Some([
+        bcd((secs % 60) as u8),
+        bcd((secs / 60 % 60) as u8),
+        bcd((secs / 3600) as u8),
+        weekday,
+        bcd(day),
+        bcd(month) | century,
+        bcd((year % 100) as u8),
+    ])
+}
+
+/// Decodes the registers into Unix seconds; None if they hold no valid time.
+pub fn decode(registers: &Registers) -> Option<u64> {
+    let [seconds, minutes, hours, _, date, month, year] = *registers;
+    if hours & TWELVE_HOUR != 0 {
+        return None;
+    }
+    let second = from_bcd(seconds & 0x7F)?;
+    let minute = from_bcd(minutes & 0x7F)?;
+    let hour = from_bcd(hours & 0x3F)?;
+    let day = from_bcd(date & 0x3F)?;
+    let month_number = from_bcd(month & 0x1F)?;
+    let century = if month & CENTURY != 0 { 2100 } else { 2000 };
+    let year = century + u32::from(from_bcd(year)?);
+
+    if second > 59 || minute > 59 || hour > 23 || !(1..=12).contains(&month_number) {
+        return None;
+    }
+    if day < 1 || day > days_in_month(year, month_number) {
+        return None;
+    }
+
+    let days = days_from_civil(year, month_number, day);
+    Some(days * 86_400 + u64::from(hour) * 3600 + u64::from(minute) * 60 + u64::from(second))
+}
+
+fn bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+fn from_bcd(value: u8) -> Option<u8> {
+    let (tens, ones) = (value >> 4, value & 0x0F);
+    (tens < 10 && ones < 10).then_some(tens * 10 + ones)
+}
+
+fn is_leap(year: u32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: u32, month: u8) -> u8 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 of a date from 1970 on, after Howard Hinnant's
+// algorithm with years starting in March
+fn days_from_civil(year: u32, month: u8, day: u8) -> u64 {
+    let year = u64::from(if month <= 2 { year - 1 } else { year });
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month = u64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + u64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// The inverse of `days_from_civil`
+fn civil_from_days(days: u64) -> (u32, u8, u8) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month + 2) / 5 + 1) as u8;
+    let month = if month < 10 { month + 3 } else { month - 9 } as u8;
+    let year = (year_of_era + era * 400) as u32 + u32::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,142 @@
+//! Rules that set the status and the LED from conditions, so policies such
+//! as "while the calendar says busy during office hours, Do Not Disturb with
+//! the LED on" need no firmware change:
+//!
+//! ```json
+//! {
+//!   "name": "meetings",
+//!   "when": [
+//!     { "claim": { "source": "calendar", "status": "dnd" } },
+//!     { "time": { "start": "09:00", "end": "17:00" } }
+//!   ],
+//!   "then": { "status": "dnd", "led": true }
+//! }
+//! ```
+//!
+//! The first rule whose conditions all hold applies. Its status is claimed
+//! as the `rules` source, so the arbiter weighs it against the other
+//! sources, and the claim is withdrawn once no rule applies.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::arbiter::Source;
+use crate::config::{parse_hhmm, TimeRange};
+use crate::hal::LocalTime;
+use crate::status::Status;
+
+/// A condition on the device's state.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// The source's latest claim is for this status.
+    Claim { source: Source, status: Status },
+    /// The local time is within the window; never before the clock is set.
+    Time(TimeRange),
+    /// Today is one of these days, 0 for Monday.
+    Weekdays(Vec<u8>),
+    /// Whether it is within working hours.
+    WorkingHours(bool),
+    /// Whether the door is open; never without a door sensor.
+    DoorOpen(bool),
+}
+
+/// What a rule does while it applies.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Action {
+    pub status: Option<Status>,
+    /// Lights the LED or keeps it dark, whatever the status.
+    pub led: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rule {
+    pub name: String,
+    /// All of them must hold; an empty list always does.
+    pub when: Vec<Condition>,
+    pub then: Action,
+}
+
+/// The state the conditions are checked against.
+#[derive(Clone, Debug, Default)]
+pub struct Facts {
+    /// None until the clock is set.
+    pub now: Option<LocalTime>,
+    /// The latest claim of each source that made one.
+    pub claims: Vec<(Source, Status)>,
+    pub working_hours: bool,
+    /// None without a door sensor.
+    pub door_open: Option<bool>,
+}
+
+/// Why a rule cannot be used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleError {
+    Time(String),
+    Weekday(u8),
+    /// A reminder needs either `at` or a non-zero `every_mins`.
+    Trigger,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Time(time) => write!(f, "{:?} is not a HH:MM time", time),
+            Self::Weekday(day) => write!(f, "{} is not a weekday (0-6)", day),
+            Self::Trigger => write!(f, "needs either `at` or a non-zero `every_mins`"),
+        }
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, facts: &Facts) -> bool {
+        match self {
+            Self::Claim { source, status } => facts.claims.contains(&(*source, *status)),
+            Self::Time(range) => facts
+                .now
+                .is_some_and(|now| range.contains(now.minute_of_day())),
+            Self::Weekdays(days) => facts.now.is_some_and(|now| days.contains(&now.weekday)),
+            Self::WorkingHours(expected) => facts.working_hours == *expected,
+            Self::DoorOpen(expected) => facts.door_open == Some(*expected),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), RuleError> {
+        match self {
+            Self::Time(range) => {
+                for time in [&range.start, &range.end] {
+                    if parse_hhmm(time).is_none() {
+                        return Err(RuleError::Time(time.clone()));
+                    }
+                }
+                Ok(())
+            }
+            Self::Weekdays(days) => match days.iter().find(|day| **day > 6) {
+                Some(day) => Err(RuleError::Weekday(*day)),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Rule {
+    pub fn applies(&self, facts: &Facts) -> bool {
+        self.when.iter().all(|condition| condition.holds(facts))
+    }
+
+    /// Checks the times and weekdays in the conditions.
+    pub fn validate(&self) -> Result<(), RuleError> {
+        self.when.iter().try_for_each(Condition::validate)
+    }
+}
+
+/// The first rule that applies.
+pub fn first_match<'a>(rules: &'a [Rule], facts: &Facts) -> Option<&'a Rule> {
+    rules.iter().find(|rule| rule.applies(facts))
+}
@@ -0,0 +1,35 @@
+//! Working hours and quiet hours.
+//!
+//! Outside working hours the device shows Away and sends no notifications.
+//! During quiet hours the buzzer and LED stay off regardless of status.
+//! Until the clock is synchronized neither schedule applies.
+
+use crate::config::{QuietHoursConfig, WorkingHoursConfig};
+use crate::hal::Clock;
+
+/// Whether `clock` shows a time within `hours`.
+pub fn working_hours_at(hours: &WorkingHoursConfig, clock: &impl Clock) -> bool {
+    if !hours.enabled {
+        return true;
+    }
+
+    let Some(now) = clock.local_now() else {
+        return true;
+    };
+
+    match &hours.days[now.weekday as usize] {
+        Some(range) => range.contains(now.minute_of_day()),
+        None => false,
+    }
+}
+
+/// Whether `clock` shows a time within `quiet`.
+pub fn quiet_hours_at(quiet: &QuietHoursConfig, clock: &impl Clock) -> bool {
+    if !quiet.enabled {
+        return false;
+    }
+
+    clock
+        .local_now()
+        .is_some_and(|now| quiet.hours.contains(now.minute_of_day()))
+}
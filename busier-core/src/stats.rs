@@ -0,0 +1,147 @@
+//! Time spent in each status and requests served, per hour and per day.
+//!
+//! The firmware records every minute with the selected status, and the
+//! requests since the last minute. Only the latest [`HOURS_KEPT`] hours and
+//! [`DAYS_KEPT`] days are kept, small enough to live in NVS. Dates are local
+//! and written as numbers such as `20261016`, so they sort and read as dates
+//! without a calendar library.
+
+use alloc::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::status::Status;
+
+pub const HOURS_KEPT: usize = 48;
+pub const DAYS_KEPT: usize = 14;
+
+/// A local hour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stamp {
+    /// YYYYMMDD.
+    pub date: u32,
+    pub hour: u8,
+}
+
+/// Minutes in each status and requests served.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Totals {
+    pub free: u32,
+    pub dnd: u32,
+    pub away: u32,
+    pub requests: u32,
+}
+
+impl Totals {
+    pub fn minutes(&self, status: Status) -> u32 {
+        match status {
+            Status::Free => self.free,
+            Status::Dnd => self.dnd,
+            Status::Away => self.away,
+        }
+    }
+
+    fn add_minute(&mut self, status: Status) {
+        let minutes = match status {
+            Status::Free => &mut self.free,
+            Status::Dnd => &mut self.dnd,
+            Status::Away => &mut self.away,
+        };
+        *minutes = minutes.saturating_add(1);
+    }
+
+    fn add(&mut self, other: &Totals) {
+        self.free = self.free.saturating_add(other.free);
+        self.dnd = self.dnd.saturating_add(other.dnd);
+        self.away = self.away.saturating_add(other.away);
+        self.requests = self.requests.saturating_add(other.requests);
+    }
+}
+
+/// The totals of an hour, or of a whole day without `hour`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bucket {
+    pub date: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hour: Option<u8>,
+    pub totals: Totals,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    hours: VecDeque<Bucket>,
+    days: VecDeque<Bucket>,
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Self {
+            hours: VecDeque::new(),
+            days: VecDeque::new(),
+        }
+    }
+
+    /// Counts a minute spent in `status`.
+    pub fn record_minute(&mut self, at: Stamp, status: Status) {
+        self.update(at, |totals| totals.add_minute(status));
+    }
+
+    /// Counts requests served.
+    pub fn record_requests(&mut self, at: Stamp, requests: u32) {
+        if requests > 0 {
+            self.update(at, |totals| {
+                totals.requests = totals.requests.saturating_add(requests)
+            });
+        }
+    }
+
+    /// The kept hours, the oldest first.
+    pub fn hours(&self) -> impl Iterator<Item = &Bucket> {
+        self.hours.iter()
+    }
+
+    /// The kept days, the oldest first.
+    pub fn days(&self) -> impl Iterator<Item = &Bucket> {
+        self.days.iter()
+    }
+
+    /// The sum of the latest `days` kept days.
+    pub fn last_days(&self, days: usize) -> Totals {
+        let mut sum = Totals::default();
+        for day in self.days.iter().rev().take(days) {
+            sum.add(&day.totals);
+        }
+        sum
+    }
+
+    fn update(&mut self, at: Stamp, change: impl Fn(&mut Totals)) {
+        change(&mut bucket(&mut self.hours, at.date, Some(at.hour), HOURS_KEPT).totals);
+        change(&mut bucket(&mut self.days, at.date, None, DAYS_KEPT).totals);
+    }
+}
+
+// The latest bucket if it is the one asked for, otherwise a new one, dropping
+// the oldest beyond `kept`
+fn bucket(
+    buckets: &mut VecDeque<Bucket>,
+    date: u32,
+    hour: Option<u8>,
+    kept: usize,
+) -> &mut Bucket {
+    let current = buckets
+        .back()
+        .is_some_and(|last| last.date == date && last.hour == hour);
+    if !current {
+        if buckets.len() >= kept {
+            buckets.pop_front();
+        }
+        buckets.push_back(Bucket {
+            date,
+            hour,
+            totals: Totals::default(),
+        });
+    }
+    buckets.back_mut().unwrap()
+}
@@ -0,0 +1,50 @@
+//! Availability statuses.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Free,
+    Dnd,
+    Away,
+}
+
+impl Status {
+    /// Name used by the API.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Free => "free",
+            Status::Dnd => "dnd",
+            Status::Away => "away",
+        }
+    }
+
+    /// Parses the status names used by the API.
+    pub fn parse(name: &str) -> Option<Status> {
+        match name {
+            "free" => Some(Status::Free),
+            "dnd" => Some(Status::Dnd),
+            "away" => Some(Status::Away),
+            _ => None,
+        }
+    }
+
+    /// Inverse of `status as u8`; unknown values map to Free.
+    pub fn from_u8(value: u8) -> Status {
+        match value {
+            1 => Status::Dnd,
+            2 => Status::Away,
+            _ => Status::Free,
+        }
+    }
+}
+
+/// The status shown for a selection, given whether it is working hours.
+pub fn shown(selected: Status, working_hours: bool) -> Status {
+    if working_hours {
+        selected
+    } else {
+        Status::Away
+    }
+}
@@ -0,0 +1,46 @@
+//! `{{key}}` placeholders in the web pages.
+//!
+//! The firmware fills in the translated texts and values such as the device
+//! name and the current status when it serves a page, so the first paint
+//! needs no request back to the device. Values that come from the
+//! configuration go through [`escape_html`] first.
+
+use alloc::string::String;
+
+/// Replaces each `{{key}}` with what `lookup` returns for it. Keys it has
+/// nothing for are left in place, so a missing entry shows up on the page.
+pub fn render<S: AsRef<str>>(page: &str, lookup: impl Fn(&str) -> Option<S>) -> String {
+    let mut rendered = String::with_capacity(page.len());
+    let mut rest = page;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let Some(end) = placeholder.find("}}") else {
+            rest = placeholder;
+            break;
+        };
+        match lookup(&placeholder[2..end]) {
+            Some(value) => rendered.push_str(value.as_ref()),
+            None => rendered.push_str(&placeholder[..end + 2]),
+        }
+        rest = &placeholder[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Escapes text for HTML elements and quoted attributes.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
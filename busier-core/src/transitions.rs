@@ -0,0 +1,113 @@
+//! Steps between two statuses, such as a few minutes of "Wrapping up"
+//! before a timed Do Not Disturb returns to Free:
+//!
+//! ```json
+//! {
+//!   "from": "dnd",
+//!   "to": "free",
+//!   "steps": [{ "label": "Wrapping up", "mins": 5, "led": false, "sound": true }]
+//! }
+//! ```
+//!
+//! The status stays `from` through the steps, which only change what the
+//! device shows: each step's label, its LED setting and a sound as it
+//! begins. The chain ends in `to` after the last step.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::status::Status;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Step {
+    /// Shown instead of the status.
+    pub label: String,
+    pub mins: u32,
+    /// Lights the LED or keeps it dark during the step.
+    pub led: Option<bool>,
+    /// Signals as the step begins.
+    pub sound: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transition {
+    pub from: Status,
+    pub to: Status,
+    pub steps: Vec<Step>,
+}
+
+impl Transition {
+    /// The configured transition between two statuses, if it has steps.
+    pub fn find(transitions: &[Transition], from: Status, to: Status) -> Option<&Transition> {
+        transitions
+            .iter()
+            .find(|t| t.from == from && t.to == to && !t.steps.is_empty())
+    }
+}
+
+/// What a [`Chain`] does on a check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// The current step goes on.
+    Same,
+    /// The next step began.
+    Next,
+    /// The last step ended; the status is now `to`.
+    Done,
+}
+
+/// A transition in progress.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chain {
+    transition: Transition,
+    step: usize,
+    /// When the current step ends, in seconds of any monotonic clock.
+    until: u64,
+}
+
+impl Chain {
+    /// Begins the first step at `now`; None without steps.
+    pub fn start(transition: &Transition, now: u64) -> Option<Chain> {
+        let first = transition.steps.first()?;
+        Some(Chain {
+            transition: transition.clone(),
+            step: 0,
+            until: now + u64::from(first.mins) * 60,
+        })
+    }
+
+    pub fn step(&self) -> &Step {
+        &self.transition.steps[self.step]
+    }
+
+    /// The status the chain ends in.
+    pub fn to(&self) -> Status {
+        self.transition.to
+    }
+
+    /// Seconds left of the whole chain.
+    pub fn remaining(&self, now: u64) -> u64 {
+        let later: u64 = self.transition.steps[self.step + 1..]
+            .iter()
+            .map(|step| u64::from(step.mins) * 60)
+            .sum();
+        self.until.saturating_sub(now) + later
+    }
+
+    /// Moves on to the step due at `now`, skipping any that already ended.
+    pub fn advance(&mut self, now: u64) -> Progress {
+        let mut progress = Progress::Same;
+        while now >= self.until {
+            if self.step + 1 == self.transition.steps.len() {
+                return Progress::Done;
+            }
+            self.step += 1;
+            self.until += u64::from(self.step().mins) * 60;
+            progress = Progress::Next;
+        }
+        progress
+    }
+}
@@ -0,0 +1,53 @@
+//! Memory watermarks and the warnings they raise.
+//!
+//! The firmware samples the least free heap since startup and the least
+//! unused stack of each task. [`Watermarks::low`] names what is below the
+//! thresholds of `memory` in the configuration, and [`newly_low`] what went
+//! below since the previous sample, so each warning is raised once.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::config::MemoryConfig;
+
+/// Name of the heap among the low ones, next to the names of tasks.
+pub const HEAP: &str = "heap";
+
+/// The stack of one task.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TaskStack {
+    pub name: String,
+    /// Least unused stack in bytes since the task started.
+    pub headroom: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Watermarks {
+    /// Least free heap in bytes since startup.
+    pub min_free_heap: u32,
+    pub tasks: Vec<TaskStack>,
+}
+
+impl Watermarks {
+    /// [`HEAP`] and the names of the tasks below the thresholds, in that
+    /// order.
+    pub fn low(&self, config: &MemoryConfig) -> Vec<String> {
+        let heap = (self.min_free_heap < config.low_heap).then(|| HEAP.to_string());
+        let tasks = self
+            .tasks
+            .iter()
+            .filter(|task| task.headroom < config.low_stack)
+            .map(|task| task.name.clone());
+        heap.into_iter().chain(tasks).collect()
+    }
+}
+
+/// The names in `now` that were not in `before`.
+pub fn newly_low(before: &[String], now: &[String]) -> Vec<String> {
+    now.iter()
+        .filter(|name| !before.contains(name))
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,102 @@
+use busier_core::arbiter::{Arbiter, Claim, Source};
+use busier_core::config::SourcesConfig;
+use busier_core::status::Status;
+
+const MINUTE: u64 = 60;
+
+fn claim(status: Status, since: u64) -> Claim<()> {
+    Claim {
+        status,
+        since,
+        back_at: None,
+        until: None,
+    }
+}
+
+fn winner(arbiter: &Arbiter<()>, now: u64) -> Option<(Source, Status)> {
+    arbiter
+        .winner(&SourcesConfig::default(), now)
+        .map(|(source, claim)| (source, claim.status))
+}
+
+#[test]
+fn no_claims_no_winner() {
+    assert_eq!(winner(&Arbiter::new(), 0), None);
+}
+
+#[test]
+fn manual_claim_holds_against_the_calendar() {
+    let mut arbiter = Arbiter::new();
+    arbiter.claim(Source::Manual, claim(Status::Dnd, 0));
+    arbiter.claim(Source::Calendar, claim(Status::Free, 10 * MINUTE));
+    assert_eq!(winner(&arbiter, 10 * MINUTE), Some((Source::Manual, Status::Dnd)));
+
+    // Once the hold ends, the more recent calendar claim wins
+    assert_eq!(winner(&arbiter, 120 * MINUTE), Some((Source::Calendar, Status::Free)));
+}
+
+#[test]
+fn most_recent_claim_wins_without_holds() {
+    let mut arbiter = Arbiter::new();
+    arbiter.claim(Source::Presence, claim(Status::Dnd, 0));
+    arbiter.claim(Source::Integration, claim(Status::Away, MINUTE));
+    assert_eq!(winner(&arbiter, MINUTE), Some((Source::Integration, Status::Away)));
+}
+
+#[test]
+fn equal_priorities_take_the_latest_holding_claim() {
+    let mut arbiter = Arbiter::new();
+    arbiter.claim(Source::Manual, claim(Status::Dnd, 0));
+    arbiter.claim(Source::Button, claim(Status::Free, MINUTE));
+    assert_eq!(winner(&arbiter, MINUTE), Some((Source::Button, Status::Free)));
+}
+
+#[test]
+fn priorities_and_holds_are_configurable() {
+    let mut config = SourcesConfig::default();
+    config.calendar.priority = 9;
+    config.calendar.hold_mins = 30;
+
+    let mut arbiter = Arbiter::new();
+    arbiter.claim(Source::Calendar, claim(Status::Dnd, 0));
+    arbiter.claim(Source::Manual, claim(Status::Free, MINUTE));
+    let (source, _) = arbiter.winner(&config, MINUTE).unwrap();
+    assert_eq!(source, Source::Calendar);
+}
+
+#[test]
+fn expired_lease_falls_back_to_the_other_claims() {
+    let mut arbiter = Arbiter::new();
+    arbiter.claim(Source::Manual, claim(Status::Free, 0));
+    let leased = Claim {
+        until: Some(200 * MINUTE),
+        ..claim(Status::Dnd, 150 * MINUTE)
+    };
+    arbiter.claim(Source::Integration, leased);
+    assert_eq!(winner(&arbiter, 160 * MINUTE), Some((Source::Integration, Status::Dnd)));
+
+    // Past the lease, the claim no longer counts even before it is expired
+    assert_eq!(winner(&arbiter, 200 * MINUTE), Some((Source::Manual, Status::Free)));
+    assert_eq!(arbiter.expire(200 * MINUTE), vec![Source::Integration]);
+    assert!(arbiter.get(Source::Integration).is_none());
+    assert!(arbiter.expire(200 * MINUTE).is_empty());
+}
+
+#[test]
+fn expired_lease_alone_has_no_winner() {
+    let mut arbiter = Arbiter::new();
+    let leased = Claim {
+        until: Some(MINUTE),
+        ..claim(Status::Dnd, 0)
+    };
+    arbiter.claim(Source::Presence, leased);
+    assert_eq!(winner(&arbiter, MINUTE), None);
+}
+
+#[test]
+fn parses_source_names() {
+    for source in Source::ALL {
+        assert_eq!(Source::parse(source.as_str()), Some(source));
+    }
+    assert_eq!(Source::parse("robot"), None);
+}
@@ -0,0 +1,52 @@
+use busier_core::arbiter::Source;
+use busier_core::audit::{AuditLog, Cause, Entry, Origin, CAPACITY};
+use busier_core::status::Status;
+
+fn entry(uptime_secs: u64) -> Entry {
+    Entry {
+        time: None,
+        uptime_secs,
+        from: Status::Free,
+        to: Status::Dnd,
+        cause: Cause::Claim,
+        source: Some(Source::Manual),
+        origin: Origin::default(),
+    }
+}
+
+#[test]
+fn lists_the_most_recent_first() {
+    let mut log = AuditLog::new();
+    log.push(entry(1));
+    log.push(entry(2));
+    let uptimes: Vec<_> = log.latest().map(|entry| entry.uptime_secs).collect();
+    assert_eq!(uptimes, [2, 1]);
+}
+
+#[test]
+fn full_log_drops_the_oldest() {
+    let mut log = AuditLog::new();
+    for i in 0..=CAPACITY as u64 {
+        log.push(entry(i));
+    }
+    assert_eq!(log.len(), CAPACITY);
+    assert_eq!(log.latest().last().unwrap().uptime_secs, 1);
+}
+
+#[test]
+fn serializes_the_attribution() {
+    let entry = Entry {
+        cause: Cause::LeaseExpired,
+        source: None,
+        origin: Origin {
+            peer: Some("192.168.1.20".to_string()),
+            token: Some("set_token".to_string()),
+        },
+        ..entry(5)
+    };
+    let json = serde_json::to_value(&entry).unwrap();
+    assert_eq!(json["cause"], "lease_expired");
+    assert_eq!(json["source"], serde_json::Value::Null);
+    assert_eq!(json["origin"]["peer"], "192.168.1.20");
+    assert_eq!(json["to"], "dnd");
+}
@@ -0,0 +1,63 @@
+use busier_core::captive::{dns_answer, wifi_qr_payload, DNS_TTL};
+
+const IP: [u8; 4] = [192, 168, 4, 1];
+
+// A query with "recursion desired" for `name` of `qtype`, class IN
+fn query(name: &str, qtype: u16) -> Vec<u8> {
+    let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&qtype.to_be_bytes());
+    query.extend_from_slice(&1u16.to_be_bytes());
+    query
+}
+
+#[test]
+fn answers_every_name_with_the_device() {
+    let query = query("connectivitycheck.gstatic.com", 1);
+    let response = dns_answer(&query, IP).unwrap();
+
+    assert_eq!(&response[..2], &[0x12, 0x34]);
+    assert_eq!(&response[2..4], &[0x85, 0x00]);
+    assert_eq!(&response[4..8], &[0, 1, 0, 1]);
+    assert_eq!(&response[12..query.len()], &query[12..]);
+
+    let answer = &response[query.len()..];
+    assert_eq!(&answer[..6], &[0xC0, 0x0C, 0, 1, 0, 1]);
+    assert_eq!(&answer[6..10], &DNS_TTL.to_be_bytes());
+    assert_eq!(&answer[10..], &[0, 4, 192, 168, 4, 1]);
+}
+
+#[test]
+fn answers_other_types_with_no_records() {
+    let query = query("captive.apple.com", 28);
+    let response = dns_answer(&query, IP).unwrap();
+    assert_eq!(&response[6..8], &[0, 0]);
+    assert_eq!(response.len(), query.len());
+}
+
+#[test]
+fn ignores_responses_and_malformed_queries() {
+    let mut response = query("example.com", 1);
+    response[2] |= 0x80;
+    assert_eq!(dns_answer(&response, IP), None);
+
+    let truncated = query("example.com", 1);
+    assert_eq!(dns_answer(&truncated[..truncated.len() - 2], IP), None);
+    assert_eq!(dns_answer(&[0; 5], IP), None);
+}
+
+#[test]
+fn wifi_payload_escapes_special_characters() {
+    assert_eq!(
+        wifi_qr_payload("busier-A1B2C3", ""),
+        "WIFI:T:nopass;S:busier-A1B2C3;;"
+    );
+    assert_eq!(
+        wifi_qr_payload("Office;1", "pa:ss\\word"),
+        r"WIFI:T:WPA;S:Office\;1;P:pa\:ss\\word;;"
+    );
+}
@@ -0,0 +1,225 @@
+use busier_core::board::Board;
+use busier_core::config::{parse_hhmm, ApiToken, CertPin, Config, Icon, PinsConfig, REDACTED};
+use busier_core::pins::{Chip, PinError};
+use busier_core::status::Status;
+
+#[test]
+fn partial_documents_keep_defaults() {
+    let config: Config = serde_json::from_str(r#"{"device_name": "Office"}"#).unwrap();
+    assert_eq!(config.device_name(), "Office");
+    assert_eq!(config.relay.statuses, vec![Status::Dnd]);
+    assert_eq!(config.displays().len(), 1);
+    assert!(config.http.lru_purge);
+    assert!(!config.coap.writable);
+}
+
+#[test]
+fn updates_replace_only_the_sections_they_contain() {
+    let mut current = Config {
+        device_name: "Office".to_string(),
+        users: vec!["Alice".to_string()],
+        ..Default::default()
+    };
+    current.https.enabled = true;
+    current.https.port = 8443;
+
+    let serde_json::Value::Object(patch) = serde_json::json!({
+        "users": ["Bob"],
+        "https": {"enabled": true},
+    }) else {
+        unreachable!()
+    };
+    let updated = current.merged(patch).unwrap();
+    assert_eq!(updated.device_name(), "Office");
+    assert_eq!(updated.users, vec!["Bob".to_string()]);
+    assert!(updated.https.enabled);
+    assert_eq!(updated.https.port, Config::default().https.port);
+}
+
+#[test]
+fn round_trips_through_json() {
+    let config = Config {
+        users: vec!["Alice".to_string()],
+        ..Default::default()
+    };
+    let json = serde_json::to_string(&config).unwrap();
+    let parsed: Config = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.users, config.users);
+}
+
+#[test]
+fn redacted_secrets_are_restored() {
+    let mut stored = Config::default();
+    stored.admin.password = "hunter2".to_string();
+    stored.admin.set_token = "s3cret".to_string();
+    stored.admin.tokens = vec![ApiToken {
+        name: "dashboard".to_string(),
+        token: "d4sh".to_string(),
+        ..Default::default()
+    }];
+    stored.memory.mqtt_password = "mqttpw".to_string();
+
+    let mut posted = stored.redacted();
+    assert_eq!(posted.admin.password, REDACTED);
+    assert_eq!(posted.admin.set_token, REDACTED);
+    assert_eq!(posted.admin.tokens[0].token, REDACTED);
+    assert_eq!(posted.memory.mqtt_password, REDACTED);
+    posted.restore_secrets(&stored);
+    assert_eq!(posted.admin.password, "hunter2");
+    assert_eq!(posted.admin.set_token, "s3cret");
+    assert_eq!(posted.admin.tokens[0].token, "d4sh");
+    assert_eq!(posted.memory.mqtt_password, "mqttpw");
+}
+
+#[test]
+fn secrets_are_taken_out_and_put_back() {
+    let mut config = Config::default();
+    config.admin.password = "hunter2".to_string();
+    config.admin.tokens = vec![ApiToken {
+        name: "dashboard".to_string(),
+        token: "d4sh".to_string(),
+        ..Default::default()
+    }];
+    config.hue.key = "huekey".to_string();
+    config.esphome.encryption_key = "bm9pc2VrZXk=".to_string();
+
+    let secrets = config.take_secrets();
+    assert_eq!(secrets["admin.password"], "hunter2");
+    assert_eq!(secrets["admin.tokens.dashboard"], "d4sh");
+    assert_eq!(secrets["snmp.community"], "public");
+    assert!(!secrets.contains_key("admin.set_token"));
+
+    let plain = serde_json::to_string(&config).unwrap();
+    for secret in ["hunter2", "d4sh", "huekey", "bm9pc2VrZXk="] {
+        assert!(!plain.contains(secret), "{} in {}", secret, plain);
+    }
+
+    let mut loaded: Config = serde_json::from_str(&plain).unwrap();
+    loaded.put_secrets(&secrets);
+    assert_eq!(loaded.admin.password, "hunter2");
+    assert_eq!(loaded.admin.tokens[0].token, "d4sh");
+    assert_eq!(loaded.hue.key, "huekey");
+    assert_eq!(loaded.esphome.encryption_key, "bm9pc2VrZXk=");
+    assert_eq!(loaded.snmp.community, "public");
+}
+
+#[test]
+fn debug_output_redacts_secrets() {
+    let mut config = Config::default();
+    config.admin.password = "hunter2".to_string();
+    config.memory.mqtt_password = "mqttpw".to_string();
+
+    let debug = format!("{:?}", config);
+    assert!(!debug.contains("hunter2"));
+    assert!(!debug.contains("mqttpw"));
+    assert!(debug.contains(REDACTED));
+}
+
+#[test]
+fn parses_times_of_day() {
+    assert_eq!(parse_hhmm("09:30"), Some(570));
+    assert_eq!(parse_hhmm("24:00"), None);
+    assert_eq!(parse_hhmm("9"), None);
+}
+
+#[test]
+fn default_pins_are_valid() {
+    for chip in [Chip::Esp32, Chip::Esp32C3, Chip::Esp32S3] {
+        assert_eq!(chip.default_pins().validate(chip, &[]), Ok(()), "{:?}", chip);
+    }
+    assert_eq!(PinsConfig::default().validate(Chip::Esp32, &[13, 14, 15]), Ok(()));
+}
+
+#[test]
+fn unusable_pins_are_rejected() {
+    let pins = |led| PinsConfig {
+        led,
+        ..Default::default()
+    };
+    assert_eq!(pins(7).validate(Chip::Esp32, &[]), Err(PinError::Flash(7)));
+    assert_eq!(pins(35).validate(Chip::Esp32, &[]), Err(PinError::InputOnly(35)));
+    assert_eq!(pins(24).validate(Chip::Esp32, &[]), Err(PinError::Missing(24)));
+    assert_eq!(pins(21).validate(Chip::Esp32, &[]), Err(PinError::Duplicate(21)));
+    assert_eq!(pins(4).validate(Chip::Esp32, &[4, 12]), Err(PinError::Reserved(4)));
+}
+
+#[test]
+fn pins_are_checked_for_the_chip() {
+    let c3 = PinsConfig {
+        led: 18,
+        ..Chip::Esp32C3.default_pins()
+    };
+    assert_eq!(c3.validate(Chip::Esp32C3, &[]), Err(PinError::Usb(18)));
+    assert_eq!(
+        PinsConfig::default().validate(Chip::Esp32C3, &[]),
+        Err(PinError::Missing(22))
+    );
+    assert_eq!(
+        Chip::Esp32S3.default_pins().validate(Chip::Esp32, &[]),
+        Err(PinError::Flash(8))
+    );
+}
+
+#[test]
+fn board_profiles_are_valid() {
+    for board in [
+        Board::HeltecWifiKit32,
+        Board::TtgoLora32,
+        Board::TtgoTDisplay,
+        Board::WaveshareEpaperEsp32,
+    ] {
+        assert_eq!(board.profile().unwrap().validate(Chip::Esp32, &[]), Ok(()), "{:?}", board);
+    }
+    assert_eq!(Board::Custom.profile(), None);
+}
+
+#[test]
+fn board_profiles_clash_with_fixed_pins() {
+    let heltec = Board::HeltecWifiKit32.profile().unwrap();
+    assert_eq!(heltec.validate(Chip::Esp32, &[16, 17]), Err(PinError::Reserved(16)));
+    assert_eq!(heltec.validate(Chip::Esp32, &[13, 14, 15]), Err(PinError::Reserved(15)));
+}
+
+#[test]
+fn cert_pins_match_hosts_and_parse_fingerprints() {
+    let pin = |host: &str, sha256: &str| CertPin {
+        host: host.to_string(),
+        sha256: sha256.to_string(),
+    };
+    let pins = [
+        pin("matrix.example.org", &"AB:".repeat(32)[..95]),
+        pin("10.0.0.5", &"0f".repeat(32)),
+    ];
+
+    let found = CertPin::find(&pins, "https://Matrix.Example.org:8448/_matrix/client").unwrap();
+    assert_eq!(found.host, "matrix.example.org");
+    assert_eq!(CertPin::find(&pins, "https://10.0.0.5/status").unwrap().host, "10.0.0.5");
+    assert!(CertPin::find(&pins, "http://10.0.0.5/status").is_none());
+    assert!(CertPin::find(&pins, "https://example.org/").is_none());
+
+    assert_eq!(pins[0].fingerprint(), Some([0xAB; 32]));
+    assert_eq!(pins[1].fingerprint(), Some([0x0F; 32]));
+    assert_eq!(pin("x", &"0f".repeat(31)).fingerprint(), None);
+    assert_eq!(pin("x", &"zz".repeat(32)).fingerprint(), None);
+}
+
+#[test]
+fn status_styles_keep_defaults_and_parse_colors() {
+    let config: Config = serde_json::from_str(
+        r##"{"styles": {"dnd": {"color": "#FF8000", "relay": true, "icon": "cross"}}}"##,
+    )
+    .unwrap();
+    let dnd = config.styles.get(Status::Dnd);
+    assert_eq!(dnd.rgb(), Some([0xFF, 0x80, 0x00]));
+    assert_eq!(dnd.relay, Some(true));
+    assert_eq!(dnd.icon, Icon::Cross);
+    assert!(!dnd.led);
+
+    let free = config.styles.get(Status::Free);
+    assert_eq!(free.rgb(), Some([0, 255, 0]));
+    assert!(!config.styles.get(Status::Away).light);
+
+    let mut style = free.clone();
+    style.color = "green".to_string();
+    assert_eq!(style.rgb(), None);
+}
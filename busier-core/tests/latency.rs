@@ -0,0 +1,38 @@
+use busier_core::latency::{Histogram, BOUNDS_US};
+
+#[test]
+fn empty_histogram_reads_zero() {
+    let histogram = Histogram::new();
+    assert_eq!(histogram.count(), 0);
+    assert_eq!(histogram.percentile(50), 0);
+    assert_eq!(histogram.max_us(), 0);
+}
+
+#[test]
+fn percentiles_come_from_the_buckets() {
+    let mut histogram = Histogram::new();
+    for _ in 0..90 {
+        histogram.record(800);
+    }
+    for _ in 0..10 {
+        histogram.record(40_000);
+    }
+    assert_eq!(histogram.count(), 100);
+    assert_eq!(histogram.percentile(50), 1_000);
+    assert_eq!(histogram.percentile(90), 1_000);
+    // Capped at the longest duration seen rather than the bucket's bound
+    assert_eq!(histogram.percentile(95), 40_000);
+    assert_eq!(histogram.max_us(), 40_000);
+    assert_eq!(histogram.sum_us(), 90 * 800 + 10 * 40_000);
+}
+
+#[test]
+fn long_durations_land_in_the_last_bucket() {
+    let mut histogram = Histogram::new();
+    histogram.record(60_000_000);
+    let buckets: Vec<_> = histogram.cumulative().collect();
+    assert_eq!(buckets.len(), BOUNDS_US.len() + 1);
+    assert_eq!(buckets[0], (Some(250), 0));
+    assert_eq!(buckets.last(), Some(&(None, 1)));
+    assert_eq!(histogram.percentile(99), 60_000_000);
+}
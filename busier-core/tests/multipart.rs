@@ -0,0 +1,57 @@
+use busier_core::multipart::{boundary, Event, MultipartError, Parser, Part};
+
+const BODY: &[u8] = b"preamble\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"note\"\r\n\
+\r\n\
+hello\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"cert.pem\"\r\n\
+Content-Type: application/x-pem-file\r\n\
+\r\n\
+-----BEGIN CERTIFICATE-----\r\n--XY\r\n\
+--XyZ--\r\n\
+epilogue";
+
+// Parses the body in pieces of `size` bytes into the parts and their data
+fn parse(body: &[u8], size: usize) -> Result<Vec<(Part, Vec<u8>)>, MultipartError> {
+    let mut parser = Parser::new("XyZ");
+    let mut parts: Vec<(Part, Vec<u8>)> = Vec::new();
+    for piece in body.chunks(size) {
+        parser.feed(piece, |event| match event {
+            Event::Part(part) => parts.push((part, Vec::new())),
+            Event::Data(data) => parts.last_mut().unwrap().1.extend_from_slice(data),
+        })?;
+    }
+    parser.finish()?;
+    Ok(parts)
+}
+
+#[test]
+fn finds_the_boundary() {
+    assert_eq!(boundary("multipart/form-data; boundary=XyZ"), Some("XyZ"));
+    assert_eq!(boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b\""), Some("a b"));
+    assert_eq!(boundary("application/json"), None);
+    assert_eq!(boundary("multipart/form-data"), None);
+}
+
+#[test]
+fn splits_the_parts_however_the_body_arrives() {
+    for size in [1, 2, 3, 7, 64, BODY.len()] {
+        let parts = parse(BODY, size).unwrap();
+        assert_eq!(parts.len(), 2, "pieces of {}", size);
+        assert_eq!(parts[0].0.name, "note");
+        assert_eq!(parts[0].0.filename, None);
+        assert_eq!(parts[0].1, b"hello");
+        assert_eq!(parts[1].0.filename.as_deref(), Some("cert.pem"));
+        assert_eq!(parts[1].0.content_type.as_deref(), Some("application/x-pem-file"));
+        assert_eq!(parts[1].1, b"-----BEGIN CERTIFICATE-----\r\n--XY");
+    }
+}
+
+#[test]
+fn rejects_broken_bodies() {
+    assert_eq!(parse(&BODY[..BODY.len() - 20], 16), Err(MultipartError::Truncated));
+    let unnamed = b"--XyZ\r\nContent-Type: text/plain\r\n\r\nx\r\n--XyZ--";
+    assert_eq!(parse(unnamed, 16), Err(MultipartError::MissingName));
+}
@@ -0,0 +1,65 @@
+use busier_core::outbox::{retry_secs, Outbox, CAPACITY, MAX_RETRY_SECS};
+
+#[test]
+fn delivers_in_order() {
+    let mut outbox = Outbox::default();
+    outbox.push("first".to_string(), "t1".to_string());
+    outbox.push("second".to_string(), "t2".to_string());
+    assert_eq!(outbox.front().unwrap().message, "first");
+    outbox.delivered();
+    assert_eq!(outbox.front().unwrap().message, "second");
+    outbox.delivered();
+    assert!(outbox.is_empty());
+}
+
+#[test]
+fn failures_back_off_exponentially() {
+    let mut outbox = Outbox::default();
+    outbox.push("status".to_string(), "t1".to_string());
+    assert_eq!(outbox.failed(), 5);
+    assert_eq!(outbox.failed(), 10);
+    assert_eq!(outbox.failed(), 20);
+    assert_eq!(outbox.front().unwrap().attempts, 3);
+    assert_eq!(retry_secs(1000), MAX_RETRY_SECS);
+}
+
+#[test]
+fn full_queue_drops_the_oldest() {
+    let mut outbox = Outbox::default();
+    for i in 0..CAPACITY {
+        assert!(outbox.push(i.to_string(), i.to_string()));
+    }
+    assert!(!outbox.push("new".to_string(), "new".to_string()));
+    assert_eq!(outbox.len(), CAPACITY);
+    assert_eq!(outbox.front().unwrap().message, "1");
+}
+
+#[test]
+fn survives_a_round_trip_through_json() {
+    let mut outbox = Outbox::default();
+    outbox.push("status".to_string(), "t1".to_string());
+    outbox.failed();
+    let json = serde_json::to_vec(&outbox).unwrap();
+    assert_eq!(serde_json::from_slice::<Outbox>(&json).unwrap(), outbox);
+}
+
+#[test]
+fn retries_keep_the_transaction_id() {
+    let mut outbox = Outbox::default();
+    outbox.push("status".to_string(), "busier-1".to_string());
+    outbox.failed();
+    let json = serde_json::to_vec(&outbox).unwrap();
+    let restored: Outbox = serde_json::from_slice(&json).unwrap();
+    assert_eq!(restored.front().unwrap().txn_id, "busier-1");
+    assert_eq!(restored.front().unwrap().attempts, 1);
+}
+
+#[test]
+fn older_queues_get_transaction_ids() {
+    let json = r#"{"queue": [{"message": "status", "attempts": 2}]}"#;
+    let mut outbox: Outbox = serde_json::from_str(json).unwrap();
+    assert_eq!(outbox.front().unwrap().txn_id, "");
+    assert!(outbox.assign_txn_ids(|| "busier-2".to_string()));
+    assert_eq!(outbox.front().unwrap().txn_id, "busier-2");
+    assert!(!outbox.assign_txn_ids(|| "busier-3".to_string()));
+}
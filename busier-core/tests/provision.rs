@@ -0,0 +1,55 @@
+use busier_core::provision::{ProvisionError, Provisioning, WifiCredentials};
+
+#[test]
+fn parses_a_document_with_wifi_and_configuration() {
+    let provisioning: Provisioning = serde_json::from_str(
+        r#"{
+            "wifi": {"ssid": "Office", "password": "correct horse"},
+            "config": {"device_name": "Room 1", "admin": {"password": "hunter2", "tokens": [{"name": "dashboard", "token": "d4sh"}]}}
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(provisioning.validate(), Ok(()));
+
+    let config = provisioning.config.unwrap();
+    assert_eq!(config.device_name(), "Room 1");
+    assert_eq!(config.admin.username, "admin");
+    assert_eq!(config.admin.tokens[0].token, "d4sh");
+    assert_eq!(provisioning.wifi.unwrap().ssid, "Office");
+}
+
+#[test]
+fn rejects_incomplete_documents() {
+    let empty: Provisioning = serde_json::from_str("{}").unwrap();
+    assert_eq!(empty.validate(), Err(ProvisionError::Empty));
+
+    let no_password: Provisioning =
+        serde_json::from_str(r#"{"config": {"device_name": "Room 1"}}"#).unwrap();
+    assert_eq!(no_password.validate(), Err(ProvisionError::AdminPassword));
+
+    assert!(serde_json::from_str::<Provisioning>(r#"{"wlan": {}}"#).is_err());
+}
+
+#[test]
+fn checks_wifi_credentials() {
+    let mut wifi = WifiCredentials {
+        ssid: "Office".to_string(),
+        password: String::new(),
+    };
+    assert_eq!(wifi.validate(), Ok(()));
+
+    wifi.password = "short".to_string();
+    assert_eq!(wifi.validate(), Err(ProvisionError::Password));
+
+    wifi.ssid = String::new();
+    assert_eq!(wifi.validate(), Err(ProvisionError::Ssid));
+}
+
+#[test]
+fn debug_output_redacts_the_password() {
+    let wifi = WifiCredentials {
+        ssid: "Office".to_string(),
+        password: "correct horse".to_string(),
+    };
+    assert!(!format!("{:?}", wifi).contains("correct horse"));
+}
@@ -0,0 +1,41 @@
+use busier_core::ratelimit::Limiter;
+
+const SECOND: u64 = 1000;
+
+#[test]
+fn bursts_up_to_the_limit_then_keeps_to_the_rate() {
+    let mut limiter = Limiter::new();
+    for _ in 0..12 {
+        assert_eq!(limiter.check(12, 0), Ok(()));
+    }
+    // Twelve a minute refill one request every five seconds
+    let wait = limiter.check(12, 0).unwrap_err();
+    assert!((5 * SECOND..=5 * SECOND + 1).contains(&wait), "{}", wait);
+    assert!(limiter.check(12, 4 * SECOND).is_err());
+    assert_eq!(limiter.check(12, 5 * SECOND), Ok(()));
+
+    let usage = limiter.usage();
+    assert_eq!(usage.requests, 13);
+    assert_eq!(usage.throttled, 2);
+    assert_eq!(usage.last_ms, Some(5 * SECOND));
+}
+
+#[test]
+fn idle_clients_get_no_more_than_a_burst() {
+    let mut limiter = Limiter::new();
+    assert_eq!(limiter.check(2, 0), Ok(()));
+    for _ in 0..2 {
+        assert_eq!(limiter.check(2, 3600 * SECOND), Ok(()));
+    }
+    assert!(limiter.check(2, 3600 * SECOND).is_err());
+}
+
+#[test]
+fn zero_means_no_limit() {
+    let mut limiter = Limiter::new();
+    for _ in 0..1000 {
+        assert_eq!(limiter.check(0, 0), Ok(()));
+    }
+    assert_eq!(limiter.usage().requests, 1000);
+    assert_eq!(limiter.usage().throttled, 0);
+}
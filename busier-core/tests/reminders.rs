@@ -0,0 +1,65 @@
+use busier_core::hal::LocalTime;
+use busier_core::reminders::Reminder;
+use busier_core::rules::{Condition, Facts, RuleError};
+
+fn at(hour: u8, minute: u8, working_hours: bool) -> Facts {
+    Facts {
+        now: Some(LocalTime {
+            weekday: 0,
+            hour,
+            minute,
+            second: 0,
+        }),
+        working_hours,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn daily_reminders_fire_once_in_their_minute() {
+    let standup: Reminder =
+        serde_json::from_str(r#"{"message": "Standup", "at": "10:00"}"#).unwrap();
+    let mut since = 0;
+    assert!(!standup.check(&at(9, 59, true), 100, &mut since));
+    assert!(standup.check(&at(10, 0, true), 130, &mut since));
+    assert_eq!(since, 130);
+    assert!(!standup.check(&at(10, 0, true), 160, &mut since));
+    assert!(!standup.check(&Facts::default(), 86_530, &mut since));
+}
+
+#[test]
+fn intervals_start_over_while_conditions_fail() {
+    let stretch = Reminder {
+        message: "Stretch".to_string(),
+        every_mins: Some(50),
+        when: vec![Condition::WorkingHours(true)],
+        ..Default::default()
+    };
+    let mut since = 0;
+    assert!(!stretch.check(&at(8, 0, false), 10_000, &mut since));
+    assert_eq!(since, 10_000);
+    assert!(!stretch.check(&at(8, 49, true), 12_999, &mut since));
+    assert!(stretch.check(&at(8, 50, true), 13_000, &mut since));
+    assert!(!stretch.check(&at(9, 0, true), 13_600, &mut since));
+    assert!(stretch.check(&at(9, 40, true), 16_000, &mut since));
+}
+
+#[test]
+fn invalid_reminders_are_rejected() {
+    let mut reminder = Reminder {
+        at: Some("10:00".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(reminder.validate(), Ok(()));
+    reminder.every_mins = Some(50);
+    assert_eq!(reminder.validate(), Err(RuleError::Trigger));
+    reminder.at = None;
+    reminder.every_mins = Some(0);
+    assert_eq!(reminder.validate(), Err(RuleError::Trigger));
+    reminder.at = Some("25:00".to_string());
+    reminder.every_mins = None;
+    assert_eq!(
+        reminder.validate(),
+        Err(RuleError::Time("25:00".to_string()))
+    );
+}
@@ -0,0 +1,27 @@
+use busier_core::reset::{crashes, is_safe_mode, ResetReason};
+
+#[test]
+fn counts_crashes_in_a_row() {
+    let mut count = 0;
+    for _ in 0..2 {
+        count = crashes(count, ResetReason::Panic);
+        assert!(!is_safe_mode(count));
+    }
+    count = crashes(count, ResetReason::Watchdog);
+    assert_eq!(count, 3);
+    assert!(is_safe_mode(count));
+}
+
+#[test]
+fn other_starts_forget_the_crashes() {
+    for reason in [
+        ResetReason::PowerOn,
+        ResetReason::External,
+        ResetReason::Software,
+        ResetReason::DeepSleep,
+        ResetReason::Brownout,
+    ] {
+        assert_eq!(crashes(5, reason), 0, "{:?}", reason);
+    }
+    assert_eq!(crashes(u8::MAX, ResetReason::Panic), u8::MAX);
+}
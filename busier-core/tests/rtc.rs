@@ -0,0 +1,35 @@
+use busier_core::rtc::{decode, encode};
+
+// 2026-10-16 14:05:09 UTC, a Friday
+const FRIDAY: u64 = 1_792_159_509;
+
+#[test]
+fn encodes_bcd_registers() {
+    assert_eq!(
+        encode(FRIDAY),
+        Some([0x09, 0x05, 0x14, 5, 0x16, 0x10, 0x26])
+    );
+    assert_eq!(
+        decode(&[0x09, 0x05, 0x14, 5, 0x16, 0x10, 0x26]),
+        Some(FRIDAY)
+    );
+}
+
+#[test]
+fn round_trips_across_leap_days_and_centuries() {
+    // 2000-02-29, 2024-12-31 23:59:59 and 2100-03-01
+    for unix in [951_782_400, 1_735_689_599, 4_107_542_400] {
+        assert_eq!(decode(&encode(unix).unwrap()), Some(unix), "{}", unix);
+    }
+    assert_eq!(encode(4_107_542_400).unwrap()[5] & 0x80, 0x80);
+}
+
+#[test]
+fn rejects_invalid_registers() {
+    // Before 2000, and a 12-hour clock
+    assert_eq!(encode(946_684_799), None);
+    assert_eq!(decode(&[0x00, 0x00, 0x52, 1, 0x01, 0x01, 0x26]), None);
+    // February 30th and a non-BCD minute
+    assert_eq!(decode(&[0x00, 0x00, 0x00, 1, 0x30, 0x02, 0x26]), None);
+    assert_eq!(decode(&[0x00, 0x0A, 0x00, 1, 0x01, 0x01, 0x26]), None);
+}
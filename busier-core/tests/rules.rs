@@ -0,0 +1,90 @@
+use busier_core::arbiter::Source;
+use busier_core::config::{Config, TimeRange};
+use busier_core::hal::LocalTime;
+use busier_core::rules::{first_match, Condition, Facts, Rule, RuleError};
+use busier_core::status::Status;
+
+fn facts(hour: u8, claims: Vec<(Source, Status)>) -> Facts {
+    Facts {
+        now: Some(LocalTime {
+            weekday: 0,
+            hour,
+            minute: 0,
+            second: 0,
+        }),
+        claims,
+        working_hours: true,
+        door_open: None,
+    }
+}
+
+fn meetings() -> Rule {
+    serde_json::from_str(
+        r#"{
+            "name": "meetings",
+            "when": [
+                { "claim": { "source": "calendar", "status": "dnd" } },
+                { "time": { "start": "09:00", "end": "17:00" } }
+            ],
+            "then": { "status": "dnd", "led": true }
+        }"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn rules_parse_from_json() {
+    let rule = meetings();
+    assert_eq!(rule.when.len(), 2);
+    assert_eq!(rule.then.status, Some(Status::Dnd));
+    assert_eq!(rule.then.led, Some(true));
+
+    let config: Config = serde_json::from_str(r#"{"rules": [{"name": "empty"}]}"#).unwrap();
+    assert_eq!(config.rules[0].when, vec![]);
+}
+
+#[test]
+fn all_conditions_must_hold() {
+    let rules = [meetings()];
+    let busy = vec![(Source::Calendar, Status::Dnd)];
+    assert_eq!(first_match(&rules, &facts(10, busy.clone())), Some(&rules[0]));
+    assert_eq!(first_match(&rules, &facts(18, busy)), None);
+    assert_eq!(first_match(&rules, &facts(10, vec![])), None);
+}
+
+#[test]
+fn time_conditions_need_a_clock() {
+    let condition = Condition::Time(TimeRange {
+        start: "00:00".to_string(),
+        end: "23:59".to_string(),
+    });
+    assert!(!condition.holds(&Facts::default()));
+}
+
+#[test]
+fn door_conditions_need_a_sensor() {
+    assert!(!Condition::DoorOpen(false).holds(&Facts::default()));
+}
+
+#[test]
+fn first_matching_rule_wins() {
+    let always = Rule {
+        name: "always".to_string(),
+        ..Default::default()
+    };
+    let rules = [meetings(), always];
+    assert_eq!(first_match(&rules, &facts(10, vec![])).unwrap().name, "always");
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let mut rule = meetings();
+    assert_eq!(rule.validate(), Ok(()));
+    rule.when.push(Condition::Weekdays(vec![0, 7]));
+    assert_eq!(rule.validate(), Err(RuleError::Weekday(7)));
+    rule.when[1] = Condition::Time(TimeRange {
+        start: "9am".to_string(),
+        end: "17:00".to_string(),
+    });
+    assert_eq!(rule.validate(), Err(RuleError::Time("9am".to_string())));
+}
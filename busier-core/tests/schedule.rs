@@ -0,0 +1,81 @@
+use busier_core::config::{QuietHoursConfig, TimeRange, WorkingHoursConfig};
+use busier_core::hal::{Clock, LocalTime};
+use busier_core::schedule::{quiet_hours_at, working_hours_at};
+use busier_core::status::{shown, Status};
+
+// Clock pinned to one moment; None for a clock that was never set
+struct FixedClock(Option<LocalTime>);
+
+impl FixedClock {
+    fn at(weekday: u8, hour: u8, minute: u8) -> Self {
+        FixedClock(Some(LocalTime {
+            weekday,
+            hour,
+            minute,
+            second: 0,
+        }))
+    }
+}
+
+impl Clock for FixedClock {
+    fn local_now(&self) -> Option<LocalTime> {
+        self.0
+    }
+
+    fn next_occurrence(&self, _minute_of_day: u16) -> Option<u64> {
+        None
+    }
+}
+
+fn working_hours() -> WorkingHoursConfig {
+    WorkingHoursConfig {
+        enabled: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn working_hours_start_on_the_minute() {
+    let hours = working_hours();
+    assert!(!working_hours_at(&hours, &FixedClock::at(0, 8, 59)));
+    assert!(working_hours_at(&hours, &FixedClock::at(0, 9, 0)));
+    assert!(working_hours_at(&hours, &FixedClock::at(0, 16, 59)));
+    assert!(!working_hours_at(&hours, &FixedClock::at(0, 17, 0)));
+}
+
+#[test]
+fn days_off_are_outside_working_hours() {
+    assert!(!working_hours_at(&working_hours(), &FixedClock::at(5, 12, 0)));
+}
+
+#[test]
+fn unset_clock_counts_as_working_hours() {
+    assert!(working_hours_at(&working_hours(), &FixedClock(None)));
+}
+
+#[test]
+fn disabled_working_hours_always_apply() {
+    let hours = WorkingHoursConfig::default();
+    assert!(working_hours_at(&hours, &FixedClock::at(6, 3, 0)));
+}
+
+#[test]
+fn quiet_hours_wrap_past_midnight() {
+    let quiet = QuietHoursConfig {
+        enabled: true,
+        hours: TimeRange {
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+        },
+    };
+    assert!(quiet_hours_at(&quiet, &FixedClock::at(2, 23, 30)));
+    assert!(quiet_hours_at(&quiet, &FixedClock::at(3, 6, 59)));
+    assert!(!quiet_hours_at(&quiet, &FixedClock::at(3, 7, 0)));
+    assert!(!quiet_hours_at(&quiet, &FixedClock(None)));
+}
+
+#[test]
+fn away_outside_working_hours() {
+    assert_eq!(shown(Status::Dnd, true), Status::Dnd);
+    assert_eq!(shown(Status::Dnd, false), Status::Away);
+}
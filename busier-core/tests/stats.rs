@@ -0,0 +1,62 @@
+use busier_core::stats::{Stamp, Stats, DAYS_KEPT, HOURS_KEPT};
+use busier_core::status::Status;
+
+fn at(date: u32, hour: u8) -> Stamp {
+    Stamp { date, hour }
+}
+
+#[test]
+fn minutes_add_up_per_hour_and_day() {
+    let mut stats = Stats::new();
+    for _ in 0..20 {
+        stats.record_minute(at(20261016, 9), Status::Dnd);
+    }
+    for _ in 0..5 {
+        stats.record_minute(at(20261016, 10), Status::Free);
+    }
+    stats.record_requests(at(20261016, 10), 3);
+
+    let hours: Vec<_> = stats.hours().collect();
+    assert_eq!(hours.len(), 2);
+    assert_eq!(hours[0].hour, Some(9));
+    assert_eq!(hours[0].totals.minutes(Status::Dnd), 20);
+    assert_eq!(hours[1].totals.requests, 3);
+
+    let days: Vec<_> = stats.days().collect();
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0].hour, None);
+    assert_eq!(days[0].totals.dnd, 20);
+    assert_eq!(days[0].totals.free, 5);
+}
+
+#[test]
+fn keeps_only_the_latest_buckets() {
+    let mut stats = Stats::new();
+    for day in 1..=20 {
+        for hour in 0..24 {
+            stats.record_minute(at(20261000 + day, hour), Status::Free);
+        }
+    }
+    assert_eq!(stats.hours().count(), HOURS_KEPT);
+    assert_eq!(stats.days().count(), DAYS_KEPT);
+    assert_eq!(stats.days().next().unwrap().date, 20261007);
+}
+
+#[test]
+fn sums_the_last_days() {
+    let mut stats = Stats::new();
+    for day in 1..=10 {
+        stats.record_minute(at(20261000 + day, 12), Status::Dnd);
+    }
+    assert_eq!(stats.last_days(7).dnd, 7);
+    assert_eq!(stats.last_days(30).dnd, 10);
+}
+
+#[test]
+fn survives_a_round_trip_through_json() {
+    let mut stats = Stats::new();
+    stats.record_minute(at(20261016, 9), Status::Away);
+    stats.record_requests(at(20261016, 9), 2);
+    let json = serde_json::to_vec(&stats).unwrap();
+    assert_eq!(serde_json::from_slice::<Stats>(&json).unwrap(), stats);
+}
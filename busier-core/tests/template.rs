@@ -0,0 +1,34 @@
+use busier_core::template::{escape_html, render};
+
+fn lookup(key: &str) -> Option<&'static str> {
+    match key {
+        "web.knock" => Some("Knock"),
+        "device_name" => Some("Lab &amp; office"),
+        _ => None,
+    }
+}
+
+#[test]
+fn placeholders_are_filled_in() {
+    assert_eq!(
+        render("<title>{{device_name}}</title><b>{{web.knock}}</b>", lookup),
+        "<title>Lab &amp; office</title><b>Knock</b>"
+    );
+}
+
+#[test]
+fn unknown_and_unclosed_placeholders_stay() {
+    assert_eq!(
+        render("{{web.missing}} {{web.knock}}", lookup),
+        "{{web.missing}} Knock"
+    );
+    assert_eq!(render("{{web.knock}} {{web.kn", lookup), "Knock {{web.kn");
+}
+
+#[test]
+fn html_is_escaped() {
+    assert_eq!(
+        escape_html(r#"<script>"Bob's" & co</script>"#),
+        "&lt;script&gt;&quot;Bob&#39;s&quot; &amp; co&lt;/script&gt;"
+    );
+}
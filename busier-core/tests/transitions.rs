@@ -0,0 +1,68 @@
+use busier_core::status::Status;
+use busier_core::transitions::{Chain, Progress, Step, Transition};
+
+fn wrap_up() -> Transition {
+    serde_json::from_str(
+        r#"{
+            "from": "dnd",
+            "to": "free",
+            "steps": [
+                { "label": "Wrapping up", "mins": 5, "led": false, "sound": true },
+                { "label": "Back soon", "mins": 2 }
+            ]
+        }"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn transitions_are_found_by_statuses() {
+    let transitions = [
+        Transition {
+            from: Status::Away,
+            to: Status::Free,
+            steps: vec![],
+        },
+        wrap_up(),
+    ];
+    assert_eq!(
+        Transition::find(&transitions, Status::Dnd, Status::Free),
+        Some(&transitions[1])
+    );
+    assert_eq!(
+        Transition::find(&transitions, Status::Away, Status::Free),
+        None
+    );
+    assert_eq!(
+        Transition::find(&transitions, Status::Free, Status::Dnd),
+        None
+    );
+}
+
+#[test]
+fn chains_run_through_their_steps() {
+    let mut chain = Chain::start(&wrap_up(), 1000).unwrap();
+    assert_eq!(chain.step().label, "Wrapping up");
+    assert_eq!(chain.step().led, Some(false));
+    assert_eq!(chain.remaining(1000), 420);
+
+    assert_eq!(chain.advance(1299), Progress::Same);
+    assert_eq!(chain.advance(1300), Progress::Next);
+    assert_eq!(
+        chain.step(),
+        &Step {
+            label: "Back soon".to_string(),
+            mins: 2,
+            ..Default::default()
+        }
+    );
+    assert_eq!(chain.remaining(1360), 60);
+    assert_eq!(chain.advance(1420), Progress::Done);
+    assert_eq!(chain.to(), Status::Free);
+}
+
+#[test]
+fn late_checks_skip_ended_steps() {
+    let mut chain = Chain::start(&wrap_up(), 0).unwrap();
+    assert_eq!(chain.advance(10_000), Progress::Done);
+}
@@ -0,0 +1,31 @@
+use busier_core::config::MemoryConfig;
+use busier_core::watermark::{newly_low, TaskStack, Watermarks, HEAP};
+
+fn task(name: &str, headroom: u32) -> TaskStack {
+    TaskStack {
+        name: name.to_string(),
+        headroom,
+    }
+}
+
+#[test]
+fn names_what_is_below_the_thresholds() {
+    let config = MemoryConfig::default();
+    let mut watermarks = Watermarks {
+        min_free_heap: 40_000,
+        tasks: vec![task("httpd", 3_200), task("notify", 480), task("main", 900)],
+    };
+    assert_eq!(watermarks.low(&config), vec!["notify"]);
+
+    watermarks.min_free_heap = 12_000;
+    assert_eq!(watermarks.low(&config), vec![HEAP, "notify"]);
+}
+
+#[test]
+fn warns_once_per_name() {
+    let before = vec!["notify".to_string()];
+    let now = vec![HEAP.to_string(), "notify".to_string()];
+    assert_eq!(newly_low(&before, &now), vec![HEAP]);
+    assert!(newly_low(&now, &now).is_empty());
+    assert!(newly_low(&now, &before).is_empty());
+}
@@ -0,0 +1,36 @@
+use std::path::Path;
+
+const OPENAPI_SOURCE: &str = "api/openapi.json";
+
+fn main() {
+    embuild::espidf::sysenv::output();
+    // The chip cfgs set by esp-idf-sys, see src/board.rs
+    println!("cargo:rustc-check-cfg=cfg(esp32, esp32c3, esp32s3)");
+    // Set with CONFIG_NVS_ENCRYPTION, see src/secrets.rs
+    println!("cargo:rustc-check-cfg=cfg(esp_idf_nvs_encryption)");
+    // Set when the console is the chip's USB port, see src/console.rs
+    println!("cargo:rustc-check-cfg=cfg(esp_idf_esp_console_usb_serial_jtag)");
+    // Lets src/tls.rs add the client CA when esp-idf-svc starts the HTTPS
+    // server
+    println!("cargo:rustc-link-arg=-Wl,--wrap=httpd_ssl_start");
+
+    openapi();
+}
+
+// Checks the OpenAPI document, stamps it with the crate version and writes
+// it minified for the firmware to embed
+fn openapi() {
+    println!("cargo:rerun-if-changed={}", OPENAPI_SOURCE);
+
+    let source = std::fs::read_to_string(OPENAPI_SOURCE).expect("read OpenAPI document");
+    let mut document: serde_json::Value =
+        serde_json::from_str(&source).expect("OpenAPI document is not valid JSON");
+    document["info"]["version"] = env!("CARGO_PKG_VERSION").into();
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(
+        Path::new(&out_dir).join("openapi.json"),
+        document.to_string(),
+    )
+    .expect("write OpenAPI document");
+}
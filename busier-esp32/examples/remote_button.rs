@@ -0,0 +1,74 @@
+//! Battery-powered ESP-NOW remote button for busier.
+//!
+//! Sleeps in deep sleep until the button on GPIO33 pulls the pin low, sends
+//! a signed toggle packet and goes back to sleep. The packet counter is kept
+//! in NVS so it keeps growing across power cycles.
+//!
+//! Build with `BUTTON_KEY` set to the key configured on the busier device,
+//! and `BUSIER_CHANNEL` set to the WiFi channel of its access point.
+
+use esp_idf_svc::espnow::{EspNow, PeerInfo, BROADCAST};
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::hal::prelude::*;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs};
+use esp_idf_svc::sys;
+use esp_idf_svc::wifi::{Configuration, EspWifi};
+
+#[path = "../src/button_protocol.rs"]
+mod button_protocol;
+
+use button_protocol::{Action, Packet};
+
+const KEY: &str = env!("BUTTON_KEY");
+const CHANNEL: &str = match option_env!("BUSIER_CHANNEL") {
+    Some(channel) => channel,
+    None => "1",
+};
+const WAKE_GPIO: i32 = 33;
+
+fn main() -> anyhow::Result<()> {
+    esp_idf_svc::sys::link_patches();
+
+    let peripherals = Peripherals::take()?;
+    let sys_loop = EspSystemEventLoop::take()?;
+    let nvs_partition = EspDefaultNvsPartition::take()?;
+
+    // Bump the counter before sending so a crash never reuses a value
+    let mut nvs = EspNvs::new(nvs_partition.clone(), "button", true)?;
+    let counter = nvs.get_u32("counter")?.unwrap_or(0) + 1;
+    nvs.set_u32("counter", counter)?;
+
+    // ESP-NOW needs the radio running on the device's channel
+    let mut wifi = EspWifi::new(peripherals.modem, sys_loop, Some(nvs_partition))?;
+    wifi.set_configuration(&Configuration::Client(Default::default()))?;
+    wifi.start()?;
+    // SAFETY: WiFi is started; the channel is a plain value
+    sys::esp!(unsafe {
+        sys::esp_wifi_set_channel(
+            CHANNEL.parse()?,
+            sys::wifi_second_chan_t_WIFI_SECOND_CHAN_NONE,
+        )
+    })?;
+
+    let espnow = EspNow::take()?;
+    espnow.add_peer(PeerInfo {
+        peer_addr: BROADCAST,
+        ..Default::default()
+    })?;
+
+    let packet = Packet {
+        action: Action::Toggle,
+        counter,
+    };
+    espnow.send(BROADCAST, &packet.encode(KEY.as_bytes()))?;
+
+    // Give the radio time to get the frame out
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // SAFETY: configuring the wake source and sleeping have no preconditions
+    unsafe {
+        sys::rtc_gpio_pullup_en(WAKE_GPIO);
+        sys::esp_sleep_enable_ext0_wakeup(WAKE_GPIO, 0);
+        sys::esp_deep_sleep_start();
+    }
+}
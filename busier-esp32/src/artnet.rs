@@ -0,0 +1,96 @@
+//! Art-Net output for DMX tally lights.
+//!
+//! Sends the channel values configured for the current status as an ArtDmx
+//! packet to one universe. Art-Net nodes drop their output when the stream
+//! stops, so the frame is repeated every second even when nothing changed.
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::config::{self, ArtnetConfig};
+use crate::status;
+
+const ARTNET_STACK_SIZE: usize = 4096;
+const SEND_INTERVAL: Duration = Duration::from_secs(1);
+const PORT: u16 = 6454;
+const BROADCAST: &str = "255.255.255.255";
+
+const OP_DMX: u16 = 0x5000;
+const PROTOCOL_VERSION: u16 = 14;
+const MAX_CHANNELS: usize = 512;
+
+/// Spawns the sending thread.
+pub fn start() -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("artnet".into())
+        .stack_size(ARTNET_STACK_SIZE)
+        .spawn(|| {
+            let socket = match UdpSocket::bind(("0.0.0.0", 0)) {
+                Ok(socket) => socket,
+                Err(e) => {
+                    warn!("Art-Net socket failed: {:?}", e);
+                    return;
+                }
+            };
+            if let Err(e) = socket.set_broadcast(true) {
+                warn!("Art-Net broadcast not available: {:?}", e);
+            }
+
+            loop {
+                std::thread::sleep(SEND_INTERVAL);
+
+                let artnet = config::get().artnet;
+                if !artnet.enabled {
+                    continue;
+                }
+
+                let host = if artnet.host.is_empty() {
+                    BROADCAST
+                } else {
+                    &artnet.host
+                };
+                let packet = dmx_packet(&artnet, status::current().as_str());
+                if let Err(e) = socket.send_to(&packet, (host, PORT)) {
+                    warn!("Art-Net send to {} failed: {:?}", host, e);
+                }
+            }
+        })?;
+
+    info!("Art-Net output started");
+    Ok(())
+}
+
+// Builds an ArtDmx packet with the status' values starting at the
+// configured channel; all other channels are zero.
+fn dmx_packet(artnet: &ArtnetConfig, status: &str) -> Vec<u8> {
+    let values = artnet
+        .values
+        .get(status)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let start = usize::from(artnet.channel.max(1)) - 1;
+
+    // DMX data length must be even and at least 2
+    let mut data = vec![0u8; (start + values.len()).clamp(2, MAX_CHANNELS)];
+    if data.len() % 2 == 1 {
+        data.push(0);
+    }
+    for (slot, value) in data.iter_mut().skip(start).zip(values) {
+        *slot = *value;
+    }
+
+    let mut packet = Vec::with_capacity(18 + data.len());
+    packet.extend_from_slice(b"Art-Net\0");
+    packet.extend_from_slice(&OP_DMX.to_le_bytes());
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    // No sequencing, physical port 0
+    packet.extend_from_slice(&[0, 0]);
+    // 15-bit port address: sub-net and universe, then net
+    packet.push(artnet.universe as u8);
+    packet.push((artnet.universe >> 8) as u8 & 0x7F);
+    packet.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&data);
+    packet
+}
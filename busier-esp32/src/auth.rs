@@ -0,0 +1,185 @@
+//! Admin authentication for the web interface.
+//!
+//! The guest page and its routes are open to anyone on the network; the
+//! admin page and every route that changes the device are wrapped in
+//! [`admin`], which requires HTTP Basic credentials matching `admin` in the
+//! configuration. With an empty password the admin routes stay open.
+//! Until the setup wizard has run they refuse every request.
+//!
+//! When the HTTPS listener requires client certificates, the certificate
+//! takes the place of the password and the admin routes are refused over
+//! plain HTTP.
+//!
+//! The changes made by an admin route are attributed to the client's
+//! address and the credential it used in the status audit log.
+//!
+//! API tokens in `admin.tokens` name their clients: every route counts the
+//! requests that carry one towards the token's usage and rate limit, see
+//! [`metered`], and a token marked `admin` opens the admin routes.
+
+use std::sync::Mutex;
+
+use busier_core::config::ApiToken;
+use busier_core::ratelimit::{Limiter, Usage};
+use embedded_svc::http::server::Request;
+use embedded_svc::http::Headers;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::EspHttpConnection;
+
+use crate::config;
+use crate::device;
+use crate::http_util;
+use crate::setup;
+use crate::status::{self, Origin};
+use crate::tls;
+
+const REALM_HEADER: (&str, &str) = ("WWW-Authenticate", "Basic realm=\"busier admin\"");
+
+// The bucket and usage of each API token that was used, by name
+static LIMITERS: Mutex<Vec<(String, Limiter)>> = Mutex::new(Vec::new());
+
+/// Wraps a handler so that it only runs for requests with the admin
+/// credentials; others are answered with 401 and a login prompt. `secure`
+/// is set for handlers on the HTTPS listener.
+pub fn admin<F>(
+    secure: bool,
+    handler: F,
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static
+where
+    F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static,
+{
+    move |mut req| {
+        if setup::is_pending() {
+            req.into_status_response(503)?
+                .write_all("Finish the setup at /setup first".as_bytes())?;
+            return Ok(());
+        }
+
+        if tls::requires_client_certs() {
+            if secure {
+                // The TLS handshake has already checked the certificate
+                let origin = origin(&mut req, Some("client certificate"));
+                return status::attributed(origin, || handler(req));
+            }
+            req.into_status_response(403)?
+                .write_all("Admin routes need a client certificate over HTTPS".as_bytes())?;
+            return Ok(());
+        }
+
+        if let Some(token) = api_token(&req).filter(|token| token.admin) {
+            let origin = origin(&mut req, Some(&token.name));
+            return status::attributed(origin, || handler(req));
+        }
+
+        if is_admin(req.header("Authorization")) {
+            // Without a password anyone is admin, with no name to give
+            let admin = config::get().admin;
+            let token = (!admin.password.is_empty()).then_some(admin.username.as_str());
+            let origin = origin(&mut req, token);
+            return status::attributed(origin, || handler(req));
+        }
+
+        req.into_response(401, None, &[REALM_HEADER])?
+            .write_all("Admin login required".as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Wraps a handler so that a request with an API token counts towards the
+/// token's usage, and is answered with 429 over its rate limit. Requests
+/// without one pass as they are.
+pub fn metered<F>(
+    handler: F,
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static
+where
+    F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static,
+{
+    move |req| {
+        let Some(token) = api_token(&req) else {
+            return handler(req);
+        };
+
+        let now = device::uptime().as_millis() as u64;
+        let checked = {
+            let mut limiters = LIMITERS.lock().unwrap();
+            let index = match limiters.iter().position(|(name, _)| *name == token.name) {
+                Some(index) => index,
+                None => {
+                    limiters.push((token.name.clone(), Limiter::new()));
+                    limiters.len() - 1
+                }
+            };
+            limiters[index].1.check(token.rate_per_min, now)
+        };
+        if let Err(wait_ms) = checked {
+            let retry_after = wait_ms.div_ceil(1000).to_string();
+            req.into_response(429, None, &[("Retry-After", retry_after.as_str())])?
+                .write_all("Rate limit exceeded".as_bytes())?;
+            return Ok(());
+        }
+        handler(req)
+    }
+}
+
+/// The usage of the API token named `name` since startup.
+pub fn usage(name: &str) -> Usage {
+    LIMITERS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(used, _)| used == name)
+        .map(|(_, limiter)| limiter.usage().clone())
+        .unwrap_or_default()
+}
+
+/// Whether `token` is `admin.set_token`, which opens `GET /set`. Never
+/// with an empty token, nor until the setup has run.
+pub fn is_set_token(token: Option<&str>) -> bool {
+    let expected = config::get().admin.set_token;
+    !setup::is_pending()
+        && !expected.is_empty()
+        && token.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+}
+
+/// The client of a request and the name of the credential it used, for
+/// the audit log.
+pub fn origin(req: &mut Request<&mut EspHttpConnection>, token: Option<&str>) -> Origin {
+    Origin {
+        peer: http_util::peer_ip(req).map(|ip| ip.to_string()),
+        token: token.map(str::to_string),
+    }
+}
+
+// The API token a request carries as a bearer token or in the query
+fn api_token(req: &Request<&mut EspHttpConnection>) -> Option<ApiToken> {
+    let sent = req
+        .header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+        .or_else(|| http_util::query_param(req.uri(), "token"))?;
+    config::get().admin.tokens.into_iter().find(|token| {
+        !token.token.is_empty() && constant_time_eq(token.token.as_bytes(), sent.as_bytes())
+    })
+}
+
+fn is_admin(authorization: Option<&str>) -> bool {
+    let admin = config::get().admin;
+    if admin.password.is_empty() {
+        return true;
+    }
+
+    let Some(credentials) = authorization
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| http_util::decode_base64(encoded.trim()))
+    else {
+        return false;
+    };
+
+    let expected = format!("{}:{}", admin.username, admin.password);
+    constant_time_eq(&credentials, expected.as_bytes())
+}
+
+/// Compares without returning early, so timing does not leak the secret.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
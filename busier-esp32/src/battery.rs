@@ -0,0 +1,229 @@
+//! Battery voltage monitoring.
+//!
+//! Samples the battery through a resistor divider on an ADC pin and turns
+//! the voltage into a charge estimate using a typical single-cell LiPo
+//! discharge curve. With `battery.source` set to `max17048` a fuel gauge on
+//! the shared I2C bus reports the charge and whether the cell is charging
+//! instead, which is far more accurate. Below `battery.low_percent` the
+//! device is considered low on battery, which the main loop uses to drop to a
+//! display-only mode.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use embedded_hal::i2c::I2c;
+use esp_idf_svc::hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
+use esp_idf_svc::hal::adc::ADC1;
+use esp_idf_svc::hal::gpio::ADCPin;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::config::{self, BatterySource};
+
+const BATTERY_STACK_SIZE: usize = 4096;
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+const SAMPLES: u32 = 16;
+// Percentage points above the threshold needed to leave low-battery mode
+const HYSTERESIS: u8 = 5;
+
+// Charge rate in percent per hour above which the cell counts as charging
+const CHARGING_RATE: f32 = 0.5;
+
+// MAX17048 registers
+const GAUGE_ADDRESS: u8 = 0x36;
+const VCELL: u8 = 0x02;
+const SOC: u8 = 0x04;
+const VERSION: u8 = 0x08;
+const CRATE: u8 = 0x16;
+
+// Cell voltage in millivolts to charge in percent, highest first
+const DISCHARGE_CURVE: [(u32, u8); 9] = [
+    (4200, 100),
+    (4100, 90),
+    (4000, 78),
+    (3900, 64),
+    (3800, 48),
+    (3700, 30),
+    (3600, 14),
+    (3500, 5),
+    (3300, 0),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Level {
+    pub millivolts: u32,
+    pub percent: u8,
+    /// Whether the cell is charging; None when measured with the ADC.
+    pub charging: Option<bool>,
+}
+
+static LEVEL: Mutex<Option<Level>> = Mutex::new(None);
+static LOW: AtomicBool = AtomicBool::new(false);
+
+/// The last measured level; None until measured or without `battery.enabled`.
+pub fn level() -> Option<Level> {
+    if !config::get().battery.enabled {
+        return None;
+    }
+    *LEVEL.lock().unwrap()
+}
+
+/// Whether the battery is below `battery.low_percent`.
+pub fn is_low() -> bool {
+    config::get().battery.enabled && LOW.load(Ordering::SeqCst)
+}
+
+/// Spawns the sampling thread. The ADC channel must be calibrated so that
+/// readings are in millivolts.
+pub fn start<P>(
+    mut channel: AdcChannelDriver<'static, P, AdcDriver<'static, ADC1>>,
+) -> anyhow::Result<()>
+where
+    P: ADCPin<Adc = ADC1> + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name("battery".into())
+        .stack_size(BATTERY_STACK_SIZE)
+        .spawn(move || loop {
+            let config = config::get().battery;
+            if config.enabled && config.source == BatterySource::Adc {
+                match sample(&mut channel, config.divider) {
+                    Ok(level) => update(level, config.low_percent),
+                    Err(e) => warn!("Failed to read battery voltage: {:?}", e),
+                }
+            }
+
+            std::thread::sleep(SAMPLE_INTERVAL);
+        })?;
+
+    Ok(())
+}
+
+fn sample<P>(
+    channel: &mut AdcChannelDriver<'static, P, AdcDriver<'static, ADC1>>,
+    divider: f32,
+) -> anyhow::Result<Level>
+where
+    P: ADCPin<Adc = ADC1>,
+{
+    let mut sum = 0;
+    for _ in 0..SAMPLES {
+        sum += u32::from(channel.read()?);
+    }
+
+    let millivolts = (sum as f32 / SAMPLES as f32 * divider) as u32;
+    Ok(Level {
+        millivolts,
+        percent: percent(millivolts),
+        charging: None,
+    })
+}
+
+/// Spawns the thread polling a MAX17048 fuel gauge. Without a gauge on the
+/// bus the thread exits after logging a warning.
+pub fn start_fuel_gauge<I>(i2c: I) -> anyhow::Result<()>
+where
+    I: I2c + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name("fuel_gauge".into())
+        .stack_size(BATTERY_STACK_SIZE)
+        .spawn(move || {
+            let mut gauge = Max17048 { i2c };
+            match gauge.read(VERSION) {
+                Ok(version) if version & 0xFFF0 == 0x0010 => {
+                    info!("MAX17048 fuel gauge version {:#06x} ready", version)
+                }
+                Ok(version) => {
+                    warn!("No MAX17048 fuel gauge: version {:#06x}", version);
+                    return;
+                }
+                Err(e) => {
+                    warn!("No MAX17048 fuel gauge: {:?}", e);
+                    return;
+                }
+            }
+
+            loop {
+                let config = config::get().battery;
+                if config.enabled && config.source == BatterySource::Max17048 {
+                    match gauge.level() {
+                        Ok(level) => update(level, config.low_percent),
+                        Err(e) => warn!("Failed to read fuel gauge: {:?}", e),
+                    }
+                }
+
+                std::thread::sleep(SAMPLE_INTERVAL);
+            }
+        })?;
+
+    Ok(())
+}
+
+struct Max17048<I> {
+    i2c: I,
+}
+
+impl<I: I2c> Max17048<I> {
+    fn level(&mut self) -> anyhow::Result<Level> {
+        // 78.125 uV, or 5/64 mV, per bit
+        let millivolts = u32::from(self.read(VCELL)?) * 5 / 64;
+        // The high byte is whole percent
+        let percent = (self.read(SOC)? >> 8).min(100) as u8;
+        // 0.208 % per hour per bit, negative while discharging
+        let rate = self.read(CRATE)? as i16 as f32 * 0.208;
+
+        Ok(Level {
+            millivolts,
+            percent,
+            charging: Some(rate > CHARGING_RATE),
+        })
+    }
+
+    fn read(&mut self, register: u8) -> anyhow::Result<u16> {
+        let mut buf = [0; 2];
+        self.i2c
+            .write_read(GAUGE_ADDRESS, &[register], &mut buf)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(u16::from_be_bytes(buf))
+    }
+}
+
+fn update(level: Level, low_percent: u8) {
+    *LEVEL.lock().unwrap() = Some(level);
+
+    let was_low = LOW.load(Ordering::SeqCst);
+    let low = if was_low {
+        level.percent < low_percent.saturating_add(HYSTERESIS)
+    } else {
+        level.percent <= low_percent
+    };
+    if low != was_low {
+        info!(
+            "Battery {} at {} mV ({}%)",
+            if low { "low" } else { "recovered" },
+            level.millivolts,
+            level.percent
+        );
+        LOW.store(low, Ordering::SeqCst);
+    }
+}
+
+// Interpolates linearly between the points of the discharge curve
+fn percent(millivolts: u32) -> u8 {
+    let (top_mv, top_percent) = DISCHARGE_CURVE[0];
+    if millivolts >= top_mv {
+        return top_percent;
+    }
+
+    for pair in DISCHARGE_CURVE.windows(2) {
+        let [(high_mv, high_percent), (low_mv, low_percent)] = [pair[0], pair[1]];
+        if millivolts >= low_mv {
+            let span = u32::from(high_percent - low_percent);
+            return low_percent + (span * (millivolts - low_mv) / (high_mv - low_mv)) as u8;
+        }
+    }
+
+    0
+}
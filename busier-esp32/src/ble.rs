@@ -0,0 +1,151 @@
+//! BLE GATT status service (feature `ble`).
+//!
+//! Exposes the status to phones and watches that are not on the same WiFi:
+//! - status characteristic: read/notify, the current status name
+//! - control characteristic: write a status name ("free", "dnd", "away")
+//!
+//! When `BLE_PASSKEY` is set at build time, writes require a bonded,
+//! passkey-authenticated connection.
+//!
+//! The status is also broadcast in the advertisement's manufacturer data so
+//! passive receivers can follow it without connecting:
+//! `company ID 0xFFFF (LE) | "BZ" | version | status | sequence`, where the
+//! status uses the same numbering as the ESP-NOW sync and the sequence
+//! increments on every change.
+
+use std::time::Duration;
+
+use esp32_nimble::enums::{AuthReq, SecurityIOCap};
+use esp32_nimble::{uuid128, BLEAdvertisementData, BLEDevice, BLEError, NimbleProperties};
+use log::{info, warn};
+
+use crate::setup;
+use crate::status::{self, Source, Status};
+
+const DEVICE_NAME: &str = "busier";
+const PASSKEY: Option<&str> = option_env!("BLE_PASSKEY");
+const BLE_STACK_SIZE: usize = 4096;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// 0xFFFF is reserved for testing and internal use by the Bluetooth SIG
+const COMPANY_ID: u16 = 0xFFFF;
+const BEACON_VERSION: u8 = 1;
+
+pub const SERVICE_UUID: esp32_nimble::utilities::BleUuid =
+    uuid128!("6e0b0001-5d1f-4b5a-9c4e-2b7a8f3c1d00");
+const STATUS_UUID: esp32_nimble::utilities::BleUuid =
+    uuid128!("6e0b0002-5d1f-4b5a-9c4e-2b7a8f3c1d00");
+const CONTROL_UUID: esp32_nimble::utilities::BleUuid =
+    uuid128!("6e0b0003-5d1f-4b5a-9c4e-2b7a8f3c1d00");
+
+/// Starts advertising the GATT service.
+pub fn start() -> anyhow::Result<()> {
+    let device = BLEDevice::take();
+    BLEDevice::set_device_name(DEVICE_NAME)?;
+
+    let mut write_properties = NimbleProperties::WRITE;
+    if let Some(passkey) = PASSKEY {
+        device
+            .security()
+            .set_auth(AuthReq::all())
+            .set_passkey(passkey.parse()?)
+            .set_io_cap(SecurityIOCap::DisplayOnly);
+        write_properties |= NimbleProperties::WRITE_ENC | NimbleProperties::WRITE_AUTHEN;
+    }
+
+    let server = device.get_server();
+    server.on_connect(|_, desc| info!("BLE client connected: {:?}", desc.address()));
+    server.on_disconnect(|desc, _| info!("BLE client disconnected: {:?}", desc.address()));
+
+    let service = server.create_service(SERVICE_UUID);
+
+    let status_characteristic = service.lock().create_characteristic(
+        STATUS_UUID,
+        NimbleProperties::READ | NimbleProperties::NOTIFY,
+    );
+    status_characteristic
+        .lock()
+        .set_value(status::current().as_str().as_bytes());
+
+    let control_characteristic = service
+        .lock()
+        .create_characteristic(CONTROL_UUID, write_properties);
+    control_characteristic.lock().on_write(|args| {
+        if setup::is_pending() {
+            warn!("BLE write before the setup has run");
+            args.reject();
+            return;
+        }
+        let new_status = std::str::from_utf8(args.recv_data())
+            .ok()
+            .and_then(|name| Status::parse(name.trim()));
+        match new_status {
+            Some(new_status) => {
+                info!("BLE set status to {}", new_status.as_str());
+                status::set(Source::Manual, new_status);
+            }
+            None => {
+                warn!("BLE write with invalid status");
+                args.reject();
+            }
+        }
+    });
+
+    // The 128-bit service UUID does not fit next to the beacon payload
+    device
+        .get_advertising()
+        .lock()
+        .scan_response_data(BLEAdvertisementData::new().add_service_uuid(SERVICE_UUID))?;
+    advertise(device, status::current(), 0)?;
+    info!("BLE status service advertising");
+
+    // Push status changes to subscribed clients
+    std::thread::Builder::new()
+        .name("ble".into())
+        .stack_size(BLE_STACK_SIZE)
+        .spawn(move || {
+            let mut last_status = status::current();
+            let mut sequence: u8 = 0;
+            loop {
+                let current_status = status::current();
+                if current_status != last_status {
+                    status_characteristic
+                        .lock()
+                        .set_value(current_status.as_str().as_bytes())
+                        .notify();
+
+                    sequence = sequence.wrapping_add(1);
+                    if let Err(e) = advertise(device, current_status, sequence) {
+                        warn!("BLE advertising update failed: {:?}", e);
+                    }
+                    last_status = current_status;
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        })?;
+
+    Ok(())
+}
+
+/// (Re)starts advertising with the status in the manufacturer data.
+fn advertise(device: &BLEDevice, status: Status, sequence: u8) -> Result<(), BLEError> {
+    let company_id = COMPANY_ID.to_le_bytes();
+    let payload = [
+        company_id[0],
+        company_id[1],
+        b'B',
+        b'Z',
+        BEACON_VERSION,
+        status as u8,
+        sequence,
+    ];
+
+    let mut advertising = device.get_advertising().lock();
+    // Fails harmlessly when not advertising yet
+    let _ = advertising.stop();
+    advertising.set_data(
+        BLEAdvertisementData::new()
+            .name(DEVICE_NAME)
+            .manufacturer_data(&payload),
+    )?;
+    advertising.start()
+}
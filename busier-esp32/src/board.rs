@@ -0,0 +1,264 @@
+//! Hardware variant of this build.
+//!
+//! Each optional driver has a cargo feature, all of them on by default, so a
+//! build for a bare board with only an OLED can leave the rest out:
+//!
+//! ```text
+//! cargo build --release --no-default-features --features oled-128x32
+//! ```
+//!
+//! Drivers left out are never started, so the linker drops their code. The
+//! panel size comes from `oled-128x32` or `oled-128x64`, the larger one
+//! winning if both are given. The `hub75` panel needs the pins of several
+//! other drivers and cannot be combined with them, and the `tft` and
+//! `epaper` panels and the badge reader all need the same SPI peripheral.
+//!
+//! The I2C bus, the button and the simple outputs take their pins from the
+//! `pins` section of the configuration instead, so carrier boards with other
+//! wiring run the same build. Dev boards with an integrated display have a
+//! profile, selected with `board`, that sets those pins and the display.
+//!
+//! The ESP32-C3 and ESP32-S3 are supported as well, with their own default
+//! and fixed pins. The C3 has too few pins for the SPI and I2S drivers and
+//! no parallel LCD peripheral for the HUB75 refresh.
+
+use esp_idf_svc::hal::gpio::{AnyIOPin, AnyInputPin, AnyOutputPin};
+use log::warn;
+
+pub use busier_core::board::{Board, Profile, Screen};
+pub use busier_core::pins::Chip;
+use busier_core::pins::{self, PinError};
+
+use crate::config::{Config, PinsConfig};
+
+#[cfg(esp32)]
+pub const CHIP: Chip = Chip::Esp32;
+#[cfg(esp32c3)]
+pub const CHIP: Chip = Chip::Esp32C3;
+#[cfg(esp32s3)]
+pub const CHIP: Chip = Chip::Esp32S3;
+#[cfg(not(any(esp32, esp32c3, esp32s3)))]
+compile_error!("busier runs on the ESP32, ESP32-C3 and ESP32-S3");
+
+/// Chip name as the discovery protocols report it.
+pub const CHIP_NAME: &str = match CHIP {
+    Chip::Esp32 => "esp32",
+    Chip::Esp32C3 => "esp32c3",
+    Chip::Esp32S3 => "esp32s3",
+};
+
+/// Highest CPU clock of the chip.
+pub const MAX_CPU_MHZ: u16 = match CHIP {
+    Chip::Esp32C3 => 160,
+    Chip::Esp32 | Chip::Esp32S3 => 240,
+};
+
+// Pins of the drivers that are not configurable
+#[cfg(esp32)]
+mod fixed {
+    // CLK, DIN and CS on the HSPI pins
+    pub const LED_MATRIX: &[u8] = &[14, 13, 15];
+    // R1, G1, B1, R2, G2, B2, A, B, C, D, CLK, LAT and OE
+    pub const HUB75: &[u8] = &[4, 5, 12, 13, 14, 15, 16, 17, 18, 19, 23, 26, 33];
+    // CLK and DIO
+    pub const COUNTDOWN: &[u8] = &[16, 17];
+    // BCLK, DIN and LRC
+    pub const CHIME: &[u8] = &[4, 12, 33];
+    pub const SERVO: &[u8] = &[26];
+    // SCK, MOSI, MISO and SDA (chip select) on the VSPI pins
+    pub const RFID: &[u8] = &[18, 23, 19, 5];
+    pub const IR: &[u8] = &[34];
+    // ADC1 channel 7, see main
+    pub const BATTERY: &[u8] = &[35];
+}
+
+// GPIO20 and 21 are free with the console on USB
+#[cfg(esp32c3)]
+mod fixed {
+    pub const LED_MATRIX: &[u8] = &[];
+    pub const HUB75: &[u8] = &[];
+    pub const COUNTDOWN: &[u8] = &[20, 21];
+    pub const CHIME: &[u8] = &[];
+    pub const SERVO: &[u8] = &[1];
+    pub const RFID: &[u8] = &[];
+    pub const IR: &[u8] = &[2];
+    // ADC1 channel 3, see main
+    pub const BATTERY: &[u8] = &[3];
+}
+
+#[cfg(esp32s3)]
+mod fixed {
+    // On the FSPI pins
+    pub const LED_MATRIX: &[u8] = &[12, 11, 10];
+    pub const HUB75: &[u8] = &[38, 39, 40, 41, 42, 21, 10, 11, 12, 13, 14, 15, 16];
+    pub const COUNTDOWN: &[u8] = &[38, 39];
+    pub const CHIME: &[u8] = &[40, 41, 42];
+    pub const SERVO: &[u8] = &[21];
+    pub const RFID: &[u8] = &[14, 15, 16, 17];
+    pub const IR: &[u8] = &[18];
+    // ADC1 channel 0, see main
+    pub const BATTERY: &[u8] = &[1];
+}
+
+pub use fixed::*;
+
+#[cfg(all(
+    esp32c3,
+    any(
+        feature = "led-matrix",
+        feature = "hub75",
+        feature = "chime",
+        feature = "rfid",
+        feature = "tft",
+        feature = "epaper"
+    )
+))]
+compile_error!(
+    "the ESP32-C3 has no pins for led-matrix, hub75, chime, rfid, tft and epaper; \
+     build it with --no-default-features and the features you need"
+);
+
+#[cfg(all(
+    feature = "hub75",
+    any(
+        feature = "chime",
+        feature = "servo",
+        feature = "led-matrix",
+        feature = "countdown",
+        feature = "rfid"
+    )
+))]
+compile_error!(
+    "hub75 uses the pins of chime, servo, led-matrix, countdown and rfid; \
+     build it with --no-default-features and the features you need"
+);
+
+#[cfg(any(
+    all(feature = "tft", feature = "rfid"),
+    all(feature = "epaper", feature = "rfid"),
+    all(feature = "tft", feature = "epaper")
+))]
+compile_error!("tft, epaper and rfid all use the SPI3 peripheral; build only one of them");
+
+// SSD1306 panel height of this build, for boards without a profile
+const OLED_HEIGHT: u8 = if cfg!(feature = "oled-128x64") {
+    64
+} else {
+    32
+};
+
+/// Optional drivers, whether this build includes them and the pins they
+/// use that are not configurable.
+pub const DRIVERS: &[(&str, bool, &[u8])] = &[
+    (
+        "oled",
+        cfg!(any(feature = "oled-128x32", feature = "oled-128x64")),
+        &[],
+    ),
+    ("tft", cfg!(feature = "tft"), &[]),
+    ("epaper", cfg!(feature = "epaper"), &[]),
+    ("lcd", cfg!(feature = "lcd"), &[]),
+    ("led-matrix", cfg!(feature = "led-matrix"), LED_MATRIX),
+    ("hub75", cfg!(feature = "hub75"), HUB75),
+    ("countdown", cfg!(feature = "countdown"), COUNTDOWN),
+    ("buzzer", cfg!(feature = "buzzer"), &[]),
+    ("chime", cfg!(feature = "chime"), CHIME),
+    ("haptic", cfg!(feature = "haptic"), &[]),
+    ("servo", cfg!(feature = "servo"), SERVO),
+    ("rfid", cfg!(feature = "rfid"), RFID),
+    ("ir", cfg!(feature = "ir"), IR),
+    ("cube", cfg!(feature = "cube"), &[]),
+    ("gesture", cfg!(feature = "gesture"), &[]),
+    ("door", cfg!(feature = "door"), &[]),
+    ("doorbell", cfg!(feature = "doorbell"), &[]),
+    ("rtc", cfg!(feature = "rtc"), &[]),
+    ("battery", cfg!(feature = "battery"), BATTERY),
+];
+
+/// Names of the drivers included in this build, for the log and `/health`.
+pub fn drivers() -> Vec<&'static str> {
+    DRIVERS
+        .iter()
+        .filter(|(_, included, _)| *included)
+        .map(|(name, _, _)| *name)
+        .collect()
+}
+
+// The board's profile, or the configured pins and the build's OLED
+fn selected(config: &Config) -> Profile {
+    config.board.profile().unwrap_or_else(|| {
+        custom(config.pins.clone().unwrap_or_else(|| CHIP.default_pins()))
+    })
+}
+
+fn custom(pins: PinsConfig) -> Profile {
+    Profile {
+        pins,
+        screen: Screen::Ssd1306 {
+            height: OLED_HEIGHT,
+            reset: None,
+        },
+    }
+}
+
+/// Checks the pins a configuration selects against the chip and the fixed
+/// pins of this build.
+pub fn validate(config: &Config) -> anyhow::Result<()> {
+    let mut reserved: Vec<u8> = DRIVERS
+        .iter()
+        .filter(|(_, included, _)| *included)
+        .flat_map(|(_, _, pins)| pins.iter().copied())
+        .collect();
+    // The doorbell is not part of the profiles, so it goes on a pin they
+    // leave free
+    if let Some(pin) = config.doorbell.pin {
+        pins::check_io(CHIP, pin).map_err(anyhow::Error::msg)?;
+        if reserved.contains(&pin) {
+            return Err(anyhow::Error::msg(PinError::Reserved(pin)));
+        }
+        reserved.push(pin);
+    }
+    selected(config)
+        .validate(CHIP, &reserved)
+        .map_err(anyhow::Error::msg)
+}
+
+/// The selected pins and display, or the defaults if the stored ones are
+/// unusable.
+pub fn profile() -> Profile {
+    let config = crate::config::get();
+    match validate(&config) {
+        Ok(()) => selected(&config),
+        Err(e) => {
+            warn!("Invalid pins for {:?}, using the defaults: {:?}", config.board, e);
+            custom(CHIP.default_pins())
+        }
+    }
+}
+
+/// The doorbell's pin, if one is set and the pins are valid.
+#[cfg(feature = "doorbell")]
+pub fn doorbell_pin() -> Option<u8> {
+    let config = crate::config::get();
+    config.doorbell.pin.filter(|_| validate(&config).is_ok())
+}
+
+/// Takes a validated or fixed pin by number.
+pub fn io_pin(pin: u8) -> AnyIOPin {
+    // SAFETY: validated and fixed pins exist, and each is handed to one
+    // driver only
+    unsafe { AnyIOPin::new(pin.into()) }
+}
+
+/// Takes a validated pin by number as an output.
+pub fn output_pin(pin: u8) -> AnyOutputPin {
+    // SAFETY: as above
+    unsafe { AnyOutputPin::new(pin.into()) }
+}
+
+/// Takes a fixed or validated pin by number as an input.
+#[cfg(any(feature = "rfid", feature = "ir", feature = "epaper"))]
+pub fn input_pin(pin: u8) -> AnyInputPin {
+    // SAFETY: as above
+    unsafe { AnyInputPin::new(pin.into()) }
+}
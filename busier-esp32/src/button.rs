@@ -0,0 +1,37 @@
+//! Push button input.
+//!
+//! Uses the BOOT button found on most ESP32 dev boards. A short press
+//! starts or stops the pomodoro timer.
+
+use esp_idf_svc::hal::gpio::{AnyIOPin, Input, PinDriver, Pull};
+
+use crate::pomodoro;
+
+const BUTTON_STACK_SIZE: usize = 4096;
+// Poll often enough to catch short presses, slow enough to debounce
+const POLL_INTERVAL_MS: u64 = 50;
+
+/// Spawns a thread that watches the button.
+pub fn start(pin: AnyIOPin) -> anyhow::Result<()> {
+    let mut button: PinDriver<'static, AnyIOPin, Input> = PinDriver::input(pin)?;
+    button.set_pull(Pull::Up)?;
+
+    std::thread::Builder::new()
+        .name("button".into())
+        .stack_size(BUTTON_STACK_SIZE)
+        .spawn(move || {
+            let mut was_pressed = false;
+            loop {
+                // The button pulls the pin low when pressed
+                let pressed = button.is_low();
+                if pressed && !was_pressed {
+                    pomodoro::toggle();
+                }
+                was_pressed = pressed;
+
+                std::thread::sleep(std::time::Duration::from_millis(POLL_INTERVAL_MS));
+            }
+        })?;
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Wire format of the ESP-NOW remote button.
+//!
+//! `"BB" | version | action | counter (u32 LE) | tag (8 bytes)`
+//!
+//! The tag is the first 8 bytes of HMAC-SHA256 over everything before it,
+//! keyed with the secret shared by the button and the device. The counter
+//! must grow with every packet, so a recorded packet cannot be replayed.
+//!
+//! This file is also compiled into the remote button example.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const MAGIC: [u8; 2] = *b"BB";
+pub const VERSION: u8 = 1;
+pub const PACKET_LEN: usize = 16;
+const TAG_LEN: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Switch between Free and Do Not Disturb.
+    Toggle = 1,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Packet {
+    pub action: Action,
+    pub counter: u32,
+}
+
+impl Packet {
+    pub fn encode(&self, key: &[u8]) -> [u8; PACKET_LEN] {
+        let mut buf = [0; PACKET_LEN];
+        buf[..2].copy_from_slice(&MAGIC);
+        buf[2] = VERSION;
+        buf[3] = self.action as u8;
+        buf[4..8].copy_from_slice(&self.counter.to_le_bytes());
+        let tag = tag(key, &buf[..PACKET_LEN - TAG_LEN]);
+        buf[PACKET_LEN - TAG_LEN..].copy_from_slice(&tag[..TAG_LEN]);
+        buf
+    }
+
+    /// Decodes a packet, returning `None` unless the tag matches `key`.
+    pub fn decode(data: &[u8], key: &[u8]) -> Option<Packet> {
+        if !is_button_packet(data) {
+            return None;
+        }
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).ok()?;
+        mac.update(&data[..PACKET_LEN - TAG_LEN]);
+        mac.verify_truncated_left(&data[PACKET_LEN - TAG_LEN..])
+            .ok()?;
+
+        let action = match data[3] {
+            1 => Action::Toggle,
+            _ => return None,
+        };
+        Some(Packet {
+            action,
+            counter: u32::from_le_bytes(data[4..8].try_into().ok()?),
+        })
+    }
+}
+
+/// Whether the data looks like a remote button packet, before verification.
+pub fn is_button_packet(data: &[u8]) -> bool {
+    data.len() == PACKET_LEN && data[..2] == MAGIC && data[2] == VERSION
+}
+
+fn tag(key: &[u8], data: &[u8]) -> [u8; 32] {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC key");
+    mac.update(data);
+
+    let mut tag = [0; 32];
+    tag.copy_from_slice(&mac.finalize().into_bytes());
+    tag
+}
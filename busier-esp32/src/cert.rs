@@ -0,0 +1,219 @@
+//! Self-signed certificate generation.
+//!
+//! Creates a P-256 key pair and a self-signed X.509 certificate for the
+//! HTTPS listener, so it can be enabled without minting certificates
+//! elsewhere. The DER is assembled by hand; only the handful of structures
+//! a certificate needs are supported.
+
+use std::net::Ipv4Addr;
+
+use esp_idf_svc::sys;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use sha2::{Digest, Sha256};
+
+// DER tags
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const UTF8_STRING: u8 = 0x0C;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+// Context-specific tags
+const EXPLICIT_0: u8 = 0xA0;
+const EXPLICIT_1: u8 = 0xA1;
+const EXPLICIT_3: u8 = 0xA3;
+const SAN_IP_ADDRESS: u8 = 0x87;
+
+// Encoded object identifiers, tag included
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x06, 0x08, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x06, 0x07, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x06, 0x08, 0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+const OID_COMMON_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x04, 0x03];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x1D, 0x11];
+
+// Valid from 2024, so it works before the clock is set, and never expires
+const NOT_BEFORE: &str = "240101000000Z";
+const NOT_AFTER: &str = "99991231235959Z";
+
+/// A generated certificate with its key, both as PEM.
+pub struct SelfSigned {
+    pub cert_pem: String,
+    pub key_pem: String,
+    /// SHA-256 of the certificate, as browsers show it.
+    pub fingerprint: [u8; 32],
+}
+
+/// Generates a key pair and a certificate for `name`, valid for `ip`.
+pub fn self_signed(name: &str, ip: Ipv4Addr) -> anyhow::Result<SelfSigned> {
+    let key = loop {
+        // Almost every 32 random bytes are a valid scalar
+        if let Ok(key) = SigningKey::from_slice(&random::<32>()) {
+            break key;
+        }
+    };
+    let public_key = key.verifying_key().to_encoded_point(false);
+    let algorithm = der(SEQUENCE, OID_ECDSA_WITH_SHA256);
+
+    let mut serial = random::<16>();
+    // Positive and without a leading zero byte
+    serial[0] = serial[0] & 0x7F | 0x40;
+
+    let name = der(
+        SEQUENCE,
+        &der(
+            SET,
+            &der(
+                SEQUENCE,
+                &[OID_COMMON_NAME, &der(UTF8_STRING, name.as_bytes())].concat(),
+            ),
+        ),
+    );
+    let public_key_info = der(
+        SEQUENCE,
+        &[
+            der(SEQUENCE, &[OID_EC_PUBLIC_KEY, OID_PRIME256V1].concat()),
+            bit_string(public_key.as_bytes()),
+        ]
+        .concat(),
+    );
+    let alt_names = der(SEQUENCE, &der(SAN_IP_ADDRESS, &ip.octets()));
+    let extensions = der(
+        EXPLICIT_3,
+        &der(
+            SEQUENCE,
+            &der(
+                SEQUENCE,
+                &[OID_SUBJECT_ALT_NAME, &der(OCTET_STRING, &alt_names)].concat(),
+            ),
+        ),
+    );
+
+    let tbs = der(
+        SEQUENCE,
+        &[
+            // Version 3
+            der(EXPLICIT_0, &der(INTEGER, &[2])),
+            der(INTEGER, &serial),
+            algorithm.clone(),
+            name.clone(),
+            der(
+                SEQUENCE,
+                &[
+                    der(UTC_TIME, NOT_BEFORE.as_bytes()),
+                    der(GENERALIZED_TIME, NOT_AFTER.as_bytes()),
+                ]
+                .concat(),
+            ),
+            name,
+            public_key_info,
+            extensions,
+        ]
+        .concat(),
+    );
+
+    let signature: Signature = key.sign(&tbs);
+    let (r, s) = signature.split_bytes();
+    let signature = der(SEQUENCE, &[integer(&r), integer(&s)].concat());
+    let cert = der(SEQUENCE, &[tbs, algorithm, bit_string(&signature)].concat());
+
+    // SEC1 ECPrivateKey
+    let private_key = der(
+        SEQUENCE,
+        &[
+            der(INTEGER, &[1]),
+            der(OCTET_STRING, &key.to_bytes()),
+            der(EXPLICIT_0, OID_PRIME256V1),
+            der(EXPLICIT_1, &bit_string(public_key.as_bytes())),
+        ]
+        .concat(),
+    );
+
+    Ok(SelfSigned {
+        cert_pem: pem("CERTIFICATE", &cert),
+        key_pem: pem("EC PRIVATE KEY", &private_key),
+        fingerprint: Sha256::digest(&cert).into(),
+    })
+}
+
+/// Fingerprint bytes as colon-separated hex, e.g. `AB:CD:EF`.
+pub fn format_fingerprint(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        encoded.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        encoded.push(0x80 | bytes.len() as u8);
+        encoded.extend(bytes);
+    }
+    encoded.extend_from_slice(content);
+    encoded
+}
+
+// Unsigned big-endian integer, minimal and positive
+fn integer(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len() - 1);
+    let bytes = &bytes[start..];
+    if bytes[0] & 0x80 != 0 {
+        der(INTEGER, &[&[0], bytes].concat())
+    } else {
+        der(INTEGER, bytes)
+    }
+}
+
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    // No unused bits
+    der(BIT_STRING, &[&[0], bytes].concat())
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in der.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// N bytes from the hardware random number generator.
+pub fn random<const N: usize>() -> [u8; N] {
+    let mut buf = [0; N];
+    // SAFETY: writes exactly N bytes into the buffer; with WiFi running the
+    // hardware RNG is seeded by RF noise
+    unsafe { sys::esp_fill_random(buf.as_mut_ptr().cast(), N) };
+    buf
+}
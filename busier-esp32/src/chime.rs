@@ -0,0 +1,146 @@
+//! I2S audio chimes.
+//!
+//! Plays short sounds embedded in the firmware through a MAX98357 I2S
+//! amplifier on knocks and status changes, instead of beeping the buzzer.
+//! Which sound each event plays and the volume come from `chime`. The
+//! sounds are 16 kHz, 16-bit mono WAV files.
+
+use std::sync::{mpsc, OnceLock};
+
+use esp_idf_svc::hal::delay::BLOCK;
+use esp_idf_svc::hal::i2s::{I2sDriver, I2sTx};
+use log::warn;
+
+use crate::config;
+use crate::output::Signal;
+
+const CHIME_STACK_SIZE: usize = 4096;
+pub const SAMPLE_RATE: u32 = 16_000;
+// Samples scaled and written per DMA transfer
+const CHUNK_SAMPLES: usize = 256;
+
+// The embedded sounds by name
+const SOUNDS: [(&str, &[u8]); 4] = [
+    ("ding", include_bytes!("../sounds/ding.wav")),
+    ("dingdong", include_bytes!("../sounds/dingdong.wav")),
+    ("chirp", include_bytes!("../sounds/chirp.wav")),
+    ("knock", include_bytes!("../sounds/knock.wav")),
+];
+
+static SENDER: OnceLock<mpsc::SyncSender<(&'static [u8], u8)>> = OnceLock::new();
+
+/// Spawns the playback thread. The driver must be set up for
+/// [`SAMPLE_RATE`] with 16-bit mono slots.
+pub fn start(mut i2s: I2sDriver<'static, I2sTx>) -> anyhow::Result<()> {
+    i2s.tx_enable()?;
+
+    // Only one sound queued; events during playback are dropped
+    let (tx, rx) = mpsc::sync_channel(1);
+
+    std::thread::Builder::new()
+        .name("chime".into())
+        .stack_size(CHIME_STACK_SIZE)
+        .spawn(move || {
+            for (samples, volume) in rx {
+                if let Err(e) = play_samples(&mut i2s, samples, volume) {
+                    warn!("Failed to play chime: {:?}", e);
+                }
+            }
+        })?;
+
+    SENDER
+        .set(tx)
+        .map_err(|_| anyhow::anyhow!("Chime thread already started"))?;
+
+    Ok(())
+}
+
+/// Plays the sound configured for a signal. Returns false if chimes are off
+/// or the sound is unknown, in which case the caller should beep instead.
+pub fn play(signal: Signal) -> bool {
+    let config = config::get().chime;
+    let Some(tx) = SENDER.get().filter(|_| config.enabled) else {
+        return false;
+    };
+
+    let name = match signal {
+        Signal::Knock => &config.knock,
+        Signal::StatusChanged => &config.status_changed,
+    };
+    // No sound selected means this event stays silent
+    if name.is_empty() {
+        return true;
+    }
+
+    let samples = SOUNDS
+        .iter()
+        .find(|(sound, _)| *sound == name.as_str())
+        .map(|(_, wav)| pcm_data(wav));
+    match samples {
+        Some(Ok(samples)) => {
+            let _ = tx.try_send((samples, config.volume.min(100)));
+            true
+        }
+        Some(Err(e)) => {
+            warn!("Bad chime {}: {:?}", name, e);
+            false
+        }
+        None => {
+            warn!("Unknown chime: {}", name);
+            false
+        }
+    }
+}
+
+fn play_samples(
+    i2s: &mut I2sDriver<'static, I2sTx>,
+    samples: &[u8],
+    volume: u8,
+) -> anyhow::Result<()> {
+    let mut buf = Vec::with_capacity(CHUNK_SAMPLES * 2);
+    for chunk in samples.chunks(CHUNK_SAMPLES * 2) {
+        buf.clear();
+        for sample in chunk.chunks_exact(2) {
+            let sample = i16::from_le_bytes([sample[0], sample[1]]);
+            let scaled = (i32::from(sample) * i32::from(volume) / 100) as i16;
+            buf.extend_from_slice(&scaled.to_le_bytes());
+        }
+        i2s.write_all(&buf, BLOCK)?;
+    }
+    Ok(())
+}
+
+// Finds the samples in a WAV file, checking they are in the expected format
+fn pcm_data(wav: &'static [u8]) -> anyhow::Result<&'static [u8]> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        anyhow::bail!("not a WAV file");
+    }
+
+    let mut rest = &wav[12..];
+    let mut format_ok = false;
+    while rest.len() >= 8 {
+        let id = &rest[0..4];
+        let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let body = rest
+            .get(8..8 + len)
+            .ok_or_else(|| anyhow::anyhow!("truncated chunk"))?;
+
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let format = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                format_ok = format == 1 && channels == 1 && rate == SAMPLE_RATE && bits == 16;
+            }
+            b"data" if format_ok => return Ok(body),
+            b"data" => anyhow::bail!("not {} Hz 16-bit mono PCM", SAMPLE_RATE),
+            _ => {}
+        }
+
+        // Chunks are padded to an even length
+        rest = rest.get(8 + len + len % 2..).unwrap_or_default();
+    }
+
+    anyhow::bail!("no data chunk")
+}
@@ -0,0 +1,256 @@
+//! Wall-clock time synchronized over SNTP.
+//!
+//! Local time follows `timezone`, or the `TZ` given at build time,
+//! defaulting to UTC. Either is a POSIX TZ string such as
+//! `CET-1CEST,M3.5.0,M10.5.0/3` or a zone name such as `Europe/Berlin` from
+//! the embedded subset of the tz database. Times of day are converted with
+//! the daylight saving rules of the day they fall on.
+//!
+//! SNTP asks the servers in `ntp` in turn, every hour. Offsets under about
+//! half an hour are slewed, the clock running slightly fast or slow until it
+//! is right, so schedules never see the time jump backwards; only a clock
+//! that is far off, as after a cold boot, is stepped. Without a successful
+//! sync for a few hours the clock counts as unsynchronized again.
+//!
+//! A backup clock such as the DS3231 can set the time as well, before the
+//! first sync and while SNTP is out of reach.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use busier_core::stats::Stamp;
+use esp_idf_svc::sntp::{EspSntp, SntpConf, SyncMode};
+use esp_idf_svc::sys;
+use log::{info, warn};
+
+use crate::config::{self, NtpConfig};
+use crate::device;
+use crate::hal::LocalTime;
+use crate::tz;
+
+const TIMEZONE: &str = match option_env!("TZ") {
+    Some(tz) => tz,
+    None => "UTC0",
+};
+
+// Anything before this means the clock has not been set yet
+const MIN_VALID_TIME: Duration = Duration::from_secs(1_704_067_200); // 2024-01-01
+
+// Three missed hourly syncs
+const MAX_SYNC_AGE: Duration = Duration::from_secs(3 * 60 * 60);
+// Time for the first sync after startup
+const FIRST_SYNC_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+// Largest offset slewed rather than stepped, as SNTP does
+#[cfg_attr(not(feature = "rtc"), allow(dead_code))]
+const MAX_SLEW: Duration = Duration::from_secs(30 * 60);
+
+const NEVER: u64 = u64::MAX;
+
+// Uptime in seconds of the latest SNTP sync and of the latest time from a
+// backup clock
+static SYNCED_AT: AtomicU64 = AtomicU64::new(NEVER);
+static BACKUP_SET_AT: AtomicU64 = AtomicU64::new(NEVER);
+static LOST: AtomicBool = AtomicBool::new(false);
+
+/// Applies the timezone and starts SNTP. Keep the returned handle alive.
+pub fn start() -> anyhow::Result<EspSntp<'static>> {
+    apply_timezone();
+
+    let mut servers = config::get().ntp.servers;
+    if servers.is_empty() {
+        servers = NtpConfig::default().servers;
+    }
+    let mut conf = SntpConf {
+        sync_mode: SyncMode::Smooth,
+        ..Default::default()
+    };
+    // Fewer servers than slots are repeated
+    for (slot, server) in conf.servers.iter_mut().zip(servers.iter().cycle()) {
+        *slot = server.as_str();
+    }
+
+    Ok(EspSntp::new_with_callback(&conf, |_| {
+        SYNCED_AT.store(device::uptime().as_secs(), Ordering::SeqCst);
+        if LOST.swap(false, Ordering::SeqCst) {
+            info!("Clock synchronized again");
+        }
+    })?)
+}
+
+/// Uptime in seconds of the latest SNTP sync, if any.
+#[cfg_attr(not(feature = "rtc"), allow(dead_code))]
+pub fn ntp_synced_at() -> Option<u64> {
+    Some(SYNCED_AT.load(Ordering::SeqCst)).filter(|at| *at != NEVER)
+}
+
+/// Sets the clock from a backup clock. An offset small enough is slewed,
+/// like SNTP does, and a larger one or an unset clock stepped.
+#[cfg_attr(not(feature = "rtc"), allow(dead_code))]
+pub fn set_from_backup(time: SystemTime) -> anyhow::Result<()> {
+    let target = time.duration_since(UNIX_EPOCH)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO);
+    let offset = target.abs_diff(now);
+
+    if is_synced() && offset < MAX_SLEW {
+        let micros = offset.as_micros() as i64;
+        let micros = if target < now { -micros } else { micros };
+        let delta = sys::timeval {
+            tv_sec: (micros / 1_000_000) as _,
+            tv_usec: (micros % 1_000_000) as _,
+        };
+        // SAFETY: `delta` is a valid local, and the old delta is not asked for
+        if unsafe { sys::adjtime(&delta, std::ptr::null_mut()) } != 0 {
+            anyhow::bail!("adjtime failed");
+        }
+    } else {
+        let time = sys::timeval {
+            tv_sec: target.as_secs() as _,
+            tv_usec: target.subsec_micros() as _,
+        };
+        // SAFETY: `time` is a valid local, and no timezone is given
+        if unsafe { sys::settimeofday(&time, std::ptr::null()) } != 0 {
+            anyhow::bail!("settimeofday failed");
+        }
+    }
+
+    BACKUP_SET_AT.store(device::uptime().as_secs(), Ordering::SeqCst);
+    Ok(())
+}
+
+/// Whether the clock has not been synchronized for a while, as the displays
+/// show. Time from a backup clock counts as a sync.
+pub fn sync_lost() -> bool {
+    let uptime = device::uptime();
+    let latest = [
+        SYNCED_AT.load(Ordering::SeqCst),
+        BACKUP_SET_AT.load(Ordering::SeqCst),
+    ]
+    .into_iter()
+    .filter(|at| *at != NEVER)
+    .max();
+    let lost = match latest {
+        None => uptime > FIRST_SYNC_TIMEOUT,
+        Some(at) => uptime.saturating_sub(Duration::from_secs(at)) > MAX_SYNC_AGE,
+    };
+    if lost && !LOST.swap(true, Ordering::SeqCst) {
+        warn!("Clock not synchronized");
+    }
+    lost
+}
+
+/// Switches local time to the configured timezone.
+pub fn apply_timezone() {
+    let timezone = config::get().timezone;
+    let timezone = if timezone.is_empty() {
+        TIMEZONE
+    } else {
+        &timezone
+    };
+    let posix = match tz::posix(timezone) {
+        Some(posix) => posix,
+        // POSIX strings never contain a slash
+        None if timezone.contains('/') => {
+            warn!("Unknown timezone {}, using UTC", timezone);
+            "UTC0"
+        }
+        None => timezone,
+    };
+
+    std::env::set_var("TZ", posix);
+    // SAFETY: tzset only reads the TZ variable set above
+    unsafe { sys::tzset() };
+}
+
+/// Whether the clock holds a plausible time.
+pub fn is_synced() -> bool {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .is_ok_and(|since_epoch| since_epoch >= MIN_VALID_TIME)
+}
+
+/// Current local time, if the clock has been set.
+pub fn local_now() -> Option<LocalTime> {
+    if !is_synced() {
+        return None;
+    }
+
+    let mut now: sys::time_t = 0;
+    // SAFETY: both pointers refer to valid, initialized locals
+    let tm = unsafe {
+        let mut tm: sys::tm = std::mem::zeroed();
+        sys::time(&mut now);
+        sys::localtime_r(&now, &mut tm);
+        tm
+    };
+
+    Some(LocalTime {
+        // tm_wday counts from Sunday
+        weekday: ((tm.tm_wday + 6) % 7) as u8,
+        hour: tm.tm_hour as u8,
+        minute: tm.tm_min as u8,
+        second: tm.tm_sec as u8,
+    })
+}
+
+/// Current local date and hour, if the clock has been set.
+pub fn local_stamp() -> Option<Stamp> {
+    if !is_synced() {
+        return None;
+    }
+
+    let mut now: sys::time_t = 0;
+    // SAFETY: both pointers refer to valid, initialized locals
+    let tm = unsafe {
+        let mut tm: sys::tm = std::mem::zeroed();
+        sys::time(&mut now);
+        sys::localtime_r(&now, &mut tm);
+        tm
+    };
+
+    Some(Stamp {
+        // tm_year counts from 1900 and tm_mon from 0
+        date: ((tm.tm_year + 1900) * 10000 + (tm.tm_mon + 1) * 100 + tm.tm_mday) as u32,
+        hour: tm.tm_hour as u8,
+    })
+}
+
+/// Next time the local clock shows the given minute of the day, today or
+/// tomorrow. `None` until the clock is synchronized.
+///
+/// Days with a daylight saving change are 23 or 25 hours long, so the time
+/// is worked out with the rules of the target day rather than by adding up
+/// minutes. A time skipped by the change falls an hour later.
+pub fn next_occurrence(minute_of_day: u16) -> Option<SystemTime> {
+    if !is_synced() {
+        return None;
+    }
+
+    let mut now: sys::time_t = 0;
+    // SAFETY: both pointers refer to valid, initialized locals
+    let mut tm = unsafe {
+        let mut tm: sys::tm = std::mem::zeroed();
+        sys::time(&mut now);
+        sys::localtime_r(&now, &mut tm);
+        tm
+    };
+
+    tm.tm_hour = i32::from(minute_of_day / 60);
+    tm.tm_min = i32::from(minute_of_day % 60);
+    tm.tm_sec = 0;
+    for _ in 0..2 {
+        // Let mktime work out whether daylight saving time applies
+        tm.tm_isdst = -1;
+        let mut day = tm;
+        // SAFETY: mktime only normalizes the local copy
+        let at = unsafe { sys::mktime(&mut day) };
+        if at > now {
+            return Some(UNIX_EPOCH + Duration::from_secs(at as u64));
+        }
+        // Already past today; mktime carries the day over month ends
+        tm.tm_mday += 1;
+    }
+
+    None
+}
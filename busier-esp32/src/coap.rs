@@ -0,0 +1,374 @@
+//! Minimal CoAP server (RFC 7252) with an observable status resource.
+//!
+//! Resources on UDP port 5683:
+//! - `/status`: GET (observable, RFC 7641) returns the status name,
+//!   PUT/POST with a status name sets it, if `coap.writable` is set
+//! - `/.well-known/core`: resource discovery (RFC 6690)
+
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::config;
+use crate::setup;
+use crate::status::{self, Source, Status};
+
+const COAP_PORT: u16 = 5683;
+const COAP_STACK_SIZE: usize = 6144;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_MESSAGE_LEN: usize = 256;
+const MAX_OBSERVERS: usize = 8;
+
+// Message types
+const CON: u8 = 0;
+const NON: u8 = 1;
+const ACK: u8 = 2;
+const RST: u8 = 3;
+
+// Method and response codes (class << 5 | detail)
+const GET: u8 = 0x01;
+const POST: u8 = 0x02;
+const PUT: u8 = 0x03;
+const CHANGED: u8 = 0x44; // 2.04
+const CONTENT: u8 = 0x45; // 2.05
+const BAD_REQUEST: u8 = 0x80; // 4.00
+const FORBIDDEN: u8 = 0x83; // 4.03
+const NOT_FOUND: u8 = 0x84; // 4.04
+const METHOD_NOT_ALLOWED: u8 = 0x85; // 4.05
+const SERVICE_UNAVAILABLE: u8 = 0xA3; // 5.03
+
+// Option numbers
+const OPTION_OBSERVE: u16 = 6;
+const OPTION_URI_PATH: u16 = 11;
+const OPTION_CONTENT_FORMAT: u16 = 12;
+
+const FORMAT_TEXT: u8 = 0;
+const FORMAT_LINK: u8 = 40;
+
+const WELL_KNOWN_CORE: &str = "</status>;obs;rt=\"busier.status\";ct=0";
+
+struct Request {
+    kind: u8,
+    code: u8,
+    message_id: u16,
+    token: Vec<u8>,
+    path: String,
+    observe: Option<u32>,
+    payload: Vec<u8>,
+}
+
+struct Response {
+    code: u8,
+    options: Vec<(u16, Vec<u8>)>,
+    payload: Vec<u8>,
+}
+
+struct Observer {
+    addr: SocketAddr,
+    token: Vec<u8>,
+}
+
+/// Spawns the CoAP server thread.
+pub fn start() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", COAP_PORT))?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    std::thread::Builder::new()
+        .name("coap".into())
+        .stack_size(COAP_STACK_SIZE)
+        .spawn(move || {
+            let mut server = Server {
+                socket,
+                observers: Vec::new(),
+                next_message_id: 0,
+                observe_sequence: 0,
+                last_status: status::current(),
+            };
+            loop {
+                if let Err(e) = server.poll() {
+                    warn!("CoAP error: {:?}", e);
+                }
+            }
+        })?;
+
+    info!("CoAP server listening on port {}", COAP_PORT);
+    Ok(())
+}
+
+struct Server {
+    socket: UdpSocket,
+    observers: Vec<Observer>,
+    next_message_id: u16,
+    observe_sequence: u32,
+    last_status: Status,
+}
+
+impl Server {
+    fn poll(&mut self) -> anyhow::Result<()> {
+        let mut buf = [0; MAX_MESSAGE_LEN];
+        match self.socket.recv_from(&mut buf) {
+            Ok((len, addr)) => self.handle(&buf[..len], addr)?,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let current_status = status::current();
+        if current_status != self.last_status {
+            self.last_status = current_status;
+            self.notify_observers(current_status)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, data: &[u8], addr: SocketAddr) -> anyhow::Result<()> {
+        let Some(request) = parse(data) else {
+            return Ok(());
+        };
+
+        // A reset answers one of our notifications: the client lost interest
+        if request.kind == RST {
+            self.observers.retain(|o| o.addr != addr);
+            return Ok(());
+        }
+        if request.code == 0 || request.kind == ACK {
+            return Ok(());
+        }
+
+        let response = self.respond(&request, addr);
+        let kind = if request.kind == CON { ACK } else { NON };
+        let message_id = if request.kind == CON {
+            request.message_id
+        } else {
+            self.message_id()
+        };
+
+        let message = encode(kind, response.code, message_id, &request.token, &response);
+        self.socket.send_to(&message, addr)?;
+        Ok(())
+    }
+
+    fn respond(&mut self, request: &Request, addr: SocketAddr) -> Response {
+        match (request.path.as_str(), request.code) {
+            ("status", GET) => {
+                let mut options = Vec::new();
+                match request.observe {
+                    Some(0) => {
+                        self.observers
+                            .retain(|o| !(o.addr == addr && o.token == request.token));
+                        if self.observers.len() < MAX_OBSERVERS {
+                            self.observers.push(Observer {
+                                addr,
+                                token: request.token.clone(),
+                            });
+                            options.push((OPTION_OBSERVE, encode_uint(self.observe_sequence)));
+                        }
+                    }
+                    Some(1) => self
+                        .observers
+                        .retain(|o| !(o.addr == addr && o.token == request.token)),
+                    _ => {}
+                }
+                options.push((OPTION_CONTENT_FORMAT, vec![FORMAT_TEXT]));
+                Response {
+                    code: CONTENT,
+                    options,
+                    payload: status::current().as_str().as_bytes().to_vec(),
+                }
+            }
+            // Anyone on the network can send these
+            ("status", PUT | POST) if setup::is_pending() => {
+                text_response(SERVICE_UNAVAILABLE, "Finish the setup first")
+            }
+            ("status", PUT | POST) if !config::get().coap.writable => {
+                text_response(FORBIDDEN, "Writes are disabled")
+            }
+            ("status", PUT | POST) => {
+                let new_status = std::str::from_utf8(&request.payload)
+                    .ok()
+                    .and_then(|name| Status::parse(name.trim()));
+                match new_status {
+                    Some(new_status) => {
+                        status::set(Source::Integration, new_status);
+                        Response {
+                            code: CHANGED,
+                            options: Vec::new(),
+                            payload: Vec::new(),
+                        }
+                    }
+                    None => text_response(BAD_REQUEST, "Invalid status"),
+                }
+            }
+            (".well-known/core", GET) => Response {
+                code: CONTENT,
+                options: vec![(OPTION_CONTENT_FORMAT, vec![FORMAT_LINK])],
+                payload: WELL_KNOWN_CORE.as_bytes().to_vec(),
+            },
+            ("status" | ".well-known/core", _) => {
+                text_response(METHOD_NOT_ALLOWED, "Method not allowed")
+            }
+            _ => text_response(NOT_FOUND, "Not found"),
+        }
+    }
+
+    fn notify_observers(&mut self, current_status: Status) -> anyhow::Result<()> {
+        if self.observers.is_empty() {
+            return Ok(());
+        }
+
+        // Observe sequence numbers are 24 bits
+        self.observe_sequence = (self.observe_sequence + 1) & 0x00FF_FFFF;
+        let response = Response {
+            code: CONTENT,
+            options: vec![
+                (OPTION_OBSERVE, encode_uint(self.observe_sequence)),
+                (OPTION_CONTENT_FORMAT, vec![FORMAT_TEXT]),
+            ],
+            payload: current_status.as_str().as_bytes().to_vec(),
+        };
+
+        for observer in &self.observers {
+            self.next_message_id = self.next_message_id.wrapping_add(1);
+            let message = encode(
+                NON,
+                CONTENT,
+                self.next_message_id,
+                &observer.token,
+                &response,
+            );
+            self.socket.send_to(&message, observer.addr)?;
+        }
+
+        Ok(())
+    }
+
+    fn message_id(&mut self) -> u16 {
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+        self.next_message_id
+    }
+}
+
+fn text_response(code: u8, text: &str) -> Response {
+    Response {
+        code,
+        options: vec![(OPTION_CONTENT_FORMAT, vec![FORMAT_TEXT])],
+        payload: text.as_bytes().to_vec(),
+    }
+}
+
+fn parse(data: &[u8]) -> Option<Request> {
+    if data.len() < 4 || data[0] >> 6 != 1 {
+        return None;
+    }
+    let kind = (data[0] >> 4) & 0x03;
+    let token_len = (data[0] & 0x0F) as usize;
+    if token_len > 8 {
+        return None;
+    }
+    let code = data[1];
+    let message_id = u16::from_be_bytes([data[2], data[3]]);
+    let token = data.get(4..4 + token_len)?.to_vec();
+
+    let mut path = Vec::new();
+    let mut observe = None;
+    let mut payload = Vec::new();
+    let mut option_number = 0u16;
+    let mut pos = 4 + token_len;
+
+    while pos < data.len() {
+        if data[pos] == 0xFF {
+            payload = data[pos + 1..].to_vec();
+            break;
+        }
+        let header = data[pos];
+        pos += 1;
+        let delta = read_option_field(header >> 4, data, &mut pos)?;
+        let len = read_option_field(header & 0x0F, data, &mut pos)? as usize;
+        let value = data.get(pos..pos + len)?;
+        pos += len;
+
+        option_number = option_number.checked_add(delta)?;
+        match option_number {
+            OPTION_URI_PATH => path.push(String::from_utf8_lossy(value).into_owned()),
+            OPTION_OBSERVE => {
+                observe = Some(value.iter().fold(0u32, |acc, b| (acc << 8) | *b as u32))
+            }
+            _ => {}
+        }
+    }
+
+    Some(Request {
+        kind,
+        code,
+        message_id,
+        token,
+        path: path.join("/"),
+        observe,
+        payload,
+    })
+}
+
+// Decodes an option delta or length nibble with its extended bytes
+fn read_option_field(nibble: u8, data: &[u8], pos: &mut usize) -> Option<u16> {
+    match nibble {
+        0..=12 => Some(nibble as u16),
+        13 => {
+            let value = *data.get(*pos)? as u16 + 13;
+            *pos += 1;
+            Some(value)
+        }
+        14 => {
+            let bytes = data.get(*pos..*pos + 2)?;
+            *pos += 2;
+            Some(u16::from_be_bytes([bytes[0], bytes[1]]).checked_add(269)?)
+        }
+        _ => None,
+    }
+}
+
+fn encode(kind: u8, code: u8, message_id: u16, token: &[u8], response: &Response) -> Vec<u8> {
+    let mut message = vec![0x40 | (kind << 4) | token.len() as u8, code];
+    message.extend_from_slice(&message_id.to_be_bytes());
+    message.extend_from_slice(token);
+
+    let mut options = response.options.clone();
+    options.sort_by_key(|(number, _)| *number);
+    let mut previous = 0u16;
+    for (number, value) in options {
+        let delta = number - previous;
+        previous = number;
+        let (delta_nibble, delta_ext) = option_field(delta);
+        let (len_nibble, len_ext) = option_field(value.len() as u16);
+        message.push((delta_nibble << 4) | len_nibble);
+        message.extend_from_slice(&delta_ext);
+        message.extend_from_slice(&len_ext);
+        message.extend_from_slice(&value);
+    }
+
+    if !response.payload.is_empty() {
+        message.push(0xFF);
+        message.extend_from_slice(&response.payload);
+    }
+    message
+}
+
+// Encodes an option delta or length into its nibble and extended bytes
+fn option_field(value: u16) -> (u8, Vec<u8>) {
+    match value {
+        0..=12 => (value as u8, Vec::new()),
+        13..=268 => (13, vec![(value - 13) as u8]),
+        _ => (14, (value - 269).to_be_bytes().to_vec()),
+    }
+}
+
+// Minimal big-endian encoding used for uint options
+fn encode_uint(value: u32) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let first = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    bytes[first..].to_vec()
+}
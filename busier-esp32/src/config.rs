@@ -0,0 +1,226 @@
+//! Runtime configuration persisted as a JSON blob in NVS.
+//!
+//! The sections themselves are defined in `busier_core::config`. Passwords,
+//! tokens and keys are left out of the blob and stored with
+//! [`crate::secrets`] instead.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::server::EspHttpServer;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use log::{info, warn};
+
+pub use busier_core::config::*;
+
+use crate::auth;
+use crate::clock;
+use crate::http_util::Routes;
+use crate::power;
+use crate::secrets;
+use crate::status::Status;
+
+const NAMESPACE: &str = "busier";
+const KEY: &str = "config";
+// Upper bound for the serialized configuration
+pub const MAX_CONFIG_LEN: usize = 8192;
+
+static CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+static NVS: Mutex<Option<EspNvs<NvsDefault>>> = Mutex::new(None);
+static STORED: AtomicBool = AtomicBool::new(false);
+
+/// Loads the configuration from NVS, falling back to defaults.
+pub fn init(partition: EspDefaultNvsPartition) -> anyhow::Result<()> {
+    let mut nvs = EspNvs::new(partition, NAMESPACE, true)?;
+
+    let mut buf = vec![0; MAX_CONFIG_LEN];
+    let mut config = match nvs.get_raw(KEY, &mut buf)? {
+        Some(data) => {
+            STORED.store(true, Ordering::Relaxed);
+            serde_json::from_slice(data).unwrap_or_else(|e| {
+                warn!("Stored configuration is invalid, using defaults: {:?}", e);
+                Config::default()
+            })
+        }
+        None => {
+            info!("No stored configuration, using defaults");
+            Config::default()
+        }
+    };
+
+    if is_stored() {
+        let mut secrets = secrets::config().unwrap_or_else(|e| {
+            warn!("Stored secrets are invalid, dropping them: {:?}", e);
+            Default::default()
+        });
+        // A configuration saved by an older firmware holds its secrets in
+        // plain text; move them over once
+        let plain = config.take_secrets();
+        if !plain.is_empty() {
+            info!(
+                "Moving {} secrets out of the stored configuration",
+                plain.len()
+            );
+            secrets.extend(plain);
+            secrets::set_config(&secrets)?;
+            nvs.set_raw(KEY, &serde_json::to_vec(&config)?)?;
+        }
+        config.put_secrets(&secrets);
+    }
+
+    *CONFIG.lock().unwrap() = Some(config);
+    *NVS.lock().unwrap() = Some(nvs);
+
+    Ok(())
+}
+
+/// Whether a configuration has been saved to NVS; false on a fresh device.
+pub fn is_stored() -> bool {
+    STORED.load(Ordering::Relaxed)
+}
+
+/// Returns a snapshot of the current configuration.
+pub fn get() -> Config {
+    CONFIG.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Applies a partial update: top-level sections present in `patch` replace
+/// the current ones, everything else is kept. The result is persisted.
+pub fn update(patch: serde_json::Value) -> anyhow::Result<()> {
+    let serde_json::Value::Object(patch) = patch else {
+        anyhow::bail!("Configuration must be a JSON object");
+    };
+
+    set(get().merged(patch)?)
+}
+
+/// Replaces the configuration and persists it.
+pub fn set(mut config: Config) -> anyhow::Result<()> {
+    crate::board::validate(&config)?;
+    for rule in &config.rules {
+        rule.validate().map_err(|e| anyhow::anyhow!("rule '{}': {}", rule.name, e))?;
+    }
+    for status in [Status::Free, Status::Dnd, Status::Away] {
+        let style = config.styles.get(status);
+        if style.rgb().is_none() {
+            anyhow::bail!(
+                "style for {}: {:?} is not a #RRGGBB color",
+                status.as_str(),
+                style.color
+            );
+        }
+    }
+    for reminder in &config.reminders {
+        reminder
+            .validate()
+            .map_err(|e| anyhow::anyhow!("reminder '{}': {}", reminder.message, e))?;
+    }
+    if config.ntp.servers.len() > MAX_NTP_SERVERS {
+        anyhow::bail!("at most {} NTP servers", MAX_NTP_SERVERS);
+    }
+    if config.ntp.servers.iter().any(|server| server.trim().is_empty()) {
+        anyhow::bail!("empty NTP server");
+    }
+    if !(1..=HttpConfig::MAX_OPEN_SOCKETS).contains(&config.http.max_open_sockets) {
+        anyhow::bail!(
+            "http.max_open_sockets must be 1 to {}",
+            HttpConfig::MAX_OPEN_SOCKETS
+        );
+    }
+    if config.http.stack_size < HttpConfig::MIN_STACK_SIZE {
+        anyhow::bail!(
+            "http.stack_size must be at least {}",
+            HttpConfig::MIN_STACK_SIZE
+        );
+    }
+    for pin in &config.cert_pins {
+        if pin.fingerprint().is_none() {
+            anyhow::bail!("cert pin for {}: not a SHA-256 fingerprint", pin.host);
+        }
+    }
+
+    let mut current = CONFIG.lock().unwrap();
+    config.restore_secrets(current.as_ref().unwrap_or(&Config::default()));
+    // Anyone can compute an HMAC keyed with an empty secret
+    if let Some(hook) = config.hooks.iter().find(|hook| hook.secret.is_empty()) {
+        anyhow::bail!("hook '{}': empty secret", hook.name);
+    }
+    if crate::esphome::decode_key(&config.esphome.encryption_key).is_none() {
+        anyhow::bail!("esphome.encryption_key must be base64 of 32 bytes");
+    }
+
+    let mut plain = config.clone();
+    let secrets = plain.take_secrets();
+    let data = serde_json::to_vec(&plain)?;
+    if data.len() > MAX_CONFIG_LEN {
+        anyhow::bail!("Configuration too big");
+    }
+
+    if let Some(nvs) = NVS.lock().unwrap().as_mut() {
+        secrets::set_config(&secrets)?;
+        nvs.set_raw(KEY, &data)?;
+        STORED.store(true, Ordering::Relaxed);
+    }
+    #[cfg(feature = "hub75")]
+    crate::hub75::set_brightness(config.hub75.brightness);
+    *current = Some(config);
+
+    Ok(())
+}
+
+/// Registers `GET` and `POST /api/config` for the admin.
+pub fn register(server: &mut EspHttpServer<'static>, secure: bool) -> anyhow::Result<()> {
+    server.route(
+        "/api/config",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let body = serde_json::to_vec(&get().redacted())?;
+
+            let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+            resp.write_all(&body)?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    server.route(
+        "/api/config",
+        Method::Post,
+        auth::admin(secure, |mut req| {
+            let len = req.content_len().unwrap_or(0) as usize;
+
+            if len > MAX_CONFIG_LEN {
+                req.into_status_response(413)?
+                    .write_all("Request too big".as_bytes())?;
+                return Ok(());
+            }
+
+            let mut buf = vec![0; len];
+            req.read_exact(&mut buf)?;
+
+            let result = serde_json::from_slice::<serde_json::Value>(&buf)
+                .map_err(anyhow::Error::from)
+                .and_then(update);
+
+            match result {
+                Ok(()) => {
+                    if let Err(e) = power::configure_cpu() {
+                        warn!("Failed to configure power management: {:?}", e);
+                    }
+                    clock::apply_timezone();
+                    req.into_ok_response()?
+                        .write_all("Configuration saved".as_bytes())?;
+                }
+                Err(e) => {
+                    req.into_status_response(400)?
+                        .write_all(format!("Invalid configuration: {}", e).as_bytes())?;
+                }
+            }
+
+            Ok(())
+        }),
+    )?;
+
+    Ok(())
+}
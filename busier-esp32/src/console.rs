@@ -0,0 +1,119 @@
+//! Zero-touch provisioning over the serial console.
+//!
+//! Until the setup wizard has run, a thread reads the serial console, UART
+//! or the chip's own USB port, for one line holding a provisioning document
+//! of [`busier_core::provision`]. The WiFi credentials go to
+//! [`crate::secrets`], the configuration is saved as `POST /api/config`
+//! would, and the device restarts with both. So a batch of devices can be
+//! set up from a script, each with its own name and tokens, without opening
+//! their web pages. The thread prints `PROVISION READY` when it listens and
+//! answers every document with `PROVISION OK` or `PROVISION ERROR: <why>`.
+
+use std::io::BufRead;
+
+use busier_core::provision::Provisioning;
+use esp_idf_svc::sys::{self, esp};
+use log::{info, warn};
+
+use crate::config;
+use crate::device;
+use crate::secrets;
+use crate::setup;
+
+const CONSOLE_STACK_SIZE: usize = 8192;
+// Bytes the console driver buffers until the thread reads them
+const RX_BUFFER_LEN: usize = 1024;
+// Upper bound for a document, the configuration and a bit for the rest
+const MAX_LINE_LEN: usize = config::MAX_CONFIG_LEN + 512;
+
+/// Spawns the thread that waits for a provisioning document, unless the
+/// device has been set up already.
+pub fn start() -> anyhow::Result<()> {
+    if !setup::is_pending() {
+        return Ok(());
+    }
+    use_driver()?;
+
+    std::thread::Builder::new()
+        .name("console".into())
+        .stack_size(CONSOLE_STACK_SIZE)
+        .spawn(|| {
+            println!("PROVISION READY");
+            for line in std::io::stdin().lock().lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        warn!("Failed to read the console: {:?}", e);
+                        continue;
+                    }
+                };
+                // Anything else typed into the console is not meant for us
+                if !line.trim_start().starts_with('{') {
+                    continue;
+                }
+
+                match apply(&line) {
+                    Ok(()) => {
+                        println!("PROVISION OK");
+                        info!("Provisioned over the serial console, restarting");
+                        device::restart();
+                    }
+                    Err(e) => println!("PROVISION ERROR: {}", e),
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+fn apply(line: &str) -> anyhow::Result<()> {
+    if line.len() > MAX_LINE_LEN {
+        anyhow::bail!("document too big");
+    }
+    let provisioning: Provisioning = serde_json::from_str(line)?;
+    provisioning
+        .validate()
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    // The configuration first, as it is the one that can still be rejected
+    if let Some(config) = provisioning.config {
+        config::set(config)?;
+    }
+    if let Some(wifi) = provisioning.wifi {
+        secrets::set_wifi(&wifi.ssid, &wifi.password)?;
+    }
+    Ok(())
+}
+
+// Without the driver, reading the console does not wait for input
+fn use_driver() -> anyhow::Result<()> {
+    #[cfg(esp_idf_esp_console_usb_serial_jtag)]
+    {
+        let mut config = sys::usb_serial_jtag_driver_config_t {
+            tx_buffer_size: 256,
+            rx_buffer_size: RX_BUFFER_LEN as u32,
+        };
+        // SAFETY: installs the driver once; the VFS then reads through it
+        unsafe {
+            esp!(sys::usb_serial_jtag_driver_install(&mut config))?;
+            sys::esp_vfs_usb_serial_jtag_use_driver();
+        }
+    }
+    #[cfg(not(esp_idf_esp_console_usb_serial_jtag))]
+    {
+        let uart = sys::CONFIG_ESP_CONSOLE_UART_NUM as i32;
+        // SAFETY: installs the driver once; the VFS then reads through it
+        unsafe {
+            esp!(sys::uart_driver_install(
+                uart,
+                RX_BUFFER_LEN as i32,
+                0,
+                0,
+                std::ptr::null_mut(),
+                0,
+            ))?;
+            sys::esp_vfs_dev_uart_use_driver(uart);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! TM1637 countdown display.
+//!
+//! A 4-digit 7-segment display that shows only the time left on the running
+//! timer: the pomodoro phase if one is running, otherwise the time until the
+//! "back at" flip to Free. It reads the same timer state as the main display
+//! and stays blank while no timer runs.
+//!
+//! The TM1637 speaks a two-wire protocol that looks like I2C but has no
+//! addresses and sends bits LSB first, so it is bit-banged on its own pins.
+
+use std::time::Duration;
+
+use esp_idf_svc::hal::delay::Ets;
+use esp_idf_svc::hal::gpio::{AnyIOPin, AnyOutputPin, InputOutput, Output, PinDriver};
+use log::warn;
+
+use crate::config;
+use crate::pomodoro;
+use crate::status;
+
+const COUNTDOWN_STACK_SIZE: usize = 4096;
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+const BIT_DELAY_US: u32 = 5;
+
+const DATA_AUTO_INCREMENT: u8 = 0x40;
+const ADDRESS_FIRST_DIGIT: u8 = 0xC0;
+const DISPLAY_ON: u8 = 0x88;
+
+// Segments for 0 to 9; bit 7 of the second digit is the colon
+const DIGITS: [u8; 10] = [0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F];
+const COLON: u8 = 0x80;
+
+/// Time left on the running timer, if any.
+pub fn remaining() -> Option<Duration> {
+    match pomodoro::state() {
+        Some(state) => Some(Duration::from_secs(state.remaining_secs)),
+        None => status::back_at().map(|back_at| back_at.remaining()),
+    }
+}
+
+/// Spawns the thread driving the display. DIO is driven open-drain, so it
+/// needs the pull-up most TM1637 boards already have.
+pub fn start(clk: AnyOutputPin, dio: AnyIOPin) -> anyhow::Result<()> {
+    let mut tm1637 = Tm1637 {
+        clk: PinDriver::output(clk)?,
+        dio: PinDriver::input_output_od(dio)?,
+    };
+    tm1637.clk.set_high()?;
+    tm1637.dio.set_high()?;
+
+    std::thread::Builder::new()
+        .name("countdown".into())
+        .stack_size(COUNTDOWN_STACK_SIZE)
+        .spawn(move || {
+            let mut shown = None;
+            loop {
+                std::thread::sleep(REFRESH_INTERVAL);
+
+                let config = config::get().countdown;
+                if !config.enabled {
+                    continue;
+                }
+
+                let segments = remaining().map(segments).unwrap_or_default();
+                let update = (segments, config.brightness.min(7));
+                if shown == Some(update) {
+                    continue;
+                }
+
+                match tm1637.show(&segments, update.1) {
+                    Ok(()) => shown = Some(update),
+                    Err(e) => warn!("Failed to update countdown display: {:?}", e),
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+// Minutes and seconds, or hours and minutes from 100 minutes on
+fn segments(remaining: Duration) -> [u8; 4] {
+    let secs = remaining.as_secs();
+    let (high, low) = if secs < 100 * 60 {
+        (secs / 60, secs % 60)
+    } else {
+        ((secs / 3600).min(99), secs / 60 % 60)
+    };
+
+    let digit = |value: u64| DIGITS[value as usize % 10];
+    [
+        digit(high / 10),
+        digit(high % 10) | COLON,
+        digit(low / 10),
+        digit(low % 10),
+    ]
+}
+
+struct Tm1637 {
+    clk: PinDriver<'static, AnyOutputPin, Output>,
+    dio: PinDriver<'static, AnyIOPin, InputOutput>,
+}
+
+impl Tm1637 {
+    fn show(&mut self, segments: &[u8; 4], brightness: u8) -> anyhow::Result<()> {
+        self.command(&[DATA_AUTO_INCREMENT])?;
+
+        let mut data = vec![ADDRESS_FIRST_DIGIT];
+        data.extend_from_slice(segments);
+        self.command(&data)?;
+
+        self.command(&[DISPLAY_ON | brightness])
+    }
+
+    fn command(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        // Start: DIO falls while CLK is high
+        self.dio.set_low()?;
+        Self::delay();
+
+        for byte in bytes {
+            self.write_byte(*byte)?;
+        }
+
+        // Stop: DIO rises while CLK is high
+        self.clk.set_low()?;
+        self.dio.set_low()?;
+        Self::delay();
+        self.clk.set_high()?;
+        Self::delay();
+        self.dio.set_high()?;
+        Self::delay();
+        Ok(())
+    }
+
+    fn write_byte(&mut self, byte: u8) -> anyhow::Result<()> {
+        for bit in 0..8 {
+            self.clk.set_low()?;
+            self.dio.set_level((byte & (1 << bit) != 0).into())?;
+            Self::delay();
+            self.clk.set_high()?;
+            Self::delay();
+        }
+
+        // Release DIO for the acknowledge, which pulls it low
+        self.clk.set_low()?;
+        self.dio.set_high()?;
+        Self::delay();
+        self.clk.set_high()?;
+        Self::delay();
+        let acked = self.dio.is_low();
+        self.clk.set_low()?;
+        Self::delay();
+
+        if !acked {
+            anyhow::bail!("TM1637 did not acknowledge");
+        }
+        Ok(())
+    }
+
+    fn delay() {
+        Ets::delay_us(BIT_DELAY_US);
+    }
+}
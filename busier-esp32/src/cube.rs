@@ -0,0 +1,197 @@
+//! MPU6050 status cube.
+//!
+//! Reads gravity from an MPU6050 on the shared I2C bus and sets the status
+//! mapped to the face the enclosure rests on: face up, face down or on its
+//! side. A face must be held for a second before it counts, and only a change
+//! of face sets the status, so other sources can still change it while the
+//! enclosure lies still.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use embedded_hal::i2c::I2c;
+use log::{info, warn};
+
+use crate::config::{self, CubeConfig};
+use crate::status::{self, Source};
+
+const CUBE_STACK_SIZE: usize = 4096;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Consecutive samples a face must be held for
+const DEBOUNCE_SAMPLES: u32 = 10;
+const CALIBRATION_SAMPLES: i32 = 32;
+
+const ADDRESS: u8 = 0x68;
+const ACCEL_XOUT_H: u8 = 0x3B;
+const PWR_MGMT_1: u8 = 0x6B;
+const WHO_AM_I: u8 = 0x75;
+// ±2 g range
+const ONE_G: i32 = 16384;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Face {
+    Up,
+    Down,
+    Side,
+}
+
+static CALIBRATE: AtomicBool = AtomicBool::new(false);
+
+/// Recalibrates on the next sample. The enclosure must lie face up.
+pub fn calibrate() {
+    CALIBRATE.store(true, Ordering::SeqCst);
+}
+
+/// Spawns the polling thread. Without an MPU6050 on the bus the thread exits
+/// after logging a warning.
+pub fn start<I>(i2c: I) -> anyhow::Result<()>
+where
+    I: I2c + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name("cube".into())
+        .stack_size(CUBE_STACK_SIZE)
+        .spawn(move || {
+            let mut mpu = Mpu6050 { i2c };
+            if let Err(e) = mpu.init() {
+                warn!("No MPU6050 status cube: {:?}", e);
+                return;
+            }
+            info!("MPU6050 status cube ready");
+
+            let mut current: Option<Face> = None;
+            let mut candidate: Option<Face> = None;
+            let mut held = 0;
+
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let config = config::get().cube;
+                if !config.enabled {
+                    current = None;
+                    continue;
+                }
+
+                if CALIBRATE.swap(false, Ordering::SeqCst) {
+                    match mpu.calibrate() {
+                        Ok(()) => info!("Status cube calibrated"),
+                        Err(e) => warn!("Status cube calibration failed: {:?}", e),
+                    }
+                    continue;
+                }
+
+                let face = match mpu.acceleration() {
+                    Ok(raw) => classify(raw, config.offset),
+                    Err(e) => {
+                        warn!("Failed to read MPU6050: {:?}", e);
+                        continue;
+                    }
+                };
+
+                if face != candidate {
+                    candidate = face;
+                    held = 0;
+                    continue;
+                }
+                held += 1;
+                let Some(face) = face else {
+                    continue;
+                };
+                if held < DEBOUNCE_SAMPLES || Some(face) == current {
+                    continue;
+                }
+
+                // The face found at startup is only remembered
+                if current.is_some() {
+                    let new_status = face_status(&config, face);
+                    info!("Status cube turned {:?}: {}", face, new_status.as_str());
+                    status::set(Source::Button, new_status);
+                }
+                current = Some(face);
+            }
+        })?;
+
+    Ok(())
+}
+
+fn face_status(config: &CubeConfig, face: Face) -> status::Status {
+    match face {
+        Face::Up => config.up,
+        Face::Down => config.down,
+        Face::Side => config.side,
+    }
+}
+
+// Classifies a sample by the angle between gravity and the Z axis. Tilted
+// or moving samples are ambiguous and yield None.
+fn classify(raw: [i16; 3], offset: [i16; 3]) -> Option<Face> {
+    let [x, y, z] = [0, 1, 2].map(|i| i64::from(raw[i]) - i64::from(offset[i]));
+    let magnitude_sq = x * x + y * y + z * z;
+    let one_g_sq = i64::from(ONE_G) * i64::from(ONE_G);
+    if !(one_g_sq * 6 / 10..=one_g_sq * 14 / 10).contains(&magnitude_sq) {
+        return None;
+    }
+
+    // cos² of the angle to the Z axis, in percent
+    let cos_sq = z * z * 100 / magnitude_sq;
+    if cos_sq > 80 {
+        Some(if z > 0 { Face::Up } else { Face::Down })
+    } else if cos_sq < 10 {
+        Some(Face::Side)
+    } else {
+        None
+    }
+}
+
+struct Mpu6050<I> {
+    i2c: I,
+}
+
+impl<I: I2c> Mpu6050<I> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        let mut id = [0];
+        self.i2c
+            .write_read(ADDRESS, &[WHO_AM_I], &mut id)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        if id[0] != ADDRESS {
+            anyhow::bail!("Unexpected WHO_AM_I {:#04x}", id[0]);
+        }
+
+        // Wake up; the accelerometer defaults to ±2 g
+        self.i2c
+            .write(ADDRESS, &[PWR_MGMT_1, 0x00])
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+
+        Ok(())
+    }
+
+    fn acceleration(&mut self) -> anyhow::Result<[i16; 3]> {
+        let mut buf = [0; 6];
+        self.i2c
+            .write_read(ADDRESS, &[ACCEL_XOUT_H], &mut buf)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+
+        Ok([0, 2, 4].map(|i| i16::from_be_bytes([buf[i], buf[i + 1]])))
+    }
+
+    // Averages samples taken face up and stores their deviation from 1 g
+    // along Z as the offset.
+    fn calibrate(&mut self) -> anyhow::Result<()> {
+        let mut sum = [0i32; 3];
+        for _ in 0..CALIBRATION_SAMPLES {
+            let sample = self.acceleration()?;
+            for (total, value) in sum.iter_mut().zip(sample) {
+                *total += i32::from(value);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let expected = [0, 0, ONE_G];
+        let offset: [i16; 3] =
+            [0, 1, 2].map(|i| (sum[i] / CALIBRATION_SAMPLES - expected[i]) as i16);
+
+        let mut cube = config::get().cube;
+        cube.offset = offset;
+        config::update(serde_json::json!({ "cube": cube }))
+    }
+}
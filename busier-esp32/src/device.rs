@@ -0,0 +1,54 @@
+//! Device identity and health.
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use esp_idf_svc::sys;
+
+/// MAC address of the WiFi station interface.
+pub fn mac() -> [u8; 6] {
+    let mut mac = [0; 6];
+    // SAFETY: the buffer is the 6 bytes esp_read_mac writes
+    unsafe { sys::esp_read_mac(mac.as_mut_ptr(), sys::esp_mac_type_t_ESP_MAC_WIFI_STA) };
+    mac
+}
+
+/// IPv4 address of the WiFi station interface, if it has one.
+pub fn ip() -> Option<Ipv4Addr> {
+    let mut info: sys::esp_netif_ip_info_t = Default::default();
+    // SAFETY: the key is NUL-terminated and the record is only written by
+    // the call; a missing interface is a null handle, which is rejected
+    let result = unsafe {
+        let netif = sys::esp_netif_get_handle_from_ifkey(c"WIFI_STA_DEF".as_ptr());
+        if netif.is_null() {
+            return None;
+        }
+        sys::esp_netif_get_ip_info(netif, &mut info)
+    };
+    // The address is kept in network byte order
+    let ip = Ipv4Addr::from(u32::from_be(info.ip.addr));
+    (result == sys::ESP_OK && !ip.is_unspecified()).then_some(ip)
+}
+
+/// Signal strength of the current access point in dBm, if connected.
+pub fn rssi() -> Option<i8> {
+    let mut info: sys::wifi_ap_record_t = Default::default();
+    // SAFETY: the record is only written by the call
+    let result = unsafe { sys::esp_wifi_sta_get_ap_info(&mut info) };
+    (result == sys::ESP_OK).then_some(info.rssi)
+}
+
+/// Time since boot.
+pub fn uptime() -> Duration {
+    // SAFETY: reads the monotonic high resolution timer
+    let micros = unsafe { sys::esp_timer_get_time() };
+    Duration::from_micros(micros.max(0) as u64)
+}
+
+/// Restarts the chip after a moment, for the answer to reach the browser.
+/// The shutdown handlers save the state first.
+pub fn restart() -> ! {
+    std::thread::sleep(Duration::from_millis(500));
+    // SAFETY: does not return
+    unsafe { sys::esp_restart() }
+}
@@ -0,0 +1,84 @@
+//! LAN discovery over UDP.
+//!
+//! Every few seconds, and right after a status change, the device broadcasts
+//! a JSON beacon on UDP port 47474:
+//!
+//! `{"type":"beacon","name":"busier","ip":"192.168.1.50","port":80,"status":"dnd","version":"0.1.0"}`
+//!
+//! A datagram containing `{"type":"discover"}` sent to that port (broadcast
+//! or unicast) is answered with the same beacon, sent back to the sender.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::config;
+use crate::status;
+
+pub const DISCOVERY_PORT: u16 = 47474;
+const DISCOVERY_STACK_SIZE: usize = 4096;
+const BEACON_INTERVAL: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const HTTP_PORT: u16 = 80;
+
+#[derive(Deserialize)]
+struct Probe<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+}
+
+/// Spawns the beacon and probe responder thread.
+pub fn start(ip: Ipv4Addr) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", DISCOVERY_PORT))?;
+    socket.set_broadcast(true)?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    std::thread::Builder::new()
+        .name("discovery".into())
+        .stack_size(DISCOVERY_STACK_SIZE)
+        .spawn(move || {
+            let broadcast = SocketAddr::from((Ipv4Addr::BROADCAST, DISCOVERY_PORT));
+            let mut last_beacon: Option<Instant> = None;
+            let mut last_status = status::current();
+            let mut buf = [0; 128];
+
+            loop {
+                if let Ok((len, sender)) = socket.recv_from(&mut buf) {
+                    let is_probe = serde_json::from_slice::<Probe>(&buf[..len])
+                        .is_ok_and(|probe| probe.kind == "discover");
+                    if is_probe {
+                        if let Err(e) = socket.send_to(&beacon(ip), sender) {
+                            warn!("Discovery reply failed: {:?}", e);
+                        }
+                    }
+                }
+
+                let current_status = status::current();
+                let due = last_beacon.map_or(true, |at| at.elapsed() >= BEACON_INTERVAL);
+                if due || current_status != last_status {
+                    if let Err(e) = socket.send_to(&beacon(ip), broadcast) {
+                        warn!("Discovery beacon failed: {:?}", e);
+                    }
+                    last_beacon = Some(Instant::now());
+                    last_status = current_status;
+                }
+            }
+        })?;
+
+    info!("Discovery beacon on UDP port {}", DISCOVERY_PORT);
+    Ok(())
+}
+
+fn beacon(ip: Ipv4Addr) -> Vec<u8> {
+    serde_json::to_vec(&serde_json::json!({
+        "type": "beacon",
+        "name": config::get().device_name(),
+        "ip": ip.to_string(),
+        "port": HTTP_PORT,
+        "status": status::current(),
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+    .unwrap_or_default()
+}
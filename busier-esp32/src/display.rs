@@ -0,0 +1,458 @@
+//! Display manager.
+//!
+//! Renders the same frame to every attached panel, each with its own layout:
+//! the detail layout shows the network, status and timers for the person at
+//! the desk, the status layout shows only the status in large letters for
+//! people at the door, or one row per person on shared devices. Messages
+//! posted over the API cover the frame for a while.
+
+use std::fmt::Debug;
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::{BinaryColor, Rgb565},
+    prelude::*,
+    primitives::{Circle, Line, PrimitiveStyle, Rectangle, Triangle},
+    text::{Alignment, Text},
+};
+use log::warn;
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, Ssd1306};
+
+use crate::battery;
+use crate::config::{DisplayLayout, Icon};
+use crate::i18n::{self, FontSize};
+use crate::output;
+use crate::status::{Status, StatusLabel};
+
+static POSTED: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+
+/// Everything the panels show.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub ip: Ipv4Addr,
+    pub status: Status,
+    pub detail: String,
+    /// Label of the transition step in progress, shown instead of the status.
+    pub step: Option<String>,
+    pub battery: Option<battery::Level>,
+    /// Whether the heap or a task's stack went below its threshold.
+    pub low_memory: bool,
+    /// Chip temperature in degrees Celsius, where the chip has a sensor.
+    pub temperature: Option<f32>,
+    /// Per-person statuses on shared devices.
+    pub users: Vec<(String, Status)>,
+}
+
+/// A monochrome panel the manager can render to.
+pub trait Panel {
+    fn init_panel(&mut self) -> anyhow::Result<()>;
+    fn show(&mut self, layout: DisplayLayout, frame: &Frame) -> anyhow::Result<()>;
+    fn message(&mut self, text: &str) -> anyhow::Result<()>;
+
+    /// Shows a QR code with a few lines of text next to it. Panels that
+    /// cannot show one show the lines as a message.
+    fn qr_code(&mut self, payload: &str, lines: &[&str]) -> anyhow::Result<()> {
+        let _ = payload;
+        self.message(&lines.join(" "))
+    }
+}
+
+impl<DI, SIZE> Panel for Ssd1306<DI, SIZE, BufferedGraphicsMode<SIZE>>
+where
+    DI: WriteOnlyDataCommand,
+    SIZE: DisplaySize,
+{
+    fn init_panel(&mut self) -> anyhow::Result<()> {
+        self.init().map_err(|e| anyhow::anyhow!("{:?}", e))
+    }
+
+    fn show(&mut self, layout: DisplayLayout, frame: &Frame) -> anyhow::Result<()> {
+        self.clear(BinaryColor::Off).unwrap();
+        match layout {
+            DisplayLayout::Detail => draw_detail(self, frame)?,
+            DisplayLayout::Status if !frame.users.is_empty() => draw_users(self, &frame.users),
+            DisplayLayout::Status => draw_status(self, frame),
+        }
+        self.flush().map_err(|e| anyhow::anyhow!("{:?}", e))
+    }
+
+    fn message(&mut self, text: &str) -> anyhow::Result<()> {
+        self.clear(BinaryColor::Off).unwrap();
+        Text::new(text, Point::new(0, 10), small_text())
+            .draw(self)
+            .unwrap();
+        self.flush().map_err(|e| anyhow::anyhow!("{:?}", e))
+    }
+
+    fn qr_code(&mut self, payload: &str, lines: &[&str]) -> anyhow::Result<()> {
+        self.clear(BinaryColor::Off).unwrap();
+        draw_qr_code(self, payload, lines)?;
+        self.flush().map_err(|e| anyhow::anyhow!("{:?}", e))
+    }
+}
+
+/// The attached panels and their layouts.
+#[derive(Default)]
+pub struct Displays {
+    panels: Vec<(Box<dyn Panel>, DisplayLayout)>,
+}
+
+impl Displays {
+    /// Initializes a panel and adds it to the set.
+    pub fn add(
+        &mut self,
+        mut panel: impl Panel + 'static,
+        layout: DisplayLayout,
+    ) -> anyhow::Result<()> {
+        panel.init_panel()?;
+        self.panels.push((Box::new(panel), layout));
+        Ok(())
+    }
+
+    /// Shows a one-line message, such as during startup, on every panel.
+    pub fn message(&mut self, text: &str) {
+        for (panel, _) in &mut self.panels {
+            if let Err(e) = panel.message(text) {
+                warn!("Display update failed: {:?}", e);
+            }
+        }
+    }
+
+    /// Shows a QR code on every panel, with the lines as a message on
+    /// panels too small for it.
+    pub fn qr_code(&mut self, payload: &str, lines: &[&str]) {
+        for (panel, _) in &mut self.panels {
+            if let Err(e) = panel.qr_code(payload, lines) {
+                warn!("Failed to show the QR code: {:?}", e);
+                let _ = panel.message(&lines.join(" "));
+            }
+        }
+    }
+
+    /// Renders a frame to every panel in its layout.
+    pub fn show(&mut self, frame: &Frame) {
+        for (panel, layout) in &mut self.panels {
+            if let Err(e) = panel.show(*layout, frame) {
+                warn!("Display update failed: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Shows a message on every panel instead of the frame for `duration`.
+pub fn post_message(text: String, duration: Duration) {
+    *POSTED.lock().unwrap() = Some((text, Instant::now() + duration));
+}
+
+/// The posted message, until it expires.
+pub fn posted_message() -> Option<String> {
+    let mut posted = POSTED.lock().unwrap();
+    match posted.as_ref() {
+        Some((text, until)) if Instant::now() < *until => Some(text.clone()),
+        _ => {
+            *posted = None;
+            None
+        }
+    }
+}
+
+/// One "NAME WORD" line per person for the status layout.
+pub fn user_lines(users: &[(String, Status)]) -> Vec<String> {
+    users
+        .iter()
+        .map(|(name, status)| format!("{} {}", name, headline(*status)))
+        .collect()
+}
+
+/// The headline of a frame: the transition step, or else the status word.
+pub fn frame_headline(frame: &Frame) -> &str {
+    frame.step.as_deref().unwrap_or(headline(frame.status))
+}
+
+/// The headline color on colour panels: the status's color from `styles`,
+/// or yellow during a transition step such as "Wrapping up".
+pub fn headline_color(frame: &Frame) -> Rgb565 {
+    if frame.step.is_some() {
+        return Rgb565::YELLOW;
+    }
+    let [r, g, b] = output::color(&output::style(frame.status));
+    Rgb565::new(r >> 3, g >> 2, b >> 3)
+}
+
+/// Draws the status's icon from `styles`, `size` pixels wide, around
+/// `center`.
+pub fn draw_icon<D>(display: &mut D, status: Status, center: Point, size: u32, color: D::Color)
+where
+    D: DrawTarget,
+    D::Error: Debug,
+{
+    let half = size as i32 / 2;
+    let line = PrimitiveStyle::with_stroke(color, 2);
+    let at = |x: i32, y: i32| center + Point::new(x, y);
+    match output::style(status).icon {
+        Icon::None => {}
+        Icon::Check => {
+            Line::new(at(-half, 0), at(-half / 3, half * 2 / 3))
+                .into_styled(line)
+                .draw(display)
+                .unwrap();
+            Line::new(at(-half / 3, half * 2 / 3), at(half, -half * 2 / 3))
+                .into_styled(line)
+                .draw(display)
+                .unwrap();
+        }
+        Icon::Cross => {
+            Line::new(at(-half, -half), at(half, half))
+                .into_styled(line)
+                .draw(display)
+                .unwrap();
+            Line::new(at(-half, half), at(half, -half))
+                .into_styled(line)
+                .draw(display)
+                .unwrap();
+        }
+        Icon::Dot => {
+            Circle::with_center(center, size)
+                .into_styled(PrimitiveStyle::with_fill(color))
+                .draw(display)
+                .unwrap();
+        }
+        Icon::Clock => {
+            Circle::with_center(center, size)
+                .into_styled(PrimitiveStyle::with_stroke(color, 1))
+                .draw(display)
+                .unwrap();
+            Line::new(center, at(0, -half * 2 / 3))
+                .into_styled(line)
+                .draw(display)
+                .unwrap();
+            Line::new(center, at(half / 2, 0))
+                .into_styled(line)
+                .draw(display)
+                .unwrap();
+        }
+    }
+}
+
+/// The single word the status layout shows.
+pub fn headline(status: Status) -> &'static str {
+    match status {
+        Status::Free => i18n::text("headline.free"),
+        Status::Dnd => i18n::text("headline.dnd"),
+        Status::Away => i18n::text("headline.away"),
+    }
+}
+
+/// The start of `text` that fits in `width` pixels in `font`; the rest is cut.
+pub fn fit<'a>(text: &'a str, font: &MonoFont, width: u32) -> &'a str {
+    let advance = font.character_size.width + font.character_spacing;
+    let columns = (width / advance.max(1)) as usize;
+    match text.char_indices().nth(columns) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+fn small_text() -> MonoTextStyle<'static, BinaryColor> {
+    MonoTextStyle::new(i18n::font(FontSize::Small), BinaryColor::On)
+}
+
+fn draw_detail<D>(display: &mut D, frame: &Frame) -> anyhow::Result<()>
+where
+    D: DrawTarget<Color = BinaryColor>,
+    D::Error: Debug,
+{
+    #[cfg(feature = "homekit")]
+    if let Some(setup) = crate::homekit::pending_setup() {
+        return draw_homekit_setup(display, &setup);
+    }
+
+    let text_style = small_text();
+
+    Text::new(
+        i18n::text("display.wifi_connected"),
+        Point::new(0, 10),
+        text_style,
+    )
+    .draw(display)
+    .unwrap();
+
+    // Battery gauge in the top right corner
+    if let Some(level) = frame.battery {
+        let charging = if level.charging == Some(true) {
+            "+"
+        } else {
+            ""
+        };
+        Text::new(
+            &format!("{}%{}", level.percent, charging),
+            Point::new(84, 10),
+            text_style,
+        )
+        .draw(display)
+        .unwrap();
+        Rectangle::new(Point::new(114, 2), Size::new(12, 7))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(display)
+            .unwrap();
+        Rectangle::new(Point::new(126, 4), Size::new(2, 3))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(display)
+            .unwrap();
+        Rectangle::new(
+            Point::new(115, 3),
+            Size::new(10 * u32::from(level.percent) / 100, 5),
+        )
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)
+        .unwrap();
+    }
+
+    Text::new(&format!("IP: {}", frame.ip), Point::new(0, 25), text_style)
+        .draw(display)
+        .unwrap();
+
+    // Translations and timers can run past the edge of a narrow panel
+    let width = display.bounding_box().size.width;
+    let status = i18n::format("display.status", &[&frame.status.label()]);
+    Text::new(
+        fit(&status, text_style.font, width),
+        Point::new(0, 40),
+        text_style,
+    )
+    .draw(display)
+    .unwrap();
+
+    Text::new(
+        fit(&frame.detail, text_style.font, width),
+        Point::new(0, 55),
+        text_style,
+    )
+    .draw(display)
+    .unwrap();
+
+    if frame.low_memory {
+        draw_warning(display, Point::new(118, 31), BinaryColor::On);
+    }
+
+    Ok(())
+}
+
+// One large word, inverted while busy so it stands out from across the room
+fn draw_status<D>(display: &mut D, frame: &Frame)
+where
+    D: DrawTarget<Color = BinaryColor>,
+    D::Error: Debug,
+{
+    let word = frame_headline(frame);
+
+    let (background, foreground) = if frame.status == Status::Dnd {
+        (BinaryColor::On, BinaryColor::Off)
+    } else {
+        (BinaryColor::Off, BinaryColor::On)
+    };
+    display.clear(background).unwrap();
+
+    let center = display.bounding_box().center();
+    Text::with_alignment(
+        word,
+        Point::new(center.x, center.y + 7),
+        MonoTextStyle::new(i18n::font(FontSize::Large), foreground),
+        Alignment::Center,
+    )
+    .draw(display)
+    .unwrap();
+    if frame.step.is_none() {
+        let icon_center = Point::new(10, center.y);
+        draw_icon(display, frame.status, icon_center, 14, foreground);
+    }
+    if frame.low_memory {
+        let corner = display.bounding_box().bottom_right().unwrap_or_default();
+        draw_warning(display, corner - Point::new(9, 9), foreground);
+    }
+}
+
+// A 10 by 10 pixel warning sign with its top left at `corner`
+fn draw_warning<D>(display: &mut D, corner: Point, color: D::Color)
+where
+    D: DrawTarget,
+    D::Error: Debug,
+{
+    let at = |x: i32, y: i32| corner + Point::new(x, y);
+    Triangle::new(at(5, 0), at(0, 9), at(9, 9))
+        .into_styled(PrimitiveStyle::with_stroke(color, 1))
+        .draw(display)
+        .unwrap();
+    Line::new(at(5, 3), at(5, 6))
+        .into_styled(PrimitiveStyle::with_stroke(color, 1))
+        .draw(display)
+        .unwrap();
+}
+
+// One row per person, as many as fit
+fn draw_users<D>(display: &mut D, users: &[(String, Status)])
+where
+    D: DrawTarget<Color = BinaryColor>,
+    D::Error: Debug,
+{
+    let rows = display.bounding_box().size.height as i32 / 10;
+    for (row, line) in user_lines(users).iter().take(rows as usize).enumerate() {
+        Text::new(line, Point::new(0, 8 + 10 * row as i32), small_text())
+            .draw(display)
+            .unwrap();
+    }
+}
+
+// Shows the HomeKit setup QR code next to the setup code
+#[cfg(feature = "homekit")]
+fn draw_homekit_setup<D>(display: &mut D, setup: &crate::homekit::Setup) -> anyhow::Result<()>
+where
+    D: DrawTarget<Color = BinaryColor>,
+    D::Error: Debug,
+{
+    draw_qr_code(display, &setup.payload, &["HomeKit", &setup.code])
+}
+
+// Draws a QR code in the top left corner and the lines to the right of it;
+// fails if the panel is not tall enough for the code. The blank margin
+// around it is two modules, or one where that is all that fits.
+fn draw_qr_code<D>(display: &mut D, payload: &str, lines: &[&str]) -> anyhow::Result<()>
+where
+    D: DrawTarget<Color = BinaryColor>,
+    D::Error: Debug,
+{
+    use qrcode::{Color, EcLevel, QrCode};
+
+    let code = QrCode::with_error_correction_level(payload, EcLevel::L)
+        .map_err(|e| anyhow::anyhow!("QR code: {:?}", e))?;
+    let width = code.width();
+    let height = display.bounding_box().size.height as usize;
+    let Some(margin) = [2, 1]
+        .into_iter()
+        .find(|margin| width + 2 * margin <= height)
+    else {
+        anyhow::bail!("QR code of {} modules does not fit", width);
+    };
+    let margin = margin as i32;
+    let pixels = code
+        .to_colors()
+        .into_iter()
+        .enumerate()
+        .filter(|(_, color)| *color == Color::Dark)
+        .map(|(i, _)| {
+            let (x, y) = ((i % width) as i32, (i / width) as i32);
+            Pixel(Point::new(margin + x, margin + y), BinaryColor::On)
+        });
+    display.draw_iter(pixels).unwrap();
+
+    let text_style = small_text();
+    for (row, line) in lines.iter().enumerate() {
+        let position = Point::new(width as i32 + 2 * margin + 3, 12 + 14 * row as i32);
+        Text::new(line, position, text_style).draw(display).unwrap();
+    }
+
+    Ok(())
+}
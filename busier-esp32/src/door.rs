@@ -0,0 +1,78 @@
+//! Magnetic door sensor.
+//!
+//! A reed switch between the pin and ground closes while the magnet on the
+//! door is near, so a low pin means the door is closed. Changes are
+//! debounced and handed to [`status::door_changed`], which may select Do Not
+//! Disturb while the door is closed.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+use esp_idf_svc::hal::gpio::{AnyIOPin, Input, PinDriver, Pull};
+use log::info;
+
+use crate::config;
+use crate::status;
+
+const DOOR_STACK_SIZE: usize = 4096;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// Consecutive samples the new state must be seen for
+const DEBOUNCE_SAMPLES: u32 = 4;
+
+const UNKNOWN: u8 = 0;
+const OPEN: u8 = 1;
+const CLOSED: u8 = 2;
+
+static STATE: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+/// Whether the door is open; None without an enabled sensor.
+pub fn is_open() -> Option<bool> {
+    if !config::get().door.enabled {
+        return None;
+    }
+
+    match STATE.load(Ordering::SeqCst) {
+        OPEN => Some(true),
+        CLOSED => Some(false),
+        _ => None,
+    }
+}
+
+/// Spawns a thread that watches the reed switch.
+pub fn start(pin: AnyIOPin) -> anyhow::Result<()> {
+    let mut switch: PinDriver<'static, AnyIOPin, Input> = PinDriver::input(pin)?;
+    switch.set_pull(Pull::Up)?;
+
+    std::thread::Builder::new()
+        .name("door".into())
+        .stack_size(DOOR_STACK_SIZE)
+        .spawn(move || {
+            let mut held = 0;
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let sample = if switch.is_low() { CLOSED } else { OPEN };
+                let state = STATE.load(Ordering::SeqCst);
+                if sample == state {
+                    held = 0;
+                    continue;
+                }
+                held += 1;
+                if held < DEBOUNCE_SAMPLES {
+                    continue;
+                }
+                held = 0;
+                STATE.store(sample, Ordering::SeqCst);
+
+                // The state found at startup is not a change
+                if state == UNKNOWN || !config::get().door.enabled {
+                    continue;
+                }
+                let open = sample == OPEN;
+                info!("Door {}", if open { "opened" } else { "closed" });
+                status::door_changed(open);
+            }
+        })?;
+
+    Ok(())
+}
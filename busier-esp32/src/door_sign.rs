@@ -0,0 +1,61 @@
+//! Battery door sign.
+//!
+//! With `door_sign.enabled` the device mirrors the status of another device,
+//! typically a busier on the desk, and spends nearly all its time in deep
+//! sleep. On every wake-up it fetches `door_sign.source` once, shows the
+//! status and, if the timer woke it, goes straight back to sleep for
+//! `sleep.interval_secs`. Waking it with the BOOT button keeps it awake for
+//! `sleep.awake_secs` as usual, to reach the web interface.
+
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::config;
+use crate::http_client;
+use crate::sleep;
+use crate::status::{self, BackAt, Status};
+
+// Enough for the JSON of `/api/status`
+const MAX_LEN: usize = 1024;
+
+/// Whether the door sign is enabled and the timer woke the device, so it
+/// sleeps again once the fetched status is shown.
+pub fn sleeps_after_sync() -> bool {
+    config::get().door_sign.enabled && sleep::woke_by_timer()
+}
+
+/// Fetches the status from the source and shows it. On failure the status
+/// from before deep sleep stays, to be fetched again on the next wake-up.
+pub fn sync() {
+    let config = config::get().door_sign;
+    if !config.enabled || config.source.is_empty() {
+        return;
+    }
+
+    let fetched = http_client::get(&config.source, MAX_LEN).and_then(|body| {
+        parse(&body).ok_or_else(|| anyhow::anyhow!("{} returned no status", config.source))
+    });
+    match fetched {
+        Ok((status, back_at)) => {
+            info!("Door sign status: {}", status.as_str());
+            status::restore(status, back_at);
+        }
+        Err(e) => warn!("Door sign update failed: {:?}", e),
+    }
+}
+
+// A JSON object as from `/api/status`, or a bare status name
+fn parse(body: &[u8]) -> Option<(Status, Option<BackAt>)> {
+    #[derive(Deserialize)]
+    struct Source<'a> {
+        status: &'a str,
+        back_at: Option<&'a str>,
+    }
+
+    if let Ok(source) = serde_json::from_slice::<Source>(body) {
+        let back_at = source.back_at.and_then(BackAt::parse);
+        return Some((Status::parse(source.status)?, back_at));
+    }
+    let name = std::str::from_utf8(body).ok()?.trim();
+    Some((Status::parse(name)?, None))
+}
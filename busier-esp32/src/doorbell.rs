@@ -0,0 +1,98 @@
+//! Doorbell button.
+//!
+//! A button between the pin and ground. While the status is not Do Not
+//! Disturb, a press rings at once: the buzzer and LED signal a knock and a
+//! notification goes out. During Do Not Disturb a press only shows that a
+//! visitor is waiting, and the ring follows as soon as the status changes.
+//! Pressing `override_presses` times in a row rings anyway, for emergencies.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::hal::gpio::{AnyIOPin, Input, PinDriver, Pull};
+use log::info;
+
+use crate::config;
+use crate::notify::{self, Event};
+use crate::status::{self, Status};
+
+const DOORBELL_STACK_SIZE: usize = 4096;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+// Consecutive samples a press or release must be seen for
+const DEBOUNCE_SAMPLES: u32 = 3;
+// Longest gap between the presses that override Do Not Disturb
+const OVERRIDE_GAP: Duration = Duration::from_millis(1500);
+
+static WAITING: AtomicBool = AtomicBool::new(false);
+
+/// Whether a visitor rang during Do Not Disturb and has not been announced.
+pub fn visitor_waiting() -> bool {
+    WAITING.load(Ordering::SeqCst)
+}
+
+/// Spawns a thread that watches the button.
+pub fn start(pin: AnyIOPin) -> anyhow::Result<()> {
+    let mut button: PinDriver<'static, AnyIOPin, Input> = PinDriver::input(pin)?;
+    button.set_pull(Pull::Up)?;
+
+    std::thread::Builder::new()
+        .name("doorbell".into())
+        .stack_size(DOORBELL_STACK_SIZE)
+        .spawn(move || {
+            let mut pressed = false;
+            let mut held = 0;
+            let mut presses = 0;
+            let mut last_press = Instant::now();
+
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+
+                // Announce a waiting visitor once Do Not Disturb ends
+                let dnd = status::current() == Status::Dnd;
+                if !dnd && WAITING.swap(false, Ordering::SeqCst) {
+                    info!("Announcing the waiting visitor");
+                    notify::send(Event::Doorbell { urgent: false });
+                }
+
+                // The button pulls the pin low when pressed
+                if button.is_low() == pressed {
+                    held = 0;
+                    continue;
+                }
+                held += 1;
+                if held < DEBOUNCE_SAMPLES {
+                    continue;
+                }
+                held = 0;
+                pressed = !pressed;
+                if !pressed {
+                    continue;
+                }
+
+                presses = if last_press.elapsed() < OVERRIDE_GAP {
+                    presses + 1
+                } else {
+                    1
+                };
+                last_press = Instant::now();
+
+                if !dnd {
+                    info!("Doorbell rang");
+                    notify::send(Event::Doorbell { urgent: false });
+                    continue;
+                }
+
+                let override_presses = config::get().doorbell.override_presses;
+                if override_presses > 0 && presses >= override_presses {
+                    info!("Doorbell rang {} times, overriding Do Not Disturb", presses);
+                    WAITING.store(false, Ordering::SeqCst);
+                    presses = 0;
+                    notify::send(Event::Doorbell { urgent: true });
+                } else if !WAITING.swap(true, Ordering::SeqCst) {
+                    info!("Doorbell rang during Do Not Disturb, visitor waiting");
+                }
+            }
+        })?;
+
+    Ok(())
+}
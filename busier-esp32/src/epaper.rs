@@ -0,0 +1,253 @@
+//! SSD1680 e-paper panel, as on 2.13" 250x122 modules.
+//!
+//! Drives the panel in landscape as another display panel. E-paper keeps its
+//! image without power, so each refresh ends with the controller in deep
+//! sleep, and a checksum of the last image is kept in RTC memory so waking
+//! up from the chip's deep sleep with an unchanged status does not refresh
+//! (and flash) the panel again.
+
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use embedded_graphics::{
+    mono_font::MonoTextStyle,
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Alignment, Text},
+};
+use esp_idf_svc::hal::gpio::{AnyInputPin, AnyOutputPin, Input, Output, PinDriver};
+use esp_idf_svc::hal::spi::{SpiDeviceDriver, SpiDriver};
+
+use crate::config::DisplayLayout;
+use crate::display::{self, Frame, Panel};
+use crate::i18n::{self, FontSize};
+use crate::status;
+
+const WIDTH: usize = 250;
+const HEIGHT: usize = 122;
+// The controller's rows run along the long side, 8 pixels per byte
+const ROW_BYTES: usize = HEIGHT.div_ceil(8);
+
+// A full refresh takes about 2 s, a reset a few ms
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// SSD1680 commands
+const DRIVER_OUTPUT: u8 = 0x01;
+const DEEP_SLEEP: u8 = 0x10;
+const DATA_ENTRY: u8 = 0x11;
+const SWRESET: u8 = 0x12;
+const TEMPERATURE_SENSOR: u8 = 0x18;
+const MASTER_ACTIVATION: u8 = 0x20;
+const UPDATE_CONTROL: u8 = 0x22;
+const WRITE_RAM: u8 = 0x24;
+const BORDER: u8 = 0x3C;
+const RAM_X_RANGE: u8 = 0x44;
+const RAM_Y_RANGE: u8 = 0x45;
+const RAM_X: u8 = 0x4E;
+const RAM_Y: u8 = 0x4F;
+
+// X and Y increment, X first
+const DATA_ENTRY_XY: u8 = 0x03;
+// Clock and analog on, load the waveform for the temperature, full refresh
+const UPDATE_FULL: u8 = 0xF7;
+const BORDER_WHITE: u8 = 0x05;
+const INTERNAL_SENSOR: u8 = 0x80;
+
+// Checksum of the image on the panel, 0 when unknown as on a cold boot
+#[link_section = ".rtc.data"]
+static SHOWN: AtomicU32 = AtomicU32::new(0);
+
+type Spi = SpiDeviceDriver<'static, SpiDriver<'static>>;
+
+/// The panel as a display panel.
+pub struct Epaper {
+    spi: Spi,
+    dc: PinDriver<'static, AnyOutputPin, Output>,
+    reset: PinDriver<'static, AnyOutputPin, Output>,
+    busy: PinDriver<'static, AnyInputPin, Input>,
+    pixels: Box<[u8]>,
+}
+
+impl Epaper {
+    pub fn new(
+        spi: Spi,
+        dc: AnyOutputPin,
+        reset: AnyOutputPin,
+        busy: AnyInputPin,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            spi,
+            dc: PinDriver::output(dc)?,
+            reset: PinDriver::output(reset)?,
+            busy: PinDriver::input(busy)?,
+            pixels: vec![0xFF; ROW_BYTES * WIDTH].into_boxed_slice(),
+        })
+    }
+
+    fn command(&mut self, command: u8, data: &[u8]) -> anyhow::Result<()> {
+        self.dc.set_low()?;
+        self.spi.write(&[command])?;
+        if !data.is_empty() {
+            self.dc.set_high()?;
+            self.spi.write(data)?;
+        }
+        Ok(())
+    }
+
+    fn wait_until_idle(&self) -> anyhow::Result<()> {
+        let start = Instant::now();
+        while self.busy.is_high() {
+            if start.elapsed() > BUSY_TIMEOUT {
+                anyhow::bail!("e-paper panel stays busy");
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    // Only a hardware reset wakes the controller from deep sleep
+    fn wake(&mut self) -> anyhow::Result<()> {
+        self.reset.set_low()?;
+        std::thread::sleep(Duration::from_millis(10));
+        self.reset.set_high()?;
+        std::thread::sleep(Duration::from_millis(10));
+        self.command(SWRESET, &[])?;
+        self.wait_until_idle()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        let checksum = fnv1a(&self.pixels);
+        if SHOWN.load(Ordering::SeqCst) == checksum {
+            return Ok(());
+        }
+
+        self.wake()?;
+        let last_row = (WIDTH as u16 - 1).to_le_bytes();
+        self.command(DRIVER_OUTPUT, &[last_row[0], last_row[1], 0x00])?;
+        self.command(DATA_ENTRY, &[DATA_ENTRY_XY])?;
+        self.command(RAM_X_RANGE, &[0, ROW_BYTES as u8 - 1])?;
+        self.command(RAM_Y_RANGE, &[0, 0, last_row[0], last_row[1]])?;
+        self.command(BORDER, &[BORDER_WHITE])?;
+        self.command(TEMPERATURE_SENSOR, &[INTERNAL_SENSOR])?;
+        self.command(RAM_X, &[0])?;
+        self.command(RAM_Y, &[0, 0])?;
+
+        self.dc.set_low()?;
+        self.spi.write(&[WRITE_RAM])?;
+        self.dc.set_high()?;
+        for row in self.pixels.chunks(ROW_BYTES) {
+            self.spi.write(row)?;
+        }
+
+        self.command(UPDATE_CONTROL, &[UPDATE_FULL])?;
+        self.command(MASTER_ACTIVATION, &[])?;
+        self.wait_until_idle()?;
+        self.command(DEEP_SLEEP, &[0x01])?;
+
+        SHOWN.store(checksum, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn clear_white(&mut self) {
+        self.pixels.fill(0xFF);
+    }
+}
+
+// Good enough to tell two images apart
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811C_9DC5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+impl Panel for Epaper {
+    fn init_panel(&mut self) -> anyhow::Result<()> {
+        // Checks that a panel answers, leaving the image alone
+        self.wake()?;
+        self.command(DEEP_SLEEP, &[0x01])
+    }
+
+    fn show(&mut self, layout: DisplayLayout, frame: &Frame) -> anyhow::Result<()> {
+        self.clear_white();
+
+        if layout == DisplayLayout::Status && !frame.users.is_empty() {
+            let style = MonoTextStyle::new(i18n::font(FontSize::Small), BinaryColor::On);
+            for (line, y) in display::user_lines(&frame.users).iter().zip((14..).step_by(12)) {
+                Text::new(line, Point::new(4, y), style).draw(self).unwrap();
+            }
+            return self.flush();
+        }
+
+        Text::with_alignment(
+            display::frame_headline(frame),
+            Point::new(WIDTH as i32 / 2, 64),
+            MonoTextStyle::new(i18n::font(FontSize::Large), BinaryColor::On),
+            Alignment::Center,
+        )
+        .draw(self)
+        .unwrap();
+
+        // The detail line changes too often for a panel that flashes on each
+        // refresh, so only the "back at" time is shown below the status
+        if let Some(back_at) = status::back_at() {
+            Text::with_alignment(
+                &back_at.display_text(),
+                Point::new(WIDTH as i32 / 2, 100),
+                MonoTextStyle::new(i18n::font(FontSize::Small), BinaryColor::On),
+                Alignment::Center,
+            )
+            .draw(self)
+            .unwrap();
+        }
+
+        self.flush()
+    }
+
+    fn message(&mut self, text: &str) -> anyhow::Result<()> {
+        self.clear_white();
+        Text::new(
+            text,
+            Point::new(4, 14),
+            MonoTextStyle::new(i18n::font(FontSize::Small), BinaryColor::On),
+        )
+        .draw(self)
+        .unwrap();
+        self.flush()
+    }
+}
+
+impl OriginDimensions for Epaper {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Epaper {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if x >= WIDTH || y >= HEIGHT {
+                continue;
+            }
+            // Landscape: a column of the image is a controller row, and a
+            // cleared bit is black
+            let column = HEIGHT - 1 - y;
+            let i = x * ROW_BYTES + column / 8;
+            let bit = 0x80 >> (column % 8);
+            match color {
+                BinaryColor::On => self.pixels[i] &= !bit,
+                BinaryColor::Off => self.pixels[i] |= bit,
+            }
+        }
+        Ok(())
+    }
+}
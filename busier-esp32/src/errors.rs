@@ -0,0 +1,148 @@
+//! Error responses of the web server.
+//!
+//! A path without a route gets a small 404 page in the device's style, or a
+//! JSON error for clients that do not ask for HTML. A handler that fails
+//! answers 500 the same way instead of with the server's bare message, and
+//! the response carries a request ID that is also in the log line of the
+//! failure, so a report from a user can be matched with the device's log.
+//!
+//! A path with routes, just not for the request's method, answers 405 with
+//! its methods in `Allow`, and lists them to OPTIONS for CORS preflights.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use embedded_svc::http::server::{Connection, Request};
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::{EspHttpConnection, EspHttpServer};
+use log::error;
+
+use crate::http_util;
+use crate::i18n;
+use crate::web;
+
+const JSON: (&str, &str) = ("Content-Type", "application/json");
+// The request headers the API reads, for CORS preflights
+const ALLOWED_HEADERS: &str = "Authorization, Content-Type, If-Match";
+
+static NEXT_REQUEST_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Registers the catch-all routes. A path with routes for other methods
+/// answers 405, or the list of its methods to OPTIONS, as in a CORS
+/// preflight; any other path answers 404. Must come after every other
+/// route, as the first match wins.
+pub fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    let key = http_util::server_key(server);
+    for method in [
+        Method::Get,
+        Method::Head,
+        Method::Post,
+        Method::Put,
+        Method::Delete,
+        Method::Patch,
+        Method::Options,
+    ] {
+        server.fn_handler::<anyhow::Error, _>("/*", method, move |req| {
+            let path = req.uri().split('?').next().unwrap_or_default();
+            let methods = http_util::methods(key, path);
+            if methods.is_empty() || methods.contains(&method) {
+                return not_found(req);
+            }
+
+            let mut allowed: Vec<_> = methods.into_iter().map(http_util::method_name).collect();
+            allowed.push("OPTIONS");
+            let allow = allowed.join(", ");
+            if method == Method::Options {
+                req.into_response(
+                    204,
+                    None,
+                    &[
+                        ("Allow", allow.as_str()),
+                        ("Access-Control-Allow-Methods", allow.as_str()),
+                        ("Access-Control-Allow-Headers", ALLOWED_HEADERS),
+                    ],
+                )?;
+                return Ok(());
+            }
+
+            let body = serde_json::json!({ "error": "Method not allowed" });
+            req.into_response(405, None, &[("Allow", allow.as_str()), JSON])?
+                .write_all(body.to_string().as_bytes())?;
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+/// Wraps a handler so that its failure is logged and answered with a 500
+/// carrying a request ID.
+pub fn catch<F>(
+    handler: F,
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static
+where
+    F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static,
+{
+    move |req| {
+        // Without the query, which may hold a token
+        let path = req.uri().split('?').next().unwrap_or_default().to_string();
+        let connection = req.release();
+        let Err(e) = handler(Request::wrap(&mut *connection)) else {
+            return Ok(());
+        };
+
+        let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+        error!("Request {} for {} failed: {:?}", id, path, e);
+        if connection.is_response_initiated() {
+            // Too late for an error page; the response is cut short
+            return Ok(());
+        }
+        respond(Request::wrap(connection), 500, Some(id))
+    }
+}
+
+/// Answers 404.
+pub fn not_found(req: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    respond(req, 404, None)
+}
+
+fn respond(
+    req: Request<&mut EspHttpConnection>,
+    code: u16,
+    request_id: Option<u32>,
+) -> anyhow::Result<()> {
+    let (key, message) = match code {
+        404 => ("web.not_found", "Not found"),
+        _ => ("web.server_error", "Internal error"),
+    };
+
+    if !wants_html(req.header("Accept")) {
+        let mut body = serde_json::json!({ "error": message });
+        if let Some(id) = request_id {
+            body["request_id"] = id.into();
+        }
+        req.into_response(code, None, &[JSON])?
+            .write_all(body.to_string().as_bytes())?;
+        return Ok(());
+    }
+
+    let request_id_text = request_id
+        .map(|id| format!("{}: {}", i18n::text("web.request_id"), id))
+        .unwrap_or_default();
+    let page = web::render_with(
+        web::ERROR_HTML,
+        vec![
+            ("code", code.to_string()),
+            ("message", i18n::text(key).to_string()),
+            ("request_id_text", request_id_text),
+        ],
+    );
+    req.into_response(code, None, &[web::HTML])?
+        .write_all(page.as_bytes())?;
+    Ok(())
+}
+
+// Browsers ask for HTML when they load a page; scripts and API clients
+// usually send `*/*` or nothing and get JSON
+fn wants_html(accept: Option<&str>) -> bool {
+    accept.is_some_and(|accept| accept.contains("text/html"))
+}
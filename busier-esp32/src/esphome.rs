@@ -0,0 +1,522 @@
+//! ESPHome native API so Home Assistant can adopt the device.
+//!
+//! Serves one client at a time on TCP port 6053 and exposes two entities: a
+//! "Do Not Disturb" switch and a "Status" text sensor. With an encryption key
+//! configured, frames are encrypted with the Noise handshake of
+//! [`crate::noise`] and plaintext clients are turned away; without one the
+//! plaintext transport is used, optionally protected by the API password.
+//!
+//! Plaintext frames are `0x00`, varint payload length, varint message type,
+//! then the protobuf-encoded message. Encrypted frames are `0x01` and a
+//! big-endian 16-bit length; the client's hello, the handshake, then
+//! messages sealed with a big-endian 16-bit type and length in front.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::board;
+use crate::cert;
+use crate::config;
+use crate::device;
+use crate::http_util;
+use crate::noise::{self, Session};
+use crate::peer_sync;
+use crate::status::{self, Source, Status};
+
+const API_PORT: u16 = 6053;
+const ESPHOME_STACK_SIZE: usize = 8192;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// Home Assistant pings every minute or so
+const IDLE_TIMEOUT: Duration = Duration::from_secs(180);
+const MAX_FRAME_LEN: usize = 512;
+const API_VERSION_MAJOR: u64 = 1;
+const API_VERSION_MINOR: u64 = 9;
+const HTTP_PORT: u64 = 80;
+const NOISE_PROLOGUE: &[u8] = b"NoiseAPIInit\x00\x00";
+const KEY_LEN: usize = 32;
+
+// Message types
+const HELLO_REQUEST: u64 = 1;
+const HELLO_RESPONSE: u64 = 2;
+const CONNECT_REQUEST: u64 = 3;
+const CONNECT_RESPONSE: u64 = 4;
+const DISCONNECT_REQUEST: u64 = 5;
+const DISCONNECT_RESPONSE: u64 = 6;
+const PING_REQUEST: u64 = 7;
+const PING_RESPONSE: u64 = 8;
+const DEVICE_INFO_REQUEST: u64 = 9;
+const DEVICE_INFO_RESPONSE: u64 = 10;
+const LIST_ENTITIES_REQUEST: u64 = 11;
+const LIST_ENTITIES_SWITCH_RESPONSE: u64 = 17;
+const LIST_ENTITIES_TEXT_SENSOR_RESPONSE: u64 = 18;
+const LIST_ENTITIES_DONE_RESPONSE: u64 = 19;
+const SUBSCRIBE_STATES_REQUEST: u64 = 20;
+const SWITCH_STATE_RESPONSE: u64 = 26;
+const TEXT_SENSOR_STATE_RESPONSE: u64 = 27;
+const SWITCH_COMMAND_REQUEST: u64 = 33;
+
+// Entity keys
+const DND_SWITCH_KEY: u32 = 1;
+const STATUS_SENSOR_KEY: u32 = 2;
+
+/// Spawns the API server thread.
+pub fn start() -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", API_PORT))?;
+
+    std::thread::Builder::new()
+        .name("esphome".into())
+        .stack_size(ESPHOME_STACK_SIZE)
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let esphome = config::get().esphome;
+                if !esphome.enabled {
+                    continue;
+                }
+                let transport = match decode_key(&esphome.encryption_key) {
+                    Some(Some(psk)) => Transport::Hello(psk),
+                    Some(None) => Transport::Plaintext,
+                    None => {
+                        warn!("esphome.encryption_key is not a base64 32-byte key");
+                        continue;
+                    }
+                };
+                if let Err(e) = Connection::new(stream, transport).and_then(|mut c| c.serve()) {
+                    warn!("ESPHome API connection closed: {:?}", e);
+                }
+            }
+        })?;
+
+    info!("ESPHome API listening on port {}", API_PORT);
+    Ok(())
+}
+
+/// The encryption key of `esphome.encryption_key`: None if it is not base64
+/// of 32 bytes, Some(None) for an empty one.
+pub fn decode_key(encoded: &str) -> Option<Option<[u8; KEY_LEN]>> {
+    if encoded.is_empty() {
+        return Some(None);
+    }
+    let key = http_util::decode_base64(encoded)?;
+    Some(Some(key.try_into().ok()?))
+}
+
+// How frames are wrapped, and how far the Noise handshake got
+enum Transport {
+    Plaintext,
+    /// Waiting for the client's hello, with the pre-shared key.
+    Hello([u8; KEY_LEN]),
+    /// Waiting for the client's handshake message.
+    Handshake([u8; KEY_LEN]),
+    Encrypted(Session),
+}
+
+struct Connection {
+    stream: TcpStream,
+    transport: Transport,
+    buffer: Vec<u8>,
+    authenticated: bool,
+    subscribed: bool,
+    last_status: Option<Status>,
+    last_activity: Instant,
+}
+
+impl Connection {
+    fn new(stream: TcpStream, transport: Transport) -> anyhow::Result<Self> {
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        Ok(Self {
+            stream,
+            transport,
+            buffer: Vec::new(),
+            authenticated: false,
+            subscribed: false,
+            last_status: None,
+            last_activity: Instant::now(),
+        })
+    }
+
+    fn serve(&mut self) -> anyhow::Result<()> {
+        let mut chunk = [0; 128];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(len) => {
+                    self.buffer.extend_from_slice(&chunk[..len]);
+                    self.last_activity = Instant::now();
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            while let Some((kind, message)) = self.next_message()? {
+                if !self.handle(kind, &message)? {
+                    return Ok(());
+                }
+            }
+
+            if self.subscribed {
+                let current_status = status::current();
+                if self.last_status != Some(current_status) {
+                    self.send_states(current_status)?;
+                }
+            }
+
+            if self.last_activity.elapsed() > IDLE_TIMEOUT {
+                anyhow::bail!("Client went quiet");
+            }
+        }
+    }
+
+    // Takes the next complete message off the buffer, going through the
+    // Noise handshake first on an encrypted connection
+    fn next_message(&mut self) -> anyhow::Result<Option<(u64, Vec<u8>)>> {
+        if matches!(self.transport, Transport::Plaintext) {
+            return self.next_plaintext_frame();
+        }
+
+        while let Some(payload) = self.next_noise_frame()? {
+            match &mut self.transport {
+                Transport::Hello(psk) => {
+                    // The client's hello has no content yet
+                    self.transport = Transport::Handshake(*psk);
+                    self.send_server_hello()?;
+                }
+                Transport::Handshake(psk) => {
+                    let psk = *psk;
+                    match payload.split_first() {
+                        Some((0x00, message)) => {
+                            let Some((answer, session)) =
+                                noise::respond(&psk, NOISE_PROLOGUE, message, cert::random())
+                            else {
+                                return self.reject("Handshake MAC failure");
+                            };
+                            let mut frame = vec![0x00];
+                            frame.extend(answer);
+                            self.write_noise_frame(&frame)?;
+                            self.transport = Transport::Encrypted(session);
+                        }
+                        Some(_) => return self.reject("Bad handshake error byte"),
+                        None => return self.reject("Empty handshake message"),
+                    }
+                }
+                Transport::Encrypted(session) => {
+                    let Some(plaintext) = session.receive.open(&[], &payload) else {
+                        anyhow::bail!("Frame failed to decrypt");
+                    };
+                    let Some(header) = plaintext.get(..4) else {
+                        anyhow::bail!("Frame too short");
+                    };
+                    let kind = u16::from_be_bytes([header[0], header[1]]);
+                    let len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+                    let Some(message) = plaintext.get(4..4 + len) else {
+                        anyhow::bail!("Frame shorter than its message");
+                    };
+                    return Ok(Some((u64::from(kind), message.to_vec())));
+                }
+                Transport::Plaintext => unreachable!(),
+            }
+        }
+        Ok(None)
+    }
+
+    // Takes the next complete encrypted frame's payload off the buffer
+    fn next_noise_frame(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(&preamble) = self.buffer.first() else {
+            return Ok(None);
+        };
+        if preamble != 0x01 {
+            // Plaintext clients take this as "encryption required"
+            return self.reject("Bad indicator byte");
+        }
+        let Some(header) = self.buffer.get(..3) else {
+            return Ok(None);
+        };
+        let len = usize::from(u16::from_be_bytes([header[1], header[2]]));
+        if len > MAX_FRAME_LEN {
+            anyhow::bail!("Frame too large");
+        }
+        if self.buffer.len() < 3 + len {
+            return Ok(None);
+        }
+
+        let payload = self.buffer[3..3 + len].to_vec();
+        self.buffer.drain(..3 + len);
+        Ok(Some(payload))
+    }
+
+    // Chosen protocol, then the node name and the MAC address, each ended
+    // by a NUL
+    fn send_server_hello(&mut self) -> anyhow::Result<()> {
+        let mut hello = vec![0x01];
+        hello.extend_from_slice(config::get().device_name().as_bytes());
+        hello.push(0x00);
+        let mac: String = device::mac()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        hello.extend_from_slice(mac.as_bytes());
+        hello.push(0x00);
+        self.write_noise_frame(&hello)
+    }
+
+    // Tells the client why the handshake failed and ends the connection
+    fn reject<T>(&mut self, reason: &str) -> anyhow::Result<T> {
+        let mut frame = vec![0x01];
+        frame.extend_from_slice(reason.as_bytes());
+        self.write_noise_frame(&frame)?;
+        anyhow::bail!("Noise handshake failed: {}", reason)
+    }
+
+    fn write_noise_frame(&mut self, payload: &[u8]) -> anyhow::Result<()> {
+        let len = u16::try_from(payload.len())?;
+        let mut frame = vec![0x01];
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(payload);
+        self.stream.write_all(&frame)?;
+        Ok(())
+    }
+
+    // Takes the next complete plaintext frame off the buffer
+    fn next_plaintext_frame(&mut self) -> anyhow::Result<Option<(u64, Vec<u8>)>> {
+        let Some(&preamble) = self.buffer.first() else {
+            return Ok(None);
+        };
+        if preamble != 0x00 {
+            anyhow::bail!("Encrypted or invalid frame; is esphome.encryption_key set?");
+        }
+
+        let mut pos = 1;
+        let Some(len) = read_varint(&self.buffer, &mut pos) else {
+            return Ok(None);
+        };
+        let Some(kind) = read_varint(&self.buffer, &mut pos) else {
+            return Ok(None);
+        };
+        let len = len as usize;
+        if len > MAX_FRAME_LEN {
+            anyhow::bail!("Frame too large");
+        }
+        if self.buffer.len() < pos + len {
+            return Ok(None);
+        }
+
+        let message = self.buffer[pos..pos + len].to_vec();
+        self.buffer.drain(..pos + len);
+        Ok(Some((kind, message)))
+    }
+
+    /// Handles one message; returns false when the client disconnects.
+    fn handle(&mut self, kind: u64, message: &[u8]) -> anyhow::Result<bool> {
+        match kind {
+            HELLO_REQUEST => {
+                let mut response = Vec::new();
+                put_varint_field(&mut response, 1, API_VERSION_MAJOR);
+                put_varint_field(&mut response, 2, API_VERSION_MINOR);
+                put_string_field(
+                    &mut response,
+                    3,
+                    &format!("busier {}", env!("CARGO_PKG_VERSION")),
+                );
+                put_string_field(&mut response, 4, config::get().device_name());
+                self.send(HELLO_RESPONSE, &response)?;
+            }
+            CONNECT_REQUEST => {
+                let password = config::get().esphome.password;
+                let given = fields(message)
+                    .into_iter()
+                    .find_map(|(number, value)| match (number, value) {
+                        (1, Field::Bytes(bytes)) => Some(bytes),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                self.authenticated = password.is_empty() || given == password.as_bytes();
+
+                let mut response = Vec::new();
+                put_varint_field(&mut response, 1, !self.authenticated as u64);
+                self.send(CONNECT_RESPONSE, &response)?;
+                if !self.authenticated {
+                    return Ok(false);
+                }
+            }
+            DISCONNECT_REQUEST => {
+                self.send(DISCONNECT_RESPONSE, &[])?;
+                return Ok(false);
+            }
+            PING_REQUEST => self.send(PING_RESPONSE, &[])?,
+            DEVICE_INFO_REQUEST => self.send_device_info()?,
+            LIST_ENTITIES_REQUEST if self.authenticated => self.send_entities()?,
+            SUBSCRIBE_STATES_REQUEST if self.authenticated => {
+                self.subscribed = true;
+                self.send_states(status::current())?;
+            }
+            SWITCH_COMMAND_REQUEST if self.authenticated => {
+                let mut key = None;
+                let mut state = false;
+                for (number, value) in fields(message) {
+                    match (number, value) {
+                        (1, Field::Fixed32(value)) => key = Some(value),
+                        (2, Field::Varint(value)) => state = value != 0,
+                        _ => {}
+                    }
+                }
+                if key == Some(DND_SWITCH_KEY) {
+                    let new_status = if state { Status::Dnd } else { Status::Free };
+                    status::set(Source::Integration, new_status);
+                }
+            }
+            // Log, service and time subscriptions are not supported
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn send_device_info(&mut self) -> anyhow::Result<()> {
+        let config = config::get();
+        let mut response = Vec::new();
+        put_varint_field(&mut response, 1, !config.esphome.password.is_empty() as u64);
+        put_string_field(&mut response, 2, config.device_name());
+        put_string_field(&mut response, 3, &peer_sync::format_mac(&device::mac()));
+        put_string_field(&mut response, 4, env!("CARGO_PKG_VERSION"));
+        put_string_field(&mut response, 6, board::CHIP_NAME);
+        put_varint_field(&mut response, 10, HTTP_PORT);
+        put_string_field(&mut response, 12, "busier");
+        put_string_field(&mut response, 13, config.device_name());
+        self.send(DEVICE_INFO_RESPONSE, &response)
+    }
+
+    fn send_entities(&mut self) -> anyhow::Result<()> {
+        let mac = peer_sync::format_mac(&device::mac());
+
+        let mut switch = Vec::new();
+        put_string_field(&mut switch, 1, "do_not_disturb");
+        put_fixed32_field(&mut switch, 2, DND_SWITCH_KEY);
+        put_string_field(&mut switch, 3, "Do Not Disturb");
+        put_string_field(&mut switch, 4, &format!("{}-dnd", mac));
+        put_string_field(&mut switch, 5, "mdi:minus-circle");
+        self.send(LIST_ENTITIES_SWITCH_RESPONSE, &switch)?;
+
+        let mut sensor = Vec::new();
+        put_string_field(&mut sensor, 1, "status");
+        put_fixed32_field(&mut sensor, 2, STATUS_SENSOR_KEY);
+        put_string_field(&mut sensor, 3, "Status");
+        put_string_field(&mut sensor, 4, &format!("{}-status", mac));
+        put_string_field(&mut sensor, 5, "mdi:account-clock");
+        self.send(LIST_ENTITIES_TEXT_SENSOR_RESPONSE, &sensor)?;
+
+        self.send(LIST_ENTITIES_DONE_RESPONSE, &[])
+    }
+
+    fn send_states(&mut self, current_status: Status) -> anyhow::Result<()> {
+        let mut switch = Vec::new();
+        put_fixed32_field(&mut switch, 1, DND_SWITCH_KEY);
+        put_varint_field(&mut switch, 2, (current_status == Status::Dnd) as u64);
+        self.send(SWITCH_STATE_RESPONSE, &switch)?;
+
+        let mut sensor = Vec::new();
+        put_fixed32_field(&mut sensor, 1, STATUS_SENSOR_KEY);
+        put_string_field(&mut sensor, 2, current_status.as_str());
+        self.send(TEXT_SENSOR_STATE_RESPONSE, &sensor)?;
+
+        self.last_status = Some(current_status);
+        Ok(())
+    }
+
+    fn send(&mut self, kind: u64, message: &[u8]) -> anyhow::Result<()> {
+        if let Transport::Encrypted(session) = &mut self.transport {
+            let mut plaintext = Vec::with_capacity(4 + message.len());
+            plaintext.extend_from_slice(&u16::try_from(kind)?.to_be_bytes());
+            plaintext.extend_from_slice(&u16::try_from(message.len())?.to_be_bytes());
+            plaintext.extend_from_slice(message);
+            let sealed = session.send.seal(&[], &plaintext);
+            return self.write_noise_frame(&sealed);
+        }
+
+        let mut frame = vec![0x00];
+        put_varint(&mut frame, message.len() as u64);
+        put_varint(&mut frame, kind);
+        frame.extend_from_slice(message);
+        self.stream.write_all(&frame)?;
+        Ok(())
+    }
+}
+
+enum Field {
+    Varint(u64),
+    Fixed32(u32),
+    Bytes(Vec<u8>),
+}
+
+// Decodes the fields of a protobuf message, stopping at the first malformed one
+fn fields(message: &[u8]) -> Vec<(u64, Field)> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < message.len() {
+        let Some(tag) = read_varint(message, &mut pos) else {
+            break;
+        };
+        let value = match tag & 0x07 {
+            0 => read_varint(message, &mut pos).map(Field::Varint),
+            2 => read_varint(message, &mut pos).and_then(|len| {
+                let bytes = message.get(pos..pos + len as usize)?.to_vec();
+                pos += len as usize;
+                Some(Field::Bytes(bytes))
+            }),
+            5 => message.get(pos..pos + 4).map(|bytes| {
+                pos += 4;
+                Field::Fixed32(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }),
+            _ => None,
+        };
+        let Some(value) = value else {
+            break;
+        };
+        fields.push((tag >> 3, value));
+    }
+    fields
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_varint_field(out: &mut Vec<u8>, number: u64, value: u64) {
+    put_varint(out, number << 3);
+    put_varint(out, value);
+}
+
+fn put_fixed32_field(out: &mut Vec<u8>, number: u64, value: u32) {
+    put_varint(out, (number << 3) | 5);
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_string_field(out: &mut Vec<u8>, number: u64, value: &str) {
+    put_varint(out, (number << 3) | 2);
+    put_varint(out, value.len() as u64);
+    out.extend_from_slice(value.as_bytes());
+}
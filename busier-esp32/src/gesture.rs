@@ -0,0 +1,203 @@
+//! APDS9960 gesture control.
+//!
+//! The sensor shares the display's I2C bus. Its gesture engine starts
+//! collecting up/down/left/right photodiode readings when something comes
+//! close and stops when it leaves; each such session is classified once it
+//! ends. Swiping left or right cycles through the statuses, any other wave
+//! snoozes notifications.
+
+use std::time::Duration;
+
+use embedded_hal::i2c::I2c;
+use log::{info, warn};
+
+use crate::snooze;
+use crate::status::{self, Source, Status};
+
+const GESTURE_STACK_SIZE: usize = 4096;
+const POLL_INTERVAL: Duration = Duration::from_millis(30);
+const WAVE_SNOOZE: Duration = Duration::from_secs(15 * 60);
+// Datasets below this on any channel are too faint to tell directions apart
+const MIN_LEVEL: u8 = 10;
+// Change of the left/right ratio, in percent, that makes a swipe
+const SWIPE_THRESHOLD: i32 = 30;
+
+const ADDRESS: u8 = 0x39;
+const ENABLE: u8 = 0x80;
+const WTIME: u8 = 0x83;
+const PPULSE: u8 = 0x8E;
+const CONTROL: u8 = 0x8F;
+const ID: u8 = 0x92;
+const GPENTH: u8 = 0xA0;
+const GEXTH: u8 = 0xA1;
+const GCONF1: u8 = 0xA2;
+const GCONF2: u8 = 0xA3;
+const GPULSE: u8 = 0xA6;
+const GCONF4: u8 = 0xAB;
+const GFLVL: u8 = 0xAE;
+const GFIFO_U: u8 = 0xFC;
+const KNOWN_IDS: [u8; 3] = [0xAB, 0x9C, 0xA8];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Gesture {
+    Left,
+    Right,
+    Wave,
+}
+
+/// Spawns the polling thread. Without an APDS9960 on the bus the thread
+/// exits after logging a warning.
+pub fn start<I>(i2c: I) -> anyhow::Result<()>
+where
+    I: I2c + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name("gesture".into())
+        .stack_size(GESTURE_STACK_SIZE)
+        .spawn(move || {
+            let mut sensor = Apds9960 { i2c };
+            if let Err(e) = sensor.init() {
+                warn!("No APDS9960 gesture sensor: {:?}", e);
+                return;
+            }
+            info!("APDS9960 gesture sensor ready");
+
+            let mut session = Vec::new();
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let active = match sensor.read_fifo(&mut session) {
+                    Ok(active) => active,
+                    Err(e) => {
+                        warn!("Failed to read APDS9960: {:?}", e);
+                        session.clear();
+                        continue;
+                    }
+                };
+                if active || session.is_empty() {
+                    continue;
+                }
+
+                if let Some(gesture) = classify(&session) {
+                    handle_gesture(gesture);
+                }
+                session.clear();
+            }
+        })?;
+
+    Ok(())
+}
+
+fn handle_gesture(gesture: Gesture) {
+    match gesture {
+        Gesture::Left | Gesture::Right => {
+            let order = [Status::Free, Status::Dnd, Status::Away];
+            let index = order
+                .iter()
+                .position(|s| *s == status::current())
+                .unwrap_or(0);
+            let step = if gesture == Gesture::Right {
+                1
+            } else {
+                order.len() - 1
+            };
+            let new_status = order[(index + step) % order.len()];
+            info!("Swiped {:?}: {}", gesture, new_status.as_str());
+            status::set(Source::Button, new_status);
+        }
+        Gesture::Wave => {
+            info!("Waved: snoozing notifications");
+            snooze::start(WAVE_SNOOZE);
+        }
+    }
+}
+
+// Compares the left/right balance at the start and the end of a session. A
+// hand moving across flips the balance; one moving straight in and out
+// leaves it roughly where it was.
+fn classify(session: &[[u8; 4]]) -> Option<Gesture> {
+    let strong: Vec<&[u8; 4]> = session
+        .iter()
+        .filter(|dataset| dataset.iter().all(|level| *level > MIN_LEVEL))
+        .collect();
+    let (first, last) = (*strong.first()?, *strong.last()?);
+
+    let ratio = |dataset: &[u8; 4]| {
+        let [_, _, left, right] = dataset.map(i32::from);
+        (left - right) * 100 / (left + right)
+    };
+    let delta = ratio(last) - ratio(first);
+
+    Some(if delta >= SWIPE_THRESHOLD {
+        Gesture::Right
+    } else if delta <= -SWIPE_THRESHOLD {
+        Gesture::Left
+    } else {
+        Gesture::Wave
+    })
+}
+
+struct Apds9960<I> {
+    i2c: I,
+}
+
+impl<I: I2c> Apds9960<I> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        let id = self.read(ID)?;
+        if !KNOWN_IDS.contains(&id) {
+            anyhow::bail!("Unexpected ID {:#04x}", id);
+        }
+
+        self.write(ENABLE, 0x00)?;
+        // Proximity: 16 us pulses, 8 of them, 100 mA LED, 4x gain
+        self.write(WTIME, 0xFF)?;
+        self.write(PPULSE, 0x87)?;
+        self.write(CONTROL, 0x05)?;
+        // Enter gesture mode above proximity 40, leave below 30
+        self.write(GPENTH, 40)?;
+        self.write(GEXTH, 30)?;
+        // Interrupt after 4 datasets, 4x gain, 2.8 ms between datasets
+        self.write(GCONF1, 0x40)?;
+        self.write(GCONF2, 0x41)?;
+        // 32 us pulses, 10 of them
+        self.write(GPULSE, 0xC9)?;
+        // Power on with wait, proximity and gesture engines
+        self.write(ENABLE, 0x4D)?;
+
+        Ok(())
+    }
+
+    // Appends the datasets waiting in the FIFO to `session` and returns
+    // whether the gesture engine is still collecting.
+    fn read_fifo(&mut self, session: &mut Vec<[u8; 4]>) -> anyhow::Result<bool> {
+        let level = self.read(GFLVL)? as usize;
+        if level > 0 {
+            let mut buf = vec![0; level * 4];
+            self.i2c
+                .write_read(ADDRESS, &[GFIFO_U], &mut buf)
+                .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+            session.extend(
+                buf.chunks_exact(4)
+                    .map(|dataset| [dataset[0], dataset[1], dataset[2], dataset[3]]),
+            );
+        }
+
+        // GMODE drops when the object leaves
+        Ok(self.read(GCONF4)? & 0x01 != 0)
+    }
+
+    fn read(&mut self, register: u8) -> anyhow::Result<u8> {
+        let mut buf = [0];
+        self.i2c
+            .write_read(ADDRESS, &[register], &mut buf)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(buf[0])
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> anyhow::Result<()> {
+        self.i2c
+            .write(ADDRESS, &[register, value])
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+//! The hardware behind the `busier_core::hal` traits.
+
+use std::net::Ipv4Addr;
+use std::time::UNIX_EPOCH;
+
+pub use busier_core::hal::{Clock, LocalTime, NetworkInfo};
+
+use crate::clock;
+use crate::device;
+
+/// The SNTP-synchronized system clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn local_now(&self) -> Option<LocalTime> {
+        clock::local_now()
+    }
+
+    fn next_occurrence(&self, minute_of_day: u16) -> Option<u64> {
+        clock::next_occurrence(minute_of_day)
+            .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_secs())
+    }
+}
+
+/// The WiFi station interface.
+pub struct SystemNetwork;
+
+impl NetworkInfo for SystemNetwork {
+    fn ip(&self) -> Option<Ipv4Addr> {
+        device::ip()
+    }
+
+    fn rssi(&self) -> Option<i8> {
+        device::rssi()
+    }
+}
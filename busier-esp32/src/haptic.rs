@@ -0,0 +1,129 @@
+//! Vibration motor.
+//!
+//! Drives an ERM vibration motor through a DRV2605L haptic driver on the
+//! shared I2C bus, so that a knock can be felt through the desk instead of
+//! heard. Each signal plays a pattern of alternating on and off times from
+//! `haptic`, with the motor strength set in real-time playback mode.
+
+use std::sync::{mpsc, OnceLock};
+use std::time::Duration;
+
+use embedded_hal::i2c::I2c;
+use log::{info, warn};
+
+use crate::config;
+use crate::output::Signal;
+
+const HAPTIC_STACK_SIZE: usize = 4096;
+
+// DRV2605L registers
+const ADDRESS: u8 = 0x5A;
+const STATUS: u8 = 0x00;
+const MODE: u8 = 0x01;
+const RTP_INPUT: u8 = 0x02;
+const CONTROL3: u8 = 0x1D;
+
+// Device IDs of the DRV2605 and DRV2605L in bits 7-5 of STATUS
+const DEVICE_IDS: [u8; 2] = [3, 7];
+const MODE_RTP: u8 = 0x05;
+// Unsigned real-time playback values
+const RTP_UNSIGNED: u8 = 0x08;
+
+static SENDER: OnceLock<mpsc::SyncSender<(Vec<u16>, u8)>> = OnceLock::new();
+
+/// Spawns the thread driving the motor. Without a DRV2605L on the bus the
+/// thread exits after logging a warning.
+pub fn start<I>(i2c: I) -> anyhow::Result<()>
+where
+    I: I2c + Send + 'static,
+{
+    // One pattern queued; signals while the motor runs are dropped
+    let (tx, rx) = mpsc::sync_channel::<(Vec<u16>, u8)>(1);
+
+    std::thread::Builder::new()
+        .name("haptic".into())
+        .stack_size(HAPTIC_STACK_SIZE)
+        .spawn(move || {
+            let mut driver = Drv2605 { i2c };
+            if let Err(e) = driver.init() {
+                warn!("No DRV2605L haptic driver: {:?}", e);
+                return;
+            }
+            info!("DRV2605L haptic driver ready");
+            // Only now, so the sound is never replaced by a missing motor
+            let _ = SENDER.set(tx);
+
+            for (pattern, strength) in rx {
+                if let Err(e) = driver.play(&pattern, strength) {
+                    warn!("Failed to drive vibration motor: {:?}", e);
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Vibrates with the pattern configured for a signal. Returns true if the
+/// motor replaces the sound, as set by `haptic.replace_sound`.
+pub fn play(signal: Signal) -> bool {
+    let config = config::get().haptic;
+    let Some(tx) = SENDER.get().filter(|_| config.enabled) else {
+        return false;
+    };
+
+    let pattern = match signal {
+        Signal::Knock => config.knock,
+        Signal::StatusChanged => config.status_changed,
+    };
+    if !pattern.is_empty() {
+        let _ = tx.try_send((pattern, config.strength.min(100)));
+    }
+
+    config.replace_sound
+}
+
+struct Drv2605<I> {
+    i2c: I,
+}
+
+impl<I: I2c> Drv2605<I> {
+    fn init(&mut self) -> anyhow::Result<()> {
+        let id = self.read(STATUS)? >> 5;
+        if !DEVICE_IDS.contains(&id) {
+            anyhow::bail!("unexpected device ID {}", id);
+        }
+
+        // Out of standby into real-time playback, motor off
+        self.write(MODE, 0)?;
+        let control3 = self.read(CONTROL3)?;
+        self.write(CONTROL3, control3 | RTP_UNSIGNED)?;
+        self.write(RTP_INPUT, 0)?;
+        self.write(MODE, MODE_RTP)?;
+        Ok(())
+    }
+
+    // Alternates between on and off, starting with on
+    fn play(&mut self, pattern: &[u16], strength: u8) -> anyhow::Result<()> {
+        let level = (u16::from(strength) * 255 / 100) as u8;
+        for (i, ms) in pattern.iter().enumerate() {
+            let on = i % 2 == 0;
+            self.write(RTP_INPUT, if on { level } else { 0 })?;
+            std::thread::sleep(Duration::from_millis((*ms).into()));
+        }
+        self.write(RTP_INPUT, 0)
+    }
+
+    fn read(&mut self, register: u8) -> anyhow::Result<u8> {
+        let mut buf = [0];
+        self.i2c
+            .write_read(ADDRESS, &[register], &mut buf)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        Ok(buf[0])
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> anyhow::Result<()> {
+        self.i2c
+            .write(ADDRESS, &[register, value])
+            .map_err(|e| anyhow::anyhow!("{:?}", e))
+    }
+}
@@ -0,0 +1,819 @@
+//! HomeKit accessory (feature `homekit`).
+//!
+//! Implements the HomeKit Accessory Protocol over IP so the Home app can pair
+//! with the device directly. The accessory exposes a "Do Not Disturb" switch
+//! and an occupancy sensor that reports occupied unless the status is Away.
+//!
+//! Until a controller has paired, the setup code and its QR code are shown
+//! on the display. The accessory's long-term secret key and the setup code
+//! are kept with [`crate::secrets`], its identifiers and pairings in the
+//! "homekit" NVS namespace.
+
+use std::collections::BTreeSet;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use ed25519_dalek::SigningKey;
+use esp_idf_svc::mdns::EspMdns;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha512};
+
+use crate::config;
+use crate::device;
+use crate::homekit_pairing::{self, SetupState, Verified, VerifyState};
+use crate::http_util;
+use crate::output::{self, Signal};
+use crate::peer_sync;
+use crate::secrets;
+use crate::status::{self, Source, Status};
+
+const HAP_PORT: u16 = 51826;
+// SRP and curve arithmetic need a generous stack
+const HOMEKIT_STACK_SIZE: usize = 20480;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_SESSIONS: usize = 8;
+const MAX_REQUEST_LEN: usize = 4096;
+const MAX_FRAME_LEN: usize = 1024;
+const NAMESPACE: &str = "homekit";
+// Keys of the long-term secret key and the setup code in the secrets
+// partition
+const LTSK_SECRET: &str = "hk_ltsk";
+const SETUP_CODE_SECRET: &str = "hk_setup_code";
+const MAX_SECRET_LEN: usize = 32;
+const MAX_PAIRINGS_LEN: usize = 2048;
+
+// Bump when the accessory database changes so controllers refetch it
+const CONFIG_NUMBER: &str = "1";
+// Switch
+const CATEGORY: u8 = 8;
+// Bonjour status flag and setup payload flag for IP accessories
+const SETUP_FLAG_IP: u64 = 2;
+
+const AID: u64 = 1;
+const IID_IDENTIFY: u64 = 2;
+const IID_DND: u64 = 11;
+const IID_OCCUPANCY: u64 = 14;
+
+// HAP status codes
+const STATUS_READ_ONLY: i64 = -70404;
+const STATUS_NOTIFICATION_UNSUPPORTED: i64 = -70406;
+const STATUS_NOT_FOUND: i64 = -70409;
+const STATUS_INVALID_VALUE: i64 = -70410;
+const STATUS_INSUFFICIENT_PRIVILEGES: i64 = -70401;
+
+/// A controller allowed to connect.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pairing {
+    pub id: String,
+    pub public_key: [u8; 32],
+    pub admin: bool,
+}
+
+/// Long-term accessory identity and pairings.
+pub struct Accessory {
+    /// Pairing identifier in "XX:XX:XX:XX:XX:XX" form.
+    pub device_id: String,
+    pub signing_key: SigningKey,
+    /// Setup code in "XXX-XX-XXX" form.
+    pub setup_code: String,
+    pub setup_id: String,
+    pub pairings: Vec<Pairing>,
+    pub setup_attempts: u32,
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl Accessory {
+    pub fn is_paired(&self) -> bool {
+        !self.pairings.is_empty()
+    }
+
+    pub fn save_pairings(&mut self) -> anyhow::Result<()> {
+        self.nvs
+            .set_raw("pairings", &serde_json::to_vec(&self.pairings)?)?;
+        Ok(())
+    }
+
+    fn load(partition: EspDefaultNvsPartition) -> anyhow::Result<Self> {
+        let mut nvs = EspNvs::new(partition, NAMESPACE, true)?;
+        // Older firmware kept the secrets in this namespace as well
+        secrets::migrate(&mut nvs, "ltsk", LTSK_SECRET, MAX_SECRET_LEN)?;
+        secrets::migrate(&mut nvs, "setup_code", SETUP_CODE_SECRET, MAX_SECRET_LEN)?;
+
+        let stored = secrets::get(LTSK_SECRET, MAX_SECRET_LEN)?
+            .and_then(|key| <[u8; 32]>::try_from(key).ok());
+        let signing_key = match stored {
+            Some(key) => SigningKey::from_bytes(&key),
+            None => {
+                let key = random::<32>();
+                secrets::set(LTSK_SECRET, &key)?;
+                SigningKey::from_bytes(&key)
+            }
+        };
+
+        let device_id = stored_or_init(&mut nvs, "device_id", || {
+            random::<6>()
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(":")
+        })?;
+        let stored = secrets::get(SETUP_CODE_SECRET, MAX_SECRET_LEN)?
+            .and_then(|code| String::from_utf8(code).ok());
+        let setup_code = match stored {
+            Some(code) => code,
+            None => {
+                let code = generate_setup_code();
+                secrets::set(SETUP_CODE_SECRET, code.as_bytes())?;
+                code
+            }
+        };
+        let setup_id = stored_or_init(&mut nvs, "setup_id", || {
+            const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+            random::<4>()
+                .iter()
+                .map(|b| ALPHABET[*b as usize % ALPHABET.len()] as char)
+                .collect()
+        })?;
+
+        let mut buf = vec![0; MAX_PAIRINGS_LEN];
+        let pairings = match nvs.get_raw("pairings", &mut buf)? {
+            Some(data) => serde_json::from_slice(data).unwrap_or_else(|e| {
+                warn!("Stored HomeKit pairings are invalid: {:?}", e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            device_id,
+            signing_key,
+            setup_code,
+            setup_id,
+            pairings,
+            setup_attempts: 0,
+            nvs,
+        })
+    }
+
+    // Setup payload encoded in the QR code: version, reserved bits,
+    // category, flags and the setup code, in base 36, then the setup id
+    fn setup_payload(&self) -> String {
+        let code: u64 = self.setup_code.replace('-', "").parse().unwrap_or(0);
+        let mut value = CATEGORY as u64;
+        value = (value << 4) | SETUP_FLAG_IP;
+        value = (value << 27) | (code & 0x07FF_FFFF);
+
+        let mut encoded = Vec::new();
+        while value > 0 {
+            encoded.push(char::from_digit((value % 36) as u32, 36).unwrap_or('0'));
+            value /= 36;
+        }
+        while encoded.len() < 9 {
+            encoded.push('0');
+        }
+        let encoded: String = encoded.iter().rev().collect();
+        format!("X-HM://{}{}", encoded.to_uppercase(), self.setup_id)
+    }
+}
+
+/// What the display shows until the accessory is paired.
+pub struct Setup {
+    pub code: String,
+    pub payload: String,
+}
+
+static ACCESSORY: Mutex<Option<Accessory>> = Mutex::new(None);
+
+/// Setup code and QR payload while no controller is paired.
+pub fn pending_setup() -> Option<Setup> {
+    let accessory = ACCESSORY.lock().unwrap();
+    let accessory = accessory.as_ref()?;
+    (!accessory.is_paired()).then(|| Setup {
+        code: accessory.setup_code.clone(),
+        payload: accessory.setup_payload(),
+    })
+}
+
+/// Loads the accessory identity and spawns the HAP server thread.
+pub fn start(partition: EspDefaultNvsPartition) -> anyhow::Result<()> {
+    let accessory = Accessory::load(partition)?;
+    if !accessory.is_paired() {
+        info!("HomeKit setup code: {}", accessory.setup_code);
+    }
+    *ACCESSORY.lock().unwrap() = Some(accessory);
+
+    let listener = TcpListener::bind(("0.0.0.0", HAP_PORT))?;
+    listener.set_nonblocking(true)?;
+    let mut mdns = EspMdns::take()?;
+    let mac = device::mac();
+    mdns.set_hostname(&format!(
+        "busier-{:02x}{:02x}{:02x}",
+        mac[3], mac[4], mac[5]
+    ))?;
+
+    std::thread::Builder::new()
+        .name("homekit".into())
+        .stack_size(HOMEKIT_STACK_SIZE)
+        .spawn(move || {
+            let mut sessions: Vec<Session> = Vec::new();
+            let mut advertised_paired: Option<bool> = None;
+            let mut last_status = status::current();
+
+            loop {
+                let paired = is_paired();
+                if advertised_paired != Some(paired) {
+                    if let Err(e) = advertise(&mut mdns) {
+                        warn!("HomeKit mDNS announcement failed: {:?}", e);
+                    }
+                    advertised_paired = Some(paired);
+                }
+
+                if let Ok((stream, addr)) = listener.accept() {
+                    if sessions.len() < MAX_SESSIONS && stream.set_nonblocking(true).is_ok() {
+                        info!("HomeKit connection from {}", addr);
+                        sessions.push(Session::new(stream));
+                    }
+                }
+
+                for session in &mut sessions {
+                    if let Err(e) = session.poll() {
+                        warn!("HomeKit session closed: {:?}", e);
+                        session.closed = true;
+                    }
+                }
+
+                let current_status = status::current();
+                if current_status != last_status {
+                    last_status = current_status;
+                    for session in &mut sessions {
+                        if let Err(e) = session.send_event() {
+                            warn!("HomeKit event failed: {:?}", e);
+                            session.closed = true;
+                        }
+                    }
+                }
+
+                // Controllers whose pairing was removed lose their sessions
+                let accessory = ACCESSORY.lock().unwrap();
+                let pairings = &accessory.as_ref().unwrap().pairings;
+                sessions.retain(|s| {
+                    !s.closed
+                        && s.controller
+                            .as_ref()
+                            .map_or(true, |id| pairings.iter().any(|p| &p.id == id))
+                });
+                drop(accessory);
+
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        })?;
+
+    info!("HomeKit accessory listening on port {}", HAP_PORT);
+    Ok(())
+}
+
+fn is_paired() -> bool {
+    ACCESSORY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|a| a.is_paired())
+}
+
+// Re-registers the _hap service so the status flag reflects pairing
+fn advertise(mdns: &mut EspMdns) -> anyhow::Result<()> {
+    let accessory = ACCESSORY.lock().unwrap();
+    let accessory = accessory.as_ref().unwrap();
+
+    let status_flag = if accessory.is_paired() { "0" } else { "1" };
+    let category = CATEGORY.to_string();
+    let setup_hash =
+        base64(&Sha512::digest(format!("{}{}", accessory.setup_id, accessory.device_id))[..4]);
+    let name = config::get().device_name().to_string();
+
+    let _ = mdns.remove_service("_hap", "_tcp");
+    mdns.add_service(
+        Some(&name),
+        "_hap",
+        "_tcp",
+        HAP_PORT,
+        &[
+            ("c#", CONFIG_NUMBER),
+            ("ff", "0"),
+            ("id", &accessory.device_id),
+            ("md", "busier"),
+            ("pv", "1.1"),
+            ("s#", "1"),
+            ("sf", status_flag),
+            ("ci", &category),
+            ("sh", &setup_hash),
+        ],
+    )?;
+    Ok(())
+}
+
+struct Session {
+    stream: TcpStream,
+    received: Vec<u8>,
+    requests: Vec<u8>,
+    encryption: Option<Encryption>,
+    setup: Option<SetupState>,
+    verify: Option<VerifyState>,
+    controller: Option<String>,
+    events: BTreeSet<u64>,
+    closed: bool,
+}
+
+struct Encryption {
+    read_key: [u8; 32],
+    write_key: [u8; 32],
+    read_counter: u64,
+    write_counter: u64,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+impl Session {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            received: Vec::new(),
+            requests: Vec::new(),
+            encryption: None,
+            setup: None,
+            verify: None,
+            controller: None,
+            events: BTreeSet::new(),
+            closed: false,
+        }
+    }
+
+    fn poll(&mut self) -> anyhow::Result<()> {
+        let mut chunk = [0; 512];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => anyhow::bail!("Connection closed by controller"),
+                Ok(len) => self.received.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.decrypt_received()?;
+        if self.requests.len() > MAX_REQUEST_LEN {
+            anyhow::bail!("Request too large");
+        }
+
+        while let Some(request) = take_request(&mut self.requests)? {
+            self.handle(request)?;
+        }
+        Ok(())
+    }
+
+    // Moves complete frames from the socket buffer to the request buffer
+    fn decrypt_received(&mut self) -> anyhow::Result<()> {
+        let Some(encryption) = self.encryption.as_mut() else {
+            self.requests.append(&mut self.received);
+            return Ok(());
+        };
+
+        while self.received.len() >= 2 {
+            let len = u16::from_le_bytes([self.received[0], self.received[1]]) as usize;
+            if len > MAX_FRAME_LEN {
+                anyhow::bail!("Frame too large");
+            }
+            if self.received.len() < 2 + len + 16 {
+                break;
+            }
+            let frame: Vec<u8> = self.received.drain(..2 + len + 16).collect();
+            let plaintext = homekit_pairing::open_frame(
+                &encryption.read_key,
+                encryption.read_counter,
+                &frame[..2],
+                &frame[2..],
+            )
+            .ok_or_else(|| anyhow::anyhow!("Frame authentication failed"))?;
+            encryption.read_counter += 1;
+            self.requests.extend(plaintext);
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, request: Request) -> anyhow::Result<()> {
+        let path = request.path.split('?').next().unwrap_or_default();
+
+        match (request.method.as_str(), path) {
+            ("POST", "/pair-setup") => {
+                let mut accessory = ACCESSORY.lock().unwrap();
+                let response = homekit_pairing::pair_setup(
+                    &mut self.setup,
+                    &request.body,
+                    accessory.as_mut().unwrap(),
+                );
+                drop(accessory);
+                self.respond(200, "application/pairing+tlv8", &response)
+            }
+            ("POST", "/pair-verify") => {
+                let accessory = ACCESSORY.lock().unwrap();
+                let (response, verified) = homekit_pairing::pair_verify(
+                    &mut self.verify,
+                    &request.body,
+                    accessory.as_ref().unwrap(),
+                );
+                drop(accessory);
+                // The final response is still sent in the clear
+                self.respond(200, "application/pairing+tlv8", &response)?;
+                if let Some(Verified {
+                    controller,
+                    write_key,
+                    read_key,
+                }) = verified
+                {
+                    info!("HomeKit controller {} verified", controller);
+                    self.controller = Some(controller);
+                    self.encryption = Some(Encryption {
+                        read_key,
+                        write_key,
+                        read_counter: 0,
+                        write_counter: 0,
+                    });
+                }
+                Ok(())
+            }
+            ("POST", "/identify") if !is_paired() => {
+                output::signal(Signal::Knock);
+                self.respond(204, "application/hap+json", &[])
+            }
+            _ if self.controller.is_none() => self.respond(
+                470,
+                "application/hap+json",
+                json!({ "status": STATUS_INSUFFICIENT_PRIVILEGES })
+                    .to_string()
+                    .as_bytes(),
+            ),
+            ("GET", "/accessories") => self.respond(
+                200,
+                "application/hap+json",
+                accessories().to_string().as_bytes(),
+            ),
+            ("GET", "/characteristics") => self.read_characteristics(&request.path),
+            ("PUT", "/characteristics") => self.write_characteristics(&request.body),
+            ("POST", "/pairings") => {
+                let controller = self.controller.clone().unwrap_or_default();
+                let mut accessory = ACCESSORY.lock().unwrap();
+                let response = homekit_pairing::pairings(
+                    &request.body,
+                    accessory.as_mut().unwrap(),
+                    &controller,
+                );
+                drop(accessory);
+                self.respond(200, "application/pairing+tlv8", &response)
+            }
+            _ => self.respond(404, "application/hap+json", &[]),
+        }
+    }
+
+    fn read_characteristics(&mut self, uri: &str) -> anyhow::Result<()> {
+        let ids = http_util::query_param(uri, "id").unwrap_or_default();
+        let mut all_found = true;
+        let characteristics: Vec<Value> = ids
+            .split(',')
+            .filter_map(|id| id.split_once('.'))
+            .map(|(aid, iid)| {
+                let (aid, iid) = (aid.parse().unwrap_or(0), iid.parse().unwrap_or(0));
+                match (aid == AID).then(|| value(iid)).flatten() {
+                    Some(value) => json!({ "aid": aid, "iid": iid, "value": value }),
+                    None => {
+                        all_found = false;
+                        json!({ "aid": aid, "iid": iid, "status": STATUS_NOT_FOUND })
+                    }
+                }
+            })
+            .collect();
+
+        // Multi-status responses carry a status for every characteristic
+        let (code, characteristics) = if all_found {
+            (200, characteristics)
+        } else {
+            let with_status = characteristics
+                .into_iter()
+                .map(|mut c| {
+                    if c.get("status").is_none() {
+                        c["status"] = json!(0);
+                    }
+                    c
+                })
+                .collect();
+            (207, with_status)
+        };
+        self.respond(
+            code,
+            "application/hap+json",
+            json!({ "characteristics": characteristics })
+                .to_string()
+                .as_bytes(),
+        )
+    }
+
+    fn write_characteristics(&mut self, body: &[u8]) -> anyhow::Result<()> {
+        let request: Value = serde_json::from_slice(body).unwrap_or_default();
+        let writes = request["characteristics"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        let mut results = Vec::new();
+        for write in writes {
+            let aid = write["aid"].as_u64().unwrap_or(0);
+            let iid = write["iid"].as_u64().unwrap_or(0);
+            let mut status_code = 0;
+
+            if aid != AID || (value(iid).is_none() && iid != IID_IDENTIFY) {
+                status_code = STATUS_NOT_FOUND;
+            } else {
+                if let Some(enable) = write["ev"].as_bool() {
+                    if iid == IID_DND || iid == IID_OCCUPANCY {
+                        if enable {
+                            self.events.insert(iid);
+                        } else {
+                            self.events.remove(&iid);
+                        }
+                    } else {
+                        status_code = STATUS_NOTIFICATION_UNSUPPORTED;
+                    }
+                }
+                if let Some(new_value) = write.get("value") {
+                    status_code = write_value(iid, new_value);
+                }
+            }
+            results.push(json!({ "aid": aid, "iid": iid, "status": status_code }));
+        }
+
+        if results.iter().all(|r| r["status"] == 0) {
+            self.respond(204, "application/hap+json", &[])
+        } else {
+            self.respond(
+                207,
+                "application/hap+json",
+                json!({ "characteristics": results }).to_string().as_bytes(),
+            )
+        }
+    }
+
+    fn send_event(&mut self) -> anyhow::Result<()> {
+        if self.events.is_empty() || self.encryption.is_none() {
+            return Ok(());
+        }
+        let characteristics: Vec<Value> = self
+            .events
+            .iter()
+            .filter_map(|iid| {
+                let value = value(*iid)?;
+                Some(json!({ "aid": AID, "iid": iid, "value": value }))
+            })
+            .collect();
+
+        let body = json!({ "characteristics": characteristics }).to_string();
+        let message = format!(
+            "EVENT/1.0 200 OK\r\nContent-Type: application/hap+json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        self.send(message.as_bytes())
+    }
+
+    fn respond(&mut self, code: u16, content_type: &str, body: &[u8]) -> anyhow::Result<()> {
+        let reason = match code {
+            200 => "OK",
+            204 => "No Content",
+            207 => "Multi-Status",
+            404 => "Not Found",
+            470 => "Connection Authorization Required",
+            _ => "Bad Request",
+        };
+        let mut message = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            code,
+            reason,
+            content_type,
+            body.len()
+        )
+        .into_bytes();
+        message.extend_from_slice(body);
+        self.send(&message)
+    }
+
+    fn send(&mut self, message: &[u8]) -> anyhow::Result<()> {
+        let Some(encryption) = self.encryption.as_mut() else {
+            self.stream.write_all(message)?;
+            return Ok(());
+        };
+
+        for chunk in message.chunks(MAX_FRAME_LEN) {
+            let aad = (chunk.len() as u16).to_le_bytes();
+            let sealed = homekit_pairing::seal_frame(
+                &encryption.write_key,
+                encryption.write_counter,
+                &aad,
+                chunk,
+            );
+            encryption.write_counter += 1;
+            self.stream.write_all(&aad)?;
+            self.stream.write_all(&sealed)?;
+        }
+        Ok(())
+    }
+}
+
+// Takes one complete HTTP request off the buffer
+// Fails on a body that could never fit in the buffer, before any
+// arithmetic on its length
+fn take_request(buffer: &mut Vec<u8>) -> anyhow::Result<Option<Request>> {
+    let Some(header_end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return Ok(None);
+    };
+    let header_end = header_end + 4;
+    let head = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        anyhow::bail!("Malformed request line");
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let content_len = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_len > MAX_REQUEST_LEN {
+        anyhow::bail!("Request too large");
+    }
+    let Some(end) = header_end.checked_add(content_len) else {
+        anyhow::bail!("Request too large");
+    };
+    if buffer.len() < end {
+        return Ok(None);
+    }
+
+    let body = buffer[header_end..end].to_vec();
+    buffer.drain(..end);
+    Ok(Some(Request { method, path, body }))
+}
+
+// Current value of a readable characteristic
+fn value(iid: u64) -> Option<Value> {
+    let current_status = status::current();
+    Some(match iid {
+        3 => json!("busier"),
+        4 => json!("busier"),
+        5 => json!(config::get().device_name()),
+        6 => json!(peer_sync::format_mac(&device::mac())),
+        7 => json!(env!("CARGO_PKG_VERSION")),
+        9 => json!("1.1.0"),
+        IID_DND => json!(current_status == Status::Dnd),
+        12 => json!("Do Not Disturb"),
+        IID_OCCUPANCY => json!((current_status != Status::Away) as u8),
+        15 => json!("Occupancy"),
+        _ => return None,
+    })
+}
+
+// Applies a write and returns the HAP status code
+fn write_value(iid: u64, new_value: &Value) -> i64 {
+    match iid {
+        IID_IDENTIFY => {
+            output::signal(Signal::Knock);
+            0
+        }
+        IID_DND => {
+            let enabled = match new_value {
+                Value::Bool(enabled) => *enabled,
+                Value::Number(n) => n.as_u64() == Some(1),
+                _ => return STATUS_INVALID_VALUE,
+            };
+            let new_status = if enabled { Status::Dnd } else { Status::Free };
+            status::set(Source::Integration, new_status);
+            0
+        }
+        _ => STATUS_READ_ONLY,
+    }
+}
+
+// The accessory database returned by GET /accessories
+fn accessories() -> Value {
+    let characteristic = |iid: u64, kind: &str, format: &str, perms: &[&str]| {
+        let mut c = json!({ "iid": iid, "type": kind, "format": format, "perms": perms });
+        if let Some(value) = value(iid) {
+            c["value"] = value;
+        }
+        c
+    };
+
+    json!({
+        "accessories": [{
+            "aid": AID,
+            "services": [
+                {
+                    "iid": 1,
+                    "type": "3E",
+                    "characteristics": [
+                        characteristic(IID_IDENTIFY, "14", "bool", &["pw"]),
+                        characteristic(3, "20", "string", &["pr"]),
+                        characteristic(4, "21", "string", &["pr"]),
+                        characteristic(5, "23", "string", &["pr"]),
+                        characteristic(6, "30", "string", &["pr"]),
+                        characteristic(7, "52", "string", &["pr"]),
+                    ]
+                },
+                {
+                    "iid": 8,
+                    "type": "A2",
+                    "characteristics": [characteristic(9, "37", "string", &["pr"])]
+                },
+                {
+                    "iid": 10,
+                    "type": "49",
+                    "primary": true,
+                    "characteristics": [
+                        characteristic(IID_DND, "25", "bool", &["pr", "pw", "ev"]),
+                        characteristic(12, "23", "string", &["pr"]),
+                    ]
+                },
+                {
+                    "iid": 13,
+                    "type": "86",
+                    "characteristics": [
+                        characteristic(IID_OCCUPANCY, "71", "uint8", &["pr", "ev"]),
+                        characteristic(15, "23", "string", &["pr"]),
+                    ]
+                }
+            ]
+        }]
+    })
+}
+
+/// Random bytes from the hardware RNG.
+pub fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    // SAFETY: the buffer is N bytes long
+    unsafe { sys::esp_fill_random(bytes.as_mut_ptr() as *mut core::ffi::c_void, N) };
+    bytes
+}
+
+fn stored_or_init(
+    nvs: &mut EspNvs<NvsDefault>,
+    key: &str,
+    init: impl FnOnce() -> String,
+) -> anyhow::Result<String> {
+    let mut buf = [0; 32];
+    if let Some(value) = nvs.get_str(key, &mut buf)? {
+        return Ok(value.to_string());
+    }
+    let value = init();
+    nvs.set_str(key, &value)?;
+    Ok(value)
+}
+
+// Eight random digits, avoiding the codes HomeKit rejects as trivial
+fn generate_setup_code() -> String {
+    loop {
+        let digits: Vec<u8> = random::<8>().iter().map(|b| b % 10).collect();
+        let text: String = digits.iter().map(|d| char::from(b'0' + d)).collect();
+        if digits.iter().all(|d| *d == digits[0]) || text == "12345678" || text == "87654321" {
+            continue;
+        }
+        return format!("{}-{}-{}", &text[..3], &text[3..5], &text[5..]);
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let group = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
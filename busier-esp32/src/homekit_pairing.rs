@@ -0,0 +1,612 @@
+//! HomeKit pairing (feature `homekit`).
+//!
+//! Implements the HAP pair-setup (SRP-6a with the 3072-bit group and
+//! SHA-512), pair-verify (X25519 plus Ed25519 signatures) and pairings
+//! management exchanges. Messages are TLV8-encoded.
+
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, Tag};
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+use hkdf::Hkdf;
+use log::{info, warn};
+use num_bigint::BigUint;
+use sha2::{Digest, Sha512};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::auth;
+use crate::homekit::{self, Accessory, Pairing};
+use crate::setup;
+
+// TLV types
+const TYPE_METHOD: u8 = 0x00;
+const TYPE_IDENTIFIER: u8 = 0x01;
+const TYPE_SALT: u8 = 0x02;
+const TYPE_PUBLIC_KEY: u8 = 0x03;
+const TYPE_PROOF: u8 = 0x04;
+const TYPE_ENCRYPTED_DATA: u8 = 0x05;
+const TYPE_STATE: u8 = 0x06;
+const TYPE_ERROR: u8 = 0x07;
+const TYPE_SIGNATURE: u8 = 0x0A;
+const TYPE_PERMISSIONS: u8 = 0x0B;
+const TYPE_SEPARATOR: u8 = 0xFF;
+
+// Error codes
+const ERROR_UNKNOWN: u8 = 0x01;
+const ERROR_AUTHENTICATION: u8 = 0x02;
+const ERROR_MAX_PEERS: u8 = 0x04;
+const ERROR_MAX_TRIES: u8 = 0x05;
+const ERROR_UNAVAILABLE: u8 = 0x06;
+
+// Pairings methods
+const METHOD_ADD_PAIRING: u8 = 0x03;
+const METHOD_REMOVE_PAIRING: u8 = 0x04;
+const METHOD_LIST_PAIRINGS: u8 = 0x05;
+
+const PERMISSION_ADMIN: u8 = 0x01;
+const MAX_PAIRINGS: usize = 16;
+// Failed pair-setup attempts before the accessory refuses further tries
+const MAX_SETUP_ATTEMPTS: u32 = 100;
+
+const SRP_USERNAME: &str = "Pair-Setup";
+const SRP_GENERATOR: u8 = 5;
+// RFC 5054 3072-bit group
+const SRP_PRIME: &str = "\
+    FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74\
+    020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F1437\
+    4FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7ED\
+    EE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF05\
+    98DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB\
+    9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3B\
+    E39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF695581718\
+    3995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33\
+    A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7\
+    ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864\
+    D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E2\
+    08E24FA074E5AB3143DB5BFCE0FD108E4B82D120A93AD2CAFFFFFFFFFFFFFFFF";
+const SRP_PRIME_LEN: usize = 384;
+
+/// Progress of a pair-setup exchange on one connection.
+pub struct SetupState {
+    salt: [u8; 16],
+    verifier: BigUint,
+    private_key: BigUint,
+    public_key: Vec<u8>,
+    session_key: Option<Vec<u8>>,
+}
+
+/// Progress of a pair-verify exchange on one connection.
+pub struct VerifyState {
+    shared_secret: [u8; 32],
+    accessory_public: [u8; 32],
+    controller_public: [u8; 32],
+    session_key: [u8; 32],
+}
+
+/// Outcome of a successful pair-verify.
+pub struct Verified {
+    pub controller: String,
+    /// Key for messages from the accessory to the controller.
+    pub write_key: [u8; 32],
+    /// Key for messages from the controller to the accessory.
+    pub read_key: [u8; 32],
+}
+
+/// Handles one `/pair-setup` request.
+pub fn pair_setup(
+    state: &mut Option<SetupState>,
+    body: &[u8],
+    accessory: &mut Accessory,
+) -> Vec<u8> {
+    let request = tlv_decode(body);
+    match tlv_find(&request, TYPE_STATE).and_then(|s| s.first().copied()) {
+        Some(1) => setup_start(state, accessory),
+        Some(3) => setup_verify_proof(state, &request, accessory),
+        Some(5) => setup_exchange(state, &request, accessory),
+        _ => tlv_error(2, ERROR_UNKNOWN),
+    }
+}
+
+// M1 -> M2: send the SRP salt and public key
+fn setup_start(state: &mut Option<SetupState>, accessory: &Accessory) -> Vec<u8> {
+    // Nobody pairs before the device has an admin password
+    if accessory.is_paired() || setup::is_pending() {
+        return tlv_error(2, ERROR_UNAVAILABLE);
+    }
+    if accessory.setup_attempts >= MAX_SETUP_ATTEMPTS {
+        return tlv_error(2, ERROR_MAX_TRIES);
+    }
+
+    let (prime, generator) = srp_group();
+    let salt = homekit::random::<16>();
+    let password = format!("{}:{}", SRP_USERNAME, accessory.setup_code);
+    let x = BigUint::from_bytes_be(&sha512(&[&salt, &sha512(&[password.as_bytes()])]));
+    let verifier = generator.modpow(&x, &prime);
+
+    let k = BigUint::from_bytes_be(&sha512(&[
+        &prime.to_bytes_be(),
+        &pad(&generator.to_bytes_be()),
+    ]));
+    let private_key = BigUint::from_bytes_be(&homekit::random::<32>());
+    let public_key = (k * &verifier + generator.modpow(&private_key, &prime)) % &prime;
+    let public_key = public_key.to_bytes_be();
+
+    let response = tlv_encode(&[
+        (TYPE_STATE, &[2]),
+        (TYPE_PUBLIC_KEY, &public_key),
+        (TYPE_SALT, &salt),
+    ]);
+    *state = Some(SetupState {
+        salt,
+        verifier,
+        private_key,
+        public_key,
+        session_key: None,
+    });
+    response
+}
+
+// M3 -> M4: check the controller's SRP proof and send ours
+fn setup_verify_proof(
+    state: &mut Option<SetupState>,
+    request: &[(u8, Vec<u8>)],
+    accessory: &mut Accessory,
+) -> Vec<u8> {
+    let (Some(setup), Some(client_public), Some(client_proof)) = (
+        state.as_mut(),
+        tlv_find(request, TYPE_PUBLIC_KEY),
+        tlv_find(request, TYPE_PROOF),
+    ) else {
+        return tlv_error(4, ERROR_UNKNOWN);
+    };
+
+    let (prime, generator) = srp_group();
+    let a = BigUint::from_bytes_be(client_public);
+    if (&a % &prime) == BigUint::default() {
+        *state = None;
+        return tlv_error(4, ERROR_AUTHENTICATION);
+    }
+
+    let u = BigUint::from_bytes_be(&sha512(&[&pad(client_public), &pad(&setup.public_key)]));
+    let secret = (a * setup.verifier.modpow(&u, &prime)).modpow(&setup.private_key, &prime);
+    let session_key = sha512(&[&secret.to_bytes_be()]);
+
+    let hash_prime = sha512(&[&prime.to_bytes_be()]);
+    let hash_generator = sha512(&[&generator.to_bytes_be()]);
+    let group_hash: Vec<u8> = hash_prime
+        .iter()
+        .zip(&hash_generator)
+        .map(|(a, b)| a ^ b)
+        .collect();
+    let expected_proof = sha512(&[
+        &group_hash,
+        &sha512(&[SRP_USERNAME.as_bytes()]),
+        &setup.salt,
+        client_public,
+        &setup.public_key,
+        &session_key,
+    ]);
+
+    if !auth::constant_time_eq(&expected_proof, client_proof) {
+        accessory.setup_attempts += 1;
+        warn!("HomeKit pair-setup with wrong setup code");
+        *state = None;
+        return tlv_error(4, ERROR_AUTHENTICATION);
+    }
+
+    let proof = sha512(&[client_public, &expected_proof, &session_key]);
+    setup.session_key = Some(session_key);
+    tlv_encode(&[(TYPE_STATE, &[4]), (TYPE_PROOF, &proof)])
+}
+
+// M5 -> M6: exchange long-term public keys
+fn setup_exchange(
+    state: &mut Option<SetupState>,
+    request: &[(u8, Vec<u8>)],
+    accessory: &mut Accessory,
+) -> Vec<u8> {
+    let Some(session_key) = state.take().and_then(|s| s.session_key) else {
+        return tlv_error(6, ERROR_UNKNOWN);
+    };
+    let encryption_key = hkdf(
+        &session_key,
+        "Pair-Setup-Encrypt-Salt",
+        "Pair-Setup-Encrypt-Info",
+    );
+
+    let Some(sub_tlv) = tlv_find(request, TYPE_ENCRYPTED_DATA)
+        .and_then(|data| open(&encryption_key, b"PS-Msg05", data))
+    else {
+        return tlv_error(6, ERROR_AUTHENTICATION);
+    };
+    let sub_tlv = tlv_decode(&sub_tlv);
+    let (Some(controller), Some(controller_key), Some(signature)) = (
+        tlv_find(&sub_tlv, TYPE_IDENTIFIER),
+        tlv_find(&sub_tlv, TYPE_PUBLIC_KEY),
+        tlv_find(&sub_tlv, TYPE_SIGNATURE),
+    ) else {
+        return tlv_error(6, ERROR_UNKNOWN);
+    };
+
+    let controller_x = hkdf(
+        &session_key,
+        "Pair-Setup-Controller-Sign-Salt",
+        "Pair-Setup-Controller-Sign-Info",
+    );
+    let controller_info = [&controller_x[..], controller, controller_key].concat();
+    let Some(public_key) = verify_signature(controller_key, &controller_info, signature) else {
+        return tlv_error(6, ERROR_AUTHENTICATION);
+    };
+
+    accessory.pairings.push(Pairing {
+        id: String::from_utf8_lossy(controller).into_owned(),
+        public_key,
+        admin: true,
+    });
+    accessory.setup_attempts = 0;
+    if let Err(e) = accessory.save_pairings() {
+        warn!("Failed to persist HomeKit pairing: {:?}", e);
+    }
+    info!(
+        "HomeKit paired with {}",
+        String::from_utf8_lossy(controller)
+    );
+
+    let accessory_x = hkdf(
+        &session_key,
+        "Pair-Setup-Accessory-Sign-Salt",
+        "Pair-Setup-Accessory-Sign-Info",
+    );
+    let accessory_key = accessory.signing_key.verifying_key().to_bytes();
+    let accessory_info = [
+        &accessory_x[..],
+        accessory.device_id.as_bytes(),
+        &accessory_key,
+    ]
+    .concat();
+    let signature = accessory.signing_key.sign(&accessory_info).to_bytes();
+
+    let sub_tlv = tlv_encode(&[
+        (TYPE_IDENTIFIER, accessory.device_id.as_bytes()),
+        (TYPE_PUBLIC_KEY, &accessory_key),
+        (TYPE_SIGNATURE, &signature),
+    ]);
+    let encrypted = seal(&encryption_key, b"PS-Msg06", &sub_tlv);
+    tlv_encode(&[(TYPE_STATE, &[6]), (TYPE_ENCRYPTED_DATA, &encrypted)])
+}
+
+/// Handles one `/pair-verify` request. Returns the response and, once the
+/// exchange completes, the session keys.
+pub fn pair_verify(
+    state: &mut Option<VerifyState>,
+    body: &[u8],
+    accessory: &Accessory,
+) -> (Vec<u8>, Option<Verified>) {
+    let request = tlv_decode(body);
+    match tlv_find(&request, TYPE_STATE).and_then(|s| s.first().copied()) {
+        Some(1) => (verify_start(state, &request, accessory), None),
+        Some(3) => verify_finish(state, &request, accessory),
+        _ => (tlv_error(2, ERROR_UNKNOWN), None),
+    }
+}
+
+// M1 -> M2: ephemeral key exchange, signed with the accessory's key
+fn verify_start(
+    state: &mut Option<VerifyState>,
+    request: &[(u8, Vec<u8>)],
+    accessory: &Accessory,
+) -> Vec<u8> {
+    let Some(controller_public) =
+        tlv_find(request, TYPE_PUBLIC_KEY).and_then(|key| <[u8; 32]>::try_from(key).ok())
+    else {
+        return tlv_error(2, ERROR_UNKNOWN);
+    };
+
+    let secret = StaticSecret::from(homekit::random::<32>());
+    let accessory_public = PublicKey::from(&secret).to_bytes();
+    let shared_secret = secret
+        .diffie_hellman(&PublicKey::from(controller_public))
+        .to_bytes();
+
+    let accessory_info = [
+        &accessory_public[..],
+        accessory.device_id.as_bytes(),
+        &controller_public,
+    ]
+    .concat();
+    let signature = accessory.signing_key.sign(&accessory_info).to_bytes();
+
+    let session_key = hkdf(
+        &shared_secret,
+        "Pair-Verify-Encrypt-Salt",
+        "Pair-Verify-Encrypt-Info",
+    );
+    let sub_tlv = tlv_encode(&[
+        (TYPE_IDENTIFIER, accessory.device_id.as_bytes()),
+        (TYPE_SIGNATURE, &signature),
+    ]);
+    let encrypted = seal(&session_key, b"PV-Msg02", &sub_tlv);
+
+    *state = Some(VerifyState {
+        shared_secret,
+        accessory_public,
+        controller_public,
+        session_key,
+    });
+    tlv_encode(&[
+        (TYPE_STATE, &[2]),
+        (TYPE_PUBLIC_KEY, &accessory_public),
+        (TYPE_ENCRYPTED_DATA, &encrypted),
+    ])
+}
+
+// M3 -> M4: check that the controller is paired and derive session keys
+fn verify_finish(
+    state: &mut Option<VerifyState>,
+    request: &[(u8, Vec<u8>)],
+    accessory: &Accessory,
+) -> (Vec<u8>, Option<Verified>) {
+    let Some(verify) = state.take() else {
+        return (tlv_error(4, ERROR_UNKNOWN), None);
+    };
+    let Some(sub_tlv) = tlv_find(request, TYPE_ENCRYPTED_DATA)
+        .and_then(|data| open(&verify.session_key, b"PV-Msg03", data))
+    else {
+        return (tlv_error(4, ERROR_AUTHENTICATION), None);
+    };
+    let sub_tlv = tlv_decode(&sub_tlv);
+    let (Some(controller), Some(signature)) = (
+        tlv_find(&sub_tlv, TYPE_IDENTIFIER),
+        tlv_find(&sub_tlv, TYPE_SIGNATURE),
+    ) else {
+        return (tlv_error(4, ERROR_UNKNOWN), None);
+    };
+
+    let controller = String::from_utf8_lossy(controller).into_owned();
+    let Some(pairing) = accessory.pairings.iter().find(|p| p.id == controller) else {
+        return (tlv_error(4, ERROR_AUTHENTICATION), None);
+    };
+    let controller_info = [
+        &verify.controller_public[..],
+        controller.as_bytes(),
+        &verify.accessory_public,
+    ]
+    .concat();
+    if verify_signature(&pairing.public_key, &controller_info, signature).is_none() {
+        return (tlv_error(4, ERROR_AUTHENTICATION), None);
+    }
+
+    let verified = Verified {
+        controller,
+        write_key: hkdf(
+            &verify.shared_secret,
+            "Control-Salt",
+            "Control-Read-Encryption-Key",
+        ),
+        read_key: hkdf(
+            &verify.shared_secret,
+            "Control-Salt",
+            "Control-Write-Encryption-Key",
+        ),
+    };
+    (tlv_encode(&[(TYPE_STATE, &[4])]), Some(verified))
+}
+
+/// Handles one `/pairings` request from a verified controller.
+pub fn pairings(body: &[u8], accessory: &mut Accessory, controller: &str) -> Vec<u8> {
+    let is_admin = accessory
+        .pairings
+        .iter()
+        .any(|p| p.id == controller && p.admin);
+    if !is_admin {
+        return tlv_error(2, ERROR_AUTHENTICATION);
+    }
+
+    let request = tlv_decode(body);
+    let method = tlv_find(&request, TYPE_METHOD).and_then(|m| m.first().copied());
+    let identifier =
+        tlv_find(&request, TYPE_IDENTIFIER).map(|id| String::from_utf8_lossy(id).into_owned());
+
+    match (method, identifier) {
+        (Some(METHOD_ADD_PAIRING), Some(id)) => {
+            let Some(public_key) =
+                tlv_find(&request, TYPE_PUBLIC_KEY).and_then(|key| <[u8; 32]>::try_from(key).ok())
+            else {
+                return tlv_error(2, ERROR_UNKNOWN);
+            };
+            let admin = tlv_find(&request, TYPE_PERMISSIONS)
+                .and_then(|p| p.first())
+                .is_some_and(|p| p & PERMISSION_ADMIN != 0);
+
+            match accessory.pairings.iter().position(|p| p.id == id) {
+                Some(i) if accessory.pairings[i].public_key != public_key => {
+                    return tlv_error(2, ERROR_UNKNOWN)
+                }
+                Some(i) => accessory.pairings[i].admin = admin,
+                None if accessory.pairings.len() >= MAX_PAIRINGS => {
+                    return tlv_error(2, ERROR_MAX_PEERS)
+                }
+                None => accessory.pairings.push(Pairing {
+                    id,
+                    public_key,
+                    admin,
+                }),
+            }
+        }
+        (Some(METHOD_REMOVE_PAIRING), Some(id)) => {
+            accessory.pairings.retain(|p| p.id != id);
+            // Without an admin nobody could manage the accessory any more
+            if !accessory.pairings.iter().any(|p| p.admin) {
+                accessory.pairings.clear();
+            }
+        }
+        (Some(METHOD_LIST_PAIRINGS), _) => {
+            let mut response = tlv_encode(&[(TYPE_STATE, &[2])]);
+            for (i, pairing) in accessory.pairings.iter().enumerate() {
+                if i > 0 {
+                    response.extend(tlv_encode(&[(TYPE_SEPARATOR, &[])]));
+                }
+                response.extend(tlv_encode(&[
+                    (TYPE_IDENTIFIER, pairing.id.as_bytes()),
+                    (TYPE_PUBLIC_KEY, &pairing.public_key),
+                    (TYPE_PERMISSIONS, &[pairing.admin as u8]),
+                ]));
+            }
+            return response;
+        }
+        _ => return tlv_error(2, ERROR_UNKNOWN),
+    }
+
+    if let Err(e) = accessory.save_pairings() {
+        warn!("Failed to persist HomeKit pairings: {:?}", e);
+    }
+    tlv_encode(&[(TYPE_STATE, &[2])])
+}
+
+/// Encrypts a session frame payload; returns ciphertext followed by the tag.
+pub fn seal_frame(key: &[u8; 32], counter: u64, aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut buffer = plaintext.to_vec();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    // Encryption only fails for messages far beyond the frame size
+    let tag = cipher
+        .encrypt_in_place_detached(&frame_nonce(counter), aad, &mut buffer)
+        .expect("frame too large");
+    buffer.extend_from_slice(&tag);
+    buffer
+}
+
+/// Decrypts a session frame payload of ciphertext followed by the tag.
+pub fn open_frame(key: &[u8; 32], counter: u64, aad: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    let (ciphertext, tag) = data.split_at(data.len().checked_sub(16)?);
+    let mut buffer = ciphertext.to_vec();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt_in_place_detached(
+            &frame_nonce(counter),
+            aad,
+            &mut buffer,
+            Tag::from_slice(tag),
+        )
+        .ok()?;
+    Some(buffer)
+}
+
+// Session nonces are four zero bytes and the little-endian frame counter
+fn frame_nonce(counter: u64) -> Nonce {
+    let mut nonce = [0; 12];
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+// Pairing messages use a fixed 8-byte label as the nonce
+fn label_nonce(label: &[u8; 8]) -> Nonce {
+    let mut nonce = [0; 12];
+    nonce[4..].copy_from_slice(label);
+    *Nonce::from_slice(&nonce)
+}
+
+fn seal(key: &[u8; 32], label: &[u8; 8], plaintext: &[u8]) -> Vec<u8> {
+    let mut buffer = plaintext.to_vec();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let tag = cipher
+        .encrypt_in_place_detached(&label_nonce(label), &[], &mut buffer)
+        .expect("message too large");
+    buffer.extend_from_slice(&tag);
+    buffer
+}
+
+fn open(key: &[u8; 32], label: &[u8; 8], data: &[u8]) -> Option<Vec<u8>> {
+    let (ciphertext, tag) = data.split_at(data.len().checked_sub(16)?);
+    let mut buffer = ciphertext.to_vec();
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt_in_place_detached(&label_nonce(label), &[], &mut buffer, Tag::from_slice(tag))
+        .ok()?;
+    Some(buffer)
+}
+
+// Returns the public key when the Ed25519 signature is valid
+fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) -> Option<[u8; 32]> {
+    let public_key = <[u8; 32]>::try_from(public_key).ok()?;
+    let signature = Signature::from_slice(signature).ok()?;
+    VerifyingKey::from_bytes(&public_key)
+        .ok()?
+        .verify(message, &signature)
+        .ok()?;
+    Some(public_key)
+}
+
+fn hkdf(ikm: &[u8], salt: &str, info: &str) -> [u8; 32] {
+    let mut key = [0; 32];
+    Hkdf::<Sha512>::new(Some(salt.as_bytes()), ikm)
+        .expand(info.as_bytes(), &mut key)
+        .expect("32 bytes is a valid HKDF-SHA512 length");
+    key
+}
+
+fn sha512(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().to_vec()
+}
+
+fn srp_group() -> (BigUint, BigUint) {
+    let prime = BigUint::parse_bytes(SRP_PRIME.as_bytes(), 16).expect("valid SRP prime");
+    (prime, BigUint::from(SRP_GENERATOR))
+}
+
+// Left-pads a big-endian number to the length of the prime
+fn pad(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = vec![0; SRP_PRIME_LEN.saturating_sub(bytes.len())];
+    padded.extend_from_slice(bytes);
+    padded
+}
+
+fn tlv_error(state: u8, error: u8) -> Vec<u8> {
+    tlv_encode(&[(TYPE_STATE, &[state]), (TYPE_ERROR, &[error])])
+}
+
+// Values longer than 255 bytes are split into consecutive items
+fn tlv_encode(items: &[(u8, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (kind, value) in items {
+        if value.is_empty() {
+            out.extend_from_slice(&[*kind, 0]);
+        }
+        for chunk in value.chunks(255) {
+            out.push(*kind);
+            out.push(chunk.len() as u8);
+            out.extend_from_slice(chunk);
+        }
+    }
+    out
+}
+
+// Consecutive items of the same type are joined back together
+fn tlv_decode(data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let mut items: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut pos = 0;
+    while pos + 2 <= data.len() {
+        let kind = data[pos];
+        let len = data[pos + 1] as usize;
+        let Some(value) = data.get(pos + 2..pos + 2 + len) else {
+            break;
+        };
+        match items.last_mut() {
+            Some((last_kind, last_value)) if *last_kind == kind => {
+                last_value.extend_from_slice(value)
+            }
+            _ => items.push((kind, value.to_vec())),
+        }
+        pos += 2 + len;
+    }
+    items
+}
+
+fn tlv_find(items: &[(u8, Vec<u8>)], kind: u8) -> Option<&[u8]> {
+    items
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, v)| v.as_slice())
+}
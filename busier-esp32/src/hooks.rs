@@ -0,0 +1,120 @@
+//! Generic inbound webhooks.
+//!
+//! `POST /api/hooks/<name>` looks up the named hook in the configuration,
+//! verifies the HMAC-SHA256 signature of the body, extracts a field with a
+//! JSON pointer and maps its value to a status.
+
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::server::EspHttpServer;
+use hmac::{Hmac, Mac};
+use log::info;
+use sha2::Sha256;
+
+use crate::auth;
+use crate::config::{self, HookConfig};
+use crate::http_util::{self, Routes};
+use crate::status::{self, Status};
+
+const HOOKS_PREFIX: &str = "/api/hooks/";
+// SaaS payloads are much larger than our own API requests
+const MAX_HOOK_LEN: usize = 4096;
+const DEFAULT_SIGNATURE_HEADER: &str = "X-Signature-256";
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    server.route("/api/hooks/*", Method::Post, |mut req| {
+        let name = req
+            .uri()
+            .strip_prefix(HOOKS_PREFIX)
+            .unwrap_or_default()
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        let Some(hook) = config::get().hooks.into_iter().find(|h| h.name == name) else {
+            req.into_status_response(404)?
+                .write_all("Unknown hook".as_bytes())?;
+            return Ok(());
+        };
+
+        let len = req.content_len().unwrap_or(0) as usize;
+        if len > MAX_HOOK_LEN {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        }
+
+        let header = if hook.signature_header.is_empty() {
+            DEFAULT_SIGNATURE_HEADER
+        } else {
+            &hook.signature_header
+        };
+        let signature = req.header(header).map(str::to_string);
+
+        let mut buf = vec![0; len];
+        req.read_exact(&mut buf)?;
+
+        if !verify_signature(&hook, signature.as_deref(), &buf) {
+            req.into_status_response(401)?
+                .write_all("Invalid signature".as_bytes())?;
+            return Ok(());
+        }
+
+        let Ok(payload) = serde_json::from_slice::<serde_json::Value>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+
+        match map_status(&hook, &payload) {
+            Some(new_status) => {
+                info!("Hook '{}' set status to {}", hook.name, new_status.as_str());
+                let origin = auth::origin(&mut req, Some(&hook.name));
+                status::attributed(origin, || status::set(hook.source, new_status));
+                req.into_ok_response()?.write_all("OK".as_bytes())?;
+            }
+            None => {
+                // Not an error: most SaaS services send events we don't care about
+                req.into_status_response(202)?
+                    .write_all("Ignored".as_bytes())?;
+            }
+        }
+
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn verify_signature(hook: &HookConfig, signature: Option<&str>, body: &[u8]) -> bool {
+    // A configuration saved before secrets were required may lack one
+    if hook.secret.is_empty() {
+        return false;
+    }
+    let Some(signature) = signature else {
+        return false;
+    };
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    let Some(expected) = http_util::decode_hex(signature) else {
+        return false;
+    };
+    let Ok(mut mac) = HmacSha256::new_from_slice(hook.secret.as_bytes()) else {
+        return false;
+    };
+
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn map_status(hook: &HookConfig, payload: &serde_json::Value) -> Option<Status> {
+    let value = match payload.pointer(&hook.pointer)? {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    hook.mapping
+        .get(&value)
+        .and_then(|name| Status::parse(name))
+}
@@ -0,0 +1,169 @@
+//! Minimal outbound HTTP(S) client used by the notification integrations and
+//! the door sign.
+//!
+//! Servers are verified against the CA bundle built into ESP-IDF, the
+//! common subset of the Mozilla bundle as set in `sdkconfig.defaults`. Hosts
+//! listed in `cert_pins` are verified by the fingerprint of their
+//! certificate instead, and nothing else is accepted from them.
+//!
+//! The handshake runs on the calling thread, so the pin for a request is
+//! kept in a thread-local for the verify callback.
+
+use std::cell::Cell;
+use std::ffi::{c_int, c_void};
+
+use embedded_svc::http::client::Client;
+use embedded_svc::http::Method;
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::client::{Configuration as HttpClientConfiguration, EspHttpConnection};
+use esp_idf_svc::sys;
+use sha2::{Digest, Sha256};
+
+use crate::config::{self, CertPin};
+
+// Give up on unresponsive servers instead of stalling the notification thread
+const TIMEOUT_MS: u64 = 10_000;
+
+thread_local! {
+    // Fingerprint the server of the current request must present
+    static PIN: Cell<Option<[u8; 32]>> = const { Cell::new(None) };
+}
+
+// Opens a connection for `url`, pinned if its host is in `cert_pins`
+fn connect(url: &str) -> anyhow::Result<EspHttpConnection> {
+    let pin = CertPin::find(&config::get().cert_pins, url).and_then(CertPin::fingerprint);
+    PIN.with(|current| current.set(pin));
+
+    let attach = if pin.is_some() {
+        attach_pinned
+    } else {
+        sys::esp_crt_bundle_attach
+    };
+    Ok(EspHttpConnection::new(&HttpClientConfiguration {
+        timeout: Some(std::time::Duration::from_millis(TIMEOUT_MS)),
+        crt_bundle_attach: Some(attach),
+        ..Default::default()
+    })?)
+}
+
+// Attaches the bundle, which also sets up a placeholder CA chain so mbedTLS
+// asks for verification, then replaces its verify callback with the pin's
+unsafe extern "C" fn attach_pinned(conf: *mut c_void) -> sys::esp_err_t {
+    // SAFETY: `conf` is the mbedTLS configuration esp-tls is setting up
+    unsafe {
+        let err = sys::esp_crt_bundle_attach(conf);
+        if err != sys::ESP_OK {
+            return err;
+        }
+        sys::mbedtls_ssl_conf_verify(conf.cast(), Some(verify_pin), std::ptr::null_mut());
+    }
+    sys::ESP_OK
+}
+
+// Called for each certificate of the chain, the server's own last at depth
+// 0. Only that one decides, by its fingerprint.
+unsafe extern "C" fn verify_pin(
+    _ctx: *mut c_void,
+    crt: *mut sys::mbedtls_x509_crt,
+    depth: c_int,
+    flags: *mut u32,
+) -> c_int {
+    // SAFETY: mbedTLS passes a parsed certificate, whose raw DER stays valid
+    // during the call, and its verification flags
+    unsafe {
+        if depth != 0 {
+            *flags = 0;
+            return 0;
+        }
+        let der = std::slice::from_raw_parts((*crt).raw.p, (*crt).raw.len);
+        let fingerprint: [u8; 32] = Sha256::digest(der).into();
+        *flags = if PIN.with(Cell::get) == Some(fingerprint) {
+            0
+        } else {
+            sys::MBEDTLS_X509_BADCERT_NOT_TRUSTED
+        };
+    }
+    0
+}
+
+/// Sends a request with an optional body and returns the HTTP status code.
+pub fn send(
+    method: Method,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+) -> anyhow::Result<u16> {
+    let mut client = Client::wrap(connect(url)?);
+
+    let content_len = body.map_or(0, |body| body.len()).to_string();
+    let mut all_headers = headers.to_vec();
+    all_headers.push(("Content-Length", &content_len));
+
+    let mut request = client.request(method, url, &all_headers)?;
+    if let Some(body) = body {
+        request.write_all(body)?;
+    }
+    request.flush()?;
+
+    let response = request.submit()?;
+    Ok(response.status())
+}
+
+/// Fetches a URL and returns the body, failing on any non-2xx response or a
+/// body longer than `max_len`.
+pub fn get(url: &str, max_len: usize) -> anyhow::Result<Vec<u8>> {
+    let mut client = Client::wrap(connect(url)?);
+
+    let mut response = client.get(url)?.submit()?;
+    let status = response.status();
+    if !(200..300).contains(&status) {
+        anyhow::bail!("{} returned HTTP {}", url, status);
+    }
+
+    let mut body = Vec::new();
+    let mut buf = [0; 256];
+    loop {
+        let len = response.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        if body.len() + len > max_len {
+            anyhow::bail!("{} returned more than {} bytes", url, max_len);
+        }
+        body.extend_from_slice(&buf[..len]);
+    }
+    Ok(body)
+}
+
+/// Sends a JSON body and fails on any non-2xx response.
+pub fn send_json(
+    method: Method,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &serde_json::Value,
+) -> anyhow::Result<()> {
+    let body = serde_json::to_vec(body)?;
+    let mut all_headers = headers.to_vec();
+    all_headers.push(("Content-Type", "application/json"));
+
+    let status = send(method, url, &all_headers, Some(&body))?;
+    if !(200..300).contains(&status) {
+        anyhow::bail!("{} returned HTTP {}", url, status);
+    }
+
+    Ok(())
+}
+
+/// Percent-encodes a string for use as a single URL path segment.
+pub fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,413 @@
+//! Small helpers for the HTTP handlers.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use busier_core::multipart::{self, Event, Parser};
+use embedded_svc::http::server::Request;
+use embedded_svc::http::{Headers, Method};
+use esp_idf_svc::handle::RawHandle;
+use esp_idf_svc::http::server::{Configuration, EspHttpConnection, EspHttpServer};
+use esp_idf_svc::sys;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::auth;
+use crate::config;
+use crate::errors;
+use crate::metrics;
+
+// Room for every route of a server, with some to spare
+const MAX_URI_HANDLERS: usize = 64;
+// Bytes of a streamed body held before they are sent
+const CHUNK_LEN: usize = 1024;
+// Bytes of a request body read at a time
+const READ_LEN: usize = 512;
+
+// The registered routes: the server's key, the path and the method
+static ROUTES: Mutex<Vec<(usize, String, Method)>> = Mutex::new(Vec::new());
+
+/// The web server settings from `http` in the configuration, shared by
+/// the HTTP and HTTPS listeners.
+pub fn server_configuration() -> Configuration {
+    let http = config::get().http;
+    Configuration {
+        stack_size: http.stack_size as usize,
+        max_open_sockets: http.max_open_sockets.into(),
+        session_timeout: Duration::from_secs(http.session_timeout_secs.into()),
+        lru_purge_enable: http.lru_purge,
+        max_uri_handlers: MAX_URI_HANDLERS,
+        uri_match_wildcard: true,
+        ..Default::default()
+    }
+}
+
+/// Route registration with the device's error handling.
+pub trait Routes {
+    /// Registers a handler; its failures are answered by [`errors::catch`],
+    /// the requests with an API token are metered by [`auth::metered`] and
+    /// its duration is counted by [`metrics::timed`].
+    fn route<F>(&mut self, uri: &str, method: Method, handler: F) -> anyhow::Result<&mut Self>
+    where
+        F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static;
+}
+
+impl Routes for EspHttpServer<'static> {
+    fn route<F>(&mut self, uri: &str, method: Method, handler: F) -> anyhow::Result<&mut Self>
+    where
+        F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static,
+    {
+        let handler = metrics::timed(uri, method, errors::catch(auth::metered(handler)));
+        self.fn_handler::<anyhow::Error, _>(uri, method, handler)?;
+        record(self, uri, method);
+        Ok(self)
+    }
+}
+
+/// Notes a route registered without [`Routes::route`], for [`methods`].
+pub fn record(server: &EspHttpServer<'static>, uri: &str, method: Method) {
+    ROUTES
+        .lock()
+        .unwrap()
+        .push((server_key(server), uri.to_string(), method));
+}
+
+/// Tells the servers apart in [`methods`]. The servers live as long as the
+/// firmware and stay put, so their address will do.
+pub fn server_key(server: &EspHttpServer<'static>) -> usize {
+    server as *const EspHttpServer as usize
+}
+
+/// The methods registered on a server for a path, without query string.
+/// Wildcard routes count for the paths they match, as in the server.
+pub fn methods(server_key: usize, path: &str) -> Vec<Method> {
+    let mut methods = Vec::new();
+    for (key, uri, method) in ROUTES.lock().unwrap().iter() {
+        let matches = match uri.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == uri,
+        };
+        if *key == server_key && matches && !methods.contains(method) {
+            methods.push(*method);
+        }
+    }
+    methods
+}
+
+/// The address of the client that sent a request. IPv4 clients of a
+/// dual-stack server come as mapped IPv6 addresses and are returned as IPv4.
+pub fn peer_ip(req: &mut Request<&mut EspHttpConnection>) -> Option<IpAddr> {
+    let raw = req.connection().raw_connection().ok()?.handle();
+    // SAFETY: the request is valid while its handler runs
+    let fd = unsafe { sys::httpd_req_to_sockfd(raw) };
+
+    // Room for either address family
+    // SAFETY: all zeroes is a valid socket address
+    let mut addr: sys::sockaddr_in6 = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<sys::sockaddr_in6>() as sys::socklen_t;
+    // SAFETY: addr and len describe a buffer of len bytes
+    let found = unsafe {
+        sys::lwip_getpeername(fd, (&mut addr as *mut sys::sockaddr_in6).cast(), &mut len)
+    } == 0;
+    if !found {
+        return None;
+    }
+
+    match u32::from(addr.sin6_family) {
+        sys::AF_INET => {
+            // SAFETY: the family says the buffer holds an IPv4 address
+            let addr = unsafe { &*(&addr as *const sys::sockaddr_in6).cast::<sys::sockaddr_in>() };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Some(IpAddr::V4(ip))
+        }
+        sys::AF_INET6 => {
+            // SAFETY: both views of the address are plain bytes
+            let ip = Ipv6Addr::from(unsafe { addr.sin6_addr.un.u8_addr });
+            Some(ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4))
+        }
+        _ => None,
+    }
+}
+
+/// The name of a method, as in an `Allow` header.
+pub fn method_name(method: Method) -> &'static str {
+    match method {
+        Method::Get => "GET",
+        Method::Head => "HEAD",
+        Method::Post => "POST",
+        Method::Put => "PUT",
+        Method::Delete => "DELETE",
+        Method::Patch => "PATCH",
+        Method::Options => "OPTIONS",
+        _ => "",
+    }
+}
+
+/// Streams a response body in chunks, so a large one never sits in RAM
+/// whole. Writes are collected up to [`CHUNK_LEN`] bytes, as serializers
+/// write a few bytes at a time and every chunk has a header on the wire.
+/// A response without `Content-Length` is sent chunked by the server.
+///
+/// Implements [`std::io::Write`] for `serde_json::to_writer` and `write!`;
+/// call [`Chunked::finish`] to send the rest.
+pub struct Chunked<W: embedded_svc::io::Write> {
+    response: W,
+    buf: Vec<u8>,
+}
+
+impl<W: embedded_svc::io::Write> Chunked<W> {
+    pub fn new(response: W) -> Self {
+        Self {
+            response,
+            buf: Vec::with_capacity(CHUNK_LEN),
+        }
+    }
+
+    /// Sends what is left of the body.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        std::io::Write::flush(&mut self)
+    }
+}
+
+impl<W: embedded_svc::io::Write> std::io::Write for Chunked<W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_LEN {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.response
+            .write_all(&self.buf)
+            .map_err(|e| std::io::Error::other(format!("{:?}", e)))?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// Reads an uploaded file of at most `max_len` bytes: the raw body, or the
+/// first file of a `multipart/form-data` body as a browser `<form>` sends
+/// it.
+pub fn read_upload<R>(req: &mut R, max_len: usize) -> anyhow::Result<Vec<u8>>
+where
+    R: embedded_svc::io::Read + Headers,
+{
+    let mut parser = req
+        .content_type()
+        .and_then(multipart::boundary)
+        .map(Parser::new);
+    let mut file = Vec::new();
+    // Whether the file's part is the current one, and whether it is over
+    let mut in_file = false;
+    let mut file_done = false;
+
+    let mut buf = [0; READ_LEN];
+    loop {
+        let len = req
+            .read(&mut buf)
+            .map_err(|e| anyhow::anyhow!("Failed to read the body: {:?}", e))?;
+        if len == 0 {
+            break;
+        }
+        let Some(parser) = parser.as_mut() else {
+            file.extend_from_slice(&buf[..len]);
+            anyhow::ensure!(file.len() <= max_len, "file over {} bytes", max_len);
+            continue;
+        };
+        parser
+            .feed(&buf[..len], |event| match event {
+                Event::Part(part) => {
+                    file_done |= in_file;
+                    in_file = !file_done && part.filename.is_some();
+                }
+                Event::Data(data) if in_file => file.extend_from_slice(data),
+                Event::Data(_) => {}
+            })
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        anyhow::ensure!(file.len() <= max_len, "file over {} bytes", max_len);
+    }
+
+    if let Some(parser) = parser {
+        parser.finish().map_err(|e| anyhow::anyhow!("{}", e))?;
+        anyhow::ensure!(in_file || file_done, "no file in the form");
+    }
+    Ok(file)
+}
+
+/// Parses a request body as JSON or, with `Content-Type:
+/// application/x-www-form-urlencoded`, as form fields such as
+/// `status=dnd&minutes=30`. JSON comes first either way, as `curl -d` sends
+/// JSON under the form content type.
+pub fn parse_body<T: DeserializeOwned>(content_type: Option<&str>, body: &[u8]) -> Option<T> {
+    if let Ok(parsed) = serde_json::from_slice(body) {
+        return Some(parsed);
+    }
+    let is_form = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|kind| kind.trim() == "application/x-www-form-urlencoded");
+    if !is_form {
+        return None;
+    }
+
+    // Values that read as numbers or booleans are taken as such, unless the
+    // target wants text there
+    let form = std::str::from_utf8(body).ok()?;
+    serde_json::from_value(form_fields(form, true))
+        .or_else(|_| serde_json::from_value(form_fields(form, false)))
+        .ok()
+}
+
+fn form_fields(form: &str, typed: bool) -> Value {
+    let mut fields = Map::new();
+    for pair in form.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = decode_component(value);
+        let value = match value.parse::<i64>() {
+            Ok(number) if typed => Value::from(number),
+            _ if typed && (value == "true" || value == "false") => Value::Bool(value == "true"),
+            _ => Value::String(value),
+        };
+        fields.insert(decode_component(key), value);
+    }
+    Value::Object(fields)
+}
+
+/// The one of `offers` the `Accept` header rates highest, preferring the
+/// earlier on a tie. Without a header, or with none acceptable, the first.
+pub fn negotiate<'a>(accept: Option<&str>, offers: &[&'a str]) -> &'a str {
+    let Some(accept) = accept else {
+        return offers[0];
+    };
+    let mut best = (offers[0], 0.0);
+    for offer in offers {
+        let quality = quality(accept, offer);
+        if quality > best.1 {
+            best = (offer, quality);
+        }
+    }
+    best.0
+}
+
+// The quality of the most specific range in `accept` that covers `offer`
+fn quality(accept: &str, offer: &str) -> f32 {
+    let mut best: Option<(u8, f32)> = None;
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let media = params.next().unwrap_or_default().trim();
+        let specificity = if media.eq_ignore_ascii_case(offer) {
+            2
+        } else if media
+            .strip_suffix("/*")
+            .is_some_and(|kind| offer.split('/').next() == Some(kind))
+        {
+            1
+        } else if media == "*/*" {
+            0
+        } else {
+            continue;
+        };
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        if best.is_none_or(|(most, _)| specificity > most) {
+            best = Some((specificity, quality));
+        }
+    }
+    best.map_or(0.0, |(_, quality)| quality)
+}
+
+/// The entity tag of a status version, as sent in `ETag`.
+pub fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Whether an `If-Match` header matches the entity tag `etag`: `*`, or
+/// a list of tags with that one among them. Weak tags count as well, as
+/// some proxies weaken the ones they pass on.
+pub fn if_match(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// Returns the decoded value of a query string parameter.
+pub fn query_param(uri: &str, name: &str) -> Option<String> {
+    let (_, query) = uri.split_once('?')?;
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (decode_component(key) == name).then(|| decode_component(value))
+    })
+}
+
+/// Decodes a hex string such as a signature; None unless every pair of
+/// characters is a byte.
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Decodes base64 in the standard alphabet, as browsers send credentials and
+/// ESPHome writes keys; None on any other character.
+pub fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for byte in encoded.trim_end_matches('=').bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | u32::from(value);
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+/// Decodes a percent-encoded URL component, treating `+` as a space.
+pub fn decode_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,352 @@
+//! HUB75 RGB LED matrix panel.
+//!
+//! Drives a 64x32 panel with 1/16 scan as another display panel, with
+//! coloured status screens: green FREE, red BUSY and yellow AWAY in large
+//! letters. HUB75 panels keep no image, so one row pair is lit at a time
+//! and the whole panel has to be redrawn continuously. The parallel LCD
+//! peripheral does that by DMA, I2S in LCD mode on the ESP32 and LCD_CAM on
+//! the S3: every panel signal but the clock is a line of its 16-bit bus, the
+//! clock is its write strobe, and a prepared frame with the pixels, row
+//! addresses, latch and output enable of all rows is sent over and over. The
+//! CPU only prepares a new frame when the image or the brightness changes.
+//!
+//! Each colour channel is either off or on, which is all status screens
+//! need, so the panel needs no binary code modulation and one pass per frame.
+//! Brightness is set by how long each row stays lit.
+
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+use embedded_graphics::{
+    mono_font::MonoTextStyle,
+    pixelcolor::Rgb565,
+    prelude::*,
+    text::{Alignment, Text},
+};
+use esp_idf_svc::hal::gpio::{AnyOutputPin, Pin};
+use esp_idf_svc::sys;
+use log::warn;
+
+use crate::config::{self, DisplayLayout};
+use crate::display::{self, Frame, Panel};
+use crate::i18n::{self, FontSize};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+const SCAN_ROWS: usize = HEIGHT / 2;
+
+const REFRESH_STACK_SIZE: usize = 4096;
+
+// Lines of the bus from the lowest: four unused ones, then R1, G1, B1, R2,
+// G2, B2, A, B, C, D, LAT and OE
+const BUS_WIDTH: usize = 16;
+const UNUSED_LINES: usize = 4;
+const COLOR_SHIFT: u32 = 4;
+const ADDRESS_SHIFT: u32 = 10;
+const LAT: u16 = 1 << 14;
+const OE: u16 = 1 << 15;
+
+// Each row pair is shifted in while dark, then lit for `hub75.brightness`
+// percent of the words that follow
+const LIT_WORDS: usize = 192;
+const ROW_WORDS: usize = WIDTH + LIT_WORDS;
+const FRAME_WORDS: usize = SCAN_ROWS * ROW_WORDS;
+// About 100 µs per row pair, or 600 frames a second
+const PCLK_HZ: u32 = 2_500_000;
+// Frames queued ahead, so that a busy CPU does not leave the panel dark
+const QUEUE_DEPTH: usize = 4;
+
+// The bus has a D/C line, which the panel has no use for. The S3 connects
+// the clock after it, so the two can share a pin. The ESP32 drives it as a
+// plain GPIO, so it goes to GPIO20, which its modules do not bring out.
+#[cfg(esp32)]
+const DC_GPIO: Option<i32> = Some(20);
+#[cfg(esp32s3)]
+const DC_GPIO: Option<i32> = None;
+
+const RED: u8 = 0b001;
+const GREEN: u8 = 0b010;
+const BLUE: u8 = 0b100;
+
+// One colour per pixel as RED | GREEN | BLUE bits
+type Pixels = [[u8; WIDTH]; HEIGHT];
+
+static PIXELS: Mutex<Pixels> = Mutex::new([[0; WIDTH]; HEIGHT]);
+// In percent; set from the configuration so the refresh never has to clone it
+static BRIGHTNESS: AtomicU8 = AtomicU8::new(0);
+// Set when the pixels or the brightness change, so the frame is prepared again
+static CHANGED: AtomicBool = AtomicBool::new(true);
+
+/// The panel's pins. The upper half of the panel is fed through R1, G1 and
+/// B1, the lower half through R2, G2 and B2.
+pub struct Pins {
+    pub r1: AnyOutputPin,
+    pub g1: AnyOutputPin,
+    pub b1: AnyOutputPin,
+    pub r2: AnyOutputPin,
+    pub g2: AnyOutputPin,
+    pub b2: AnyOutputPin,
+    pub a: AnyOutputPin,
+    pub b: AnyOutputPin,
+    pub c: AnyOutputPin,
+    pub d: AnyOutputPin,
+    pub clk: AnyOutputPin,
+    pub lat: AnyOutputPin,
+    pub oe: AnyOutputPin,
+}
+
+/// Sets how long each row stays lit, in percent. Called by
+/// [`crate::config::set`] whenever the configuration changes.
+pub fn set_brightness(percent: u8) {
+    BRIGHTNESS.store(percent.min(100), Ordering::Relaxed);
+    CHANGED.store(true, Ordering::Relaxed);
+}
+
+/// The panel as a display panel.
+pub struct Hub75 {
+    pins: Option<Pins>,
+    pixels: Box<Pixels>,
+}
+
+impl Hub75 {
+    pub fn new(pins: Pins) -> Self {
+        Self {
+            pins: Some(pins),
+            pixels: Box::new([[0; WIDTH]; HEIGHT]),
+        }
+    }
+
+    fn publish(&mut self) {
+        *PIXELS.lock().unwrap() = *self.pixels;
+        CHANGED.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Panel for Hub75 {
+    fn init_panel(&mut self) -> anyhow::Result<()> {
+        let pins = self
+            .pins
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("HUB75 panel already started"))?;
+        let refresh = Refresh::new(pins)?;
+        set_brightness(config::get().hub75.brightness);
+
+        std::thread::Builder::new()
+            .name("hub75".into())
+            .stack_size(REFRESH_STACK_SIZE)
+            .spawn(move || refresh.run())?;
+
+        Ok(())
+    }
+
+    fn show(&mut self, layout: DisplayLayout, frame: &Frame) -> anyhow::Result<()> {
+        self.clear(Rgb565::BLACK).unwrap();
+
+        let color = display::headline_color(frame);
+        let headline_y = match layout {
+            DisplayLayout::Detail => 18,
+            DisplayLayout::Status => 23,
+        };
+        // Longer words in other languages get a smaller font
+        let headline = display::frame_headline(frame);
+        let font = [FontSize::Large, FontSize::Small, FontSize::Tiny]
+            .into_iter()
+            .map(i18n::font)
+            .find(|font| headline.chars().count() * font.character_size.width as usize <= WIDTH)
+            .unwrap_or(i18n::font(FontSize::Tiny));
+        Text::with_alignment(
+            headline,
+            Point::new(WIDTH as i32 / 2, headline_y),
+            MonoTextStyle::new(font, color),
+            Alignment::Center,
+        )
+        .draw(self)
+        .unwrap();
+
+        if layout == DisplayLayout::Detail {
+            let font = i18n::font(FontSize::Tiny);
+            Text::with_alignment(
+                display::fit(&frame.detail, font, WIDTH as u32),
+                Point::new(WIDTH as i32 / 2, 29),
+                MonoTextStyle::new(font, Rgb565::WHITE),
+                Alignment::Center,
+            )
+            .draw(self)
+            .unwrap();
+        }
+
+        self.publish();
+        Ok(())
+    }
+
+    fn message(&mut self, text: &str) -> anyhow::Result<()> {
+        self.clear(Rgb565::BLACK).unwrap();
+        Text::new(
+            text,
+            Point::new(0, 6),
+            MonoTextStyle::new(i18n::font(FontSize::Tiny), Rgb565::WHITE),
+        )
+        .draw(self)
+        .unwrap();
+        self.publish();
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Hub75 {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Hub75 {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if let Some(pixel) = self.pixels.get_mut(y).and_then(|row| row.get_mut(x)) {
+                // Each channel is on from half intensity up
+                *pixel = u8::from(color.r() > 15) * RED
+                    | u8::from(color.g() > 31) * GREEN
+                    | u8::from(color.b() > 15) * BLUE;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Keeps the bus fed with frames
+struct Refresh {
+    io: sys::esp_lcd_panel_io_handle_t,
+    // One is sent while the other is prepared
+    frames: [&'static mut [u16]; 2],
+    shown: usize,
+}
+
+// SAFETY: only the refresh thread uses the handle and the frames
+unsafe impl Send for Refresh {}
+
+impl Refresh {
+    fn new(pins: Pins) -> anyhow::Result<Self> {
+        let clk = pins.clk.pin();
+        let used = [
+            &pins.r1, &pins.g1, &pins.b1, &pins.r2, &pins.g2, &pins.b2, &pins.a, &pins.b, &pins.c,
+            &pins.d, &pins.lat, &pins.oe,
+        ]
+        .map(|pin| pin.pin());
+
+        let mut bus_config = sys::esp_lcd_i80_bus_config_t {
+            dc_gpio_num: DC_GPIO.unwrap_or(clk),
+            wr_gpio_num: clk,
+            clk_src: sys::soc_periph_lcd_clk_src_t_LCD_CLK_SRC_DEFAULT,
+            bus_width: BUS_WIDTH,
+            max_transfer_bytes: FRAME_WORDS * std::mem::size_of::<u16>(),
+            ..Default::default()
+        };
+        // Every line needs a pin. The unused ones repeat the first used ones,
+        // which are connected after them and so keep their pins.
+        let lines = used[..UNUSED_LINES].iter().chain(&used);
+        for (line, gpio) in bus_config.data_gpio_nums.iter_mut().zip(lines) {
+            *line = *gpio;
+        }
+        let io_config = sys::esp_lcd_panel_io_i80_config_t {
+            cs_gpio_num: -1,
+            pclk_hz: PCLK_HZ,
+            trans_queue_depth: QUEUE_DEPTH,
+            lcd_cmd_bits: 8,
+            lcd_param_bits: 8,
+            ..Default::default()
+        };
+
+        let mut bus = std::ptr::null_mut();
+        let mut io = std::ptr::null_mut();
+        // SAFETY: the driver writes the handles, which are then kept for good
+        unsafe {
+            sys::esp!(sys::esp_lcd_new_i80_bus(&bus_config, &mut bus))?;
+            sys::esp!(sys::esp_lcd_new_panel_io_i80(bus, &io_config, &mut io))?;
+        }
+
+        Ok(Self {
+            io,
+            frames: [dma_frame()?, dma_frame()?],
+            shown: 0,
+        })
+    }
+
+    fn run(mut self) {
+        // Times the shown frame was queued since it was prepared. Once it
+        // filled the queue, the other frame is no longer being sent.
+        let mut queued = QUEUE_DEPTH;
+        loop {
+            if queued == QUEUE_DEPTH && CHANGED.swap(false, Ordering::Relaxed) {
+                self.shown ^= 1;
+                encode(
+                    &PIXELS.lock().unwrap(),
+                    BRIGHTNESS.load(Ordering::Relaxed),
+                    self.frames[self.shown],
+                );
+                queued = 0;
+            }
+
+            let frame = &self.frames[self.shown];
+            // SAFETY: the frame lives for good and is not written while
+            // queued; this waits while the queue is full
+            let sent = sys::esp!(unsafe {
+                sys::esp_lcd_panel_io_tx_color(
+                    self.io,
+                    -1,
+                    frame.as_ptr().cast(),
+                    frame.len() * std::mem::size_of::<u16>(),
+                )
+            });
+            if let Err(e) = sent {
+                warn!("HUB75 refresh stopped: {:?}", e);
+                return;
+            }
+            queued = (queued + 1).min(QUEUE_DEPTH);
+        }
+    }
+}
+
+// Allocates a frame the DMA can read, kept for good
+fn dma_frame() -> anyhow::Result<&'static mut [u16]> {
+    // SAFETY: zeroed memory of the right size and alignment, never freed
+    unsafe {
+        let frame =
+            sys::heap_caps_calloc(FRAME_WORDS, std::mem::size_of::<u16>(), sys::MALLOC_CAP_DMA);
+        if frame.is_null() {
+            anyhow::bail!("No DMA memory for the HUB75 frames");
+        }
+        Ok(std::slice::from_raw_parts_mut(frame.cast(), FRAME_WORDS))
+    }
+}
+
+// Prepares the bus words of a frame
+fn encode(pixels: &Pixels, brightness: u8, frame: &mut [u16]) {
+    let lit = (LIT_WORDS - 1) * usize::from(brightness) / 100;
+    for (row, words) in frame.chunks_exact_mut(ROW_WORDS).enumerate() {
+        let address = (row as u16) << ADDRESS_SHIFT;
+        let (shifted, rest) = words.split_at_mut(WIDTH);
+
+        // Switch rows while dark. The latch is open during the last clock,
+        // so it takes the row once fully shifted in.
+        for (x, word) in shifted.iter_mut().enumerate() {
+            let pair = pixels[row][x] | pixels[row + SCAN_ROWS][x] << 3;
+            *word = OE | address | u16::from(pair) << COLOR_SHIFT;
+        }
+        shifted[WIDTH - 1] |= LAT;
+
+        // The last word stays dark, as does the gap between frames
+        for (i, word) in rest.iter_mut().enumerate() {
+            *word = if i < lit { address } else { OE | address };
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Philips Hue light mirroring the status.
+//!
+//! Uses the bridge's local API to give a light or group the color of the
+//! status in `styles`, or turn it off for a status without `light`: red
+//! while busy, green while free and off while away by default. Create an application key once with
+//! `POST http://<bridge>/api {"devicetype":"busier"}` after pressing the
+//! bridge's link button, then store it in `hue.key`.
+
+use embedded_svc::http::Method;
+use serde_json::json;
+
+use crate::config::{HueConfig, StatusStyle};
+use crate::http_client;
+use crate::mirror;
+use crate::output;
+use crate::status::Status;
+
+const MAX_BRIGHTNESS: u8 = 254;
+
+/// Spawns the thread that keeps the light in sync with the status.
+pub fn start() -> anyhow::Result<()> {
+    mirror::start(
+        "hue",
+        |config| {
+            let hue = config.hue;
+            (hue.enabled && !hue.bridge.is_empty() && !hue.key.is_empty()).then_some(hue)
+        },
+        apply,
+    )
+}
+
+fn apply(hue: &HueConfig, _status: Status, style: &StatusStyle) -> anyhow::Result<()> {
+    let (kind, state) = if hue.group {
+        ("groups", "action")
+    } else {
+        ("lights", "state")
+    };
+    let url = format!(
+        "http://{}/api/{}/{}/{}/{}",
+        hue.bridge,
+        http_client::encode_path_segment(&hue.key),
+        kind,
+        http_client::encode_path_segment(&hue.id),
+        state
+    );
+
+    let body = if style.light {
+        json!({ "on": true, "xy": xy(output::color(style)), "bri": MAX_BRIGHTNESS })
+    } else {
+        json!({ "on": false })
+    };
+
+    http_client::send_json(Method::Put, &url, &[], &body)
+}
+
+// CIE xy coordinates of an sRGB color, as in the Hue developer
+// documentation; the bridge maps them into the bulb's gamut
+fn xy(rgb: [u8; 3]) -> [f32; 2] {
+    let [r, g, b] = rgb.map(|channel| {
+        let c = f32::from(channel) / 255.0;
+        if c > 0.04045 {
+            ((c + 0.055) / 1.055).powf(2.4)
+        } else {
+            c / 12.92
+        }
+    });
+    let x = r * 0.649_926 + g * 0.103_455 + b * 0.197_109;
+    let y = r * 0.234_327 + g * 0.743_075 + b * 0.022_598;
+    let z = g * 0.053_077 + b * 1.035_763;
+    let sum = x + y + z;
+    if sum == 0.0 {
+        // Black has no chromaticity; use the white point
+        return [0.3227, 0.329];
+    }
+    [x / sum, y / sum]
+}
@@ -0,0 +1,154 @@
+//! Philips Hue bridge emulation for Alexa.
+//!
+//! Echo devices discover Hue bridges over SSDP and control their lights
+//! through the local REST API. With `alexa.enabled` set, the SSDP responder
+//! presents the device as a bridge and the routes below expose a single
+//! dimmable light whose on state is Do Not Disturb, so "Alexa, turn on Do Not
+//! Disturb" works without any cloud skill.
+
+use embedded_svc::http::Method;
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::server::EspHttpServer;
+use log::info;
+use serde_json::{json, Value};
+
+use crate::config;
+use crate::device;
+use crate::errors;
+use crate::http_util::Routes;
+use crate::status::{self, Source, Status};
+
+const API_PREFIX: &str = "/api/";
+const LIGHT_ID: &str = "1";
+const MAX_BODY_LEN: usize = 256;
+const DEFAULT_LIGHT_NAME: &str = "Do Not Disturb";
+
+pub fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    // Route for user registration; any name is accepted
+    server.route("/api", Method::Post, |req| {
+        if !config::get().alexa.enabled {
+            return errors::not_found(req);
+        }
+
+        let body = json!([{ "success": { "username": username() } }]);
+        let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+        resp.write_all(body.to_string().as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Routes for reading lights. Registered after the device's own API, so
+    // those take precedence.
+    server.route("/api/*", Method::Get, |req| {
+        if !config::get().alexa.enabled {
+            return errors::not_found(req);
+        }
+
+        let path = api_path(req.uri());
+        let body = match path.as_slice() {
+            [_user] => json!({ "lights": { LIGHT_ID: light() } }),
+            [_user, "lights"] => json!({ LIGHT_ID: light() }),
+            [_user, "lights", LIGHT_ID] => light(),
+            _ => json!([{ "error": { "type": 3, "description": "resource not available" } }]),
+        };
+
+        let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+        resp.write_all(body.to_string().as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for switching the light
+    server.route("/api/*", Method::Put, |mut req| {
+        if !config::get().alexa.enabled {
+            return errors::not_found(req);
+        }
+
+        let len = req.content_len().unwrap_or(0) as usize;
+        if len > MAX_BODY_LEN {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        }
+        let mut buf = vec![0; len];
+        req.read_exact(&mut buf)?;
+
+        let path = api_path(req.uri());
+        let state: Value = serde_json::from_slice(&buf).unwrap_or_default();
+        let body = match (path.as_slice(), state["on"].as_bool()) {
+            ([_user, "lights", LIGHT_ID, "state"], Some(on)) => {
+                info!(
+                    "Alexa turned Do Not Disturb {}",
+                    if on { "on" } else { "off" }
+                );
+                let new_status = if on { Status::Dnd } else { Status::Free };
+                status::set(Source::Integration, new_status);
+                json!([{ "success": { "/lights/1/state/on": on } }])
+            }
+            ([_user, "lights", LIGHT_ID, "state"], None) => {
+                // Brightness changes are acknowledged but have no effect
+                json!([{ "success": {} }])
+            }
+            _ => json!([{ "error": { "type": 3, "description": "resource not available" } }]),
+        };
+
+        let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+        resp.write_all(body.to_string().as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    Ok(())
+}
+
+/// Bridge id advertised in SSDP responses: the MAC with FFFE in the middle.
+pub fn bridge_id() -> String {
+    let mac = device::mac();
+    format!(
+        "{:02X}{:02X}{:02X}FFFE{:02X}{:02X}{:02X}",
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+    )
+}
+
+// Splits "/api/<user>/lights/1?x" into ["<user>", "lights", "1"]
+fn api_path(uri: &str) -> Vec<&str> {
+    uri.strip_prefix(API_PREFIX)
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+fn username() -> String {
+    format!("busier{}", bridge_id().to_lowercase())
+}
+
+fn light() -> Value {
+    let config = config::get();
+    let name = if config.alexa.name.is_empty() {
+        DEFAULT_LIGHT_NAME
+    } else {
+        &config.alexa.name
+    };
+    let mac = device::mac();
+
+    json!({
+        "state": {
+            "on": status::current() == Status::Dnd,
+            "bri": 254,
+            "alert": "none",
+            "mode": "homeautomation",
+            "reachable": true
+        },
+        "type": "Dimmable light",
+        "name": name,
+        "modelid": "LWB010",
+        "manufacturername": "Philips",
+        "productname": "Hue white lamp",
+        "uniqueid": format!(
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:00:11-0b",
+            mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+        ),
+        "swversion": "1.46.13_r26312"
+    })
+}
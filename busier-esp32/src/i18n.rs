@@ -0,0 +1,565 @@
+//! Translated strings for the web pages and the displays.
+//!
+//! Every string shown to people is looked up by key in one catalog with a
+//! column per language, chosen by `language` in the configuration. The
+//! pages carry `{{key}}` placeholders that are filled in when served, and
+//! the panels draw with fonts that have the letters of the language.
+
+use std::borrow::Cow;
+use std::fmt::Display;
+
+use embedded_graphics::mono_font::{iso_8859_1 as latin, iso_8859_7 as greek, MonoFont};
+
+use busier_core::template;
+
+use crate::config;
+
+pub use busier_core::config::Language;
+
+/// Display font sizes, the same in every language.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontSize {
+    /// 4x6 pixels
+    Tiny,
+    /// 5x8 pixels
+    Narrow,
+    /// 6x10 pixels
+    Small,
+    /// 10x20 pixels
+    Large,
+}
+
+// Key, then the English, German and Greek text. "{}" marks arguments.
+// Page strings end up in JavaScript string literals, so no apostrophes.
+const CATALOG: &[(&str, [&str; 3])] = &[
+    ("lang", ["en", "de", "el"]),
+    // Statuses
+    ("status.free", ["Free", "Frei", "Ελεύθερο"]),
+    (
+        "status.dnd",
+        ["Do Not Disturb", "Bitte nicht stören", "Μην ενοχλείτε"],
+    ),
+    ("status.away", ["Away", "Abwesend", "Εκτός γραφείου"]),
+    ("headline.free", ["FREE", "FREI", "ΕΛΕΥΘΕΡΟ"]),
+    ("headline.dnd", ["BUSY", "BESETZT", "ΑΠΑΣΧΟΛΗΜΕΝΟ"]),
+    ("headline.away", ["AWAY", "ABWESEND", "ΕΚΤΟΣ"]),
+    // Displays
+    (
+        "display.wifi_connected",
+        ["WiFi Connected", "WLAN verbunden", "WiFi συνδέθηκε"],
+    ),
+    (
+        "display.connecting",
+        [
+            "Connecting to WiFi...",
+            "Verbinde mit WLAN...",
+            "Σύνδεση στο WiFi...",
+        ],
+    ),
+    (
+        "display.status",
+        ["Status: {}", "Status: {}", "Κατάσταση: {}"],
+    ),
+    (
+        "display.back_at",
+        [
+            "Back at {} (+{}m)",
+            "Zurück {} (+{}m)",
+            "Πίσω στις {} (+{}')",
+        ],
+    ),
+    (
+        "display.meeting_ends",
+        [
+            "Meeting ends in {} min",
+            "Termin endet in {} Min.",
+            "Η σύσκεψη λήγει σε {} λεπτά",
+        ],
+    ),
+    (
+        "display.transition_ends",
+        ["{} in {} min", "{} in {} Min.", "{} σε {} λεπτά"],
+    ),
+    (
+        "display.meeting_over",
+        ["Meeting over", "Termin vorbei", "Η σύσκεψη έληξε"],
+    ),
+    (
+        "display.battery",
+        ["Battery {}%", "Akku {}%", "Μπαταρία {}%"],
+    ),
+    (
+        "display.battery_low",
+        ["Battery low", "Akku schwach", "Χαμηλή μπαταρία"],
+    ),
+    (
+        "display.door_open",
+        ["Door open", "Tür offen", "Πόρτα ανοιχτή"],
+    ),
+    (
+        "display.door_closed",
+        ["Door closed", "Tür zu", "Πόρτα κλειστή"],
+    ),
+    (
+        "display.visitor_waiting",
+        ["Visitor waiting", "Besuch wartet", "Επισκέπτης περιμένει"],
+    ),
+    (
+        "display.clock_unsynced",
+        ["Clock not synced", "Uhr nicht synchron", "Ρολόι μη συγχρονισμένο"],
+    ),
+    (
+        "display.scan_to_set_up",
+        ["Scan to set up", "Zum Einrichten", "Σάρωση QR"],
+    ),
+    (
+        "display.scan_to_join",
+        ["Scan to join", "Zum Verbinden", "Σάρωση QR"],
+    ),
+    (
+        "display.safe_mode",
+        ["SAFE MODE", "SICHERER MODUS", "ΑΣΦΑΛΗΣ ΛΕΙΤΟΥΡΓΙΑ"],
+    ),
+    (
+        "display.requests",
+        ["Requests: {}", "Anfragen: {}", "Αιτήματα: {}"],
+    ),
+    (
+        "display.requests_temperature",
+        ["Requests: {}  {}°C", "Anfragen: {}  {}°C", "Αιτήματα: {}  {}°C"],
+    ),
+    (
+        "display.temperature",
+        ["Chip {}°C", "Chip {}°C", "Τσιπ {}°C"],
+    ),
+    (
+        "display.too_hot",
+        ["Too hot: {}°C", "Zu heiß: {}°C", "Υπερθέρμανση: {}°C"],
+    ),
+    ("pomodoro.focus", ["Focus", "Fokus", "Εστίαση"]),
+    ("pomodoro.break", ["Break", "Pause", "Διάλειμμα"]),
+    // Notifications
+    (
+        "notify.status_changed",
+        [
+            "Status changed to {}",
+            "Status geändert auf {}",
+            "Η κατάσταση άλλαξε σε {}",
+        ],
+    ),
+    (
+        "notify.user_status_changed",
+        [
+            "{} changed status to {}",
+            "{} hat den Status auf {} geändert",
+            "{} άλλαξε κατάσταση σε {}",
+        ],
+    ),
+    (
+        "notify.knock",
+        [
+            "Someone is knocking",
+            "Jemand klopft an",
+            "Κάποιος χτυπά την πόρτα",
+        ],
+    ),
+    (
+        "notify.doorbell",
+        [
+            "Someone rang the doorbell",
+            "Jemand hat geklingelt",
+            "Κάποιος χτύπησε το κουδούνι",
+        ],
+    ),
+    (
+        "notify.doorbell_urgent",
+        [
+            "Someone rang the doorbell urgently",
+            "Jemand hat dringend geklingelt",
+            "Κάποιος χτύπησε επειγόντως το κουδούνι",
+        ],
+    ),
+    (
+        "notify.message",
+        [
+            "Message from a visitor: {}",
+            "Nachricht von einem Besucher: {}",
+            "Μήνυμα από επισκέπτη: {}",
+        ],
+    ),
+    // Guest page
+    ("web.currently", ["Currently:", "Aktuell:", "Τώρα:"]),
+    (
+        "web.loading",
+        ["Loading...", "Wird geladen...", "Φόρτωση..."],
+    ),
+    ("web.back_at", ["Back at", "Zurück um", "Επιστροφή στις"]),
+    ("web.knock", ["Knock", "Anklopfen", "Χτυπήστε"]),
+    (
+        "web.knock_sent",
+        ["Knock sent", "Angeklopft", "Το χτύπημα στάλθηκε"],
+    ),
+    (
+        "web.leave_message",
+        [
+            "Leave a message",
+            "Nachricht hinterlassen",
+            "Αφήστε ένα μήνυμα",
+        ],
+    ),
+    (
+        "web.send_message",
+        ["Send message", "Nachricht senden", "Αποστολή μηνύματος"],
+    ),
+    (
+        "web.message_sent",
+        ["Message sent", "Nachricht gesendet", "Το μήνυμα στάλθηκε"],
+    ),
+    ("web.admin", ["Admin", "Verwaltung", "Διαχείριση"]),
+    // Admin page
+    (
+        "web.admin_title",
+        [
+            "ESP32 Status Controller",
+            "ESP32-Statussteuerung",
+            "Έλεγχος κατάστασης ESP32",
+        ],
+    ),
+    (
+        "web.current_status",
+        [
+            "Current Status:",
+            "Aktueller Status:",
+            "Τρέχουσα κατάσταση:",
+        ],
+    ),
+    ("web.people", ["People:", "Personen:", "Άτομα:"]),
+    ("web.api_tokens", ["API tokens:", "API-Tokens:", "Διακριτικά API:"]),
+    (
+        "web.token_usage",
+        [
+            "{requests} requests, {throttled} throttled, limit {limit}/min",
+            "{requests} Anfragen, {throttled} gedrosselt, Limit {limit}/min",
+            "{requests} αιτήματα, {throttled} περιορισμένα, όριο {limit}/λεπτό",
+        ],
+    ),
+    ("web.no_limit", ["none", "keins", "κανένα"]),
+    ("web.pomodoro", ["Pomodoro:", "Pomodoro:", "Pomodoro:"]),
+    ("web.stopped", ["Stopped", "Gestoppt", "Σταματημένο"]),
+    (
+        "web.start_pomodoro",
+        ["Start 25/5", "Start 25/5", "Έναρξη 25/5"],
+    ),
+    ("web.stop", ["Stop", "Stopp", "Διακοπή"]),
+    ("web.cycle", ["cycle", "Runde", "κύκλος"]),
+    (
+        "web.last_badge",
+        ["Last badge:", "Letzter Ausweis:", "Τελευταία κάρτα:"],
+    ),
+    ("web.none", ["None", "Keiner", "Καμία"]),
+    ("web.my_badge", ["My badge", "Mein Ausweis", "Η κάρτα μου"]),
+    (
+        "web.guest_card",
+        ["Guest card", "Gästekarte", "Κάρτα επισκέπτη"],
+    ),
+    (
+        "web.ir_remote",
+        ["IR remote:", "IR-Fernbedienung:", "Τηλεχειριστήριο IR:"],
+    ),
+    (
+        "web.learn_button",
+        ["Learn button", "Taste anlernen", "Εκμάθηση κουμπιού"],
+    ),
+    (
+        "web.press_button",
+        [
+            "Press a button...",
+            "Taste drücken...",
+            "Πατήστε ένα κουμπί...",
+        ],
+    ),
+    (
+        "web.ringtones",
+        [
+            "Ringtones (RTTTL, empty to beep):",
+            "Klingeltöne (RTTTL, leer für Piepton):",
+            "Ήχοι (RTTTL, κενό για μπιπ):",
+        ],
+    ),
+    (
+        "web.status_change",
+        ["Status change", "Statuswechsel", "Αλλαγή κατάστασης"],
+    ),
+    ("web.save", ["Save", "Speichern", "Αποθήκευση"]),
+    (
+        "web.https_files",
+        [
+            "HTTPS certificate:",
+            "HTTPS-Zertifikat:",
+            "Πιστοποιητικό HTTPS:",
+        ],
+    ),
+    ("web.certificate", ["Certificate", "Zertifikat", "Πιστοποιητικό"]),
+    (
+        "web.private_key",
+        ["Private key", "Privater Schlüssel", "Ιδιωτικό κλειδί"],
+    ),
+    ("web.client_ca", ["Client CA", "Client-CA", "CA πελατών"]),
+    ("web.upload", ["Upload", "Hochladen", "Μεταφόρτωση"]),
+    (
+        "web.firmware_update",
+        [
+            "Firmware update:",
+            "Firmware-Update:",
+            "Ενημέρωση υλικολογισμικού:",
+        ],
+    ),
+    ("web.firmware_image", ["Image", "Abbild", "Εικόνα"]),
+    (
+        "web.signature",
+        ["Signature (hex)", "Signatur (hex)", "Υπογραφή (hex)"],
+    ),
+    // Setup page
+    (
+        "web.setup_title",
+        ["Busier setup", "Busier einrichten", "Ρύθμιση Busier"],
+    ),
+    (
+        "web.welcome",
+        [
+            "Welcome to Busier",
+            "Willkommen bei Busier",
+            "Καλώς ήρθατε στο Busier",
+        ],
+    ),
+    (
+        "web.setup_intro",
+        [
+            "A few settings before the device can be used.",
+            "Ein paar Einstellungen, bevor das Gerät genutzt werden kann.",
+            "Λίγες ρυθμίσεις πριν χρησιμοποιηθεί η συσκευή.",
+        ],
+    ),
+    (
+        "web.admin_password",
+        [
+            "1. Admin password",
+            "1. Administratorpasswort",
+            "1. Κωδικός διαχειριστή",
+        ],
+    ),
+    (
+        "web.repeat_password",
+        [
+            "Repeat the password",
+            "Passwort wiederholen",
+            "Επαναλάβετε τον κωδικό",
+        ],
+    ),
+    (
+        "web.password_hint",
+        [
+            "Log in on the admin page as \"admin\" with this password.",
+            "Auf der Verwaltungsseite als „admin“ mit diesem Passwort anmelden.",
+            "Συνδεθείτε στη σελίδα διαχείρισης ως «admin» με αυτόν τον κωδικό.",
+        ],
+    ),
+    (
+        "web.device_name",
+        ["2. Device name", "2. Gerätename", "2. Όνομα συσκευής"],
+    ),
+    (
+        "web.device_name_hint",
+        [
+            "Shown to discovery clients and smart home apps.",
+            "Wird bei der Geräteerkennung und in Smart-Home-Apps angezeigt.",
+            "Εμφανίζεται στην ανίχνευση συσκευών και στις εφαρμογές έξυπνου σπιτιού.",
+        ],
+    ),
+    (
+        "web.timezone",
+        ["3. Timezone", "3. Zeitzone", "3. Ζώνη ώρας"],
+    ),
+    (
+        "web.timezone_hint",
+        [
+            "A zone name or POSIX TZ string; working hours and \"back at\" times use it.",
+            "Ein Zonenname oder POSIX-TZ-String; Arbeitszeiten und Rückkehrzeiten richten sich danach.",
+            "Όνομα ζώνης ή συμβολοσειρά POSIX TZ· τη χρησιμοποιούν το ωράριο και οι ώρες επιστροφής.",
+        ],
+    ),
+    ("web.language", ["4. Language", "4. Sprache", "4. Γλώσσα"]),
+    (
+        "web.language_hint",
+        [
+            "Used on the displays and these pages.",
+            "Für die Anzeigen und diese Seiten.",
+            "Για τις οθόνες και αυτές τις σελίδες.",
+        ],
+    ),
+    ("web.board", ["5. Board", "5. Board", "5. Πλακέτα"]),
+    (
+        "web.board_hint",
+        [
+            "Dev boards with a built-in display bring their own pins. Takes effect after a restart.",
+            "Entwicklungsboards mit eingebauter Anzeige bringen ihre eigenen Pins mit. Gilt nach einem Neustart.",
+            "Οι πλακέτες με ενσωματωμένη οθόνη έχουν τις δικές τους ακίδες. Ισχύει μετά από επανεκκίνηση.",
+        ],
+    ),
+    (
+        "web.board_custom",
+        [
+            "Separate modules",
+            "Einzelne Module",
+            "Ξεχωριστά κυκλώματα",
+        ],
+    ),
+    (
+        "web.finish_setup",
+        [
+            "Finish setup",
+            "Einrichtung abschließen",
+            "Ολοκλήρωση ρύθμισης",
+        ],
+    ),
+    (
+        "web.passwords_mismatch",
+        [
+            "The passwords do not match",
+            "Die Passwörter stimmen nicht überein",
+            "Οι κωδικοί δεν ταιριάζουν",
+        ],
+    ),
+    // Safe mode page
+    (
+        "web.safe_mode_title",
+        ["Safe mode", "Abgesicherter Modus", "Ασφαλής λειτουργία"],
+    ),
+    (
+        "web.safe_mode_intro",
+        [
+            "The device crashed several times in a row right after starting, so it started with only this page. Fix the configuration, save it and restart.",
+            "Das Gerät ist mehrmals hintereinander kurz nach dem Start abgestürzt und startete deshalb nur mit dieser Seite. Korrigieren Sie die Konfiguration, speichern Sie sie und starten Sie neu.",
+            "Η συσκευή κατέρρευσε αρκετές φορές στη σειρά αμέσως μετά την εκκίνηση, γι' αυτό ξεκίνησε μόνο με αυτή τη σελίδα. Διορθώστε τις ρυθμίσεις, αποθηκεύστε τις και κάντε επανεκκίνηση.",
+        ],
+    ),
+    (
+        "web.last_reset",
+        ["Last reset", "Letzter Neustart", "Τελευταία επανεκκίνηση"],
+    ),
+    ("web.restart", ["Restart", "Neu starten", "Επανεκκίνηση"]),
+    // Setup access point page
+    ("web.portal_title", ["WiFi setup", "WLAN einrichten", "Ρύθμιση WiFi"]),
+    (
+        "web.portal_intro",
+        [
+            "The device could not join a network. Enter the one it should use; it restarts and connects to it.",
+            "Das Gerät konnte sich mit keinem Netzwerk verbinden. Geben Sie das Netzwerk ein, das es nutzen soll; es startet dann neu und verbindet sich.",
+            "Η συσκευή δεν μπόρεσε να συνδεθεί σε δίκτυο. Εισαγάγετε το δίκτυο που πρέπει να χρησιμοποιεί· θα επανεκκινήσει και θα συνδεθεί σε αυτό.",
+        ],
+    ),
+    ("web.wifi_ssid", ["Network name", "Netzwerkname", "Όνομα δικτύου"]),
+    ("web.wifi_password", ["Password", "Passwort", "Κωδικός"]),
+    (
+        "web.portal_saved",
+        [
+            "Saved, the device restarts and joins the network",
+            "Gespeichert, das Gerät startet neu und verbindet sich",
+            "Αποθηκεύτηκε, η συσκευή επανεκκινεί και συνδέεται",
+        ],
+    ),
+    // Error page
+    (
+        "web.not_found",
+        [
+            "There is no page here",
+            "Diese Seite gibt es nicht",
+            "Η σελίδα δεν υπάρχει",
+        ],
+    ),
+    (
+        "web.server_error",
+        [
+            "Something went wrong",
+            "Etwas ist schiefgelaufen",
+            "Κάτι πήγε στραβά",
+        ],
+    ),
+    (
+        "web.request_id",
+        ["Request ID", "Anfrage-ID", "Αναγνωριστικό αιτήματος"],
+    ),
+    (
+        "web.back_home",
+        [
+            "Back to the status",
+            "Zurück zum Status",
+            "Πίσω στην κατάσταση",
+        ],
+    ),
+];
+
+/// The configured language.
+pub fn language() -> Language {
+    config::get().language
+}
+
+/// Text for `key` in the configured language. Unknown keys come back as
+/// they are, so a missing entry shows up instead of an empty label.
+pub fn text(key: &'static str) -> &'static str {
+    lookup(language(), key).unwrap_or(key)
+}
+
+/// Text for `key` with each "{}" replaced by the next argument.
+pub fn format(key: &'static str, args: &[&dyn Display]) -> String {
+    let mut args = args.iter();
+    let mut formatted = String::new();
+    for (i, part) in text(key).split("{}").enumerate() {
+        if i > 0 {
+            if let Some(arg) = args.next() {
+                formatted.push_str(&arg.to_string());
+            }
+        }
+        formatted.push_str(part);
+    }
+    formatted
+}
+
+/// Fills in the `{{key}}` placeholders of a page. Unknown keys are left in
+/// place.
+pub fn localize(page: &str) -> String {
+    localize_with(page, &[])
+}
+
+/// Like [`localize`], filling in `values` by name before the texts. The
+/// values are HTML-escaped.
+pub fn localize_with(page: &str, values: &[(&str, String)]) -> String {
+    let language = language();
+    template::render(page, |key| {
+        match values.iter().find(|(name, _)| *name == key) {
+            Some((_, value)) => Some(Cow::Owned(template::escape_html(value))),
+            None => lookup(language, key).map(Cow::Borrowed),
+        }
+    })
+}
+
+/// Font of the given size with the letters of the configured language.
+pub fn font(size: FontSize) -> &'static MonoFont<'static> {
+    match (language(), size) {
+        (Language::El, FontSize::Tiny) => &greek::FONT_4X6,
+        (Language::El, FontSize::Narrow) => &greek::FONT_5X8,
+        (Language::El, FontSize::Small) => &greek::FONT_6X10,
+        (Language::El, FontSize::Large) => &greek::FONT_10X20,
+        (_, FontSize::Tiny) => &latin::FONT_4X6,
+        (_, FontSize::Narrow) => &latin::FONT_5X8,
+        (_, FontSize::Small) => &latin::FONT_6X10,
+        (_, FontSize::Large) => &latin::FONT_10X20,
+    }
+}
+
+fn lookup(language: Language, key: &str) -> Option<&'static str> {
+    CATALOG
+        .iter()
+        .find(|(entry, _)| *entry == key)
+        .map(|(_, texts)| texts[language as usize])
+}
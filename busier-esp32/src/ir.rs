@@ -0,0 +1,161 @@
+//! Infrared remote receiver.
+//!
+//! A TSOP38238 demodulates the 38 kHz carrier; the RMT peripheral records
+//! the resulting pulse train, which is decoded as NEC. Codes listed in
+//! `ir.codes` toggle Do Not Disturb. In learn mode the next code received is
+//! added to that list, so any spare remote button can be used.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::hal::delay::BLOCK;
+use esp_idf_svc::hal::gpio::PinState;
+use esp_idf_svc::hal::rmt::{Pulse, Receive, RxRmtDriver};
+use log::{info, warn};
+
+use crate::config;
+use crate::status::{self, Source};
+
+const IR_STACK_SIZE: usize = 4096;
+const LEARN_WINDOW: Duration = Duration::from_secs(30);
+// Remotes that resend the full frame while a button is held
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// NEC timings in microseconds
+const LEADER_MARK: u16 = 9000;
+const LEADER_SPACE: u16 = 4500;
+const BIT_MARK: u16 = 560;
+const ONE_SPACE: u16 = 1690;
+const ZERO_SPACE: u16 = 560;
+
+static LEARN_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+static LAST_CODE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Adds the next code received within 30 seconds to `ir.codes`.
+pub fn start_learning() {
+    *LEARN_UNTIL.lock().unwrap() = Some(Instant::now() + LEARN_WINDOW);
+}
+
+/// Whether learn mode is active.
+pub fn is_learning() -> bool {
+    LEARN_UNTIL
+        .lock()
+        .unwrap()
+        .is_some_and(|until| Instant::now() < until)
+}
+
+/// The most recently received code, known or not.
+pub fn last_code() -> Option<String> {
+    LAST_CODE.lock().unwrap().clone()
+}
+
+/// Spawns the receiver thread. The RMT channel must tick every microsecond.
+pub fn start(mut rx: RxRmtDriver<'static>) -> anyhow::Result<()> {
+    rx.start()?;
+
+    std::thread::Builder::new()
+        .name("ir".into())
+        .stack_size(IR_STACK_SIZE)
+        .spawn(move || {
+            let mut pulses = [(Pulse::zero(), Pulse::zero()); 64];
+            let mut last: Option<(u32, Instant)> = None;
+
+            loop {
+                let len = match rx.receive(&mut pulses, BLOCK) {
+                    Ok(Receive::Read(len)) => len,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        warn!("IR receive failed: {:?}", e);
+                        continue;
+                    }
+                };
+
+                let Some(code) = decode_nec(&pulses[..len]) else {
+                    continue;
+                };
+                if last.is_some_and(|(prev, at)| prev == code && at.elapsed() < DEBOUNCE) {
+                    last = Some((code, Instant::now()));
+                    continue;
+                }
+                last = Some((code, Instant::now()));
+
+                let code = format!("{:08x}", code);
+                if let Err(e) = handle_code(&code) {
+                    warn!("Failed to handle IR code {}: {:?}", code, e);
+                }
+                *LAST_CODE.lock().unwrap() = Some(code);
+            }
+        })?;
+
+    Ok(())
+}
+
+fn handle_code(code: &str) -> anyhow::Result<()> {
+    let mut codes = config::get().ir.codes;
+
+    if is_learning() {
+        *LEARN_UNTIL.lock().unwrap() = None;
+        if !codes.iter().any(|known| known.eq_ignore_ascii_case(code)) {
+            codes.push(code.to_string());
+            config::update(serde_json::json!({ "ir": { "codes": codes } }))?;
+        }
+        info!("Learned IR code {}", code);
+        return Ok(());
+    }
+
+    if codes.iter().any(|known| known.eq_ignore_ascii_case(code)) {
+        let new_status = status::toggle_dnd(Source::Button);
+        info!("IR remote set status to {}", new_status.as_str());
+    } else {
+        info!("Unknown IR code {}", code);
+    }
+
+    Ok(())
+}
+
+// Decodes a full NEC frame into its 32 bits, first received bit in the
+// lowest position. Repeat frames and other protocols yield None.
+fn decode_nec(pulses: &[(Pulse, Pulse)]) -> Option<u32> {
+    // The receiver output is low while the carrier is present
+    let mut marks_and_spaces = pulses
+        .iter()
+        .flat_map(|(a, b)| [a, b])
+        .filter(|pulse| pulse.ticks.ticks() > 0)
+        .map(|pulse| (pulse.pin_state == PinState::Low, pulse.ticks.ticks()));
+
+    let mut expect = |mark: bool| {
+        marks_and_spaces
+            .next()
+            .filter(|(is_mark, _)| *is_mark == mark)
+            .map(|(_, ticks)| ticks)
+    };
+
+    if !near(expect(true)?, LEADER_MARK) || !near(expect(false)?, LEADER_SPACE) {
+        return None;
+    }
+
+    let mut code = 0;
+    for bit in 0..32 {
+        if !near(expect(true)?, BIT_MARK) {
+            return None;
+        }
+        let space = expect(false)?;
+        if near(space, ONE_SPACE) {
+            code |= 1 << bit;
+        } else if !near(space, ZERO_SPACE) {
+            return None;
+        }
+    }
+
+    // The command byte is followed by its complement
+    let command = (code >> 16) as u8;
+    if (code >> 24) as u8 != !command {
+        return None;
+    }
+
+    Some(code)
+}
+
+fn near(actual: u16, expected: u16) -> bool {
+    actual.abs_diff(expected) <= expected / 4
+}
@@ -0,0 +1,207 @@
+//! HD44780 character LCD.
+//!
+//! Drives a 16x2 or 20x4 character LCD through the usual PCF8574 I2C
+//! backpack as another display panel. Both layouts are rendered as plain
+//! text: the detail layout puts the status, timers, address and battery on
+//! one row each, as far as there are rows; the status layout centres the
+//! single status word, or lists one person per row on shared devices.
+//!
+//! The character ROM (A00, the common one) has the German umlauts but no
+//! Greek alphabet, so Greek text is spelled in Latin letters.
+
+use std::time::Duration;
+
+use embedded_hal::i2c::I2c;
+
+use crate::config::{DisplayLayout, LcdConfig};
+use crate::display::{self, Frame, Panel};
+use crate::i18n;
+use crate::status::StatusLabel;
+
+// PCF8574 pins: the control lines on P0 to P3, the data nibble on P4 to P7
+const RS: u8 = 0x01;
+const EN: u8 = 0x04;
+const BACKLIGHT: u8 = 0x08;
+
+const CLEAR: u8 = 0x01;
+const ENTRY_LEFT: u8 = 0x06;
+const DISPLAY_ON: u8 = 0x0C;
+// 4-bit bus, two lines, 5x8 font
+const FUNCTION_SET: u8 = 0x28;
+const SET_DDRAM: u8 = 0x80;
+
+// DDRAM address of each row's first character
+const ROW_OFFSETS: [u8; 4] = [0x00, 0x40, 0x14, 0x54];
+
+// Latin spelling of the Greek letters Alpha to Omega
+const GREEK: [&str; 24] = [
+    "A", "V", "G", "D", "E", "Z", "I", "Th", "I", "K", "L", "M", "N", "X", "O", "P", "R", "S", "T",
+    "Y", "F", "Ch", "Ps", "O",
+];
+
+pub struct Lcd<I> {
+    i2c: I,
+    address: u8,
+    columns: usize,
+    rows: usize,
+}
+
+impl<I: I2c> Lcd<I> {
+    pub fn new(i2c: I, config: &LcdConfig) -> Self {
+        Self {
+            i2c,
+            address: config.address,
+            columns: usize::from(config.columns.clamp(8, 20)),
+            rows: usize::from(config.rows.clamp(1, 4)),
+        }
+    }
+
+    fn write_lines(&mut self, lines: &[String]) -> anyhow::Result<()> {
+        for row in 0..self.rows {
+            let line = lines.get(row).map(String::as_str).unwrap_or_default();
+            // Pad instead of clearing, so the update does not flicker
+            let text: Vec<u8> = rom_codes(line)
+                .into_iter()
+                .chain(std::iter::repeat(b' '))
+                .take(self.columns)
+                .collect();
+
+            self.command(SET_DDRAM | ROW_OFFSETS[row])?;
+            for byte in text {
+                self.write_byte(byte, RS)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, command: u8) -> anyhow::Result<()> {
+        self.write_byte(command, 0)
+    }
+
+    fn write_byte(&mut self, byte: u8, mode: u8) -> anyhow::Result<()> {
+        self.write_nibble(byte & 0xF0, mode)?;
+        self.write_nibble(byte << 4, mode)
+    }
+
+    // The HD44780 latches the nibble on the falling edge of EN
+    fn write_nibble(&mut self, nibble: u8, mode: u8) -> anyhow::Result<()> {
+        let data = nibble | mode | BACKLIGHT;
+        self.i2c
+            .write(self.address, &[data | EN, data])
+            .map_err(|e| anyhow::anyhow!("{:?}", e))
+    }
+
+    fn centre(&self, text: &str) -> String {
+        let margin = self.columns.saturating_sub(text.len()) / 2;
+        format!("{}{}", " ".repeat(margin), text)
+    }
+}
+
+impl<I: I2c> Panel for Lcd<I> {
+    fn init_panel(&mut self) -> anyhow::Result<()> {
+        // Reset into 4-bit mode from whatever mode the controller is in
+        std::thread::sleep(Duration::from_millis(50));
+        for _ in 0..3 {
+            self.write_nibble(0x30, 0)?;
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        self.write_nibble(0x20, 0)?;
+
+        self.command(FUNCTION_SET)?;
+        self.command(DISPLAY_ON)?;
+        self.command(ENTRY_LEFT)?;
+        self.command(CLEAR)?;
+        std::thread::sleep(Duration::from_millis(2));
+        Ok(())
+    }
+
+    fn show(&mut self, layout: DisplayLayout, frame: &Frame) -> anyhow::Result<()> {
+        let lines = match layout {
+            DisplayLayout::Detail => {
+                let mut lines = vec![
+                    frame.status.label().to_string(),
+                    frame.detail.clone(),
+                    format!("IP {}", frame.ip),
+                ];
+                if let Some(level) = frame.battery {
+                    lines.push(i18n::format("display.battery", &[&level.percent]));
+                }
+                if let Some(celsius) = frame.temperature {
+                    lines.push(i18n::format("display.temperature", &[&celsius.round()]));
+                }
+                lines
+            }
+            DisplayLayout::Status if !frame.users.is_empty() => display::user_lines(&frame.users),
+            DisplayLayout::Status => {
+                let mut lines = vec![String::new(); (self.rows - 1) / 2];
+                lines.push(self.centre(display::frame_headline(frame)));
+                lines
+            }
+        };
+        self.write_lines(&lines)
+    }
+
+    fn message(&mut self, text: &str) -> anyhow::Result<()> {
+        // Wrap across the rows
+        let chars: Vec<char> = text.chars().collect();
+        let lines: Vec<String> = chars
+            .chunks(self.columns)
+            .map(|chunk| chunk.iter().collect())
+            .collect();
+        self.write_lines(&lines)
+    }
+}
+
+// Character codes for text, '?' where the ROM has no match
+fn rom_codes(text: &str) -> Vec<u8> {
+    let mut codes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            c if c.is_ascii() => codes.push(c as u8),
+            // Only the lower case umlauts are in the ROM
+            'ä' | 'Ä' => codes.push(0xE1),
+            'ö' | 'Ö' => codes.push(0xEF),
+            'ü' | 'Ü' => codes.push(0xF5),
+            'ß' => codes.push(0xE2),
+            c => match greek_in_latin(c) {
+                Some(latin) => codes.extend(latin.bytes()),
+                None => codes.push(b'?'),
+            },
+        }
+    }
+    codes
+}
+
+fn greek_in_latin(c: char) -> Option<String> {
+    // Drop the accents first
+    let c = match c {
+        'ά' => 'α',
+        'έ' => 'ε',
+        'ή' => 'η',
+        'ί' | 'ϊ' | 'ΐ' => 'ι',
+        'ό' => 'ο',
+        'ύ' | 'ϋ' | 'ΰ' => 'υ',
+        'ώ' => 'ω',
+        'Ά' => 'Α',
+        'Έ' => 'Ε',
+        'Ή' => 'Η',
+        'Ί' | 'Ϊ' => 'Ι',
+        'Ό' => 'Ο',
+        'Ύ' | 'Ϋ' => 'Υ',
+        'Ώ' => 'Ω',
+        c => c,
+    };
+    let (index, upper) = match c {
+        'Α'..='Ω' => (c as usize - 'Α' as usize, true),
+        'α'..='ω' => (c as usize - 'α' as usize, false),
+        _ => return None,
+    };
+    // Code point 17 is unused in upper case and the final sigma in lower
+    // case, which both land on Sigma
+    let latin = GREEK[if index > 17 { index - 1 } else { index }];
+    Some(if upper {
+        latin.to_uppercase()
+    } else {
+        latin.to_lowercase()
+    })
+}
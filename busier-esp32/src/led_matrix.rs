@@ -0,0 +1,239 @@
+//! MAX7219 LED matrix.
+//!
+//! Drives a chain of MAX7219 8x8 modules, such as the common 32x8 boards,
+//! as another display panel. Text that fits is shown centred; longer text
+//! scrolls in from the right. New text is picked up between passes so a
+//! scroll is never cut off halfway.
+
+use std::convert::Infallible;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use embedded_graphics::{
+    mono_font::MonoTextStyle,
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Baseline, Text},
+};
+use esp_idf_svc::hal::spi::{SpiDeviceDriver, SpiDriver};
+use log::warn;
+
+use crate::config::{self, DisplayLayout};
+use crate::display::{self, Frame, Panel};
+use crate::i18n::{self, FontSize};
+use crate::status::StatusLabel;
+
+const MATRIX_STACK_SIZE: usize = 4096;
+// How often new text is looked for while nothing scrolls
+const IDLE_INTERVAL: Duration = Duration::from_millis(100);
+
+// MAX7219 registers; the digit registers 1 to 8 hold the rows
+const DIGIT_0: u8 = 0x01;
+const DECODE_MODE: u8 = 0x09;
+const INTENSITY: u8 = 0x0A;
+const SCAN_LIMIT: u8 = 0x0B;
+const SHUTDOWN: u8 = 0x0C;
+const DISPLAY_TEST: u8 = 0x0F;
+
+type Spi = SpiDeviceDriver<'static, SpiDriver<'static>>;
+
+static TEXT: Mutex<String> = Mutex::new(String::new());
+
+/// The matrix as a display panel. The first module in the chain shows the
+/// leftmost eight columns.
+pub struct LedMatrix {
+    chain: Option<Chain>,
+}
+
+impl LedMatrix {
+    pub fn new(spi: Spi, modules: u8) -> Self {
+        Self {
+            chain: Some(Chain {
+                spi,
+                modules: usize::from(modules.max(1)),
+            }),
+        }
+    }
+}
+
+impl Panel for LedMatrix {
+    fn init_panel(&mut self) -> anyhow::Result<()> {
+        let mut chain = self
+            .chain
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("LED matrix already started"))?;
+        chain.init()?;
+
+        std::thread::Builder::new()
+            .name("led_matrix".into())
+            .stack_size(MATRIX_STACK_SIZE)
+            .spawn(move || run(chain))?;
+
+        Ok(())
+    }
+
+    fn show(&mut self, layout: DisplayLayout, frame: &Frame) -> anyhow::Result<()> {
+        let text = match layout {
+            DisplayLayout::Detail => format!("{}  {}", frame.status.label(), frame.detail),
+            DisplayLayout::Status if !frame.users.is_empty() => {
+                display::user_lines(&frame.users).join("  ")
+            }
+            DisplayLayout::Status => display::frame_headline(frame).to_string(),
+        };
+        *TEXT.lock().unwrap() = text;
+        Ok(())
+    }
+
+    fn message(&mut self, text: &str) -> anyhow::Result<()> {
+        *TEXT.lock().unwrap() = text.to_string();
+        Ok(())
+    }
+}
+
+fn run(mut chain: Chain) {
+    let width = chain.modules * 8;
+    let mut text = String::new();
+    let mut columns = Vec::new();
+    let mut offset = 0;
+    let mut shown = Vec::new();
+    let mut intensity = None;
+
+    loop {
+        let config = config::get().led_matrix;
+
+        if offset == 0 {
+            let current = TEXT.lock().unwrap();
+            if *current != text {
+                text = current.clone();
+                columns = render(&text);
+            }
+        }
+
+        let scrolling = columns.len() > width;
+        let window: Vec<u8> = if scrolling {
+            // The text runs in from the right edge and out at the left
+            let window = (offset..offset + width)
+                .map(|i| i.checked_sub(width).and_then(|i| columns.get(i)))
+                .map(|column| column.copied().unwrap_or(0))
+                .collect();
+            offset = (offset + 1) % (columns.len() + width);
+            window
+        } else {
+            let margin = (width - columns.len()) / 2;
+            let mut window = vec![0; width];
+            window[margin..margin + columns.len()].copy_from_slice(&columns);
+            window
+        };
+
+        let level = config.intensity.min(15);
+        if intensity != Some(level) {
+            match chain.write_all(INTENSITY, &vec![level; chain.modules]) {
+                Ok(()) => intensity = Some(level),
+                Err(e) => warn!("Failed to set LED matrix intensity: {:?}", e),
+            }
+        }
+
+        if window != shown {
+            match chain.draw(&window) {
+                Ok(()) => shown = window,
+                Err(e) => warn!("Failed to update LED matrix: {:?}", e),
+            }
+        }
+
+        std::thread::sleep(if scrolling {
+            Duration::from_millis(config.scroll_ms.max(1).into())
+        } else {
+            IDLE_INTERVAL
+        });
+    }
+}
+
+// Renders text into columns of eight pixels, the top row in bit 0
+fn render(text: &str) -> Vec<u8> {
+    let text = Text::with_baseline(
+        text,
+        Point::zero(),
+        MonoTextStyle::new(i18n::font(FontSize::Narrow), BinaryColor::On),
+        Baseline::Top,
+    );
+    let mut columns = Columns(vec![0; text.bounding_box().size.width as usize]);
+    let _ = text.draw(&mut columns);
+    columns.0
+}
+
+struct Columns(Vec<u8>);
+
+impl OriginDimensions for Columns {
+    fn size(&self) -> Size {
+        Size::new(self.0.len() as u32, 8)
+    }
+}
+
+impl DrawTarget for Columns {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), u8::try_from(point.y)) else {
+                continue;
+            };
+            if let Some(column) = self.0.get_mut(x).filter(|_| y < 8) {
+                if color.is_on() {
+                    *column |= 1 << y;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Chain {
+    spi: Spi,
+    modules: usize,
+}
+
+impl Chain {
+    fn init(&mut self) -> anyhow::Result<()> {
+        let modules = self.modules;
+        self.write_all(DISPLAY_TEST, &vec![0; modules])?;
+        // Raw segments on all eight digits
+        self.write_all(DECODE_MODE, &vec![0; modules])?;
+        self.write_all(SCAN_LIMIT, &vec![7; modules])?;
+        self.draw(&vec![0; modules * 8])?;
+        self.write_all(SHUTDOWN, &vec![1; modules])
+    }
+
+    // Writes one column per pixel, the leftmost in each module as bit 7
+    fn draw(&mut self, window: &[u8]) -> anyhow::Result<()> {
+        for row in 0..8u8 {
+            let data: Vec<u8> = window
+                .chunks(8)
+                .map(|module| {
+                    module
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, column)| *column & (1 << row) != 0)
+                        .fold(0, |byte, (x, _)| byte | 0x80 >> x)
+                })
+                .collect();
+            self.write_all(DIGIT_0 + row, &data)?;
+        }
+        Ok(())
+    }
+
+    // Sets a register on every module in one transfer. The data shifts
+    // through the chain, so the last module's value goes out first.
+    fn write_all(&mut self, register: u8, data: &[u8]) -> anyhow::Result<()> {
+        let buf: Vec<u8> = data
+            .iter()
+            .rev()
+            .flat_map(|value| [register, *value])
+            .collect();
+        self.spi.write(&buf)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! `GET /status/wait?since=<version>`: long polling for status changes.
+//!
+//! The request is held until the status version differs from `since`, or
+//! until `timeout` seconds pass, and then answered with the status as in
+//! `GET /api/status`. A client passes the `version` of each answer as the
+//! next `since`, which gets it every change without WebSockets or SSE.
+//!
+//! The web server runs every handler on one task, so a handler that blocked
+//! would hold up every other request. The route is therefore registered on
+//! the server directly as an asynchronous handler: the request is detached
+//! from the server task and answered from the long-poll thread.
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use embedded_svc::http::Method;
+use esp_idf_svc::handle::RawHandle;
+use esp_idf_svc::http::server::EspHttpServer;
+use esp_idf_svc::sys;
+use log::warn;
+
+use crate::http_util;
+use crate::status;
+
+const PATH: &CStr = c"/status/wait";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_TIMEOUT: Duration = Duration::from_secs(60);
+// Each held request keeps a socket of the server open
+const MAX_WAITERS: usize = 4;
+// How often timeouts are checked when the status does not change
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const LONG_POLL_STACK_SIZE: usize = 4096;
+
+// A request detached from the server task
+#[derive(Clone, Copy)]
+struct Waiter {
+    req: *mut sys::httpd_req_t,
+    since: u64,
+    until: Instant,
+}
+
+// SAFETY: a detached request may be answered from any task, and only once,
+// as it leaves WAITERS when it is answered
+unsafe impl Send for Waiter {}
+
+static WAITERS: Mutex<Vec<Waiter>> = Mutex::new(Vec::new());
+
+/// Starts the thread that answers the held requests.
+pub fn start() -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("long_poll".into())
+        .stack_size(LONG_POLL_STACK_SIZE)
+        .spawn(|| {
+            let mut version = status::version();
+            loop {
+                version = status::wait_for_change(version, CHECK_INTERVAL);
+                let now = Instant::now();
+                let mut due = Vec::new();
+                WAITERS.lock().unwrap().retain(|waiter| {
+                    let answer = waiter.since != version || waiter.until <= now;
+                    if answer {
+                        due.push(*waiter);
+                    }
+                    !answer
+                });
+                for waiter in due {
+                    // SAFETY: the request was detached and is completed once
+                    unsafe {
+                        respond(waiter.req);
+                        sys::httpd_req_async_handler_complete(waiter.req);
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Registers the route.
+pub fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    let uri = sys::httpd_uri_t {
+        uri: PATH.as_ptr(),
+        method: sys::http_method_HTTP_GET,
+        handler: Some(handle),
+        user_ctx: ptr::null_mut(),
+        ..Default::default()
+    };
+    // SAFETY: the server copies the route, and the path is static
+    sys::esp!(unsafe { sys::httpd_register_uri_handler(server.handle(), &uri) })?;
+    http_util::record(server, &PATH.to_string_lossy(), Method::Get);
+    Ok(())
+}
+
+unsafe extern "C" fn handle(req: *mut sys::httpd_req_t) -> sys::esp_err_t {
+    // SAFETY: the server passes a valid request with a NUL-terminated URI
+    let uri = unsafe { CStr::from_ptr((*req).uri.as_ptr()) }.to_string_lossy();
+    let since = http_util::query_param(&uri, "since").and_then(|since| since.parse().ok());
+    let timeout = http_util::query_param(&uri, "timeout")
+        .and_then(|secs| secs.parse().ok())
+        .map_or(DEFAULT_TIMEOUT, Duration::from_secs)
+        .min(MAX_TIMEOUT);
+
+    // Without `since`, or once it is out of date, there is nothing to wait for
+    let mut waiters = WAITERS.lock().unwrap();
+    if since != Some(status::version()) || timeout.is_zero() || waiters.len() >= MAX_WAITERS {
+        drop(waiters);
+        // SAFETY: the request is answered within its handler
+        return unsafe { respond(req) };
+    }
+
+    let mut detached = ptr::null_mut();
+    // SAFETY: req is valid; the copy stays valid until it is completed
+    if let Err(e) = sys::esp!(unsafe { sys::httpd_req_async_handler_begin(req, &mut detached) }) {
+        drop(waiters);
+        warn!("Failed to hold status request: {:?}", e);
+        // SAFETY: the request is still the handler's to answer
+        return unsafe { respond(req) };
+    }
+    waiters.push(Waiter {
+        req: detached,
+        since: since.unwrap_or_default(),
+        until: Instant::now() + timeout,
+    });
+    sys::ESP_OK
+}
+
+// Answers with the current status
+unsafe fn respond(req: *mut sys::httpd_req_t) -> sys::esp_err_t {
+    let body = crate::status_json();
+    let etag = http_util::etag(body["version"].as_u64().unwrap_or_default());
+    // The server keeps the header's pointer until the response is sent
+    let etag = CString::new(etag).unwrap_or_default();
+    let body = body.to_string();
+    unsafe {
+        sys::httpd_resp_set_type(req, c"application/json".as_ptr());
+        sys::httpd_resp_set_hdr(req, c"Cache-Control".as_ptr(), c"no-store".as_ptr());
+        sys::httpd_resp_set_hdr(req, c"ETag".as_ptr(), etag.as_ptr());
+        sys::httpd_resp_send(req, body.as_ptr().cast(), body.len() as _)
+    }
+}
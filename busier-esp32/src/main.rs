@@ -0,0 +1,1799 @@
+//! ESP32 HTTP Server with WiFi Client and SSD1306 OLED display.
+//!
+//! Connects to existing WiFi network, serves a simple HTML page,
+//! and displays information on an SSD1306 OLED display.
+//! Includes a "Do Not Disturb" toggle button.
+
+mod artnet;
+mod auth;
+mod board;
+#[cfg_attr(not(feature = "battery"), allow(dead_code))]
+mod battery;
+#[cfg(feature = "ble")]
+mod ble;
+mod button;
+mod button_protocol;
+mod cert;
+#[cfg_attr(not(feature = "chime"), allow(dead_code))]
+mod chime;
+mod clock;
+mod coap;
+mod config;
+mod console;
+#[cfg_attr(not(feature = "countdown"), allow(dead_code))]
+mod countdown;
+#[cfg_attr(not(feature = "cube"), allow(dead_code))]
+mod cube;
+mod device;
+mod discovery;
+mod display;
+#[cfg_attr(not(feature = "door"), allow(dead_code))]
+mod door;
+mod door_sign;
+#[cfg_attr(not(feature = "doorbell"), allow(dead_code))]
+mod doorbell;
+#[cfg(feature = "epaper")]
+mod epaper;
+mod errors;
+mod esphome;
+#[cfg_attr(not(feature = "gesture"), allow(dead_code))]
+mod gesture;
+mod hal;
+#[cfg_attr(not(feature = "haptic"), allow(dead_code))]
+mod haptic;
+#[cfg(feature = "homekit")]
+mod homekit;
+#[cfg(feature = "homekit")]
+mod homekit_pairing;
+mod hooks;
+mod http_client;
+mod http_util;
+#[cfg(feature = "hub75")]
+mod hub75;
+mod hue;
+mod hue_emulation;
+mod i18n;
+#[cfg_attr(not(feature = "ir"), allow(dead_code))]
+mod ir;
+#[cfg_attr(not(feature = "lcd"), allow(dead_code))]
+mod lcd;
+#[cfg_attr(not(feature = "led-matrix"), allow(dead_code))]
+mod led_matrix;
+mod long_poll;
+mod matrix;
+mod memory;
+mod metrics;
+mod mirror;
+mod modbus;
+mod noise;
+mod notify;
+mod ota;
+mod output;
+mod peer_sync;
+mod pomodoro;
+mod portal;
+mod power;
+#[cfg(feature = "ble")]
+mod provisioning;
+mod redirect;
+mod reminders;
+mod remote_button;
+#[cfg_attr(not(feature = "rfid"), allow(dead_code))]
+mod rfid;
+mod rpc;
+#[cfg(feature = "rtc")]
+mod rtc;
+mod rtttl;
+mod rules;
+mod safe_mode;
+mod schedule;
+mod secrets;
+#[cfg_attr(not(feature = "servo"), allow(dead_code))]
+mod servo;
+mod setup;
+mod sleep;
+mod snmp;
+mod snooze;
+mod ssdp;
+mod state;
+mod stats;
+mod status;
+mod tasks;
+mod temperature;
+#[cfg(feature = "tft")]
+mod tft;
+mod tls;
+mod tz;
+mod users;
+mod web;
+mod wled;
+
+use core::convert::TryInto;
+#[cfg(any(
+    feature = "oled-128x32",
+    feature = "oled-128x64",
+    feature = "lcd",
+    feature = "battery",
+    feature = "cube",
+    feature = "gesture",
+    feature = "haptic"
+))]
+use embedded_hal_bus::i2c::MutexDevice;
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::Write;
+use embedded_svc::wifi::{AuthMethod, ClientConfiguration, Configuration};
+
+#[cfg(feature = "battery")]
+use esp_idf_svc::hal::adc::attenuation::DB_11;
+#[cfg(feature = "battery")]
+use esp_idf_svc::hal::adc::oneshot::config::{AdcChannelConfig, Calibration};
+#[cfg(feature = "battery")]
+use esp_idf_svc::hal::adc::oneshot::{AdcChannelDriver, AdcDriver};
+#[cfg(any(
+    feature = "led-matrix",
+    feature = "chime",
+    feature = "tft",
+    feature = "epaper"
+))]
+use esp_idf_svc::hal::gpio::AnyIOPin;
+use esp_idf_svc::hal::gpio::PinDriver;
+#[cfg(any(
+    feature = "oled-128x32",
+    feature = "oled-128x64",
+    feature = "lcd",
+    feature = "battery",
+    feature = "cube",
+    feature = "gesture",
+    feature = "haptic"
+))]
+use esp_idf_svc::hal::i2c;
+#[cfg(feature = "chime")]
+use esp_idf_svc::hal::i2s::config::{
+    Config as I2sConfig, DataBitWidth, SlotMode, StdClkConfig, StdConfig, StdGpioConfig,
+    StdSlotConfig,
+};
+#[cfg(feature = "chime")]
+use esp_idf_svc::hal::i2s::I2sDriver;
+#[cfg(any(feature = "buzzer", feature = "servo"))]
+use esp_idf_svc::hal::ledc::{config::TimerConfig, LedcDriver, LedcTimerDriver, Resolution};
+use esp_idf_svc::hal::prelude::*;
+#[cfg(feature = "ir")]
+use esp_idf_svc::hal::rmt::{config::ReceiveConfig, RxRmtDriver};
+#[cfg(any(
+    feature = "led-matrix",
+    feature = "rfid",
+    feature = "tft",
+    feature = "epaper"
+))]
+use esp_idf_svc::hal::spi::{config::Config as SpiConfig, SpiDeviceDriver, SpiDriverConfig};
+use esp_idf_svc::log::EspLogger;
+use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
+use esp_idf_svc::{
+    eventloop::EspSystemEventLoop, http::server::EspHttpServer, nvs::EspDefaultNvsPartition,
+};
+
+use log::{info, warn};
+
+use hal::NetworkInfo;
+use http_util::Routes;
+use notify::Event;
+use status::{Source, Status, StatusLabel};
+
+// SSD1306 OLED display
+#[cfg(any(feature = "oled-128x32", feature = "oled-128x64"))]
+use ssd1306::{prelude::*, Ssd1306};
+
+// Standard library
+use std::sync::atomic::Ordering;
+#[cfg(any(
+    feature = "oled-128x32",
+    feature = "oled-128x64",
+    feature = "lcd",
+    feature = "battery",
+    feature = "cube",
+    feature = "gesture",
+    feature = "haptic"
+))]
+use std::sync::Mutex;
+
+// Without build-time credentials the device is provisioned over BLE
+const SSID: Option<&str> = option_env!("WIFI_SSID");
+const PASSWORD: Option<&str> = option_env!("WIFI_PASS");
+
+// OpenAPI document, checked and stamped with the version by build.rs
+static OPENAPI_JSON: &str = include_str!(concat!(env!("OUT_DIR"), "/openapi.json"));
+
+// Max payload length
+const MAX_LEN: usize = 128;
+// Max payload length for a guest message
+const MAX_MESSAGE_LEN: usize = 1024;
+// Snooze length when none is given, and the longest allowed
+const DEFAULT_SNOOZE_MINUTES: u64 = 15;
+const MAX_SNOOZE_MINUTES: u64 = 24 * 60;
+// How long the detail line shows each person on shared devices
+const USER_TURN_SECS: u64 = 3;
+// How long a new HTTPS certificate's fingerprint is shown after boot
+const FINGERPRINT_SECS: u64 = 5 * 60;
+// Tone of the piezo buzzer
+#[cfg(feature = "buzzer")]
+const BUZZER_FREQUENCY: Hertz = Hertz(2000);
+
+// Shared state between threads
+static REQUEST_COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+fn main() -> anyhow::Result<()> {
+    // Initialize ESP-IDF
+    esp_idf_svc::sys::link_patches();
+    EspLogger::initialize_default();
+
+    // Get peripherals
+    let peripherals = Peripherals::take()?;
+    let sys_loop = EspSystemEventLoop::take()?;
+    let nvs = EspDefaultNvsPartition::take()?;
+
+    // Load runtime configuration, with its secrets from their own partition
+    secrets::init()?;
+    config::init(nvs.clone())?;
+
+    // A new device also takes its network and configuration over serial
+    console::start()?;
+
+    // Count crashes in a row; too many start safe mode
+    safe_mode::init(nvs.clone())?;
+    safe_mode::start()?;
+
+    // Resume the last checkpoint, then the status saved before deep sleep
+    state::init(nvs.clone())?;
+    tls::init(nvs.clone())?;
+    notify::init(nvs.clone())?;
+    stats::init(nvs.clone())?;
+    remote_button::init(nvs.clone())?;
+    sleep::restore();
+    state::start()?;
+    stats::start()?;
+
+    // Scale the CPU clock with load
+    if safe_mode::is_active() {
+        info!("Safe mode, leaving the CPU clock alone");
+    } else if let Err(e) = power::configure_cpu() {
+        warn!("Failed to configure power management: {:?}", e);
+    }
+
+    info!("Drivers in this build: {}", board::drivers().join(", "));
+
+    // Pins of the I2C bus, the display and the simple inputs and outputs
+    let profile = board::profile();
+    let pins = &profile.pins;
+
+    // Initialize the I2C bus of the displays and sensors
+    #[cfg(any(
+        feature = "oled-128x32",
+        feature = "oled-128x64",
+        feature = "lcd",
+        feature = "battery",
+        feature = "cube",
+        feature = "gesture",
+        feature = "haptic",
+        feature = "rtc"
+    ))]
+    let i2c_bus: &'static Mutex<i2c::I2cDriver<'static>> = {
+        let i2c = i2c::I2cDriver::new(
+            peripherals.i2c0,
+            board::io_pin(pins.sda),
+            board::io_pin(pins.scl),
+            &i2c::I2cConfig::new().baudrate(400.kHz().into()),
+        )?;
+
+        // The bus is shared between the display and the sensors
+        Box::leak(Box::new(Mutex::new(i2c)))
+    };
+
+    // Set the clock from the DS3231 before anything reads it
+    #[cfg(feature = "rtc")]
+    rtc::start(MutexDevice::new(i2c_bus))?;
+
+    // Attach the configured OLED panels, typically at 0x3C and 0x3D
+    #[allow(unused_mut)]
+    let mut displays = display::Displays::default();
+    #[cfg(any(feature = "oled-128x32", feature = "oled-128x64"))]
+    if let board::Screen::Ssd1306 { height, reset } = profile.screen {
+        // Boards with the panel's reset on a pin hold it in reset until pulsed
+        if let Some(reset) = reset {
+            let mut reset = PinDriver::output(board::output_pin(reset))?;
+            reset.set_low()?;
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            reset.set_high()?;
+            // Keep driving it high
+            std::mem::forget(reset);
+        }
+
+        for panel in config::get().displays() {
+            let interface = I2CInterface::new(MutexDevice::new(i2c_bus), panel.address, 0x40);
+            let rotation = if panel.rotate {
+                DisplayRotation::Rotate180
+            } else {
+                DisplayRotation::Rotate0
+            };
+            let added = if height == 64 {
+                let oled = Ssd1306::new(interface, DisplaySize128x64, rotation);
+                displays.add(oled.into_buffered_graphics_mode(), panel.layout)
+            } else {
+                let oled = Ssd1306::new(interface, DisplaySize128x32, rotation);
+                displays.add(oled.into_buffered_graphics_mode(), panel.layout)
+            };
+            if let Err(e) = added {
+                warn!("No display at {:#04x}: {:?}", panel.address, e);
+            }
+        }
+    }
+
+    // ST7789 TFT of boards like the T-Display, on the VSPI peripheral
+    #[cfg(feature = "tft")]
+    if let board::Screen::St7789 {
+        sclk,
+        mosi,
+        cs,
+        dc,
+        reset,
+        backlight,
+    } = profile.screen
+    {
+        let tft_spi = SpiDeviceDriver::new_single(
+            peripherals.spi3,
+            board::output_pin(sclk),
+            board::output_pin(mosi),
+            None::<AnyIOPin>,
+            Some(board::output_pin(cs)),
+            &SpiDriverConfig::new(),
+            &SpiConfig::new().baudrate(40.MHz().into()),
+        )?;
+        let tft = tft::Tft::new(
+            tft_spi,
+            board::output_pin(dc),
+            board::output_pin(reset),
+            board::output_pin(backlight),
+        )?;
+        // The panel takes the layout of the first display
+        let layout = config::get().displays()[0].layout;
+        if let Err(e) = displays.add(tft, layout) {
+            warn!("Failed to start TFT: {:?}", e);
+        }
+    }
+
+    // SSD1680 e-paper of boards like the Waveshare driver board, on the VSPI
+    // peripheral
+    #[cfg(feature = "epaper")]
+    if let board::Screen::Ssd1680 {
+        sclk,
+        mosi,
+        cs,
+        dc,
+        reset,
+        busy,
+    } = profile.screen
+    {
+        let epaper_spi = SpiDeviceDriver::new_single(
+            peripherals.spi3,
+            board::output_pin(sclk),
+            board::output_pin(mosi),
+            None::<AnyIOPin>,
+            Some(board::output_pin(cs)),
+            &SpiDriverConfig::new(),
+            &SpiConfig::new().baudrate(4.MHz().into()),
+        )?;
+        let epaper = epaper::Epaper::new(
+            epaper_spi,
+            board::output_pin(dc),
+            board::output_pin(reset),
+            board::input_pin(busy),
+        )?;
+        let layout = config::get().displays()[0].layout;
+        if let Err(e) = displays.add(epaper, layout) {
+            warn!("Failed to start e-paper: {:?}", e);
+        }
+    }
+
+    // HD44780 character LCD on the same bus
+    #[cfg(feature = "lcd")]
+    let lcd_config = config::get().lcd;
+    #[cfg(feature = "lcd")]
+    if lcd_config.enabled {
+        let lcd = lcd::Lcd::new(MutexDevice::new(i2c_bus), &lcd_config);
+        if let Err(e) = displays.add(lcd, lcd_config.layout) {
+            warn!("No LCD at {:#04x}: {:?}", lcd_config.address, e);
+        }
+    }
+
+    // HUB75 RGB panel, on the pins of drivers left out of this build
+    #[cfg(feature = "hub75")]
+    {
+        let panel_pins = hub75::Pins {
+            r1: board::output_pin(board::HUB75[0]),
+            g1: board::output_pin(board::HUB75[1]),
+            b1: board::output_pin(board::HUB75[2]),
+            r2: board::output_pin(board::HUB75[3]),
+            g2: board::output_pin(board::HUB75[4]),
+            b2: board::output_pin(board::HUB75[5]),
+            a: board::output_pin(board::HUB75[6]),
+            b: board::output_pin(board::HUB75[7]),
+            c: board::output_pin(board::HUB75[8]),
+            d: board::output_pin(board::HUB75[9]),
+            clk: board::output_pin(board::HUB75[10]),
+            lat: board::output_pin(board::HUB75[11]),
+            oe: board::output_pin(board::HUB75[12]),
+        };
+        if let Err(e) = displays.add(hub75::Hub75::new(panel_pins), config::get().hub75.layout) {
+            warn!("Failed to start HUB75 panel: {:?}", e);
+        }
+    }
+
+    // MAX7219 LED matrix on the SPI2 peripheral
+    #[cfg(feature = "led-matrix")]
+    {
+        let matrix_config = config::get().led_matrix;
+        if matrix_config.enabled {
+            let matrix_spi = SpiDeviceDriver::new_single(
+                peripherals.spi2,
+                board::output_pin(board::LED_MATRIX[0]),
+                board::output_pin(board::LED_MATRIX[1]),
+                None::<AnyIOPin>, // No MISO
+                Some(board::output_pin(board::LED_MATRIX[2])),
+                &SpiDriverConfig::new(),
+                &SpiConfig::new().baudrate(1.MHz().into()),
+            )?;
+            let matrix = led_matrix::LedMatrix::new(matrix_spi, matrix_config.modules);
+            if let Err(e) = displays.add(matrix, matrix_config.layout) {
+                warn!("Failed to start LED matrix: {:?}", e);
+            }
+        }
+    }
+
+    // Setup WiFi
+    let mut wifi = BlockingWifi::wrap(
+        EspWifi::new(peripherals.modem, sys_loop.clone(), Some(nvs.clone()))?,
+        sys_loop,
+    )?;
+
+    // Display connecting message, unless waking up with the status still shown
+    if !sleep::woke_up() {
+        displays.message(i18n::text("display.connecting"));
+    }
+
+    // Connect to WiFi network. A door sign without a network keeps showing
+    // the last status until the next wake-up; anything else opens the setup
+    // access point to be given one.
+    if let Err(e) = connect_wifi(&mut wifi, &mut displays) {
+        warn!("WiFi connection failed: {:?}", e);
+        if door_sign::sleeps_after_sync() {
+            sleep::enter();
+        }
+        return portal::run(&mut wifi, &mut displays);
+    }
+
+    // Get and display IP address
+    let ip_info = wifi.wifi().sta_netif().get_ip_info()?;
+    info!("Wifi DHCP info: {:?}", ip_info);
+    info!("HTTP server will be available at http://{}/", ip_info.ip);
+
+    // After crashing again and again, serve only what it takes to fix the
+    // configuration
+    if safe_mode::is_active() {
+        return safe_mode::run(&mut displays, ip_info.ip);
+    }
+
+    // Show the status of a door sign's source
+    door_sign::sync();
+
+    // Mirror the status to paired devices over ESP-NOW
+    peer_sync::start()?;
+
+    // Expose the status over BLE
+    #[cfg(feature = "ble")]
+    ble::start()?;
+
+    // Announce the device on the LAN
+    discovery::start(ip_info.ip)?;
+
+    // Advertise the device to UPnP clients
+    ssdp::start(ip_info.ip)?;
+
+    // Serve the status over CoAP
+    coap::start()?;
+
+    // Answer SNMP monitoring queries
+    snmp::start()?;
+
+    // Expose registers to building management systems
+    modbus::start()?;
+
+    // Mirror the status on a Hue light
+    hue::start()?;
+
+    // Mirror the status on a WLED controller
+    wled::start()?;
+
+    // Drive DMX tally lights over Art-Net
+    artnet::start()?;
+
+    // Let Home Assistant adopt the device
+    esphome::start()?;
+
+    // Let the Home app pair with the device
+    #[cfg(feature = "homekit")]
+    homekit::start(nvs)?;
+
+    // Start time synchronization for working hours
+    let _sntp = clock::start()?;
+
+    // Update display with initial status
+    displays.show(&display::Frame {
+        ip: ip_info.ip,
+        status: status::current(),
+        detail: i18n::format("display.requests", &[&0]),
+        step: None,
+        battery: battery::level(),
+        low_memory: false,
+        temperature: temperature::celsius(),
+        users: users::list(),
+    });
+
+    // Drive the buzzer, the status LED and the relay. With 10 bits the
+    // buzzer's timer reaches ringtone notes down to 80 Hz.
+    #[cfg(feature = "buzzer")]
+    let buzzer = {
+        let buzzer_timer = LedcTimerDriver::new(
+            peripherals.ledc.timer0,
+            &TimerConfig::new()
+                .frequency(BUZZER_FREQUENCY)
+                .resolution(Resolution::Bits10),
+        )?;
+        Some(LedcDriver::new(
+            peripherals.ledc.channel0,
+            buzzer_timer,
+            board::io_pin(pins.buzzer),
+        )?)
+    };
+    #[cfg(not(feature = "buzzer"))]
+    let buzzer = None;
+    let led = PinDriver::output(board::output_pin(pins.led))?;
+    output::start(buzzer, led, board::io_pin(pins.relay))?;
+
+    // MAX98357 I2S amplifier for chimes, on the pins in `board::CHIME`
+    #[cfg(feature = "chime")]
+    {
+        let chime_config = StdConfig::new(
+            I2sConfig::default().auto_clear(true),
+            StdClkConfig::from_sample_rate_hz(chime::SAMPLE_RATE),
+            StdSlotConfig::philips_slot_default(DataBitWidth::Bits16, SlotMode::Mono),
+            StdGpioConfig::default(),
+        );
+        let i2s = I2sDriver::new_std_tx(
+            peripherals.i2s0,
+            &chime_config,
+            board::output_pin(board::CHIME[0]),
+            board::output_pin(board::CHIME[1]),
+            None::<AnyIOPin>,
+            board::output_pin(board::CHIME[2]),
+        )?;
+        chime::start(i2s)?;
+    }
+
+    // Servo flag, driven with the usual 50 Hz servo pulses
+    #[cfg(feature = "servo")]
+    {
+        let servo_timer = LedcTimerDriver::new(
+            peripherals.ledc.timer1,
+            &TimerConfig::new()
+                .frequency(50.Hz().into())
+                .resolution(Resolution::Bits14),
+        )?;
+        let servo_pwm = LedcDriver::new(
+            peripherals.ledc.channel1,
+            servo_timer,
+            board::output_pin(board::SERVO[0]),
+        )?;
+        servo::start(servo_pwm)?;
+    }
+
+    // Start watching the button, BOOT by default
+    button::start(board::io_pin(pins.button))?;
+
+    // Reed switch door sensor between its pin and ground
+    #[cfg(feature = "door")]
+    door::start(board::io_pin(pins.door))?;
+
+    // Doorbell button between its pin and ground
+    #[cfg(feature = "doorbell")]
+    if let Some(pin) = board::doorbell_pin() {
+        doorbell::start(board::io_pin(pin))?;
+    }
+
+    // TM1637 countdown display
+    #[cfg(feature = "countdown")]
+    countdown::start(
+        board::output_pin(board::COUNTDOWN[0]),
+        board::io_pin(board::COUNTDOWN[1]),
+    )?;
+
+    // Battery voltage through a divider on an ADC1 pin, or a MAX17048 fuel
+    // gauge on the display's I2C bus, used instead if configured
+    #[cfg(feature = "battery")]
+    {
+        // The pin in `board::BATTERY`; the ADC driver needs its type
+        #[cfg(esp32)]
+        let battery_pin = peripherals.pins.gpio35;
+        #[cfg(esp32c3)]
+        let battery_pin = peripherals.pins.gpio3;
+        #[cfg(esp32s3)]
+        let battery_pin = peripherals.pins.gpio1;
+        let battery_adc = AdcChannelDriver::new(
+            AdcDriver::new(peripherals.adc1)?,
+            battery_pin,
+            &AdcChannelConfig {
+                attenuation: DB_11,
+                calibration: Calibration::Line,
+                ..Default::default()
+            },
+        )?;
+        battery::start(battery_adc)?;
+        battery::start_fuel_gauge(MutexDevice::new(i2c_bus))?;
+    }
+
+    // MFRC522 badge reader on the SPI3 peripheral
+    #[cfg(feature = "rfid")]
+    {
+        let rfid_spi = SpiDeviceDriver::new_single(
+            peripherals.spi3,
+            board::output_pin(board::RFID[0]),
+            board::output_pin(board::RFID[1]),
+            Some(board::input_pin(board::RFID[2])),
+            Some(board::output_pin(board::RFID[3])),
+            &SpiDriverConfig::new(),
+            &SpiConfig::new().baudrate(4.MHz().into()),
+        )?;
+        rfid::start(rfid_spi)?;
+    }
+
+    // MPU6050 status cube on the display's I2C bus
+    #[cfg(feature = "cube")]
+    cube::start(MutexDevice::new(i2c_bus))?;
+
+    // APDS9960 gesture sensor on the same bus
+    #[cfg(feature = "gesture")]
+    gesture::start(MutexDevice::new(i2c_bus))?;
+
+    // DRV2605L vibration motor driver on the same bus
+    #[cfg(feature = "haptic")]
+    haptic::start(MutexDevice::new(i2c_bus))?;
+
+    // TSOP38238 IR receiver, sampled by the RMT in 1 us ticks
+    #[cfg(feature = "ir")]
+    {
+        // Any channel receives on the ESP32, only the upper ones on the others
+        #[cfg(esp32)]
+        let ir_channel = peripherals.rmt.channel0;
+        #[cfg(esp32c3)]
+        let ir_channel = peripherals.rmt.channel2;
+        #[cfg(esp32s3)]
+        let ir_channel = peripherals.rmt.channel4;
+        let ir_rx = RxRmtDriver::new(
+            ir_channel,
+            board::input_pin(board::IR[0]),
+            &ReceiveConfig::new()
+                .clock_divider(80)
+                .idle_threshold(12_000)
+                .filter_ticks_thresh(100),
+            1000,
+        )?;
+        ir::start(ir_rx)?;
+    }
+
+    // Watch the heap and the tasks' stacks
+    memory::start()?;
+
+    // Watch the chip temperature, where there is a sensor
+    temperature::start()?;
+
+    // Start delivering outbound notifications
+    notify::start()?;
+    if matrix::is_enabled() {
+        info!("Matrix notifications enabled");
+    }
+    long_poll::start()?;
+
+    // Create HTTP server
+    let mut server = EspHttpServer::new(&http_util::server_configuration())?;
+
+    // Same routes over HTTPS, if configured
+    let mut https_server = tls::start(ip_info.ip)?;
+    if let Some(https_server) = https_server.as_mut() {
+        register_routes(https_server, ip_info.ip, true)?;
+    }
+
+    // Set up routes. Once HTTPS is up plain HTTP redirects there, unless
+    // HTTPS needs client certificates and guests would be locked out.
+    if https_server.is_some() && !tls::requires_client_certs() {
+        redirect::register(&mut server, ip_info.ip)?;
+    } else {
+        register_routes(&mut server, ip_info.ip, false)?;
+    }
+
+    info!("HTTP server started and running");
+
+    // Keep the application running and update display periodically
+    let mut last_frame: Option<display::Frame> = None;
+    let mut shown_message: Option<String> = None;
+    let mut was_low_battery = false;
+    let awake_since = std::time::Instant::now();
+
+    loop {
+        // Advance the timers before reading the status
+        pomodoro::tick();
+        rules::tick();
+        reminders::tick();
+        status::tick();
+
+        // On low battery only the display stays on
+        let low_battery = battery::is_low();
+        if low_battery != was_low_battery {
+            let result = if low_battery {
+                info!("Low battery, turning WiFi off");
+                wifi.stop().map_err(anyhow::Error::from)
+            } else {
+                info!("Battery recovered, reconnecting WiFi");
+                connect_wifi(&mut wifi, &mut displays)
+            };
+            if let Err(e) = result {
+                warn!("Failed to switch power mode: {:?}", e);
+            }
+            was_low_battery = low_battery;
+        }
+
+        // Get current values
+        let transition = status::transition();
+        let current_detail = match (pomodoro::state(), &transition, status::back_at()) {
+            _ if low_battery => i18n::text("display.battery_low").to_string(),
+            _ if temperature::is_high() => {
+                let celsius = temperature::celsius().unwrap_or_default().round();
+                i18n::format("display.too_hot", &[&celsius])
+            }
+            _ if doorbell::visitor_waiting() => i18n::text("display.visitor_waiting").to_string(),
+            _ if clock::sync_lost() => i18n::text("display.clock_unsynced").to_string(),
+            (Some(state), _, _) => state.display_text(),
+            (None, Some((_, to, remaining)), _) => {
+                let minutes = remaining.div_ceil(60);
+                i18n::format("display.transition_ends", &[&to.label(), &minutes])
+            }
+            (None, None, Some(back_at)) => back_at.display_text(),
+            (None, None, None) => match door::is_open() {
+                Some(true) => i18n::text("display.door_open").to_string(),
+                Some(false) => i18n::text("display.door_closed").to_string(),
+                None => {
+                    let requests = REQUEST_COUNTER.load(Ordering::SeqCst);
+                    match temperature::celsius() {
+                        Some(celsius) => i18n::format(
+                            "display.requests_temperature",
+                            &[&requests, &celsius.round()],
+                        ),
+                        None => i18n::format("display.requests", &[&requests]),
+                    }
+                }
+            },
+        };
+
+        // On shared devices the detail line takes turns with each person
+        let users = users::list();
+        let turn = awake_since.elapsed().as_secs() / USER_TURN_SECS;
+        let current_detail = match turn as usize % (users.len() + 1) {
+            0 => current_detail,
+            i => format!("{}: {}", users[i - 1].0, users[i - 1].1.label()),
+        };
+
+        // A freshly generated HTTPS certificate can be checked against the
+        // browser for a few minutes
+        let current_detail = match tls::generated_fingerprint() {
+            Some(fingerprint) if awake_since.elapsed().as_secs() < FINGERPRINT_SECS => {
+                format!("TLS {}", cert::format_fingerprint(&fingerprint[..6]))
+            }
+            _ => current_detail,
+        };
+
+        // Until HomeKit is paired the display shows the setup code instead
+        #[cfg(feature = "homekit")]
+        let current_detail = match homekit::pending_setup() {
+            Some(setup) => setup.code,
+            None => current_detail,
+        };
+
+        let frame = display::Frame {
+            ip: ip_info.ip,
+            status: status::current(),
+            detail: current_detail,
+            step: transition.map(|(step, _, _)| step.label),
+            battery: battery::level(),
+            low_memory: memory::is_low(),
+            temperature: temperature::celsius(),
+            users,
+        };
+
+        // Update the displays if anything shown has changed. A posted
+        // message covers the frame until it expires.
+        match display::posted_message() {
+            Some(text) => {
+                if shown_message.as_ref() != Some(&text) {
+                    displays.message(&text);
+                    shown_message = Some(text);
+                    last_frame = None;
+                }
+            }
+            None => {
+                shown_message = None;
+                if last_frame.as_ref() != Some(&frame) {
+                    displays.show(&frame);
+                    last_frame = Some(frame);
+                }
+            }
+        }
+
+        // Battery builds sleep between refreshes once the display is current
+        if sleep::is_due(awake_since.elapsed()) {
+            sleep::enter();
+        }
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+
+    // This line will never be reached
+    #[allow(unreachable_code)]
+    Ok(())
+}
+
+// Registers the web interface and API routes. `secure` is set for the
+// HTTPS listener.
+fn register_routes(
+    server: &mut EspHttpServer<'static>,
+    ip: std::net::Ipv4Addr,
+    secure: bool,
+) -> anyhow::Result<()> {
+    // Route for serving the guest page
+    server.route("/", Method::Get, |req| {
+        // Increment request counter
+        REQUEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+        // A fresh device starts with the setup wizard
+        if setup::is_pending() {
+            req.into_response(302, None, &[("Location", setup::SETUP_PATH)])?;
+            return Ok(());
+        }
+
+        let mut resp = req.into_response(200, None, &[web::HTML])?;
+        resp.write_all(web::render(web::INDEX_HTML).as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for serving the admin page
+    server.route(
+        "/admin",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let mut resp = req.into_response(200, None, &[web::HTML])?;
+            resp.write_all(web::render(web::ADMIN_HTML).as_bytes())?;
+            Ok(())
+        }),
+    )?;
+
+    // Routes for the pages' scripts and style sheet
+    web::register(server)?;
+
+    // Routes for the API index and its OpenAPI document
+    server.route("/api", Method::Get, |req| {
+        req.into_response(200, None, &[web::HTML])?
+            .write_all(web::API_INDEX_HTML.as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    server.route("/api/openapi.json", Method::Get, |req| {
+        let mut resp = req.into_response(
+            200,
+            None,
+            &[
+                ("Content-Type", "application/json"),
+                // Swagger UI is usually served from another origin
+                ("Access-Control-Allow-Origin", "*"),
+            ],
+        )?;
+        resp.write_all(OPENAPI_JSON.as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for the UPnP device description
+    server.route(ssdp::DESCRIPTION_PATH, Method::Get, move |req| {
+        let mut resp = req.into_response(200, None, &[("Content-Type", "text/xml")])?;
+        resp.write_all(ssdp::description_xml(ip).as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for handling POST requests with JSON
+    server.route("/post", Method::Post, |mut req| {
+        use embedded_svc::io::Read;
+        use serde::Deserialize;
+
+        // Increment request counter
+        REQUEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+        #[derive(Deserialize)]
+        struct FormData<'a> {
+            first_name: &'a str,
+            age: u32,
+            birthplace: &'a str,
+        }
+
+        let len = req.content_len().unwrap_or(0) as usize;
+
+        if len > MAX_LEN {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        }
+
+        let mut buf = vec![0; len];
+        req.read_exact(&mut buf)?;
+        let mut resp = req.into_ok_response()?;
+
+        if let Ok(form) = serde_json::from_slice::<FormData>(&buf) {
+            write!(
+                resp,
+                "Hello, {}-year-old {} from {}!",
+                form.age, form.first_name, form.birthplace
+            )?;
+        } else {
+            resp.write_all("JSON error".as_bytes())?;
+        }
+
+        Ok(())
+    })?;
+
+    // Route for getting current status, or a person's with ?user=
+    server.route("/status", Method::Get, |req| {
+        let user = http_util::query_param(req.uri(), "user");
+        let Some(status) = user.as_deref().map_or(Some(status::current()), users::get) else {
+            req.into_status_response(404)?
+                .write_all("Unknown user".as_bytes())?;
+            return Ok(());
+        };
+        // The version to pass back in If-Match when setting the status
+        let etag = http_util::etag(status::version());
+        let etag = ("ETag", etag.as_str());
+
+        // Plain text for scripts, unless they ask for JSON; a small page for
+        // dashboards that embed it in an iframe
+        let accept = req.header("Accept");
+        match http_util::negotiate(accept, &["text/plain", "application/json", "text/html"]) {
+            "application/json" => {
+                let body = match &user {
+                    Some(user) => serde_json::json!({ "user": user, "status": status }),
+                    None => status_json(),
+                };
+                req.into_response(200, None, &[("Content-Type", "application/json"), etag])?
+                    .write_all(body.to_string().as_bytes())?;
+            }
+            "text/html" => {
+                // People's statuses have no "back at" time
+                let detail = match &user {
+                    Some(_) => String::new(),
+                    None => status::back_at()
+                        .map(|b| b.display_text())
+                        .unwrap_or_default(),
+                };
+                let name = user.unwrap_or_else(|| config::get().device_name().to_string());
+                let page = web::render_with(
+                    web::STATUS_HTML,
+                    vec![
+                        ("name", name),
+                        ("label", status.label().to_string()),
+                        ("color", output::style(status).color),
+                        ("detail", detail),
+                    ],
+                );
+                req.into_response(200, None, &[web::HTML, etag])?
+                    .write_all(page.as_bytes())?;
+            }
+            _ => {
+                req.into_response(200, None, &[etag])?
+                    .write_all(status.as_str().as_bytes())?;
+            }
+        }
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for the statuses of the people sharing the device
+    server.route("/api/users", Method::Get, |req| {
+        let body: Vec<_> = users::list()
+            .into_iter()
+            .map(|(name, status)| serde_json::json!({ "name": name, "status": status }))
+            .collect();
+
+        let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+        resp.write_all(&serde_json::to_vec(&body)?)?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for getting the full status as JSON
+    server.route("/api/status", Method::Get, |req| {
+        let body = status_json();
+        let etag = http_util::etag(body["version"].as_u64().unwrap_or_default());
+        let mut resp = req.into_response(
+            200,
+            None,
+            &[
+                ("Content-Type", "application/json"),
+                ("ETag", etag.as_str()),
+            ],
+        )?;
+        resp.write_all(&serde_json::to_vec(&body)?)?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for the recent changes of the status and who made them
+    server.route(
+        "/api/audit",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let limit = http_util::query_param(req.uri(), "limit")
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(usize::MAX);
+            let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+            resp.write_all(&serde_json::to_vec(&status::audit(limit))?)?;
+            Ok(())
+        }),
+    )?;
+
+    // Route for the usage of each API token
+    server.route(
+        "/api/tokens",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let now = device::uptime().as_millis() as u64;
+            let body: Vec<_> = config::get()
+                .admin
+                .tokens
+                .into_iter()
+                .map(|token| {
+                    let usage = auth::usage(&token.name);
+                    serde_json::json!({
+                        "name": token.name,
+                        "rate_per_min": token.rate_per_min,
+                        "admin": token.admin,
+                        "requests": usage.requests,
+                        "throttled": usage.throttled,
+                        "last_secs_ago": usage.last_ms.map(|last| now.saturating_sub(last) / 1000),
+                    })
+                })
+                .collect();
+            let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+            resp.write_all(&serde_json::to_vec(&body)?)?;
+            Ok(())
+        }),
+    )?;
+
+    // Route for setting status
+    server.route(
+        "/status",
+        Method::Post,
+        auth::admin(secure, |mut req| {
+            use embedded_svc::io::Read;
+
+            let len = req.content_len().unwrap_or(0) as usize;
+
+            if len > MAX_LEN {
+                req.into_status_response(413)?
+                    .write_all("Request too big".as_bytes())?;
+                return Ok(());
+            }
+
+            let mut buf = vec![0; len];
+            req.read_exact(&mut buf)?;
+            let if_match = req.header("If-Match").map(str::to_string);
+            let reply = match http_util::parse_body(req.content_type(), &buf) {
+                Some(request) => set_status(request, if_match.as_deref()),
+                None => Ok("JSON or form error".to_string()),
+            };
+            match reply {
+                Ok(reply) => req.into_ok_response()?.write_all(reply.as_bytes())?,
+                // Someone else changed the status since the client read it
+                Err(version) => {
+                    let etag = http_util::etag(version);
+                    req.into_response(409, None, &[("ETag", etag.as_str())])?
+                        .write_all("Status changed in the meantime".as_bytes())?
+                }
+            }
+            Ok(())
+        }),
+    )?;
+
+    // Route for clients that can only send GET requests, such as Stream
+    // Deck plugins; off unless `admin.set_token` is set
+    server.route("/set", Method::Get, |mut req| {
+        let uri = req.uri().to_string();
+        let param = |name| http_util::query_param(&uri, name);
+        if config::get().admin.set_token.is_empty() {
+            return errors::not_found(req);
+        }
+        if !auth::is_set_token(param("token").as_deref()) {
+            req.into_status_response(403)?
+                .write_all("Invalid token".as_bytes())?;
+            return Ok(());
+        }
+        let Some(status) = param("status") else {
+            req.into_status_response(400)?
+                .write_all("Missing status".as_bytes())?;
+            return Ok(());
+        };
+
+        let request = StatusRequest {
+            status,
+            back_at: param("back_at"),
+            // Anything but a number is refused like a zero
+            minutes: param("minutes").map(|minutes| minutes.parse().unwrap_or(0)),
+            user: param("user"),
+            source: param("source"),
+            ttl_secs: param("ttl_secs").map(|secs| secs.parse().unwrap_or(0)),
+        };
+        // Unconditional, as GET requests carry no If-Match
+        let origin = auth::origin(&mut req, Some("set_token"));
+        let reply = status::attributed(origin, || set_status(request, None)).unwrap_or_default();
+        req.into_ok_response()?.write_all(reply.as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for knocking on the door
+    server.route("/knock", Method::Post, |req| {
+        // Increment request counter
+        REQUEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+        notify::send(Event::Knock);
+
+        req.into_ok_response()?
+            .write_all(i18n::text("web.knock_sent").as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for leaving a message from the guest page
+    server.route("/message", Method::Post, |mut req| {
+        use embedded_svc::io::Read;
+        use serde::Deserialize;
+
+        #[derive(Deserialize)]
+        struct MessageData {
+            text: String,
+        }
+
+        let len = req.content_len().unwrap_or(0) as usize;
+
+        if len > MAX_MESSAGE_LEN {
+            req.into_status_response(413)?
+                .write_all("Message too long".as_bytes())?;
+            return Ok(());
+        }
+
+        let mut buf = vec![0; len];
+        req.read_exact(&mut buf)?;
+
+        match http_util::parse_body::<MessageData>(req.content_type(), &buf) {
+            Some(data) if !data.text.trim().is_empty() => {
+                notify::send(Event::Message {
+                    text: data.text.trim().to_string(),
+                });
+                req.into_ok_response()?
+                    .write_all(i18n::text("web.message_sent").as_bytes())?;
+            }
+            _ => {
+                req.into_status_response(400)?
+                    .write_all("Empty message".as_bytes())?;
+            }
+        }
+
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for JSON-RPC calls, single or batched
+    server.route(
+        "/rpc",
+        Method::Post,
+        auth::admin(secure, |mut req| {
+            use embedded_svc::io::Read;
+
+            let len = req.content_len().unwrap_or(0) as usize;
+
+            if len > rpc::MAX_RPC_LEN {
+                req.into_status_response(413)?
+                    .write_all("Request too big".as_bytes())?;
+                return Ok(());
+            }
+
+            let mut buf = vec![0; len];
+            req.read_exact(&mut buf)?;
+
+            match rpc::handle(&buf) {
+                Some(response) => {
+                    let mut resp =
+                        req.into_response(200, None, &[("Content-Type", "application/json")])?;
+                    resp.write_all(response.to_string().as_bytes())?;
+                }
+                // Only notifications, nothing to answer
+                None => {
+                    req.into_status_response(204)?;
+                }
+            }
+            Ok(())
+        }),
+    )?;
+
+    // Route for snoozing notifications without changing the status
+    server.route(
+        "/api/snooze",
+        Method::Post,
+        auth::admin(secure, |req| {
+            let minutes = http_util::query_param(req.uri(), "minutes")
+                .unwrap_or_else(|| DEFAULT_SNOOZE_MINUTES.to_string())
+                .parse::<u64>();
+
+            match minutes {
+                Ok(minutes) if minutes <= MAX_SNOOZE_MINUTES => {
+                    snooze::start(std::time::Duration::from_secs(minutes * 60));
+                    let mut resp = req.into_ok_response()?;
+                    if minutes == 0 {
+                        resp.write_all("Snooze cancelled".as_bytes())?;
+                    } else {
+                        write!(resp, "Snoozed for {} minutes", minutes)?;
+                    }
+                }
+                _ => {
+                    req.into_status_response(400)?
+                        .write_all("Invalid minutes".as_bytes())?;
+                }
+            }
+
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    // Routes for ESP-NOW peer sync
+    server.route(
+        "/api/peers",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let peers: Vec<String> = peer_sync::paired_peers()
+                .iter()
+                .map(peer_sync::format_mac)
+                .collect();
+
+            let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+            resp.write_all(&serde_json::to_vec(&peers)?)?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    server.route(
+        "/api/peers/pair",
+        Method::Post,
+        auth::admin(secure, |req| {
+            peer_sync::start_pairing();
+
+            req.into_ok_response()?
+                .write_all("Pairing for 60 seconds".as_bytes())?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    // Route for forgetting provisioned WiFi credentials
+    #[cfg(feature = "ble")]
+    server.route(
+        "/api/wifi/reset",
+        Method::Post,
+        auth::admin(secure, |req| {
+            provisioning::reset()?;
+
+            req.into_ok_response()?
+                .write_all("WiFi credentials cleared, provisioning runs on next boot".as_bytes())?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    // Routes for reading and replacing the runtime configuration
+    config::register(server, secure)?;
+
+    // Route for signed firmware updates
+    ota::register(server, secure)?;
+
+    // Routes for reading and replacing the rules
+    server.route(
+        "/api/rules",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let body = serde_json::json!({
+                "rules": config::get().rules,
+                "active": rules::active(),
+            });
+
+            let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+            resp.write_all(&serde_json::to_vec(&body)?)?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    server.route(
+        "/api/rules",
+        Method::Post,
+        auth::admin(secure, |mut req| {
+            use embedded_svc::io::Read;
+
+            let len = req.content_len().unwrap_or(0) as usize;
+
+            if len > config::MAX_CONFIG_LEN {
+                req.into_status_response(413)?
+                    .write_all("Request too big".as_bytes())?;
+                return Ok(());
+            }
+
+            let mut buf = vec![0; len];
+            req.read_exact(&mut buf)?;
+
+            // The list replaces the `rules` section as a whole
+            let result = serde_json::from_slice::<Vec<rules::Rule>>(&buf)
+                .map_err(anyhow::Error::from)
+                .and_then(|rules| config::update(serde_json::json!({ "rules": rules })));
+
+            match result {
+                Ok(()) => {
+                    req.into_ok_response()?
+                        .write_all("Rules saved".as_bytes())?;
+                }
+                Err(e) => {
+                    req.into_status_response(400)?
+                        .write_all(format!("Invalid rules: {}", e).as_bytes())?;
+                }
+            }
+
+            Ok(())
+        }),
+    )?;
+
+    server.route(
+        "/api/reminders",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+            resp.write_all(&serde_json::to_vec(&config::get().reminders)?)?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    server.route(
+        "/api/reminders",
+        Method::Post,
+        auth::admin(secure, |mut req| {
+            use embedded_svc::io::Read;
+
+            let len = req.content_len().unwrap_or(0) as usize;
+
+            if len > config::MAX_CONFIG_LEN {
+                req.into_status_response(413)?
+                    .write_all("Request too big".as_bytes())?;
+                return Ok(());
+            }
+
+            let mut buf = vec![0; len];
+            req.read_exact(&mut buf)?;
+
+            // The list replaces the `reminders` section as a whole
+            let result = serde_json::from_slice::<Vec<reminders::Reminder>>(&buf)
+                .map_err(anyhow::Error::from)
+                .and_then(|reminders| {
+                    config::update(serde_json::json!({ "reminders": reminders }))
+                });
+
+            match result {
+                Ok(()) => {
+                    req.into_ok_response()?
+                        .write_all("Reminders saved".as_bytes())?;
+                }
+                Err(e) => {
+                    req.into_status_response(400)?
+                        .write_all(format!("Invalid reminders: {}", e).as_bytes())?;
+                }
+            }
+
+            Ok(())
+        }),
+    )?;
+
+    // Route for uploading the HTTPS certificate, key and client CA as PEM
+    server.route(
+        "/api/tls/*",
+        Method::Post,
+        auth::admin(secure, |mut req| {
+            let file = req
+                .uri()
+                .trim_start_matches("/api/tls/")
+                .split('?')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+
+            // A raw body from curl, or a form from the admin page
+            let buf = match http_util::read_upload(&mut req, tls::MAX_PEM_LEN) {
+                Ok(buf) => buf,
+                Err(e) => {
+                    req.into_status_response(400)?
+                        .write_all(format!("Invalid upload: {}", e).as_bytes())?;
+                    return Ok(());
+                }
+            };
+
+            match tls::store(&file, &buf) {
+                Ok(()) => req
+                    .into_ok_response()?
+                    .write_all("Saved, used after restart".as_bytes())?,
+                Err(e) => req
+                    .into_status_response(400)?
+                    .write_all(format!("Invalid file: {}", e).as_bytes())?,
+            }
+            Ok(())
+        }),
+    )?;
+
+    // Route for the time spent in each status
+    server.route("/api/stats/summary", Method::Get, |req| {
+        use serde::Serializer as _;
+        use std::io::Write;
+
+        // Two weeks of days and two days of hours, streamed as they are
+        // serialized, straight from the buckets
+        let stats = stats::get();
+        let resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+        let mut body = http_util::Chunked::new(resp);
+        body.write_all(b"{\"week\":")?;
+        serde_json::to_writer(&mut body, &stats.last_days(7))?;
+        body.write_all(b",\"days\":")?;
+        serde_json::Serializer::new(&mut body).collect_seq(stats.days())?;
+        body.write_all(b",\"hours\":")?;
+        serde_json::Serializer::new(&mut body).collect_seq(stats.hours())?;
+        body.write_all(b"}")?;
+        body.finish()?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Routes for the pomodoro timer
+    server.route("/api/pomodoro", Method::Get, |req| {
+        let body = match pomodoro::state() {
+            Some(state) => serde_json::json!({
+                "running": true,
+                "phase": state.phase,
+                "cycle": state.cycle,
+                "remaining_secs": state.remaining_secs,
+            }),
+            None => serde_json::json!({ "running": false }),
+        };
+
+        let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+        resp.write_all(&serde_json::to_vec(&body)?)?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    server.route(
+        "/api/pomodoro",
+        Method::Post,
+        auth::admin(secure, |mut req| {
+            use embedded_svc::io::Read;
+            use serde::Deserialize;
+
+            #[derive(Deserialize)]
+            struct PomodoroData {
+                action: String,
+            }
+
+            let len = req.content_len().unwrap_or(0) as usize;
+
+            if len > MAX_LEN {
+                req.into_status_response(413)?
+                    .write_all("Request too big".as_bytes())?;
+                return Ok(());
+            }
+
+            let mut buf = vec![0; len];
+            req.read_exact(&mut buf)?;
+            let data = http_util::parse_body::<PomodoroData>(req.content_type(), &buf);
+            let mut resp = req.into_ok_response()?;
+
+            if let Some(data) = data {
+                match data.action.as_str() {
+                    "start" => {
+                        pomodoro::start();
+                        resp.write_all("Pomodoro started".as_bytes())?;
+                    }
+                    "stop" => {
+                        pomodoro::stop();
+                        resp.write_all("Pomodoro stopped".as_bytes())?;
+                    }
+                    _ => {
+                        resp.write_all("Invalid action".as_bytes())?;
+                    }
+                }
+            } else {
+                resp.write_all("JSON or form error".as_bytes())?;
+            }
+
+            Ok(())
+        }),
+    )?;
+
+    // Route for the last tapped badge
+    server.route(
+        "/api/rfid",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let body = serde_json::json!({ "last_uid": rfid::last_uid() });
+            let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+            resp.write_all(body.to_string().as_bytes())?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    // Routes for the IR remote
+    server.route(
+        "/api/ir",
+        Method::Get,
+        auth::admin(secure, |req| {
+            let body = serde_json::json!({
+                "learning": ir::is_learning(),
+                "last_code": ir::last_code(),
+            });
+            let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+            resp.write_all(body.to_string().as_bytes())?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    server.route(
+        "/api/ir/learn",
+        Method::Post,
+        auth::admin(secure, |req| {
+            ir::start_learning();
+
+            req.into_ok_response()?
+                .write_all("Learning for 30 seconds".as_bytes())?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    // Route for calibrating the status cube; it must lie face up
+    server.route(
+        "/api/cube/calibrate",
+        Method::Post,
+        auth::admin(secure, |req| {
+            cube::calibrate();
+
+            req.into_ok_response()?
+                .write_all("Calibrating".as_bytes())?;
+            Ok::<(), anyhow::Error>(())
+        }),
+    )?;
+
+    // Route for health checks
+    server.route("/health", Method::Get, |req| {
+        let network = hal::SystemNetwork;
+        // SAFETY: reads the heap allocator's counters
+        let free_heap = unsafe { esp_idf_svc::sys::esp_get_free_heap_size() };
+        let watermarks = memory::sample();
+        let body = serde_json::json!({
+            "uptime_secs": device::uptime().as_secs(),
+            "ip": network.ip(),
+            "rssi": network.rssi(),
+            "free_heap": free_heap,
+            "min_free_heap": watermarks.min_free_heap,
+            "tasks": watermarks.tasks,
+            "memory_low": memory::low(),
+            "battery": battery::level(),
+            "battery_low": battery::is_low(),
+            "drivers": board::drivers(),
+            "outbox": notify::queue_depth(),
+            "clock_synced": !clock::sync_lost(),
+            "temperature": temperature::celsius(),
+            "temperature_high": temperature::is_high(),
+            "reset_reason": safe_mode::reason(),
+            "crashes": safe_mode::crashes(),
+            "safe_mode": false,
+        });
+        let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+        resp.write_all(body.to_string().as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Route for the battery level
+    server.route("/api/battery", Method::Get, |req| {
+        let body = serde_json::json!({
+            "level": battery::level(),
+            "low": battery::is_low(),
+        });
+        let mut resp = req.into_response(200, None, &[("Content-Type", "application/json")])?;
+        resp.write_all(body.to_string().as_bytes())?;
+        Ok::<(), anyhow::Error>(())
+    })?;
+
+    // Routes for the handlers' latency
+    metrics::register(server)?;
+
+    // Route for the FreeRTOS tasks
+    tasks::register(server)?;
+
+    // Routes for the first-boot setup wizard
+    setup::register(server)?;
+
+    // Routes for inbound webhooks
+    hooks::register(server)?;
+
+    // Routes for the emulated Hue bridge; after the device's own, as they
+    // catch all of /api/*
+    hue_emulation::register(server)?;
+
+    // Long polling for status changes, answered from its own thread
+    long_poll::register(server)?;
+
+    // 404 for everything else; last, as it catches all paths
+    errors::register(server)?;
+
+    Ok(())
+}
+
+/// The status details of `/api/status`.
+fn status_json() -> serde_json::Value {
+    let back_at = status::back_at();
+    serde_json::json!({
+        "status": status::current(),
+        "version": status::version(),
+        "selected": status::selected(),
+        "working_hours": schedule::in_working_hours(),
+        "snooze_remaining_secs": snooze::remaining().map_or(0, |r| r.as_secs()),
+        "back_at": back_at.as_ref().map(|b| b.time.clone()),
+        "back_in_secs": back_at.as_ref().map(|b| b.remaining().as_secs()),
+        "source": status::source(),
+        "lease_remaining_secs": status::lease_remaining().map(|r| r.as_secs()),
+        "door_open": door::is_open(),
+        "transition": status::transition().map(|(step, to, remaining)| {
+            serde_json::json!({ "step": step.label, "to": to, "remaining_secs": remaining })
+        }),
+    })
+}
+
+/// A status change from `POST /status` or `GET /set`.
+#[derive(serde::Deserialize)]
+struct StatusRequest {
+    status: String,
+    back_at: Option<String>,
+    /// Instead of `back_at`, minutes from now.
+    minutes: Option<u32>,
+    user: Option<String>,
+    source: Option<String>,
+    /// Withdraws the claim unless it is made again within this many
+    /// seconds, for automated sources.
+    ttl_secs: Option<u32>,
+}
+
+/// Applies a status change, returning the reply for the client. With
+/// `if_match`, the device's status only changes if it still has one of
+/// the versions in it; otherwise the version it has is returned.
+fn set_status(request: StatusRequest, if_match: Option<&str>) -> Result<String, u64> {
+    let back_at = match (request.back_at.as_deref(), request.minutes) {
+        (Some(time), _) => Some(status::BackAt::parse(time)),
+        (None, Some(minutes)) => Some(status::BackAt::in_minutes(minutes)),
+        (None, None) => None,
+    };
+    let source = request.source.as_deref();
+    let Some(source) = source.map_or(Some(Source::Manual), Source::parse) else {
+        return Ok("Invalid source".to_string());
+    };
+    let lease = request
+        .ttl_secs
+        .map(|secs| std::time::Duration::from_secs(secs.into()));
+    if lease.is_some_and(|lease| lease.is_zero()) {
+        return Ok("Invalid ttl_secs".to_string());
+    }
+    let user = request.user.as_deref();
+    if lease.is_some() && user.is_some() {
+        return Ok("ttl_secs is not supported for users".to_string());
+    }
+    let reply = match (Status::parse(&request.status), back_at, user) {
+        (None, _, _) => "Invalid status".to_string(),
+        // People's statuses have no "back at" time
+        (Some(_), Some(_), Some(_)) => "back_at is not supported for users".to_string(),
+        (Some(new_status), None, Some(user)) => match users::set(user, new_status) {
+            Some(_) => format!("{} set to {}", user, new_status.label()),
+            None => "Unknown user".to_string(),
+        },
+        (Some(_), Some(None), None) => {
+            "Invalid back_at or minutes, or clock not synchronized".to_string()
+        }
+        (Some(new_status), back_at, None) => {
+            let back_at = back_at.flatten();
+            match if_match {
+                Some(if_match) => {
+                    status::set_if(source, new_status, back_at, lease, |version| {
+                        http_util::if_match(if_match, &http_util::etag(version))
+                    })?;
+                }
+                None => {
+                    status::set_with_lease(source, new_status, back_at, lease);
+                }
+            }
+            // A source of higher priority may hold its status
+            match status::source() {
+                Some(winner) if winner != source => format!(
+                    "Status kept at {} by {}",
+                    status::selected().label(),
+                    winner.as_str()
+                ),
+                _ => format!("Status set to {}", new_status.label()),
+            }
+        }
+    };
+    Ok(reply)
+}
+
+#[cfg_attr(not(feature = "ble"), allow(unused_variables))]
+fn connect_wifi(
+    wifi: &mut BlockingWifi<EspWifi<'static>>,
+    displays: &mut display::Displays,
+) -> anyhow::Result<()> {
+    // Credentials saved on the device win over the ones built in, so the
+    // setup access point can replace them
+    let wifi_configuration: Configuration = match secrets::wifi() {
+        Some((ssid, password)) => client_configuration(&ssid, &password),
+        None => match (SSID, PASSWORD) {
+            (Some(ssid), Some(password)) => client_configuration(ssid, password),
+            #[cfg(feature = "ble")]
+            _ => {
+                // Credentials come from the provisioning app, which leaves
+                // them with the driver; they move to the secrets
+                provisioning::ensure_provisioned(displays)?;
+                let configuration = wifi.get_configuration()?;
+                if let Configuration::Client(client) = &configuration {
+                    secrets::set_wifi(&client.ssid, &client.password)?;
+                }
+                provisioning::forget_driver_credentials()?;
+                configuration
+            }
+            #[cfg(not(feature = "ble"))]
+            _ => anyhow::bail!("No WiFi credentials"),
+        },
+    };
+
+    secrets::keep_wifi_in_ram()?;
+    wifi.set_configuration(&wifi_configuration)?;
+    wifi.start()?;
+    info!("Wifi started");
+    if let Err(e) = power::configure_wifi() {
+        warn!("Failed to configure WiFi power save: {:?}", e);
+    }
+    wifi.connect()?;
+    info!("Wifi connected");
+    wifi.wait_netif_up()?;
+    info!("Wifi netif up");
+
+    Ok(())
+}
+
+fn client_configuration(ssid: &str, password: &str) -> Configuration {
+    Configuration::Client(ClientConfiguration {
+        ssid: ssid.try_into().unwrap(),
+        bssid: None,
+        auth_method: if password.is_empty() {
+            AuthMethod::None
+        } else {
+            AuthMethod::WPA2Personal
+        },
+        password: password.try_into().unwrap(),
+        channel: None,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,63 @@
+//! Matrix notifications.
+//!
+//! Posts `m.notice` messages to a room through the client-server API.
+//! Enabled when `MATRIX_HOMESERVER`, `MATRIX_ACCESS_TOKEN` and
+//! `MATRIX_ROOM_ID` are set at build time.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use embedded_svc::http::Method;
+use serde_json::json;
+
+use crate::http_client;
+
+const HOMESERVER: Option<&str> = option_env!("MATRIX_HOMESERVER");
+const ACCESS_TOKEN: Option<&str> = option_env!("MATRIX_ACCESS_TOKEN");
+const ROOM_ID: Option<&str> = option_env!("MATRIX_ROOM_ID");
+
+// Transaction IDs must be unique per access token, so they combine a
+// per-boot random prefix with a counter.
+static TXN_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+pub fn is_enabled() -> bool {
+    HOMESERVER.is_some() && ACCESS_TOKEN.is_some() && ROOM_ID.is_some()
+}
+
+/// A transaction id for a new message. The homeserver posts a message only
+/// once per id, however often it is sent.
+pub fn new_txn_id() -> String {
+    format!(
+        "busier-{:08x}-{}",
+        boot_nonce(),
+        TXN_COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// Posts a notice to the configured room, with the id from [`new_txn_id`]
+/// it was queued with.
+pub fn send_notice(body: &str, txn_id: &str) -> anyhow::Result<()> {
+    let (Some(homeserver), Some(token), Some(room_id)) = (HOMESERVER, ACCESS_TOKEN, ROOM_ID) else {
+        return Ok(());
+    };
+
+    let url = format!(
+        "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+        homeserver.trim_end_matches('/'),
+        http_client::encode_path_segment(room_id),
+        txn_id
+    );
+    let auth = format!("Bearer {}", token);
+
+    http_client::send_json(
+        Method::Put,
+        &url,
+        &[("Authorization", &auth)],
+        &json!({ "msgtype": "m.notice", "body": body }),
+    )
+}
+
+fn boot_nonce() -> u32 {
+    static NONCE: std::sync::OnceLock<u32> = std::sync::OnceLock::new();
+    // SAFETY: esp_random has no preconditions
+    *NONCE.get_or_init(|| unsafe { esp_idf_svc::sys::esp_random() })
+}
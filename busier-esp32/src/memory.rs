@@ -0,0 +1,124 @@
+//! Heap and stack watermarks.
+//!
+//! A background thread samples the least free heap since startup and the
+//! least unused stack of every task in [`crate::tasks`], the real numbers
+//! behind the stack sizes guessed for the web server and the other threads.
+//! Whatever goes below the thresholds of `memory` in the configuration is
+//! logged, marked on the display and, with `memory.mqtt_url` set, published
+//! to an MQTT broker; see [`busier_core::watermark`].
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use busier_core::config::MemoryConfig;
+use busier_core::watermark::{self, TaskStack, Watermarks};
+use esp_idf_svc::mqtt::client::{EspMqttClient, MqttClientConfiguration, QoS};
+use esp_idf_svc::sys;
+use log::{info, warn};
+
+use crate::config;
+use crate::tasks;
+
+const MEMORY_STACK_SIZE: usize = 4096;
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+
+// What was below the thresholds at the last sample
+static LOW: Mutex<Vec<String>> = Mutex::new(Vec::new());
+// The broker's URL and the client connected to it
+static CLIENT: Mutex<Option<(String, EspMqttClient<'static>)>> = Mutex::new(None);
+
+/// Whether the heap or a task's stack went below its threshold.
+pub fn is_low() -> bool {
+    !LOW.lock().unwrap().is_empty()
+}
+
+/// "heap" and the names of the tasks that went below their thresholds.
+pub fn low() -> Vec<String> {
+    LOW.lock().unwrap().clone()
+}
+
+/// The least free heap and the least unused stack of each task so far.
+pub fn sample() -> Watermarks {
+    // SAFETY: reads the heap allocator's counters
+    let min_free_heap = unsafe { sys::esp_get_minimum_free_heap_size() };
+
+    let tasks = tasks::list()
+        .into_iter()
+        .map(|task| TaskStack {
+            name: task.name,
+            headroom: task.stack_headroom,
+        })
+        .collect();
+
+    Watermarks {
+        min_free_heap,
+        tasks,
+    }
+}
+
+/// Spawns the sampling thread.
+pub fn start() -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("memory".into())
+        .stack_size(MEMORY_STACK_SIZE)
+        .spawn(|| loop {
+            let config = config::get().memory;
+            let watermarks = sample();
+            let low = watermarks.low(&config);
+
+            let newly_low = watermark::newly_low(&LOW.lock().unwrap(), &low);
+            if !newly_low.is_empty() {
+                warn!(
+                    "Low memory: {} (least free heap {} bytes)",
+                    newly_low.join(", "),
+                    watermarks.min_free_heap
+                );
+                if let Err(e) = publish(&config, &watermarks, &newly_low) {
+                    warn!("Failed to publish the memory alert: {:?}", e);
+                }
+            }
+            *LOW.lock().unwrap() = low;
+
+            std::thread::sleep(SAMPLE_INTERVAL);
+        })?;
+
+    Ok(())
+}
+
+// Publishes an alert to `memory.mqtt_topic`, connecting to the broker first
+// or again when its URL changed
+fn publish(config: &MemoryConfig, watermarks: &Watermarks, low: &[String]) -> anyhow::Result<()> {
+    if config.mqtt_url.is_empty() {
+        return Ok(());
+    }
+
+    let mut client = CLIENT.lock().unwrap();
+    if client.as_ref().map(|(url, _)| url) != Some(&config.mqtt_url) {
+        let device = config::get().device_name().to_string();
+        let conf = MqttClientConfiguration {
+            client_id: Some(device.as_str()),
+            username: Some(config.mqtt_username.as_str()).filter(|user| !user.is_empty()),
+            password: Some(config.mqtt_password.as_str()).filter(|pw| !pw.is_empty()),
+            ..Default::default()
+        };
+        let mqtt = EspMqttClient::new_cb(&config.mqtt_url, &conf, |_| {})?;
+        info!("Memory alerts go to {}", config.mqtt_url);
+        *client = Some((config.mqtt_url.clone(), mqtt));
+    }
+
+    let body = serde_json::json!({
+        "device": config::get().device_name(),
+        "low": low,
+        "min_free_heap": watermarks.min_free_heap,
+        "tasks": watermarks.tasks,
+    });
+    // Queued until the client is connected
+    let (_, mqtt) = client.as_mut().unwrap();
+    mqtt.enqueue(
+        &config.mqtt_topic,
+        QoS::AtLeastOnce,
+        false,
+        body.to_string().as_bytes(),
+    )?;
+    Ok(())
+}
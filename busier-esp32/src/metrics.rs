@@ -0,0 +1,143 @@
+//! How long the request handlers take, per route.
+//!
+//! Every route registered through [`crate::http_util::Routes`] is timed
+//! from the start of its handler to the end of its response, and counted in
+//! a histogram of [`busier_core::latency`] under its method and path as
+//! registered, so `/api/hooks/*` is one route whatever the hook.
+//! `GET /metrics` exports the histograms for Prometheus, `GET /api/stats`
+//! as p50, p95 and max per route.
+
+use std::fmt::Write as _;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use busier_core::latency::Histogram;
+use embedded_svc::http::server::Request;
+use embedded_svc::http::Method;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::{EspHttpConnection, EspHttpServer};
+
+use crate::http_util::{self, Routes};
+
+// The histogram of each route by method and path, in registration order
+static ROUTES: Mutex<Vec<(Method, String, Histogram)>> = Mutex::new(Vec::new());
+
+/// Wraps a handler so that its duration is counted for the route.
+pub fn timed<F>(
+    uri: &str,
+    method: Method,
+    handler: F,
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static
+where
+    F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> anyhow::Result<()> + Send + 'static,
+{
+    // Both listeners register the same routes; they share a histogram
+    let mut routes = ROUTES.lock().unwrap();
+    let index = match routes
+        .iter()
+        .position(|(m, path, _)| *m == method && path == uri)
+    {
+        Some(index) => index,
+        None => {
+            routes.push((method, uri.to_string(), Histogram::new()));
+            routes.len() - 1
+        }
+    };
+    drop(routes);
+
+    move |req| {
+        let start = Instant::now();
+        let result = handler(req);
+        let us = u32::try_from(start.elapsed().as_micros()).unwrap_or(u32::MAX);
+        ROUTES.lock().unwrap()[index].2.record(us);
+        result
+    }
+}
+
+/// Registers `GET /metrics` and `GET /api/stats`.
+pub fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    server.route("/metrics", Method::Get, |req| {
+        req.into_response(200, None, &[("Content-Type", "text/plain; version=0.0.4")])?
+            .write_all(prometheus().as_bytes())?;
+        Ok(())
+    })?;
+
+    server.route("/api/stats", Method::Get, |req| {
+        let routes: Vec<_> = ROUTES
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, _, histogram)| histogram.count() > 0)
+            .map(|(method, path, histogram)| {
+                serde_json::json!({
+                    "method": http_util::method_name(*method),
+                    "route": path,
+                    "count": histogram.count(),
+                    "p50_ms": millis(histogram.percentile(50)),
+                    "p95_ms": millis(histogram.percentile(95)),
+                    "max_ms": millis(histogram.max_us()),
+                })
+            })
+            .collect();
+        let body = serde_json::json!({ "routes": routes });
+        req.into_response(200, None, &[("Content-Type", "application/json")])?
+            .write_all(body.to_string().as_bytes())?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn millis(us: u32) -> f64 {
+    f64::from(us) / 1000.0
+}
+
+// The histograms in the Prometheus text format, with the longest duration
+// of each route as a gauge
+fn prometheus() -> String {
+    const NAME: &str = "busier_http_request_duration_seconds";
+    let routes = ROUTES.lock().unwrap();
+    let mut text = String::new();
+
+    let _ = writeln!(text, "# HELP {} Time spent answering a route.", NAME);
+    let _ = writeln!(text, "# TYPE {} histogram", NAME);
+    for (method, path, histogram) in routes.iter() {
+        let labels = format!(
+            "method=\"{}\",route=\"{}\"",
+            http_util::method_name(*method),
+            path
+        );
+        for (bound, count) in histogram.cumulative() {
+            let le = bound.map_or("+Inf".to_string(), |us| seconds(u64::from(us)));
+            let _ = writeln!(
+                text,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                NAME, labels, le, count
+            );
+        }
+        let sum = seconds(histogram.sum_us());
+        let _ = writeln!(text, "{}_sum{{{}}} {}", NAME, labels, sum);
+        let _ = writeln!(text, "{}_count{{{}}} {}", NAME, labels, histogram.count());
+    }
+
+    let _ = writeln!(
+        text,
+        "# HELP {}_max Longest time spent answering a route.",
+        NAME
+    );
+    let _ = writeln!(text, "# TYPE {}_max gauge", NAME);
+    for (method, path, histogram) in routes.iter() {
+        let method = http_util::method_name(*method);
+        let max = seconds(u64::from(histogram.max_us()));
+        let _ = writeln!(
+            text,
+            "{}_max{{method=\"{}\",route=\"{}\"}} {}",
+            NAME, method, path, max
+        );
+    }
+    text
+}
+
+fn seconds(us: u64) -> String {
+    format!("{}.{:06}", us / 1_000_000, us % 1_000_000)
+}
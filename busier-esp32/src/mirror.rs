@@ -0,0 +1,71 @@
+//! Outbound lights that mirror the status.
+//!
+//! A mirror thread polls the status and its style and pushes them to a
+//! device on the network whenever either, or the device's settings, change.
+//! A failed push is retried after a pause rather than every poll. The Hue
+//! and WLED integrations differ only in their settings and in how they push.
+
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::config::{self, Config, StatusStyle};
+use crate::output;
+use crate::status::{self, Status};
+
+const MIRROR_STACK_SIZE: usize = 8192;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Spawns the thread for the mirror `name`. `settings` picks its settings
+/// out of the configuration, None while it is off; `push` sends a status
+/// with its style to the device.
+pub fn start<S, P>(
+    name: &'static str,
+    settings: fn(Config) -> Option<S>,
+    push: P,
+) -> anyhow::Result<()>
+where
+    S: PartialEq + Send + 'static,
+    P: Fn(&S, Status, &StatusStyle) -> anyhow::Result<()> + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name(name.into())
+        .stack_size(MIRROR_STACK_SIZE)
+        .spawn(move || {
+            let mut applied: Option<(Status, StatusStyle, S)> = None;
+            let mut last_failure: Option<Instant> = None;
+
+            loop {
+                std::thread::sleep(POLL_INTERVAL);
+
+                let Some(settings) = settings(config::get()) else {
+                    applied = None;
+                    continue;
+                };
+
+                let current_status = status::current();
+                let wanted = (current_status, output::style(current_status), settings);
+                if applied.as_ref() == Some(&wanted) {
+                    continue;
+                }
+                if last_failure.is_some_and(|at| at.elapsed() < RETRY_INTERVAL) {
+                    continue;
+                }
+
+                match push(&wanted.2, current_status, &wanted.1) {
+                    Ok(()) => {
+                        applied = Some(wanted);
+                        last_failure = None;
+                    }
+                    Err(e) => {
+                        warn!("Mirror {}: update failed: {:?}", name, e);
+                        last_failure = Some(Instant::now());
+                    }
+                }
+            }
+        })?;
+
+    info!("Mirror {} started", name);
+    Ok(())
+}
@@ -0,0 +1,195 @@
+//! Modbus TCP server.
+//!
+//! Serves one client at a time on TCP port 502 (unit id ignored). Holding
+//! registers, also readable as input registers:
+//!
+//! | Register | Value |
+//! | --- | --- |
+//! | 0 | status (0 Free, 1 DND, 2 Away), writable |
+//! | 1 | selected status, ignoring working hours |
+//! | 2-3 | HTTP page requests, high word first |
+//! | 4-5 | uptime in seconds, high word first |
+//! | 6 | WiFi RSSI in dBm, signed |
+//! | 7 | snooze minutes remaining |
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::config;
+use crate::device;
+use crate::snooze;
+use crate::status::{self, Source, Status};
+
+const MODBUS_PORT: u16 = 502;
+const MODBUS_STACK_SIZE: usize = 6144;
+// Drop idle clients so the next one can connect
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const MBAP_HEADER_LEN: usize = 7;
+const MAX_PDU_LEN: usize = 253;
+
+// Function codes
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+// Exception codes
+const ILLEGAL_FUNCTION: u8 = 0x01;
+const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+const ILLEGAL_DATA_VALUE: u8 = 0x03;
+
+const STATUS_REGISTER: u16 = 0;
+const REGISTER_COUNT: u16 = 8;
+// Protocol limit for a single read
+const MAX_READ_COUNT: u16 = 125;
+
+/// Spawns the server thread.
+pub fn start() -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", MODBUS_PORT))?;
+
+    std::thread::Builder::new()
+        .name("modbus".into())
+        .stack_size(MODBUS_STACK_SIZE)
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if !config::get().modbus.enabled {
+                    continue;
+                }
+                if let Err(e) = serve(stream) {
+                    warn!("Modbus connection closed: {:?}", e);
+                }
+            }
+        })?;
+
+    info!("Modbus TCP server listening on port {}", MODBUS_PORT);
+    Ok(())
+}
+
+fn serve(mut stream: TcpStream) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+
+    let mut header = [0; MBAP_HEADER_LEN];
+    let mut pdu = [0; MAX_PDU_LEN];
+    loop {
+        match stream.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+
+        // The length field covers the unit id and the PDU
+        let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if header[2..4] != [0, 0] || len < 2 || len - 1 > MAX_PDU_LEN {
+            anyhow::bail!("Invalid MBAP header");
+        }
+        let pdu = &mut pdu[..len - 1];
+        stream.read_exact(pdu)?;
+
+        let response = respond(pdu);
+        let mut frame = Vec::with_capacity(MBAP_HEADER_LEN + response.len());
+        frame.extend_from_slice(&header[..4]);
+        frame.extend_from_slice(&(response.len() as u16 + 1).to_be_bytes());
+        frame.push(header[6]);
+        frame.extend_from_slice(&response);
+        stream.write_all(&frame)?;
+    }
+}
+
+fn respond(pdu: &[u8]) -> Vec<u8> {
+    let function = pdu[0];
+    let result = match function {
+        READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => read_registers(pdu),
+        WRITE_SINGLE_REGISTER => write_single_register(pdu),
+        WRITE_MULTIPLE_REGISTERS => write_multiple_registers(pdu),
+        _ => Err(ILLEGAL_FUNCTION),
+    };
+
+    match result {
+        Ok(data) => {
+            let mut response = vec![function];
+            response.extend(data);
+            response
+        }
+        Err(exception) => vec![function | 0x80, exception],
+    }
+}
+
+fn read_registers(pdu: &[u8]) -> Result<Vec<u8>, u8> {
+    let (address, count) = address_and_count(pdu)?;
+    if count == 0 || count > MAX_READ_COUNT {
+        return Err(ILLEGAL_DATA_VALUE);
+    }
+    if address as u32 + count as u32 > REGISTER_COUNT as u32 {
+        return Err(ILLEGAL_DATA_ADDRESS);
+    }
+
+    let registers = registers();
+    let mut data = vec![(count * 2) as u8];
+    for register in &registers[address as usize..(address + count) as usize] {
+        data.extend_from_slice(&register.to_be_bytes());
+    }
+    Ok(data)
+}
+
+fn write_single_register(pdu: &[u8]) -> Result<Vec<u8>, u8> {
+    let (address, value) = address_and_count(pdu)?;
+    write_register(address, value)?;
+    // The response echoes the request
+    Ok(pdu[1..5].to_vec())
+}
+
+fn write_multiple_registers(pdu: &[u8]) -> Result<Vec<u8>, u8> {
+    let (address, count) = address_and_count(pdu)?;
+    let values = pdu.get(6..).ok_or(ILLEGAL_DATA_VALUE)?;
+    if count == 0 || pdu[5] as usize != count as usize * 2 || values.len() != pdu[5] as usize {
+        return Err(ILLEGAL_DATA_VALUE);
+    }
+    for (i, value) in values.chunks_exact(2).enumerate() {
+        write_register(address + i as u16, u16::from_be_bytes([value[0], value[1]]))?;
+    }
+    Ok(pdu[1..5].to_vec())
+}
+
+fn write_register(address: u16, value: u16) -> Result<(), u8> {
+    if address != STATUS_REGISTER {
+        return Err(ILLEGAL_DATA_ADDRESS);
+    }
+    if value > Status::Away as u16 {
+        return Err(ILLEGAL_DATA_VALUE);
+    }
+    status::set(Source::Integration, Status::from_u8(value as u8));
+    Ok(())
+}
+
+// Parses the two big-endian words after the function code
+fn address_and_count(pdu: &[u8]) -> Result<(u16, u16), u8> {
+    let bytes = pdu.get(1..5).ok_or(ILLEGAL_DATA_VALUE)?;
+    Ok((
+        u16::from_be_bytes([bytes[0], bytes[1]]),
+        u16::from_be_bytes([bytes[2], bytes[3]]),
+    ))
+}
+
+fn registers() -> [u16; REGISTER_COUNT as usize] {
+    let requests = crate::REQUEST_COUNTER.load(Ordering::SeqCst);
+    let uptime = device::uptime().as_secs() as u32;
+    let snooze_minutes = snooze::remaining().map_or(0, |d| d.as_secs().div_ceil(60));
+
+    [
+        status::current() as u16,
+        status::selected() as u16,
+        (requests >> 16) as u16,
+        requests as u16,
+        (uptime >> 16) as u16,
+        uptime as u16,
+        device::rssi().unwrap_or(0) as i16 as u16,
+        snooze_minutes.min(u16::MAX as u64) as u16,
+    ]
+}
@@ -0,0 +1,175 @@
+//! Noise_NNpsk0_25519_ChaChaPoly_SHA256, the responder side.
+//!
+//! The encrypted transport of the ESPHome native API. Both sides share a
+//! 32-byte key and neither has a static key pair: the client's one
+//! handshake message carries its ephemeral key, the device's answer its
+//! own, and each direction then has its own ChaCha20-Poly1305 key. The names
+//! below follow the Noise specification, <https://noiseprotocol.org/noise.html>.
+
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, Tag};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const PROTOCOL_NAME: &[u8] = b"Noise_NNpsk0_25519_ChaChaPoly_SHA256";
+const DH_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+
+/// One direction of a session.
+pub struct CipherState {
+    key: [u8; 32],
+    nonce: u64,
+}
+
+impl CipherState {
+    fn new(key: [u8; 32]) -> Self {
+        Self { key, nonce: 0 }
+    }
+
+    pub fn seal(&mut self, ad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let mut buffer = plaintext.to_vec();
+        let tag = ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .encrypt_in_place_detached(&self.next_nonce(), ad, &mut buffer)
+            .expect("ChaCha20-Poly1305 takes any message length");
+        buffer.extend_from_slice(&tag);
+        buffer
+    }
+
+    /// None if the data was not sealed with this key, nonce and `ad`.
+    pub fn open(&mut self, ad: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+        let split = data.len().checked_sub(TAG_LEN)?;
+        let (ciphertext, tag) = data.split_at(split);
+        let mut buffer = ciphertext.to_vec();
+        ChaCha20Poly1305::new(Key::from_slice(&self.key))
+            .decrypt_in_place_detached(&self.next_nonce(), ad, &mut buffer, Tag::from_slice(tag))
+            .ok()?;
+        Some(buffer)
+    }
+
+    // Four zero bytes and the little-endian message counter
+    fn next_nonce(&mut self) -> Nonce {
+        let mut nonce = Nonce::default();
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce += 1;
+        nonce
+    }
+}
+
+/// The keys of an established session, from the device's side.
+pub struct Session {
+    pub receive: CipherState,
+    pub send: CipherState,
+}
+
+/// Answers the client's handshake message. `ephemeral` is 32 random bytes
+/// for the device's ephemeral key. Returns the answer and the session, or
+/// None unless the client used the same key and prologue.
+pub fn respond(
+    psk: &[u8; 32],
+    prologue: &[u8],
+    message: &[u8],
+    ephemeral: [u8; 32],
+) -> Option<(Vec<u8>, Session)> {
+    let mut state = SymmetricState::new(prologue);
+
+    // -> psk, e
+    state.mix_key_and_hash(psk);
+    let remote: [u8; DH_LEN] = message.get(..DH_LEN)?.try_into().ok()?;
+    state.mix_hash(&remote);
+    state.mix_key(&remote);
+    state.decrypt_and_hash(&message[DH_LEN..])?;
+
+    // <- e, ee
+    let secret = StaticSecret::from(ephemeral);
+    let public = PublicKey::from(&secret);
+    state.mix_hash(public.as_bytes());
+    state.mix_key(public.as_bytes());
+    state.mix_key(secret.diffie_hellman(&PublicKey::from(remote)).as_bytes());
+    let mut answer = public.as_bytes().to_vec();
+    answer.extend(state.encrypt_and_hash(&[]));
+
+    let [initiator, responder, _] = hkdf(&state.chaining_key, &[]);
+    let session = Session {
+        receive: CipherState::new(initiator),
+        send: CipherState::new(responder),
+    };
+    Some((answer, session))
+}
+
+struct SymmetricState {
+    chaining_key: [u8; 32],
+    hash: [u8; 32],
+    cipher: Option<CipherState>,
+}
+
+impl SymmetricState {
+    fn new(prologue: &[u8]) -> Self {
+        // The name is longer than a hash, so it starts out hashed
+        let hash: [u8; 32] = Sha256::digest(PROTOCOL_NAME).into();
+        let mut state = Self {
+            chaining_key: hash,
+            hash,
+            cipher: None,
+        };
+        state.mix_hash(prologue);
+        state
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        self.hash = Sha256::new()
+            .chain_update(self.hash)
+            .chain_update(data)
+            .finalize()
+            .into();
+    }
+
+    fn mix_key(&mut self, input: &[u8]) {
+        let [chaining_key, key, _] = hkdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+        self.cipher = Some(CipherState::new(key));
+    }
+
+    fn mix_key_and_hash(&mut self, input: &[u8]) {
+        let [chaining_key, hash, key] = hkdf(&self.chaining_key, input);
+        self.chaining_key = chaining_key;
+        self.mix_hash(&hash);
+        self.cipher = Some(CipherState::new(key));
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let ciphertext = match &mut self.cipher {
+            Some(cipher) => cipher.seal(&self.hash, plaintext),
+            None => plaintext.to_vec(),
+        };
+        self.mix_hash(&ciphertext);
+        ciphertext
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+        let plaintext = match &mut self.cipher {
+            Some(cipher) => cipher.open(&self.hash, ciphertext)?,
+            None => ciphertext.to_vec(),
+        };
+        self.mix_hash(ciphertext);
+        Some(plaintext)
+    }
+}
+
+// HKDF with three outputs; callers that need two ignore the third
+fn hkdf(chaining_key: &[u8; 32], input: &[u8]) -> [[u8; 32]; 3] {
+    let temp_key = hmac(chaining_key, &[input]);
+    let first = hmac(&temp_key, &[&[1]]);
+    let second = hmac(&temp_key, &[&first, &[2]]);
+    let third = hmac(&temp_key, &[&second, &[3]]);
+    [first, second, third]
+}
+
+fn hmac(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
@@ -0,0 +1,218 @@
+//! Outbound notifications.
+//!
+//! HTTP handlers queue events here and a background thread delivers them,
+//! so a slow or unreachable chat server never blocks the web interface.
+//!
+//! Messages wait in an outbox kept in NVS until the chat server takes them,
+//! so a WiFi outage or a reboot loses none; see [`busier_core::outbox`] for
+//! the retries.
+
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use busier_core::outbox::Outbox;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use log::{info, warn};
+
+use crate::i18n;
+use crate::matrix;
+use crate::output::{self, Signal};
+use crate::schedule;
+use crate::snooze;
+use crate::status::{Status, StatusLabel};
+
+// TLS handshakes need a generous stack
+const NOTIFY_STACK_SIZE: usize = 12288;
+
+const NAMESPACE: &str = "outbox";
+const KEY: &str = "queue";
+// Upper bound for the serialized outbox
+const MAX_OUTBOX_LEN: usize = 8192;
+
+/// Something worth telling the outside world about.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// The selected status was changed, the device's own or a person's.
+    StatusChanged {
+        status: Status,
+        user: Option<String>,
+    },
+    /// Someone knocked via the web interface.
+    Knock,
+    /// A visitor left a message on the guest page.
+    Message { text: String },
+    /// Someone rang the doorbell; urgently to override Do Not Disturb.
+    #[cfg_attr(not(feature = "doorbell"), allow(dead_code))]
+    Doorbell { urgent: bool },
+}
+
+impl Event {
+    /// Human-readable message used by chat integrations.
+    pub fn message(&self) -> String {
+        match self {
+            Event::StatusChanged { status, user: None } => {
+                i18n::format("notify.status_changed", &[&status.label()])
+            }
+            Event::StatusChanged {
+                status,
+                user: Some(user),
+            } => i18n::format("notify.user_status_changed", &[user, &status.label()]),
+            Event::Knock => i18n::text("notify.knock").to_string(),
+            Event::Message { text } => i18n::format("notify.message", &[text]),
+            Event::Doorbell { urgent: false } => i18n::text("notify.doorbell").to_string(),
+            Event::Doorbell { urgent: true } => i18n::text("notify.doorbell_urgent").to_string(),
+        }
+    }
+}
+
+static SENDER: OnceLock<mpsc::Sender<Event>> = OnceLock::new();
+static OUTBOX: Mutex<Outbox> = Mutex::new(Outbox::new());
+static NVS: Mutex<Option<EspNvs<NvsDefault>>> = Mutex::new(None);
+
+/// Restores the messages that were not delivered before the restart.
+pub fn init(partition: EspDefaultNvsPartition) -> anyhow::Result<()> {
+    let nvs = EspNvs::new(partition, NAMESPACE, true)?;
+
+    let mut buf = vec![0; MAX_OUTBOX_LEN];
+    let mut migrated = false;
+    if let Some(data) = nvs.get_raw(KEY, &mut buf)? {
+        match serde_json::from_slice::<Outbox>(data) {
+            // A build without a chat server has nowhere to deliver them
+            Ok(mut outbox) if matrix::is_enabled() => {
+                migrated = outbox.assign_txn_ids(matrix::new_txn_id);
+                if !outbox.is_empty() {
+                    info!("{} notifications waiting for delivery", outbox.len());
+                }
+                *OUTBOX.lock().unwrap() = outbox;
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Stored outbox is invalid, discarding it: {:?}", e),
+        }
+    }
+
+    *NVS.lock().unwrap() = Some(nvs);
+    if migrated {
+        save(&OUTBOX.lock().unwrap());
+    }
+    Ok(())
+}
+
+/// Starts the notification thread. Events sent before this are dropped.
+pub fn start() -> anyhow::Result<()> {
+    let (tx, rx) = mpsc::channel::<Event>();
+
+    std::thread::Builder::new()
+        .name("notify".into())
+        .stack_size(NOTIFY_STACK_SIZE)
+        .spawn(move || {
+            let mut retry_at = None;
+            loop {
+                deliver(&mut retry_at);
+
+                // Wait for the next event, or until the next retry is due
+                let event = match retry_at {
+                    None => match rx.recv() {
+                        Ok(event) => event,
+                        Err(_) => return,
+                    },
+                    Some(at) => {
+                        match rx.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                            Ok(event) => event,
+                            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                        }
+                    }
+                };
+
+                if matrix::is_enabled() {
+                    let mut outbox = OUTBOX.lock().unwrap();
+                    if !outbox.push(event.message(), matrix::new_txn_id()) {
+                        warn!("Outbox full, dropped the oldest notification");
+                    }
+                    save(&outbox);
+                }
+            }
+        })?;
+
+    SENDER
+        .set(tx)
+        .map_err(|_| anyhow::anyhow!("Notification thread already started"))?;
+
+    Ok(())
+}
+
+/// Queues an event for delivery. Events outside working hours or while
+/// snoozed are dropped, except urgent rings of the doorbell.
+pub fn send(event: Event) {
+    let urgent = matches!(event, Event::Doorbell { urgent: true });
+    if !urgent && (!schedule::in_working_hours() || snooze::is_active()) {
+        return;
+    }
+
+    output::signal(match event {
+        Event::StatusChanged { .. } => Signal::StatusChanged,
+        Event::Knock | Event::Message { .. } | Event::Doorbell { .. } => Signal::Knock,
+    });
+
+    if let Some(tx) = SENDER.get() {
+        let _ = tx.send(event);
+    }
+}
+
+/// Messages waiting for delivery, for `/health`.
+pub fn queue_depth() -> usize {
+    OUTBOX.lock().unwrap().len()
+}
+
+// Sends the queued messages in order until one fails, unless a retry is
+// still pending
+fn deliver(retry_at: &mut Option<Instant>) {
+    if retry_at.is_some_and(|at| Instant::now() < at) {
+        return;
+    }
+    *retry_at = None;
+
+    loop {
+        let Some(delivery) = OUTBOX.lock().unwrap().front().cloned() else {
+            return;
+        };
+
+        // Not holding the lock, as sending can take seconds
+        let result = matrix::send_notice(&delivery.message, &delivery.txn_id);
+
+        let mut outbox = OUTBOX.lock().unwrap();
+        match result {
+            Ok(()) => {
+                outbox.delivered();
+                save(&outbox);
+            }
+            Err(e) => {
+                let secs = outbox.failed();
+                warn!("Notification failed, retrying in {} s: {:?}", secs, e);
+                *retry_at = Some(Instant::now() + Duration::from_secs(secs.into()));
+                return;
+            }
+        }
+    }
+}
+
+// Only changes to the queue are saved, not each failed attempt, to spare
+// the flash during long outages
+fn save(outbox: &Outbox) {
+    let data = match serde_json::to_vec(outbox) {
+        Ok(data) => data,
+        Err(e) => {
+            warn!("Failed to serialize the outbox: {:?}", e);
+            return;
+        }
+    };
+    if data.len() > MAX_OUTBOX_LEN {
+        warn!("Outbox too large to save ({} bytes)", data.len());
+        return;
+    }
+    if let Some(nvs) = NVS.lock().unwrap().as_mut() {
+        if let Err(e) = nvs.set_raw(KEY, &data) {
+            warn!("Failed to save the outbox: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Signed firmware updates over the air.
+//!
+//! `POST /api/ota` streams a firmware image into the spare OTA partition
+//! while hashing it, and only switches the boot partition once the
+//! `X-Signature` header holds a valid ECDSA P-256 signature of the image
+//! under the public key built into the firmware. Unsigned or tampered
+//! images are discarded, and a build without `OTA_PUBLIC_KEY` takes no
+//! updates at all. The admin page's form sends the image as a
+//! `multipart/form-data` file instead, with the signature in a `signature`
+//! field; the image is written out as it arrives either way.
+
+use busier_core::multipart::{self, Event, Parser};
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::server::EspHttpServer;
+use esp_idf_svc::ota::{EspOta, EspOtaUpdate};
+use log::{info, warn};
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::auth;
+use crate::device;
+use crate::http_util::{self, Routes};
+
+// Hex of the SEC1 encoded public key, uncompressed or compressed
+const PUBLIC_KEY: Option<&str> = option_env!("OTA_PUBLIC_KEY");
+const SIGNATURE_HEADER: &str = "X-Signature";
+const SIGNATURE_FIELD: &str = "signature";
+// Hex of a DER signature takes at most 144 characters
+const MAX_SIGNATURE_LEN: usize = 256;
+// Bytes of the image read at a time
+const CHUNK_LEN: usize = 4096;
+
+/// Registers `POST /api/ota` for the admin.
+pub fn register(server: &mut EspHttpServer<'static>, secure: bool) -> anyhow::Result<()> {
+    server.route(
+        "/api/ota",
+        Method::Post,
+        auth::admin(secure, |mut req| {
+            let Some(key) = public_key() else {
+                req.into_status_response(403)?
+                    .write_all("This build has no OTA_PUBLIC_KEY".as_bytes())?;
+                return Ok(());
+            };
+            // A raw image needs its signature up front, a form may bring it
+            // after the file
+            let is_form = req.content_type().and_then(multipart::boundary).is_some();
+            let header = req.header(SIGNATURE_HEADER).map(str::to_string);
+            if header.is_none() && !is_form {
+                req.into_status_response(400)?
+                    .write_all("Missing or invalid X-Signature".as_bytes())?;
+                return Ok(());
+            }
+
+            let mut ota = EspOta::new()?;
+            let mut update = ota.initiate_update()?;
+            let (hash, field) = match receive(&mut req, &mut update) {
+                Ok(received) => received,
+                Err(e) => {
+                    update.abort()?;
+                    req.into_status_response(400)?
+                        .write_all(format!("Invalid upload: {}", e).as_bytes())?;
+                    return Ok(());
+                }
+            };
+
+            let signature = header
+                .or(field)
+                .and_then(|hex| http_util::decode_hex(hex.trim()))
+                .and_then(|der| Signature::from_der(&der).ok());
+            let Some(signature) = signature else {
+                update.abort()?;
+                req.into_status_response(400)?
+                    .write_all("Missing or invalid signature".as_bytes())?;
+                return Ok(());
+            };
+
+            if key.verify_prehash(&hash, &signature).is_err() {
+                update.abort()?;
+                warn!("Rejected a firmware update with a bad signature");
+                req.into_status_response(403)?
+                    .write_all("The signature does not match the image".as_bytes())?;
+                return Ok(());
+            }
+
+            update.complete()?;
+            info!("Firmware update verified, restarting");
+            req.into_ok_response()?
+                .write_all("Firmware updated, restarting".as_bytes())?;
+            device::restart()
+        }),
+    )?;
+
+    Ok(())
+}
+
+// The form part being read
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Image,
+    Signature,
+    Other,
+}
+
+// Writes the image to `update` as it arrives: the raw body, or the first
+// file of a form. Returns the image's SHA-256 and the form's signature
+// field, if it has one.
+fn receive<R>(req: &mut R, update: &mut EspOtaUpdate) -> anyhow::Result<([u8; 32], Option<String>)>
+where
+    R: Read + Headers,
+{
+    let mut parser = req
+        .content_type()
+        .and_then(multipart::boundary)
+        .map(Parser::new);
+    let mut hasher = Sha256::new();
+    let mut field = Field::Other;
+    let mut has_image = false;
+    let mut signature: Option<Vec<u8>> = None;
+    // A failed write inside the parser's callback, reported after it
+    let mut failed: Option<anyhow::Error> = None;
+
+    let mut buf = vec![0; CHUNK_LEN];
+    loop {
+        let len = req
+            .read(&mut buf)
+            .map_err(|e| anyhow::anyhow!("Failed to read the image: {:?}", e))?;
+        if len == 0 {
+            break;
+        }
+        let Some(parser) = parser.as_mut() else {
+            hasher.update(&buf[..len]);
+            update.write_all(&buf[..len])?;
+            continue;
+        };
+        parser
+            .feed(&buf[..len], |event| match event {
+                Event::Part(part) if part.filename.is_some() && !has_image => {
+                    has_image = true;
+                    field = Field::Image;
+                }
+                Event::Part(part) if part.name == SIGNATURE_FIELD => {
+                    signature = Some(Vec::new());
+                    field = Field::Signature;
+                }
+                Event::Part(_) => field = Field::Other,
+                Event::Data(_) if failed.is_some() => {}
+                Event::Data(data) => match (field, signature.as_mut()) {
+                    (Field::Image, _) => {
+                        hasher.update(data);
+                        failed = update.write_all(data).err().map(Into::into);
+                    }
+                    (Field::Signature, Some(signature)) => {
+                        signature.extend_from_slice(data);
+                        if signature.len() > MAX_SIGNATURE_LEN {
+                            failed = Some(anyhow::anyhow!(
+                                "signature over {} bytes",
+                                MAX_SIGNATURE_LEN
+                            ));
+                        }
+                    }
+                    _ => {}
+                },
+            })
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        if let Some(e) = failed.take() {
+            return Err(e);
+        }
+    }
+
+    if let Some(parser) = parser {
+        parser.finish().map_err(|e| anyhow::anyhow!("{}", e))?;
+        anyhow::ensure!(has_image, "no image in the form");
+    }
+    let signature = signature.map(|hex| String::from_utf8_lossy(&hex).into_owned());
+    Ok((hasher.finalize().into(), signature))
+}
+
+fn public_key() -> Option<VerifyingKey> {
+    let key = http_util::decode_hex(PUBLIC_KEY?.trim())?;
+    VerifyingKey::from_sec1_bytes(&key).ok()
+}
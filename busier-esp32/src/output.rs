@@ -0,0 +1,265 @@
+//! Buzzer, LED and relay outputs.
+//!
+//! Every audible or bright-light output goes through this module so that
+//! quiet hours are enforced in one place, and the drivers take the color,
+//! LED, relay and ringtone of each status from [`style`] rather than
+//! deciding themselves. The LED is lit for the statuses whose style says so,
+//! Do Not Disturb by default, unless a rule or transition step says
+//! otherwise, and flashes on knocks; the buzzer beeps on knocks and status
+//! changes, or plays the RTTTL ringtone of the new status or the one
+//! configured in `buzzer`, unless an I2S chime or the vibration motor
+//! replaces it. The relay output is on for the statuses whose style says so,
+//! or else those in `relay.statuses`, either as a level or as a short pulse
+//! on every change for lamps with a toggle input.
+
+use std::sync::{mpsc, OnceLock};
+use std::time::Duration;
+
+use esp_idf_svc::hal::gpio::{AnyIOPin, AnyOutputPin, InputOutput, Output, PinDriver};
+use esp_idf_svc::hal::ledc::LedcDriver;
+use esp_idf_svc::sys::{
+    esp, ledc_get_freq, ledc_mode_t_LEDC_LOW_SPEED_MODE, ledc_set_freq, ledc_timer_t_LEDC_TIMER_0,
+};
+use log::warn;
+
+use crate::chime;
+use crate::config::{self, StatusStyle};
+use crate::haptic;
+use crate::rtttl::{self, Note};
+use crate::rules;
+use crate::schedule;
+use crate::status::{self, Status};
+
+const OUTPUT_STACK_SIZE: usize = 4096;
+// How often the steady LED state is refreshed when idle
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+const BEEP_DURATION: Duration = Duration::from_millis(120);
+const PAUSE_DURATION: Duration = Duration::from_millis(120);
+
+/// A short attention signal.
+#[derive(Clone, Copy, Debug)]
+pub enum Signal {
+    Knock,
+    StatusChanged,
+}
+
+static SENDER: OnceLock<mpsc::Sender<Signal>> = OnceLock::new();
+
+struct Outputs {
+    // None in builds without the `buzzer` feature
+    buzzer: Option<LedcDriver<'static>>,
+    led: PinDriver<'static, AnyOutputPin, Output>,
+    relay: PinDriver<'static, AnyIOPin, InputOutput>,
+    // Whether the relay's lamp is meant to be on
+    relay_on: bool,
+}
+
+impl Outputs {
+    fn play(&mut self, signal: Signal) -> anyhow::Result<()> {
+        let beeps = match signal {
+            Signal::Knock => 3,
+            Signal::StatusChanged => 1,
+        };
+
+        // The LED still flashes along with a chime or the motor
+        let replaced = haptic::play(signal) || chime::play(signal);
+        if !replaced && self.buzzer.is_some() {
+            if let Some(notes) = ringtone(signal) {
+                return self.play_ringtone(signal, &notes);
+            }
+        }
+
+        for _ in 0..beeps {
+            if !replaced {
+                self.beep(true)?;
+            }
+            if matches!(signal, Signal::Knock) {
+                self.led.toggle()?;
+            }
+            std::thread::sleep(BEEP_DURATION);
+            self.beep(false)?;
+            if matches!(signal, Signal::Knock) {
+                self.led.toggle()?;
+            }
+            std::thread::sleep(PAUSE_DURATION);
+        }
+
+        Ok(())
+    }
+
+    // The buzzer's timer is switched to each note's frequency and back
+    fn play_ringtone(&mut self, signal: Signal, notes: &[Note]) -> anyhow::Result<()> {
+        // SAFETY: the buzzer owns LEDC timer 0, see main
+        let beep_frequency =
+            unsafe { ledc_get_freq(ledc_mode_t_LEDC_LOW_SPEED_MODE, ledc_timer_t_LEDC_TIMER_0) };
+
+        for note in notes {
+            let audible = note.frequency.is_some_and(|frequency| {
+                // SAFETY: as above
+                esp!(unsafe {
+                    ledc_set_freq(
+                        ledc_mode_t_LEDC_LOW_SPEED_MODE,
+                        ledc_timer_t_LEDC_TIMER_0,
+                        frequency,
+                    )
+                })
+                .is_ok()
+            });
+            if audible {
+                self.beep(true)?;
+            }
+            if matches!(signal, Signal::Knock) {
+                self.led.toggle()?;
+            }
+
+            // A short gap keeps repeated notes apart
+            std::thread::sleep(note.duration * 9 / 10);
+            self.beep(false)?;
+            std::thread::sleep(note.duration / 10);
+        }
+
+        // SAFETY: as above
+        esp!(unsafe {
+            ledc_set_freq(
+                ledc_mode_t_LEDC_LOW_SPEED_MODE,
+                ledc_timer_t_LEDC_TIMER_0,
+                beep_frequency,
+            )
+        })?;
+        Ok(())
+    }
+
+    fn beep(&mut self, on: bool) -> anyhow::Result<()> {
+        if let Some(buzzer) = &mut self.buzzer {
+            let duty = if on { buzzer.get_max_duty() / 2 } else { 0 };
+            buzzer.set_duty(duty)?;
+        }
+        Ok(())
+    }
+
+    fn refresh_led(&mut self) -> anyhow::Result<()> {
+        // A transition step's setting wins over a rule's
+        let step_led = status::transition().and_then(|(step, _, _)| step.led);
+        let lit = step_led
+            .or_else(rules::led)
+            .unwrap_or_else(|| style(status::current()).led)
+            && !schedule::in_quiet_hours();
+        self.led.set_level(lit.into())?;
+        self.refresh_relay()
+    }
+
+    fn refresh_relay(&mut self) -> anyhow::Result<()> {
+        let config = config::get().relay;
+        let current = status::current();
+        let on = config.enabled
+            && style(current)
+                .relay
+                .unwrap_or_else(|| config.statuses.contains(&current))
+            && !schedule::in_quiet_hours();
+
+        match config.pulse_ms {
+            // Lamps with a toggle input get one pulse per change
+            Some(pulse_ms) => {
+                if on != self.relay_on {
+                    self.relay.set_level((!config.inverted).into())?;
+                    std::thread::sleep(Duration::from_millis(pulse_ms.into()));
+                }
+                self.relay.set_level(config.inverted.into())?;
+            }
+            None => self.relay.set_level((on != config.inverted).into())?,
+        }
+        self.relay_on = on;
+
+        Ok(())
+    }
+}
+
+/// Spawns the thread driving the buzzer, the LED and the relay. The relay
+/// pin's mode is taken from the configuration at startup.
+pub fn start(
+    buzzer: Option<LedcDriver<'static>>,
+    led: PinDriver<'static, AnyOutputPin, Output>,
+    relay: AnyIOPin,
+) -> anyhow::Result<()> {
+    let relay_config = config::get().relay;
+    let mut relay = if relay_config.open_drain {
+        PinDriver::input_output_od(relay)?
+    } else {
+        PinDriver::input_output(relay)?
+    };
+    relay.set_level(relay_config.inverted.into())?;
+
+    let (tx, rx) = mpsc::channel::<Signal>();
+    let mut outputs = Outputs {
+        buzzer,
+        led,
+        relay,
+        relay_on: false,
+    };
+
+    std::thread::Builder::new()
+        .name("output".into())
+        .stack_size(OUTPUT_STACK_SIZE)
+        .spawn(move || loop {
+            let result = match rx.recv_timeout(REFRESH_INTERVAL) {
+                Ok(signal) => outputs
+                    .refresh_led()
+                    .and_then(|_| outputs.play(signal))
+                    .and_then(|_| outputs.refresh_led()),
+                Err(mpsc::RecvTimeoutError::Timeout) => outputs.refresh_led(),
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            if let Err(e) = result {
+                warn!("Output error: {:?}", e);
+            }
+        })?;
+
+    SENDER
+        .set(tx)
+        .map_err(|_| anyhow::anyhow!("Output thread already started"))?;
+
+    Ok(())
+}
+
+// The ringtone configured for a signal; None to beep
+fn ringtone(signal: Signal) -> Option<Vec<Note>> {
+    let config = config::get().buzzer;
+    let text = match signal {
+        Signal::Knock => config.knock,
+        Signal::StatusChanged => {
+            let style = style(status::current());
+            match style.ringtone.is_empty() {
+                true => config.status_changed,
+                false => style.ringtone,
+            }
+        }
+    };
+    if text.is_empty() {
+        return None;
+    }
+
+    rtttl::parse(&text)
+        .map_err(|e| warn!("Bad ringtone {:?}: {:?}", text, e))
+        .ok()
+}
+
+/// How the outputs show a status, from `styles`.
+pub fn style(status: Status) -> StatusStyle {
+    config::get().styles.get(status).clone()
+}
+
+/// A style's color, white if it does not parse.
+pub fn color(style: &StatusStyle) -> [u8; 3] {
+    style.rgb().unwrap_or([255; 3])
+}
+
+/// Plays a signal unless quiet hours are active.
+pub fn signal(signal: Signal) {
+    if schedule::in_quiet_hours() {
+        return;
+    }
+
+    if let Some(tx) = SENDER.get() {
+        let _ = tx.send(signal);
+    }
+}
@@ -0,0 +1,271 @@
+//! ESP-NOW status sync between busier devices.
+//!
+//! Paired devices mirror each other's status directly over ESP-NOW, which
+//! keeps working while the router is down. Conflicting updates are resolved
+//! last-writer-wins using a Lamport clock, with the sender's MAC address as
+//! the tie breaker.
+//!
+//! Pairing: start pairing on both devices within a minute of each other.
+//! Each broadcasts pairing requests and remembers every device that answers.
+//!
+//! The receiver also forwards packets from remote buttons.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::espnow::{EspNow, PeerInfo, BROADCAST};
+use log::{info, warn};
+
+use crate::button_protocol;
+use crate::config;
+use crate::device;
+use crate::remote_button;
+use crate::status::{self, Status};
+
+const SYNC_STACK_SIZE: usize = 4096;
+const PAIRING_WINDOW: Duration = Duration::from_secs(60);
+const PAIRING_INTERVAL: Duration = Duration::from_secs(1);
+
+// Wire format: magic, version, kind, Lamport clock (LE), status
+const MAGIC: [u8; 2] = *b"BZ";
+const VERSION: u8 = 1;
+const MESSAGE_LEN: usize = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Status = 1,
+    PairRequest = 2,
+    PairAccept = 3,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Message {
+    kind: Kind,
+    clock: u32,
+    status: Status,
+}
+
+impl Message {
+    fn encode(&self) -> [u8; MESSAGE_LEN] {
+        let mut buf = [0; MESSAGE_LEN];
+        buf[..2].copy_from_slice(&MAGIC);
+        buf[2] = VERSION;
+        buf[3] = self.kind as u8;
+        buf[4..8].copy_from_slice(&self.clock.to_le_bytes());
+        buf[8] = self.status as u8;
+        buf
+    }
+
+    fn decode(data: &[u8]) -> Option<Message> {
+        if data.len() != MESSAGE_LEN || data[..2] != MAGIC || data[2] != VERSION {
+            return None;
+        }
+        let kind = match data[3] {
+            1 => Kind::Status,
+            2 => Kind::PairRequest,
+            3 => Kind::PairAccept,
+            _ => return None,
+        };
+        Some(Message {
+            kind,
+            clock: u32::from_le_bytes(data[4..8].try_into().ok()?),
+            status: Status::from_u8(data[8]),
+        })
+    }
+}
+
+enum Command {
+    Publish(Status, u32),
+    StartPairing,
+    Received([u8; 6], Vec<u8>),
+}
+
+static SENDER: OnceLock<mpsc::Sender<Command>> = OnceLock::new();
+// Lamport clock of the last status change seen, and who made it
+static CLOCK: AtomicU32 = AtomicU32::new(0);
+static LAST_WRITER: Mutex<[u8; 6]> = Mutex::new([0; 6]);
+
+/// Starts ESP-NOW and the sync thread. WiFi must already be started.
+pub fn start() -> anyhow::Result<()> {
+    let (tx, rx) = mpsc::channel::<Command>();
+
+    let espnow = EspNow::take()?;
+    let callback_tx = tx.clone();
+    espnow.register_recv_cb(move |info, data| {
+        let _ = callback_tx.send(Command::Received(*info.src_addr, data.to_vec()));
+    })?;
+
+    add_peer(&espnow, BROADCAST)?;
+    for peer in paired_peers() {
+        add_peer(&espnow, peer)?;
+    }
+
+    let own_mac = device::mac();
+    info!("ESP-NOW sync ready as {}", format_mac(&own_mac));
+
+    std::thread::Builder::new()
+        .name("peer_sync".into())
+        .stack_size(SYNC_STACK_SIZE)
+        .spawn(move || {
+            let mut pairing_until: Option<Instant> = None;
+            loop {
+                let pairing = pairing_until.is_some_and(|until| Instant::now() < until);
+                let command = match rx.recv_timeout(PAIRING_INTERVAL) {
+                    Ok(command) => Some(command),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+
+                let result = match command {
+                    Some(Command::Publish(status, clock)) => {
+                        publish_to_peers(&espnow, status, clock)
+                    }
+                    Some(Command::StartPairing) => {
+                        info!("ESP-NOW pairing started");
+                        pairing_until = Some(Instant::now() + PAIRING_WINDOW);
+                        Ok(())
+                    }
+                    Some(Command::Received(src, data)) => {
+                        handle_message(&espnow, pairing, src, &data, &own_mac)
+                    }
+                    None if pairing => send(&espnow, BROADCAST, Kind::PairRequest),
+                    None => Ok(()),
+                };
+                if let Err(e) = result {
+                    warn!("ESP-NOW sync error: {:?}", e);
+                }
+            }
+        })?;
+
+    SENDER
+        .set(tx)
+        .map_err(|_| anyhow::anyhow!("Peer sync already started"))?;
+
+    Ok(())
+}
+
+/// Sends a locally made status change to the paired devices.
+pub fn publish(status: Status) {
+    let mut last_writer = LAST_WRITER.lock().unwrap();
+    let clock = CLOCK.fetch_add(1, Ordering::SeqCst) + 1;
+    *last_writer = device::mac();
+    drop(last_writer);
+
+    command(Command::Publish(status, clock));
+}
+
+/// Opens the pairing window.
+pub fn start_pairing() {
+    command(Command::StartPairing);
+}
+
+/// MAC addresses of the paired devices.
+pub fn paired_peers() -> Vec<[u8; 6]> {
+    config::get()
+        .peer_sync
+        .peers
+        .iter()
+        .filter_map(|mac| parse_mac(mac))
+        .collect()
+}
+
+fn command(command: Command) {
+    if let Some(tx) = SENDER.get() {
+        let _ = tx.send(command);
+    }
+}
+
+fn handle_message(
+    espnow: &EspNow<'static>,
+    pairing: bool,
+    src: [u8; 6],
+    data: &[u8],
+    own_mac: &[u8; 6],
+) -> anyhow::Result<()> {
+    // Remote buttons share the ESP-NOW receiver
+    if button_protocol::is_button_packet(data) {
+        return remote_button::handle(src, data);
+    }
+
+    let Some(message) = Message::decode(data) else {
+        return Ok(());
+    };
+
+    match message.kind {
+        Kind::PairRequest | Kind::PairAccept if pairing => {
+            if !paired_peers().contains(&src) {
+                info!("Paired with {}", format_mac(&src));
+                add_peer(espnow, src)?;
+                let mut peer_sync = config::get().peer_sync;
+                peer_sync.peers.push(format_mac(&src));
+                config::update(serde_json::json!({ "peer_sync": peer_sync }))?;
+            }
+            if message.kind == Kind::PairRequest {
+                send(espnow, src, Kind::PairAccept)?;
+            }
+        }
+        Kind::Status if paired_peers().contains(&src) => {
+            let mut last_writer = LAST_WRITER.lock().unwrap();
+            let local = (CLOCK.load(Ordering::SeqCst), *last_writer);
+            if (message.clock, src) > local && src != *own_mac {
+                CLOCK.store(message.clock, Ordering::SeqCst);
+                *last_writer = src;
+                drop(last_writer);
+                status::apply_from_peer(message.status);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn publish_to_peers(espnow: &EspNow<'static>, status: Status, clock: u32) -> anyhow::Result<()> {
+    let message = Message {
+        kind: Kind::Status,
+        clock,
+        status,
+    };
+    for peer in paired_peers() {
+        espnow.send(peer, &message.encode())?;
+    }
+    Ok(())
+}
+
+fn send(espnow: &EspNow<'static>, peer: [u8; 6], kind: Kind) -> anyhow::Result<()> {
+    let message = Message {
+        kind,
+        clock: CLOCK.load(Ordering::SeqCst),
+        status: status::selected(),
+    };
+    espnow.send(peer, &message.encode())?;
+    Ok(())
+}
+
+fn add_peer(espnow: &EspNow<'static>, peer: [u8; 6]) -> anyhow::Result<()> {
+    if espnow.peer_exists(peer)? {
+        return Ok(());
+    }
+    espnow.add_peer(PeerInfo {
+        peer_addr: peer,
+        ..Default::default()
+    })?;
+    Ok(())
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+pub fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    let mut mac = [0; 6];
+    let mut parts = text.split(':');
+    for byte in &mut mac {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    parts.next().is_none().then_some(mac)
+}
@@ -0,0 +1,138 @@
+//! Pomodoro focus timer.
+//!
+//! Alternates 25 minute work intervals (Do Not Disturb) with 5 minute breaks
+//! (Free) until stopped. The main loop calls [`tick`] to advance phases.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::info;
+use serde::Serialize;
+
+use crate::i18n;
+use crate::status::{self, Source, Status};
+
+const WORK_DURATION: Duration = Duration::from_secs(25 * 60);
+const BREAK_DURATION: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Work,
+    Break,
+}
+
+impl Phase {
+    fn duration(self) -> Duration {
+        match self {
+            Phase::Work => WORK_DURATION,
+            Phase::Break => BREAK_DURATION,
+        }
+    }
+}
+
+struct Timer {
+    phase: Phase,
+    phase_started: Instant,
+    cycle: u32,
+}
+
+/// Snapshot of a running timer.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct State {
+    pub phase: Phase,
+    pub cycle: u32,
+    pub remaining_secs: u64,
+}
+
+impl State {
+    /// Short text for the display, e.g. "Focus 24:59 #1".
+    pub fn display_text(&self) -> String {
+        let label = match self.phase {
+            Phase::Work => i18n::text("pomodoro.focus"),
+            Phase::Break => i18n::text("pomodoro.break"),
+        };
+        format!(
+            "{} {:02}:{:02} #{}",
+            label,
+            self.remaining_secs / 60,
+            self.remaining_secs % 60,
+            self.cycle
+        )
+    }
+}
+
+static TIMER: Mutex<Option<Timer>> = Mutex::new(None);
+
+/// Starts a new cycle, restarting any running timer.
+pub fn start() {
+    *TIMER.lock().unwrap() = Some(Timer {
+        phase: Phase::Work,
+        phase_started: Instant::now(),
+        cycle: 1,
+    });
+    info!("Pomodoro started");
+    status::set(Source::Schedule, Status::Dnd);
+}
+
+/// Stops the timer and returns to Free.
+pub fn stop() {
+    if TIMER.lock().unwrap().take().is_some() {
+        info!("Pomodoro stopped");
+        status::set(Source::Schedule, Status::Free);
+    }
+}
+
+/// Starts the timer if idle, stops it otherwise.
+pub fn toggle() {
+    if is_running() {
+        stop();
+    } else {
+        start();
+    }
+}
+
+pub fn is_running() -> bool {
+    TIMER.lock().unwrap().is_some()
+}
+
+pub fn state() -> Option<State> {
+    TIMER.lock().unwrap().as_ref().map(|timer| State {
+        phase: timer.phase,
+        cycle: timer.cycle,
+        remaining_secs: timer
+            .phase
+            .duration()
+            .saturating_sub(timer.phase_started.elapsed())
+            .as_secs(),
+    })
+}
+
+/// Advances to the next phase once the current one has elapsed.
+pub fn tick() {
+    let mut guard = TIMER.lock().unwrap();
+    let Some(timer) = guard.as_mut() else {
+        return;
+    };
+
+    if timer.phase_started.elapsed() < timer.phase.duration() {
+        return;
+    }
+
+    timer.phase_started = Instant::now();
+    let new_status = match timer.phase {
+        Phase::Work => {
+            timer.phase = Phase::Break;
+            Status::Free
+        }
+        Phase::Break => {
+            timer.phase = Phase::Work;
+            timer.cycle += 1;
+            Status::Dnd
+        }
+    };
+    info!("Pomodoro cycle {} entering {:?}", timer.cycle, timer.phase);
+    drop(guard);
+
+    status::set(Source::Schedule, new_status);
+}
@@ -0,0 +1,285 @@
+//! Setup access point with a captive portal.
+//!
+//! When the device cannot join a network, because it has no credentials or
+//! the ones it has fail, it opens an access point of its own and asks for
+//! the network on a page of its web server. A wildcard DNS responder sends
+//! every name to the device and any other page redirects there, so phones
+//! and laptops pop the page up on their own; see [`busier_core::captive`].
+//! The displays show a QR code to join the access point. Saved credentials
+//! go to [`crate::secrets`] and the device restarts to use them.
+
+use std::net::{Ipv4Addr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use busier_core::captive;
+use busier_core::provision::WifiCredentials;
+use embedded_svc::http::server::Request;
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::{Read, Write};
+use embedded_svc::wifi::{AccessPointConfiguration, AuthMethod, Configuration};
+use esp_idf_svc::http::server::{EspHttpConnection, EspHttpServer};
+use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
+use log::{info, warn};
+
+use crate::auth;
+use crate::device;
+use crate::display::Displays;
+use crate::http_util::{self, Routes};
+use crate::i18n;
+use crate::secrets;
+use crate::setup;
+
+// WPA2 password of the access point; without one it is open
+const PASSWORD: Option<&str> = option_env!("AP_PASS");
+const DNS_STACK_SIZE: usize = 4096;
+// Restart to try the network again after this long, if there are
+// credentials to try
+const RETRY_AFTER: Duration = Duration::from_secs(600);
+// Max payload length for the WiFi form
+const MAX_WIFI_LEN: usize = 256;
+
+static PORTAL_HTML: &str = r#"<!DOCTYPE html>
+<html lang="{{lang}}">
+<head>
+    <meta charset="utf-8">
+    <title>{{web.portal_title}}</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <style>
+        body {
+            font-family: Arial, sans-serif;
+            margin: 0;
+            padding: 20px;
+            background-color: #f5f5f5;
+        }
+        .container {
+            max-width: 600px;
+            margin: 0 auto;
+            background-color: white;
+            padding: 30px;
+            border-radius: 8px;
+            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
+        }
+        label {
+            display: block;
+            font-weight: bold;
+            margin: 15px 0 5px;
+        }
+        input {
+            width: 100%;
+            box-sizing: border-box;
+            padding: 8px;
+        }
+        button {
+            background-color: #4CAF50;
+            color: white;
+            padding: 12px 25px;
+            border: none;
+            border-radius: 4px;
+            cursor: pointer;
+            font-size: 16px;
+            margin-top: 20px;
+        }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>{{web.portal_title}}</h1>
+        <p>{{web.portal_intro}}</p>
+
+        <label for="ssid">{{web.wifi_ssid}}</label>
+        <input id="ssid" autocomplete="off" autocapitalize="none">
+        <label for="password">{{web.wifi_password}}</label>
+        <input id="password" type="password" autocomplete="off">
+
+        <button onclick="save()">{{web.save}}</button>
+        <p id="result"></p>
+    </div>
+
+    <script>
+        function save() {
+            fetch('/api/wifi', {
+                method: 'POST',
+                headers: {
+                    'Content-Type': 'application/json',
+                },
+                body: JSON.stringify({
+                    ssid: document.getElementById('ssid').value,
+                    password: document.getElementById('password').value,
+                }),
+            })
+            .then(response => response.text())
+            .then(text => {
+                document.getElementById('result').textContent = text;
+            });
+        }
+    </script>
+</body>
+</html>"#;
+
+/// Opens the access point and serves the portal until credentials are
+/// saved, then restarts. Does not return.
+pub fn run(
+    wifi: &mut BlockingWifi<EspWifi<'static>>,
+    displays: &mut Displays,
+) -> anyhow::Result<()> {
+    let ssid = ssid();
+    let password = password();
+    if let Err(e) = wifi.stop() {
+        warn!("Failed to stop WiFi: {:?}", e);
+    }
+    wifi.set_configuration(&Configuration::AccessPoint(AccessPointConfiguration {
+        ssid: ssid.as_str().try_into().unwrap(),
+        password: password.try_into().unwrap(),
+        auth_method: if password.is_empty() {
+            AuthMethod::None
+        } else {
+            AuthMethod::WPA2Personal
+        },
+        ..Default::default()
+    }))?;
+    wifi.start()?;
+    wifi.wait_netif_up()?;
+
+    let ip = wifi.wifi().ap_netif().get_ip_info()?.ip;
+    info!("Setup access point {} at http://{}/", ssid, ip);
+    displays.qr_code(
+        &captive::wifi_qr_payload(&ssid, password),
+        &[i18n::text("display.scan_to_join"), &ssid, &ip.to_string()],
+    );
+
+    start_dns(ip)?;
+    let mut server = EspHttpServer::new(&http_util::server_configuration())?;
+    register(&mut server, ip)?;
+
+    let started = Instant::now();
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        if started.elapsed() >= RETRY_AFTER && has_credentials() {
+            info!("Trying the network again");
+            device::restart();
+        }
+    }
+}
+
+// "busier-" and the end of the MAC address, as the device has no name on
+// the network yet
+fn ssid() -> String {
+    let mac = device::mac();
+    format!("busier-{:02X}{:02X}{:02X}", mac[3], mac[4], mac[5])
+}
+
+fn password() -> &'static str {
+    match PASSWORD {
+        Some(password) if (8..=63).contains(&password.len()) => password,
+        Some(_) => {
+            warn!("AP_PASS must have 8 to 63 characters, the access point is open");
+            ""
+        }
+        None => "",
+    }
+}
+
+fn has_credentials() -> bool {
+    secrets::wifi().is_some() || (crate::SSID.is_some() && crate::PASSWORD.is_some())
+}
+
+// Answers every DNS query with the device's address
+fn start_dns(ip: Ipv4Addr) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 53))?;
+    std::thread::Builder::new()
+        .name("dns".into())
+        .stack_size(DNS_STACK_SIZE)
+        .spawn(move || {
+            let mut buf = [0; 512];
+            loop {
+                let (len, peer) = match socket.recv_from(&mut buf) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!("DNS receive failed: {:?}", e);
+                        continue;
+                    }
+                };
+                if let Some(response) = captive::dns_answer(&buf[..len], ip.octets()) {
+                    if let Err(e) = socket.send_to(&response, peer) {
+                        warn!("DNS response failed: {:?}", e);
+                    }
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+fn register(server: &mut EspHttpServer<'static>, ip: Ipv4Addr) -> anyhow::Result<()> {
+    let portal = format!("http://{}/", ip);
+
+    let location = portal.clone();
+    server.route("/", Method::Get, move |req| {
+        if !is_own_host(req.header("Host"), ip) {
+            req.into_response(302, None, &[("Location", location.as_str())])?;
+            return Ok(());
+        }
+        req.into_ok_response()?
+            .write_all(i18n::localize(PORTAL_HTML).as_bytes())?;
+        Ok(())
+    })?;
+
+    // A device set up before needs the admin to change its network; a new
+    // one has no admin yet
+    let admin = auth::admin(false, save_wifi);
+    server.route("/api/wifi", Method::Post, move |req| {
+        if setup::is_pending() {
+            save_wifi(req)
+        } else {
+            admin(req)
+        }
+    })?;
+
+    // The connectivity checks and any other page lead to the portal
+    server.route("/*", Method::Get, move |req| {
+        req.into_response(302, None, &[("Location", portal.as_str())])?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn save_wifi(mut req: Request<&mut EspHttpConnection>) -> anyhow::Result<()> {
+    let len = req.content_len().unwrap_or(0) as usize;
+    if len > MAX_WIFI_LEN {
+        req.into_status_response(413)?
+            .write_all("Request too big".as_bytes())?;
+        return Ok(());
+    }
+
+    let mut buf = vec![0; len];
+    req.read_exact(&mut buf)?;
+
+    let wifi = serde_json::from_slice::<WifiCredentials>(&buf)
+        .map_err(|e| e.to_string())
+        .and_then(|wifi| wifi.validate().map(|()| wifi).map_err(|e| e.to_string()));
+    let wifi = match wifi {
+        Ok(wifi) => wifi,
+        Err(e) => {
+            req.into_status_response(400)?
+                .write_all(format!("Invalid request: {}", e).as_bytes())?;
+            return Ok(());
+        }
+    };
+
+    secrets::set_wifi(&wifi.ssid, &wifi.password)?;
+    info!("WiFi credentials saved, restarting");
+    req.into_ok_response()?
+        .write_all(i18n::text("web.portal_saved").as_bytes())?;
+    device::restart()
+}
+
+// Whether the request was addressed to the device rather than to a name
+// the DNS responder sent here
+fn is_own_host(host: Option<&str>, ip: Ipv4Addr) -> bool {
+    let Some(host) = host else {
+        return true;
+    };
+    let host = host.rsplit_once(':').map_or(host, |(host, _)| host);
+    host == ip.to_string()
+}
@@ -0,0 +1,92 @@
+//! Power management.
+//!
+//! The CPU clock scales between `cpu.min_mhz` and `cpu.max_mhz` with load,
+//! and with `cpu.light_sleep` the chip light sleeps whenever every task is
+//! blocked. Light sleep stops the buzzer and delays input polling slightly.
+//!
+//! WiFi modem sleep trades latency for idle current: with `min` the radio
+//! wakes for every DTIM beacon, with `max` only every `listen_interval`
+//! beacons, so requests can take that many beacon intervals (about 100 ms
+//! each) to arrive.
+
+use esp_idf_svc::sys;
+use log::info;
+
+use crate::board;
+use crate::config::{self, WifiPowerSave};
+
+// Frequencies the PLL supports, up to the chip's maximum
+const CPU_FREQUENCIES_MHZ: [u16; 3] = [80, 160, 240];
+
+/// Applies `wifi_power` to the station. The listen interval only takes
+/// effect on the next association, so call this before connecting.
+pub fn configure_wifi() -> anyhow::Result<()> {
+    let config = config::get().wifi_power;
+
+    let mut sta_config: sys::wifi_config_t = Default::default();
+    // SAFETY: the station variant of the union is the one used in STA mode
+    unsafe {
+        sys::esp!(sys::esp_wifi_get_config(
+            sys::wifi_interface_t_WIFI_IF_STA,
+            &mut sta_config
+        ))?;
+        sta_config.sta.listen_interval = config.listen_interval.into();
+        sys::esp!(sys::esp_wifi_set_config(
+            sys::wifi_interface_t_WIFI_IF_STA,
+            &mut sta_config
+        ))?;
+    }
+
+    let ps = match config.mode {
+        WifiPowerSave::None => sys::wifi_ps_type_t_WIFI_PS_NONE,
+        WifiPowerSave::Min => sys::wifi_ps_type_t_WIFI_PS_MIN_MODEM,
+        WifiPowerSave::Max => sys::wifi_ps_type_t_WIFI_PS_MAX_MODEM,
+    };
+    // SAFETY: plain setter; fails while Bluetooth needs modem sleep
+    unsafe { sys::esp!(sys::esp_wifi_set_ps(ps))? };
+
+    info!(
+        "WiFi power save {:?}, listen interval {}",
+        config.mode, config.listen_interval
+    );
+    Ok(())
+}
+
+/// Applies `cpu` to the power management driver. Safe to call again after
+/// the configuration changes.
+pub fn configure_cpu() -> anyhow::Result<()> {
+    let config = config::get().cpu;
+    if !CPU_FREQUENCIES_MHZ.contains(&config.min_mhz)
+        || !CPU_FREQUENCIES_MHZ.contains(&config.max_mhz)
+        || config.min_mhz > config.max_mhz
+    {
+        anyhow::bail!(
+            "Unsupported CPU frequencies {}-{} MHz",
+            config.min_mhz,
+            config.max_mhz
+        );
+    }
+    // The default maximum is more than the C3 can do
+    let min_mhz = config.min_mhz.min(board::MAX_CPU_MHZ);
+    let max_mhz = config.max_mhz.min(board::MAX_CPU_MHZ);
+
+    let pm_config = sys::esp_pm_config_t {
+        max_freq_mhz: max_mhz.into(),
+        min_freq_mhz: min_mhz.into(),
+        light_sleep_enable: config.light_sleep,
+    };
+    // SAFETY: the driver copies the configuration before returning
+    unsafe {
+        sys::esp!(sys::esp_pm_configure(
+            &pm_config as *const sys::esp_pm_config_t as *const core::ffi::c_void
+        ))?
+    };
+
+    info!(
+        "CPU {}-{} MHz, light sleep {}",
+        min_mhz,
+        max_mhz,
+        if config.light_sleep { "on" } else { "off" }
+    );
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! BLE WiFi provisioning (feature `ble`).
+//!
+//! When no WiFi credentials were given at build time and none are stored
+//! yet, runs the ESP-IDF provisioning manager over BLE so the Espressif
+//! "ESP BLE Provisioning" app can push credentials. The displays show the
+//! QR code the app scans to find the device. The credentials are then moved
+//! to [`crate::secrets`], so this only happens on first boot.
+
+use std::ffi::CString;
+
+use esp_idf_svc::sys::{self, esp};
+use log::info;
+
+use crate::device;
+use crate::display::Displays;
+use crate::i18n;
+use crate::secrets;
+
+const POP: &str = match option_env!("PROV_POP") {
+    Some(pop) => pop,
+    None => "busier",
+};
+
+/// Blocks until the device has WiFi credentials. The WiFi driver must be
+/// initialized but not started.
+pub fn ensure_provisioned(displays: &mut Displays) -> anyhow::Result<()> {
+    let config = sys::wifi_prov_mgr_config_t {
+        // SAFETY: the scheme is a constant provided by ESP-IDF
+        scheme: unsafe { sys::wifi_prov_scheme_ble },
+        // Keep the BT controller around for the BLE status service
+        scheme_event_handler: sys::wifi_prov_event_handler_t {
+            event_cb: None,
+            user_data: std::ptr::null_mut(),
+        },
+        app_event_handler: sys::wifi_prov_event_handler_t {
+            event_cb: None,
+            user_data: std::ptr::null_mut(),
+        },
+    };
+
+    // SAFETY: the manager copies the configuration
+    esp!(unsafe { sys::wifi_prov_mgr_init(config) })?;
+
+    let mut provisioned = false;
+    // SAFETY: the manager is initialized and the pointer is valid
+    esp!(unsafe { sys::wifi_prov_mgr_is_provisioned(&mut provisioned) })?;
+
+    if !provisioned {
+        let name = service_name();
+        displays.qr_code(
+            &qr_payload(&name),
+            &[i18n::text("display.scan_to_set_up"), &name],
+        );
+
+        let service_name = CString::new(name)?;
+        let pop = CString::new(POP)?;
+        info!(
+            "Waiting for BLE provisioning as {:?}",
+            service_name.to_string_lossy()
+        );
+
+        // SAFETY: both strings outlive the provisioning session, which ends
+        // before wifi_prov_mgr_wait returns
+        unsafe {
+            esp!(sys::wifi_prov_mgr_start_provisioning(
+                sys::wifi_prov_security_WIFI_PROV_SECURITY_1,
+                pop.as_ptr() as *const core::ffi::c_void,
+                service_name.as_ptr(),
+                std::ptr::null(),
+            ))?;
+            sys::wifi_prov_mgr_wait();
+        }
+        info!("BLE provisioning finished");
+    }
+
+    // SAFETY: no provisioning session is running any more
+    unsafe { sys::wifi_prov_mgr_deinit() };
+
+    Ok(())
+}
+
+/// Forgets the stored credentials; provisioning runs again on next boot.
+pub fn reset() -> anyhow::Result<()> {
+    secrets::clear_wifi()?;
+    forget_driver_credentials()
+}
+
+/// Clears the credentials the driver saved in plain text, by provisioning
+/// or by an older firmware.
+pub fn forget_driver_credentials() -> anyhow::Result<()> {
+    // SAFETY: only clears the WiFi configuration stored by the driver
+    esp!(unsafe { sys::esp_wifi_restore() })?;
+    Ok(())
+}
+
+// The Espressif app lists devices whose name starts with "PROV_". Two bytes
+// of the MAC address keep the QR code small enough for a 128x32 panel.
+fn service_name() -> String {
+    let mac = device::mac();
+    format!("PROV_{:02X}{:02X}", mac[4], mac[5])
+}
+
+// What the app expects in the QR code of a device, see Espressif's
+// esp_prov documentation. The optional version is left out, so that with
+// the default proof of possession the code is a version 3 one of 29 modules.
+fn qr_payload(name: &str) -> String {
+    serde_json::json!({
+        "name": name,
+        "pop": POP,
+        "transport": "ble",
+    })
+    .to_string()
+}
@@ -0,0 +1,87 @@
+//! Plain HTTP listener in front of HTTPS.
+//!
+//! With the HTTPS listener up, port 80 only sends clients on to the HTTPS
+//! origin, so old bookmarks and scripts keep working. A few routes are
+//! still answered directly: the connectivity checks phones and laptops make
+//! on joining a network, which must not be redirected, and the UPnP
+//! description and Hue emulation for Alexa, which only speak plain HTTP.
+
+use std::net::Ipv4Addr;
+
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::EspHttpServer;
+
+use crate::config;
+use crate::http_util::Routes;
+use crate::hue_emulation;
+use crate::ssdp;
+
+// Connectivity checks and the replies that mean "online, no portal"
+const CONNECTIVITY_CHECKS: [(&str, u16, &str); 5] = [
+    ("/generate_204", 204, ""),
+    ("/gen_204", 204, ""),
+    (
+        "/hotspot-detect.html",
+        200,
+        "<HTML><HEAD><TITLE>Success</TITLE></HEAD><BODY>Success</BODY></HTML>",
+    ),
+    ("/connecttest.txt", 200, "Microsoft Connect Test"),
+    ("/ncsi.txt", 200, "Microsoft NCSI"),
+];
+
+/// Registers the redirect and the routes that stay on plain HTTP.
+pub fn register(server: &mut EspHttpServer<'static>, ip: Ipv4Addr) -> anyhow::Result<()> {
+    for (path, status, body) in CONNECTIVITY_CHECKS {
+        server.route(path, Method::Get, move |req| {
+            req.into_status_response(status)?
+                .write_all(body.as_bytes())?;
+            Ok(())
+        })?;
+    }
+
+    // Route for the UPnP device description
+    server.route(ssdp::DESCRIPTION_PATH, Method::Get, move |req| {
+        let mut resp = req.into_response(200, None, &[("Content-Type", "text/xml")])?;
+        resp.write_all(ssdp::description_xml(ip).as_bytes())?;
+        Ok(())
+    })?;
+
+    // Echo devices cannot follow a redirect to HTTPS
+    if config::get().alexa.enabled {
+        hue_emulation::register(server)?;
+    }
+
+    let port = config::get().https.port;
+    for method in [
+        Method::Get,
+        Method::Head,
+        Method::Post,
+        Method::Put,
+        Method::Delete,
+    ] {
+        server.route("/*", method, move |req| {
+            // Keep the name the client used, without its port
+            let host = req
+                .header("Host")
+                .and_then(|host| host.split(':').next())
+                .filter(|host| !host.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| ip.to_string());
+            let location = match port {
+                443 => format!("https://{}{}", host, req.uri()),
+                port => format!("https://{}:{}{}", host, port, req.uri()),
+            };
+
+            // 308 keeps the method and body of API calls; browsers get 301
+            let status = match method {
+                Method::Get | Method::Head => 301,
+                _ => 308,
+            };
+            req.into_response(status, None, &[("Location", &location)])?;
+            Ok(())
+        })?;
+    }
+
+    Ok(())
+}
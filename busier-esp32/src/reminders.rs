@@ -0,0 +1,77 @@
+//! Fires the reminders in the configuration.
+//!
+//! Checked once a second from the display loop, against the same facts as
+//! the rules. A reminder that fires beeps and flashes like a knock, within
+//! quiet hours, and shows its message on the display for a minute. Changing
+//! the list starts every interval over. The reminders themselves are in
+//! `busier_core::reminders`.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use busier_core::rules::Facts;
+
+pub use busier_core::reminders::Reminder;
+
+use crate::clock;
+use crate::config;
+use crate::device;
+use crate::display;
+use crate::door;
+use crate::output::{self, Signal};
+use crate::schedule;
+use crate::status;
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(60);
+
+// The reminders last checked, each with when it last fired or was set up
+static STATE: Mutex<Option<(Vec<Reminder>, Vec<u64>)>> = Mutex::new(None);
+
+/// Checks the reminders and fires those that are due.
+pub fn tick() {
+    let facts = Facts {
+        now: clock::local_now(),
+        claims: status::claims(),
+        working_hours: schedule::in_working_hours(),
+        door_open: door::is_open(),
+    };
+    let now = device::uptime().as_secs();
+    let reminders = config::get().reminders;
+
+    let mut state = STATE.lock().unwrap();
+    if state
+        .as_ref()
+        .is_none_or(|(checked, _)| *checked != reminders)
+    {
+        let since = vec![now; reminders.len()];
+        *state = Some((reminders, since));
+    }
+    let (checked, since) = state.as_mut().unwrap();
+
+    let mut fired_once = Vec::new();
+    for (index, (reminder, since)) in checked.iter().zip(since.iter_mut()).enumerate() {
+        if !reminder.check(&facts, now, since) {
+            continue;
+        }
+        info!("Reminder: {}", reminder.message);
+        output::signal(Signal::Knock);
+        display::post_message(reminder.message.clone(), MESSAGE_DURATION);
+        if reminder.once {
+            fired_once.push(index);
+        }
+    }
+    if fired_once.is_empty() {
+        return;
+    }
+
+    // Drop the one-shot reminders that fired, keeping the others' timing
+    for index in fired_once.into_iter().rev() {
+        checked.remove(index);
+        since.remove(index);
+    }
+    if let Err(e) = config::update(serde_json::json!({ "reminders": checked })) {
+        warn!("Failed to remove fired reminders: {:?}", e);
+    }
+}
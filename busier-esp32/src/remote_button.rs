@@ -0,0 +1,93 @@
+//! Battery-powered ESP-NOW remote buttons.
+//!
+//! Packets arrive through the ESP-NOW receiver in [`crate::peer_sync`].
+//! Each configured button has its own key. The last accepted counter of each
+//! button is persisted in its own NVS namespace, outside the configuration,
+//! so replays stay rejected across reboots, a press writes a single value
+//! rather than the whole configuration, and sending an old configuration
+//! back cannot roll a counter back.
+
+use std::sync::Mutex;
+
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use log::{info, warn};
+
+use crate::button_protocol::{Action, Packet};
+use crate::config;
+use crate::peer_sync::{format_mac, parse_mac};
+use crate::status::{self, Source};
+
+const NAMESPACE: &str = "buttons";
+
+static NVS: Mutex<Option<EspNvs<NvsDefault>>> = Mutex::new(None);
+
+/// Opens the counter store and carries over counters that an older firmware
+/// kept in the configuration. Call after [`crate::config::init`].
+pub fn init(partition: EspDefaultNvsPartition) -> anyhow::Result<()> {
+    let nvs = EspNvs::new(partition, NAMESPACE, true)?;
+
+    for button in config::get().remote_buttons {
+        let Some(mac) = parse_mac(&button.mac) else {
+            continue;
+        };
+        let key = counter_key(&mac);
+        if button.counter > 0 && nvs.get_u32(&key)?.is_none() {
+            info!("Moving the counter of remote button {}", button.mac);
+            nvs.set_u32(&key, button.counter)?;
+        }
+    }
+
+    *NVS.lock().unwrap() = Some(nvs);
+
+    Ok(())
+}
+
+/// Handles a remote button packet from `src`.
+pub fn handle(src: [u8; 6], data: &[u8]) -> anyhow::Result<()> {
+    let buttons = config::get().remote_buttons;
+    let Some(button) = buttons
+        .iter()
+        .find(|button| parse_mac(&button.mac) == Some(src))
+    else {
+        info!(
+            "Ignoring remote button {}: not configured",
+            format_mac(&src)
+        );
+        return Ok(());
+    };
+
+    let Some(packet) = Packet::decode(data, button.key.as_bytes()) else {
+        warn!("Remote button {}: invalid tag", button.mac);
+        return Ok(());
+    };
+
+    {
+        let mut nvs = NVS.lock().unwrap();
+        let nvs = nvs
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("No remote button counters"))?;
+        let key = counter_key(&src);
+        if packet.counter <= nvs.get_u32(&key)?.unwrap_or(0) {
+            warn!(
+                "Remote button {}: replayed counter {}",
+                button.mac, packet.counter
+            );
+            return Ok(());
+        }
+        nvs.set_u32(&key, packet.counter)?;
+    }
+
+    match packet.action {
+        Action::Toggle => {
+            let new_status = status::toggle_dnd(Source::Button);
+            info!("Remote button set status to {}", new_status.as_str());
+        }
+    }
+
+    Ok(())
+}
+
+// The MAC address in hex, within the 15 characters of an NVS key
+fn counter_key(mac: &[u8; 6]) -> String {
+    mac.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,239 @@
+//! MFRC522 badge reader.
+//!
+//! Polls an MFRC522 on SPI for ISO 14443A cards and runs the action mapped
+//! to the card's UID in `rfid.cards`. A card triggers once per tap: it is
+//! halted after reading and only answers again once it left the field.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::hal::spi::{SpiDeviceDriver, SpiDriver};
+use log::{info, warn};
+
+use crate::config::{self, RfidAction};
+use crate::notify::{self, Event};
+use crate::status::{self, Source, Status};
+
+const RFID_STACK_SIZE: usize = 4096;
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const TRANSCEIVE_TIMEOUT: Duration = Duration::from_millis(40);
+
+// Registers
+const COMMAND_REG: u8 = 0x01;
+const COM_IRQ_REG: u8 = 0x04;
+const ERROR_REG: u8 = 0x06;
+const FIFO_DATA_REG: u8 = 0x09;
+const FIFO_LEVEL_REG: u8 = 0x0A;
+const BIT_FRAMING_REG: u8 = 0x0D;
+const COLL_REG: u8 = 0x0E;
+const MODE_REG: u8 = 0x11;
+const TX_CONTROL_REG: u8 = 0x14;
+const TX_ASK_REG: u8 = 0x15;
+const T_MODE_REG: u8 = 0x2A;
+const T_PRESCALER_REG: u8 = 0x2B;
+const T_RELOAD_REG_H: u8 = 0x2C;
+const T_RELOAD_REG_L: u8 = 0x2D;
+const VERSION_REG: u8 = 0x37;
+
+// Commands
+const CMD_IDLE: u8 = 0x00;
+const CMD_TRANSCEIVE: u8 = 0x0C;
+const CMD_SOFT_RESET: u8 = 0x0F;
+
+// ISO 14443A
+const PICC_REQA: u8 = 0x26;
+const PICC_HLTA: u8 = 0x50;
+const PICC_CASCADE_TAG: u8 = 0x88;
+const PICC_SEL_CL: [u8; 3] = [0x93, 0x95, 0x97];
+
+static LAST_UID: Mutex<Option<String>> = Mutex::new(None);
+
+type Spi = SpiDeviceDriver<'static, SpiDriver<'static>>;
+
+/// UID of the most recently tapped card, known or not.
+pub fn last_uid() -> Option<String> {
+    LAST_UID.lock().unwrap().clone()
+}
+
+/// Spawns the reader thread. Without a reader attached the thread exits
+/// after logging a warning.
+pub fn start(spi: Spi) -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("rfid".into())
+        .stack_size(RFID_STACK_SIZE)
+        .spawn(move || {
+            let mut reader = Mfrc522 { spi };
+            match reader.init() {
+                Ok(version) => info!("MFRC522 version {:#04x} ready", version),
+                Err(e) => {
+                    warn!("No MFRC522 badge reader: {:?}", e);
+                    return;
+                }
+            }
+
+            loop {
+                if let Some(uid) = reader.read_uid() {
+                    let uid: String = uid.iter().map(|b| format!("{:02x}", b)).collect();
+                    handle_card(&uid);
+                    *LAST_UID.lock().unwrap() = Some(uid);
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        })?;
+
+    Ok(())
+}
+
+fn handle_card(uid: &str) {
+    let Some(card) = config::get()
+        .rfid
+        .cards
+        .into_iter()
+        .find(|card| card.uid.eq_ignore_ascii_case(uid))
+    else {
+        info!("Unknown badge {}", uid);
+        return;
+    };
+
+    info!("Badge {} tapped: {:?}", uid, card.action);
+    match card.action {
+        RfidAction::Toggle => {
+            status::toggle_dnd(Source::Button);
+        }
+        RfidAction::Knock => notify::send(Event::Knock),
+        RfidAction::Free => {
+            status::set(Source::Button, Status::Free);
+        }
+        RfidAction::Dnd => {
+            status::set(Source::Button, Status::Dnd);
+        }
+        RfidAction::Away => {
+            status::set(Source::Button, Status::Away);
+        }
+    }
+}
+
+struct Mfrc522 {
+    spi: Spi,
+}
+
+impl Mfrc522 {
+    fn init(&mut self) -> anyhow::Result<u8> {
+        self.write(COMMAND_REG, CMD_SOFT_RESET)?;
+        std::thread::sleep(Duration::from_millis(50));
+
+        let version = self.read(VERSION_REG)?;
+        if version == 0x00 || version == 0xFF {
+            anyhow::bail!("Unexpected version register {:#04x}", version);
+        }
+
+        // Timer for a 25 ms transceive timeout
+        self.write(T_MODE_REG, 0x80)?;
+        self.write(T_PRESCALER_REG, 0xA9)?;
+        self.write(T_RELOAD_REG_H, 0x03)?;
+        self.write(T_RELOAD_REG_L, 0xE8)?;
+        // 100% ASK modulation, CRC preset 0x6363
+        self.write(TX_ASK_REG, 0x40)?;
+        self.write(MODE_REG, 0x3D)?;
+        // Antenna on
+        let tx_control = self.read(TX_CONTROL_REG)?;
+        self.write(TX_CONTROL_REG, tx_control | 0x03)?;
+
+        Ok(version)
+    }
+
+    /// Wakes an idle card, selects it and returns its UID.
+    fn read_uid(&mut self) -> Option<Vec<u8>> {
+        // REQA is a short frame of 7 bits
+        self.transceive(&[PICC_REQA], 7).ok()?;
+
+        let mut uid = Vec::new();
+        for select in PICC_SEL_CL {
+            // Anticollision: the card returns 4 UID bytes and their XOR
+            self.write(COLL_REG, 0x80).ok()?;
+            let response = self.transceive(&[select, 0x20], 0).ok()?;
+            if response.len() != 5 || response[..4].iter().fold(0, |acc, b| acc ^ b) != response[4]
+            {
+                return None;
+            }
+
+            let mut frame = vec![select, 0x70];
+            frame.extend_from_slice(&response);
+            frame.extend_from_slice(&crc_a(&frame));
+            let sak = self.transceive(&frame, 0).ok()?;
+            let complete = sak.first()? & 0x04 == 0;
+
+            if response[0] == PICC_CASCADE_TAG && !complete {
+                uid.extend_from_slice(&response[1..4]);
+            } else {
+                uid.extend_from_slice(&response[..4]);
+                break;
+            }
+        }
+
+        // Halt the card so it stays quiet until it is presented again
+        let mut halt = vec![PICC_HLTA, 0x00];
+        halt.extend_from_slice(&crc_a(&halt));
+        let _ = self.transceive(&halt, 0);
+
+        Some(uid)
+    }
+
+    // Sends a frame and returns the card's answer. `last_bits` is the number
+    // of valid bits in the last byte, 0 meaning all eight.
+    fn transceive(&mut self, data: &[u8], last_bits: u8) -> anyhow::Result<Vec<u8>> {
+        self.write(COMMAND_REG, CMD_IDLE)?;
+        self.write(COM_IRQ_REG, 0x7F)?;
+        self.write(FIFO_LEVEL_REG, 0x80)?;
+        for byte in data {
+            self.write(FIFO_DATA_REG, *byte)?;
+        }
+        self.write(BIT_FRAMING_REG, last_bits)?;
+        self.write(COMMAND_REG, CMD_TRANSCEIVE)?;
+        self.write(BIT_FRAMING_REG, 0x80 | last_bits)?;
+
+        let started = Instant::now();
+        loop {
+            let irq = self.read(COM_IRQ_REG)?;
+            // RxIRq or IdleIRq: done
+            if irq & 0x30 != 0 {
+                break;
+            }
+            // TimerIRq: no card answered
+            if irq & 0x01 != 0 || started.elapsed() > TRANSCEIVE_TIMEOUT {
+                anyhow::bail!("No answer");
+            }
+        }
+
+        // BufferOvfl, ParityErr or ProtocolErr
+        if self.read(ERROR_REG)? & 0x13 != 0 {
+            anyhow::bail!("Transmission error");
+        }
+
+        let len = self.read(FIFO_LEVEL_REG)?;
+        (0..len).map(|_| self.read(FIFO_DATA_REG)).collect()
+    }
+
+    fn read(&mut self, register: u8) -> anyhow::Result<u8> {
+        let mut buf = [0; 2];
+        self.spi
+            .transfer(&mut buf, &[0x80 | (register << 1), 0x00])?;
+        Ok(buf[1])
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> anyhow::Result<()> {
+        self.spi.write(&[register << 1, value])?;
+        Ok(())
+    }
+}
+
+// ISO 14443A CRC, appended little-endian
+fn crc_a(data: &[u8]) -> [u8; 2] {
+    let mut crc: u16 = 0x6363;
+    for byte in data {
+        let mut b = byte ^ (crc as u8);
+        b ^= b << 4;
+        crc = (crc >> 8) ^ ((b as u16) << 8) ^ ((b as u16) << 3) ^ ((b as u16) >> 4);
+    }
+    crc.to_le_bytes()
+}
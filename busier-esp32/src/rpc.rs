@@ -0,0 +1,213 @@
+//! JSON-RPC 2.0 control endpoint.
+//!
+//! `POST /rpc` takes a single request or a batch and offers the common
+//! operations of the REST routes as methods, for scripts that would rather
+//! talk to one endpoint:
+//!
+//! - `status.get`, optionally with `{"user": name}`
+//! - `status.set` with `{"status": "dnd"}`, plus `back_at`, `user`, `source`
+//!   or `ttl_secs`
+//! - `config.get`, secrets redacted
+//! - `display.message` with `{"text": "...", "seconds": 10}`
+//!
+//! The whole endpoint sits behind the admin login.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config;
+use crate::display;
+use crate::status::{self, Source, Status};
+use crate::users;
+
+// Upper bound for a request or batch
+pub const MAX_RPC_LEN: usize = 4096;
+const DEFAULT_MESSAGE_SECS: u64 = 10;
+const MAX_MESSAGE_SECS: u64 = 5 * 60;
+
+// Error codes from the specification
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl Error {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+/// Handles a request body. Returns the response, or None when it held only
+/// notifications.
+pub fn handle(body: &[u8]) -> Option<Value> {
+    let request = match serde_json::from_slice::<Value>(body) {
+        Ok(request) => request,
+        Err(e) => return Some(error(Value::Null, Error::new(PARSE_ERROR, e.to_string()))),
+    };
+
+    match request {
+        Value::Array(batch) if batch.is_empty() => Some(error(
+            Value::Null,
+            Error::new(INVALID_REQUEST, "Empty batch"),
+        )),
+        Value::Array(batch) => {
+            let responses: Vec<Value> = batch.into_iter().filter_map(call).collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => call(request),
+    }
+}
+
+// Runs one request; None for notifications, which get no response
+fn call(request: Value) -> Option<Value> {
+    #[derive(Deserialize)]
+    struct Request {
+        jsonrpc: String,
+        method: String,
+        #[serde(default)]
+        params: Value,
+    }
+
+    // Only a missing id makes a notification; null is a valid id
+    let id = request.get("id").cloned();
+    let request = match serde_json::from_value::<Request>(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        _ => {
+            return Some(error(
+                id.unwrap_or_default(),
+                Error::new(INVALID_REQUEST, "Not a JSON-RPC 2.0 request"),
+            ))
+        }
+    };
+
+    let result = match request.method.as_str() {
+        "status.get" => status_get(request.params),
+        "status.set" => status_set(request.params),
+        "config.get" => Ok(json!(config::get().redacted())),
+        "display.message" => display_message(request.params),
+        method => Err(Error::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+        )),
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(e) => error(id, e),
+    })
+}
+
+fn error(id: Value, error: Error) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": { "code": error.code, "message": error.message },
+        "id": id,
+    })
+}
+
+// Missing params are the same as empty ones
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, Error> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(|e| Error::params(e.to_string()))
+}
+
+fn status_get(params: Value) -> Result<Value, Error> {
+    #[derive(Deserialize)]
+    struct Params {
+        user: Option<String>,
+    }
+
+    let params: Params = self::params(params)?;
+    match params.user {
+        Some(user) => users::get(&user)
+            .map(|status| json!({ "user": user, "status": status }))
+            .ok_or_else(|| Error::params("Unknown user")),
+        None => Ok(json!({
+            "status": status::current(),
+            "selected": status::selected(),
+            "back_at": status::back_at().map(|b| b.time),
+            "source": status::source(),
+        })),
+    }
+}
+
+fn status_set(params: Value) -> Result<Value, Error> {
+    #[derive(Deserialize)]
+    struct Params {
+        status: String,
+        back_at: Option<String>,
+        user: Option<String>,
+        source: Option<String>,
+        ttl_secs: Option<u64>,
+    }
+
+    let params: Params = self::params(params)?;
+    let new_status =
+        Status::parse(&params.status).ok_or_else(|| Error::params("Invalid status"))?;
+    let source = match params.source {
+        Some(name) => Source::parse(&name).ok_or_else(|| Error::params("Invalid source"))?,
+        None => Source::Manual,
+    };
+
+    let lease = match params.ttl_secs {
+        Some(0) => return Err(Error::params("Invalid ttl_secs")),
+        ttl_secs => ttl_secs.map(Duration::from_secs),
+    };
+
+    let changed = match (params.user, params.back_at) {
+        // People's statuses have no "back at" time or lease
+        (Some(_), Some(_)) => return Err(Error::params("back_at is not supported for users")),
+        (Some(_), None) if lease.is_some() => {
+            return Err(Error::params("ttl_secs is not supported for users"))
+        }
+        (Some(user), None) => {
+            users::set(&user, new_status).ok_or_else(|| Error::params("Unknown user"))?
+        }
+        (None, Some(time)) => {
+            let back_at = status::BackAt::parse(&time)
+                .ok_or_else(|| Error::params("Invalid back_at time or clock not synchronized"))?;
+            status::set_with_lease(source, new_status, Some(back_at), lease)
+        }
+        (None, None) => status::set_with_lease(source, new_status, None, lease),
+    };
+
+    Ok(json!({ "status": new_status, "changed": changed }))
+}
+
+fn display_message(params: Value) -> Result<Value, Error> {
+    #[derive(Deserialize)]
+    struct Params {
+        text: String,
+        seconds: Option<u64>,
+    }
+
+    let params: Params = self::params(params)?;
+    let seconds = params.seconds.unwrap_or(DEFAULT_MESSAGE_SECS);
+    if seconds == 0 || seconds > MAX_MESSAGE_SECS {
+        return Err(Error::params(format!(
+            "seconds must be between 1 and {}",
+            MAX_MESSAGE_SECS
+        )));
+    }
+
+    display::post_message(params.text, Duration::from_secs(seconds));
+    Ok(json!({ "seconds": seconds }))
+}
@@ -0,0 +1,131 @@
+//! DS3231 real-time clock.
+//!
+//! A battery-backed clock on the shared I2C bus that keeps the time through
+//! power cuts. At startup it sets the system clock, so schedules work before
+//! WiFi and SNTP are up. Afterwards the two keep each other right: every
+//! SNTP sync is written to the DS3231, and while SNTP is out of reach the
+//! DS3231, which drifts far less than the chip, sets the system clock every
+//! hour.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use busier_core::rtc::{self, Registers};
+use embedded_hal::i2c::I2c;
+use log::{info, warn};
+
+use crate::clock;
+use crate::device;
+
+const RTC_STACK_SIZE: usize = 4096;
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+// How often the DS3231 sets the clock without SNTP
+const RESTORE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// DS3231 registers
+const ADDRESS: u8 = 0x68;
+const TIME: u8 = 0x00;
+const STATUS: u8 = 0x0F;
+
+// Set when the oscillator stopped, as on a flat battery; the time is lost
+const OSCILLATOR_STOPPED: u8 = 0x80;
+
+struct Ds3231<I> {
+    i2c: I,
+}
+
+impl<I: I2c> Ds3231<I> {
+    fn error(e: I::Error) -> anyhow::Error {
+        anyhow::anyhow!("I2C error: {:?}", e)
+    }
+
+    /// The time, or None if the chip lost it.
+    fn read(&mut self) -> anyhow::Result<Option<SystemTime>> {
+        let mut status = [0];
+        self.i2c
+            .write_read(ADDRESS, &[STATUS], &mut status)
+            .map_err(Self::error)?;
+        if status[0] & OSCILLATOR_STOPPED != 0 {
+            return Ok(None);
+        }
+
+        let mut registers: Registers = [0; 7];
+        self.i2c
+            .write_read(ADDRESS, &[TIME], &mut registers)
+            .map_err(Self::error)?;
+        Ok(rtc::decode(&registers).map(|secs| UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+
+    /// Sets the time and clears the oscillator flag.
+    fn write(&mut self, time: SystemTime) -> anyhow::Result<()> {
+        let secs = time.duration_since(UNIX_EPOCH)?.as_secs();
+        let registers =
+            rtc::encode(secs).ok_or_else(|| anyhow::anyhow!("{} is out of range", secs))?;
+
+        let mut data = [TIME; 8];
+        data[1..].copy_from_slice(&registers);
+        self.i2c.write(ADDRESS, &data).map_err(Self::error)?;
+        self.i2c.write(ADDRESS, &[STATUS, 0]).map_err(Self::error)
+    }
+}
+
+/// Sets the clock from the DS3231 unless it is set already, as after deep
+/// sleep, and spawns the thread that keeps them in step. Without a DS3231 on
+/// the bus no thread is started.
+pub fn start<I>(i2c: I) -> anyhow::Result<()>
+where
+    I: I2c + Send + 'static,
+{
+    let mut rtc = Ds3231 { i2c };
+    match rtc.read() {
+        Ok(Some(time)) if !clock::is_synced() => match clock::set_from_backup(time) {
+            Ok(()) => info!("Clock set from the DS3231"),
+            Err(e) => warn!("Failed to set the clock from the DS3231: {:?}", e),
+        },
+        Ok(Some(_)) => info!("DS3231 ready"),
+        Ok(None) => info!("DS3231 lost its time, waiting for SNTP"),
+        Err(e) => {
+            warn!("No DS3231 real-time clock: {:?}", e);
+            return Ok(());
+        }
+    }
+
+    std::thread::Builder::new()
+        .name("rtc".into())
+        .stack_size(RTC_STACK_SIZE)
+        .spawn(move || {
+            let mut written_sync = None;
+            let mut restored_at = Instant::now();
+
+            loop {
+                std::thread::sleep(CHECK_INTERVAL);
+
+                let synced_at = clock::ntp_synced_at();
+                if synced_at.is_some() && synced_at != written_sync {
+                    match rtc.write(SystemTime::now()) {
+                        Ok(()) => written_sync = synced_at,
+                        Err(e) => warn!("Failed to set the DS3231: {:?}", e),
+                    }
+                    continue;
+                }
+
+                let ntp_stale = synced_at.is_none_or(|at| {
+                    device::uptime().as_secs().saturating_sub(at) >= RESTORE_INTERVAL.as_secs()
+                });
+                if !ntp_stale || restored_at.elapsed() < RESTORE_INTERVAL {
+                    continue;
+                }
+                restored_at = Instant::now();
+                match rtc.read() {
+                    Ok(Some(time)) => {
+                        if let Err(e) = clock::set_from_backup(time) {
+                            warn!("Failed to set the clock from the DS3231: {:?}", e);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Failed to read the DS3231: {:?}", e),
+                }
+            }
+        })?;
+
+    Ok(())
+}
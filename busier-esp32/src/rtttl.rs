@@ -0,0 +1,117 @@
+//! RTTTL ringtone parser.
+//!
+//! Parses the Ring Tone Text Transfer Language used by old Nokia phones,
+//! e.g. `"Beep:d=8,o=6,b=140:c,p,c"`: a name, the default duration, octave
+//! and tempo, then comma-separated notes of the form
+//! `[duration]note[#][.][octave][.]`, with `p` as a pause.
+
+use std::time::Duration;
+
+const DEFAULT_DURATION: u32 = 4;
+const DEFAULT_OCTAVE: u32 = 6;
+const DEFAULT_BPM: u32 = 63;
+
+/// One note to play; no frequency for a pause.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    pub frequency: Option<u32>,
+    pub duration: Duration,
+}
+
+/// Parses a ringtone into its notes.
+pub fn parse(text: &str) -> anyhow::Result<Vec<Note>> {
+    let mut sections = text.trim().splitn(3, ':');
+    let (Some(_name), Some(defaults), Some(notes)) =
+        (sections.next(), sections.next(), sections.next())
+    else {
+        anyhow::bail!("expected name:defaults:notes");
+    };
+
+    let mut duration = DEFAULT_DURATION;
+    let mut octave = DEFAULT_OCTAVE;
+    let mut bpm = DEFAULT_BPM;
+    for setting in defaults.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (key, value) = setting
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("bad setting {:?}", setting))?;
+        let value: u32 = value.trim().parse()?;
+        match key.trim() {
+            "d" => duration = value,
+            "o" => octave = value,
+            "b" => bpm = value,
+            _ => anyhow::bail!("unknown setting {:?}", key),
+        }
+    }
+    if !matches!(duration, 1 | 2 | 4 | 8 | 16 | 32) || bpm == 0 {
+        anyhow::bail!("bad defaults {:?}", defaults);
+    }
+
+    // A whole note lasts four beats
+    let whole_ms = 4 * 60_000 / bpm;
+
+    notes
+        .split(',')
+        .map(str::trim)
+        .filter(|note| !note.is_empty())
+        .map(|note| parse_note(note, duration, octave, whole_ms))
+        .collect()
+}
+
+fn parse_note(text: &str, duration: u32, octave: u32, whole_ms: u32) -> anyhow::Result<Note> {
+    let bad = || anyhow::anyhow!("bad note {:?}", text);
+    let lower = text.to_ascii_lowercase();
+    let mut rest = lower.as_str();
+
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let duration = match &rest[..digits] {
+        "" => duration,
+        value => value.parse().map_err(|_| bad())?,
+    };
+    rest = &rest[digits..];
+    if !matches!(duration, 1 | 2 | 4 | 8 | 16 | 32) {
+        return Err(bad());
+    }
+
+    let mut chars = rest.chars();
+    // Semitones above C
+    let semitone = match chars.next().ok_or_else(bad)? {
+        'c' => Some(0),
+        'd' => Some(2),
+        'e' => Some(4),
+        'f' => Some(5),
+        'g' => Some(7),
+        'a' => Some(9),
+        'b' | 'h' => Some(11),
+        'p' => None,
+        _ => return Err(bad()),
+    };
+    rest = chars.as_str();
+
+    let sharp = rest.starts_with('#');
+    rest = rest.trim_start_matches('#');
+
+    // The dot may come before or after the octave
+    let mut dotted = rest.starts_with('.');
+    rest = rest.trim_start_matches('.');
+    let octave = match rest.trim_end_matches('.') {
+        "" => octave,
+        value => value.parse().map_err(|_| bad())?,
+    };
+    dotted |= rest.ends_with('.');
+
+    let mut ms = whole_ms / duration;
+    if dotted {
+        ms += ms / 2;
+    }
+
+    Ok(Note {
+        frequency: semitone.map(|semitone| frequency(semitone + u32::from(sharp), octave)),
+        duration: Duration::from_millis(ms.into()),
+    })
+}
+
+// Equal temperament, A4 at 440 Hz
+fn frequency(semitone: u32, octave: u32) -> u32 {
+    let from_a4 = semitone as f32 - 9.0 + 12.0 * (octave as f32 - 4.0);
+    (440.0 * 2f32.powf(from_a4 / 12.0)).round() as u32
+}
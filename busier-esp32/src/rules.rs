@@ -0,0 +1,68 @@
+//! Applies the rules in the configuration.
+//!
+//! Checked once a second from the display loop. The status of the rule that
+//! applies is claimed as the `rules` source and its LED setting overrides
+//! the LED's usual behaviour; both go away once no rule applies. The rules
+//! themselves are in `busier_core::rules`.
+
+use std::sync::Mutex;
+
+use log::info;
+
+use busier_core::rules::{self, Facts};
+
+pub use busier_core::rules::Rule;
+
+use crate::clock;
+use crate::config;
+use crate::door;
+use crate::schedule;
+use crate::status::{self, Source};
+
+// The rule that applied on the last check
+static ACTIVE: Mutex<Option<Rule>> = Mutex::new(None);
+
+/// Checks the rules and applies the first that matches.
+pub fn tick() {
+    let facts = Facts {
+        now: clock::local_now(),
+        claims: status::claims(),
+        working_hours: schedule::in_working_hours(),
+        door_open: door::is_open(),
+    };
+    let rules = config::get().rules;
+    let matched = rules::first_match(&rules, &facts);
+
+    let mut active = ACTIVE.lock().unwrap();
+    if active.as_ref() == matched {
+        return;
+    }
+    *active = matched.cloned();
+    drop(active);
+
+    match matched {
+        Some(rule) => {
+            info!("Rule '{}' applies", rule.name);
+            match rule.then.status {
+                Some(new_status) => {
+                    status::set(Source::Rules, new_status);
+                }
+                None => status::withdraw(Source::Rules),
+            }
+        }
+        None => {
+            info!("No rule applies");
+            status::withdraw(Source::Rules);
+        }
+    }
+}
+
+/// Name of the rule that applies, if any.
+pub fn active() -> Option<String> {
+    ACTIVE.lock().unwrap().as_ref().map(|rule| rule.name.clone())
+}
+
+/// Whether the applying rule lights the LED or keeps it dark.
+pub fn led() -> Option<bool> {
+    ACTIVE.lock().unwrap().as_ref().and_then(|rule| rule.then.led)
+}
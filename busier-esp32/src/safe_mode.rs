@@ -0,0 +1,273 @@
+//! Reset reasons and safe mode.
+//!
+//! Each start records why the chip reset and counts the crashes in a row in
+//! NVS; see [`busier_core::reset`]. After too many, the device starts in
+//! safe mode: it connects to WiFi and serves only the setup wizard, the
+//! configuration, firmware updates and a restart, with every integration
+//! left off and "SAFE MODE" on the display, so a bad setting or firmware
+//! cannot keep it from starting.
+
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use busier_core::reset::{self, ResetReason};
+use embedded_svc::http::Method;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::EspHttpServer;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys;
+use log::{info, warn};
+
+use crate::auth;
+use crate::battery;
+use crate::config;
+use crate::device;
+use crate::display::{self, Displays};
+use crate::http_util::{self, Routes};
+use crate::i18n;
+use crate::ota;
+use crate::setup;
+use crate::status;
+use crate::users;
+use crate::web;
+
+const NAMESPACE: &str = "boot";
+const CRASHES_KEY: &str = "crashes";
+const SAFE_MODE_STACK_SIZE: usize = 3072;
+
+static REASON: OnceLock<ResetReason> = OnceLock::new();
+static CRASHES: AtomicU8 = AtomicU8::new(0);
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static NVS: Mutex<Option<EspNvs<NvsDefault>>> = Mutex::new(None);
+
+static SAFE_MODE_HTML: &str = r#"<!DOCTYPE html>
+<html lang="{{lang}}">
+<head>
+    <meta charset="utf-8">
+    <title>{{web.safe_mode_title}}</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <style>
+        body {
+            font-family: Arial, sans-serif;
+            margin: 0;
+            padding: 20px;
+            background-color: #f5f5f5;
+        }
+        .container {
+            max-width: 600px;
+            margin: 0 auto;
+            background-color: white;
+            padding: 30px;
+            border-radius: 8px;
+            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
+        }
+        textarea {
+            width: 100%;
+            box-sizing: border-box;
+            height: 300px;
+            font-family: monospace;
+        }
+        button {
+            background-color: #4CAF50;
+            color: white;
+            padding: 12px 25px;
+            border: none;
+            border-radius: 4px;
+            cursor: pointer;
+            font-size: 16px;
+        }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>{{web.safe_mode_title}}</h1>
+        <p>{{web.safe_mode_intro}}</p>
+        <p>{{web.last_reset}}: <code>{{reset_reason}}</code></p>
+
+        <textarea id="config" spellcheck="false"></textarea>
+        <p>
+            <button onclick="save()">{{web.save}}</button>
+            <button onclick="restart()">{{web.restart}}</button>
+        </p>
+        <p id="result"></p>
+    </div>
+
+    <script>
+        const result = document.getElementById('result');
+
+        fetch('/api/config')
+            .then(response => response.json())
+            .then(config => {
+                document.getElementById('config').value = JSON.stringify(config, null, 2);
+            });
+
+        function save() {
+            fetch('/api/config', {
+                method: 'POST',
+                headers: {
+                    'Content-Type': 'application/json',
+                },
+                body: document.getElementById('config').value,
+            })
+            .then(response => response.text())
+            .then(text => {
+                result.textContent = text;
+            });
+        }
+
+        function restart() {
+            fetch('/api/restart', { method: 'POST' })
+                .then(response => response.text())
+                .then(text => {
+                    result.textContent = text;
+                });
+        }
+    </script>
+</body>
+</html>"#;
+
+/// Records the reset reason and counts the crash, if it was one. Call once,
+/// right after the configuration is loaded.
+pub fn init(partition: EspDefaultNvsPartition) -> anyhow::Result<()> {
+    let mut nvs = EspNvs::new(partition, NAMESPACE, true)?;
+
+    let reason = reset_reason();
+    let crashes = reset::crashes(nvs.get_u8(CRASHES_KEY)?.unwrap_or(0), reason);
+    nvs.set_u8(CRASHES_KEY, crashes)?;
+
+    if reason.is_crash() {
+        warn!(
+            "Restarted after a {:?}, {} crashes in a row",
+            reason, crashes
+        );
+    } else {
+        info!("Started after {:?}", reason);
+    }
+    if reset::is_safe_mode(crashes) {
+        warn!("Starting in safe mode");
+        ACTIVE.store(true, Ordering::SeqCst);
+    }
+
+    let _ = REASON.set(reason);
+    CRASHES.store(crashes, Ordering::SeqCst);
+    *NVS.lock().unwrap() = Some(nvs);
+
+    Ok(())
+}
+
+/// Why the chip last started.
+pub fn reason() -> ResetReason {
+    REASON.get().copied().unwrap_or(ResetReason::Other)
+}
+
+/// The crashes in a row, counting this start.
+pub fn crashes() -> u8 {
+    CRASHES.load(Ordering::SeqCst)
+}
+
+/// Whether the device started in safe mode.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Spawns a thread that forgets the crashes once the device has run for a
+/// while.
+pub fn start() -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("safe_mode".into())
+        .stack_size(SAFE_MODE_STACK_SIZE)
+        .spawn(|| {
+            std::thread::sleep(Duration::from_secs(reset::STABLE_SECS));
+            if let Some(nvs) = NVS.lock().unwrap().as_mut() {
+                if let Err(e) = nvs.set_u8(CRASHES_KEY, 0) {
+                    warn!("Failed to clear the crash counter: {:?}", e);
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Runs safe mode: shows it on the displays and serves the pages to fix
+/// the configuration over plain HTTP. Does not return.
+pub fn run(displays: &mut Displays, ip: Ipv4Addr) -> anyhow::Result<()> {
+    let text = i18n::text("display.safe_mode").to_string();
+    displays.show(&display::Frame {
+        ip,
+        status: status::current(),
+        detail: text.clone(),
+        step: Some(text),
+        battery: battery::level(),
+        low_memory: false,
+        temperature: None,
+        users: users::list(),
+    });
+
+    let mut server = EspHttpServer::new(&http_util::server_configuration())?;
+    setup::register(&mut server)?;
+    config::register(&mut server, false)?;
+    ota::register(&mut server, false)?;
+    register(&mut server)?;
+    info!("Safe mode pages at http://{}/", ip);
+
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    server.route(
+        "/",
+        Method::Get,
+        auth::admin(false, |req| {
+            let reason = serde_json::to_value(reason())?;
+            let reason = reason.as_str().unwrap_or_default().to_string();
+            let page = web::render_with(SAFE_MODE_HTML, vec![("reset_reason", reason)]);
+            req.into_response(200, None, &[web::HTML])?
+                .write_all(page.as_bytes())?;
+            Ok(())
+        }),
+    )?;
+
+    server.route(
+        "/api/restart",
+        Method::Post,
+        auth::admin(false, |req| {
+            req.into_ok_response()?.write_all("Restarting".as_bytes())?;
+            device::restart()
+        }),
+    )?;
+
+    server.route("/health", Method::Get, |req| {
+        let body = serde_json::json!({
+            "safe_mode": true,
+            "reset_reason": reason(),
+            "crashes": crashes(),
+        });
+        req.into_response(200, None, &[("Content-Type", "application/json")])?
+            .write_all(body.to_string().as_bytes())?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn reset_reason() -> ResetReason {
+    // SAFETY: only reads the reason recorded at boot
+    match unsafe { sys::esp_reset_reason() } {
+        sys::esp_reset_reason_t_ESP_RST_POWERON => ResetReason::PowerOn,
+        sys::esp_reset_reason_t_ESP_RST_EXT | sys::esp_reset_reason_t_ESP_RST_USB => {
+            ResetReason::External
+        }
+        sys::esp_reset_reason_t_ESP_RST_SW => ResetReason::Software,
+        sys::esp_reset_reason_t_ESP_RST_PANIC => ResetReason::Panic,
+        sys::esp_reset_reason_t_ESP_RST_INT_WDT
+        | sys::esp_reset_reason_t_ESP_RST_TASK_WDT
+        | sys::esp_reset_reason_t_ESP_RST_WDT => ResetReason::Watchdog,
+        sys::esp_reset_reason_t_ESP_RST_DEEPSLEEP => ResetReason::DeepSleep,
+        sys::esp_reset_reason_t_ESP_RST_BROWNOUT => ResetReason::Brownout,
+        _ => ResetReason::Other,
+    }
+}
@@ -0,0 +1,16 @@
+//! Working hours and quiet hours on the system clock, as configured.
+//!
+//! The rules themselves are in `busier_core::schedule`.
+
+use busier_core::schedule::{quiet_hours_at, working_hours_at};
+
+use crate::config;
+use crate::hal::SystemClock;
+
+pub fn in_working_hours() -> bool {
+    working_hours_at(&config::get().working_hours, &SystemClock)
+}
+
+pub fn in_quiet_hours() -> bool {
+    quiet_hours_at(&config::get().quiet_hours, &SystemClock)
+}
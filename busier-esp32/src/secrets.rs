@@ -0,0 +1,177 @@
+//! Secrets kept apart from the rest of the settings.
+//!
+//! The WiFi credentials, the secrets of the configuration, that is the
+//! passwords, API tokens and keys of [`busier_core::config::Config::take_secrets`],
+//! and the private keys of other modules, such as the HTTPS key and the
+//! HomeKit identity, live in the `secrets` NVS partition rather than next
+//! to the configuration in plain text. With `CONFIG_NVS_ENCRYPTION`, which
+//! takes flash encryption, the partition is encrypted with keys generated on
+//! first boot into the `nvs_keys` partition, itself covered by flash
+//! encryption. Without it the partition is separate but plain, and the log
+//! says so at startup.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[cfg(not(esp_idf_nvs_encryption))]
+use esp_idf_svc::nvs::{EspCustomNvsPartition, NvsCustom};
+#[cfg(esp_idf_nvs_encryption)]
+use esp_idf_svc::nvs::{EspEncryptedNvsPartition, NvsEncrypted};
+use esp_idf_svc::nvs::{EspNvs, NvsPartitionId};
+use esp_idf_svc::sys::{self, esp};
+use log::info;
+#[cfg(not(esp_idf_nvs_encryption))]
+use log::warn;
+
+const PARTITION: &str = "secrets";
+#[cfg(esp_idf_nvs_encryption)]
+const KEYS_PARTITION: &str = "nvs_keys";
+const NAMESPACE: &str = "secrets";
+const CONFIG_KEY: &str = "config";
+const SSID_KEY: &str = "wifi_ssid";
+const PASSWORD_KEY: &str = "wifi_pass";
+// Upper bound for the serialized secrets of the configuration
+const MAX_SECRETS_LEN: usize = 4096;
+// A WPA passphrase of up to 64 characters and the terminating NUL
+const MAX_WIFI_LEN: usize = 65;
+
+#[cfg(esp_idf_nvs_encryption)]
+type Partition = NvsEncrypted;
+#[cfg(not(esp_idf_nvs_encryption))]
+type Partition = NvsCustom;
+
+static NVS: Mutex<Option<EspNvs<Partition>>> = Mutex::new(None);
+
+/// Opens the secrets partition. Call once, before [`crate::config::init`].
+pub fn init() -> anyhow::Result<()> {
+    #[cfg(esp_idf_nvs_encryption)]
+    let partition = {
+        info!("Secrets are stored encrypted");
+        EspEncryptedNvsPartition::take(PARTITION, Some(KEYS_PARTITION))?
+    };
+    #[cfg(not(esp_idf_nvs_encryption))]
+    let partition = {
+        warn!("NVS encryption is off, secrets are stored unencrypted");
+        EspCustomNvsPartition::take(PARTITION)?
+    };
+
+    *NVS.lock().unwrap() = Some(EspNvs::new(partition, NAMESPACE, true)?);
+
+    Ok(())
+}
+
+/// The secrets of the configuration by key; empty when none are stored.
+pub fn config() -> anyhow::Result<BTreeMap<String, String>> {
+    let nvs = NVS.lock().unwrap();
+    let Some(nvs) = nvs.as_ref() else {
+        return Ok(BTreeMap::new());
+    };
+
+    let mut buf = vec![0; MAX_SECRETS_LEN];
+    match nvs.get_raw(CONFIG_KEY, &mut buf)? {
+        Some(data) => Ok(serde_json::from_slice(data)?),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/// Replaces the secrets of the configuration.
+pub fn set_config(secrets: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    let data = serde_json::to_vec(secrets)?;
+    if data.len() > MAX_SECRETS_LEN {
+        anyhow::bail!("Secrets too big");
+    }
+
+    let mut nvs = NVS.lock().unwrap();
+    let nvs = nvs
+        .as_mut()
+        .ok_or_else(|| anyhow::anyhow!("No secrets partition"))?;
+    nvs.set_raw(CONFIG_KEY, &data)?;
+    Ok(())
+}
+
+/// A secret of another module stored under `key`, of at most `max_len`
+/// bytes. Its keys must not clash with the ones used here.
+pub fn get(key: &str, max_len: usize) -> anyhow::Result<Option<Vec<u8>>> {
+    let nvs = NVS.lock().unwrap();
+    let nvs = nvs
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No secrets partition"))?;
+
+    let mut buf = vec![0; max_len];
+    Ok(nvs.get_raw(key, &mut buf)?.map(<[u8]>::to_vec))
+}
+
+/// Stores a secret of another module under `key`.
+pub fn set(key: &str, value: &[u8]) -> anyhow::Result<()> {
+    let mut nvs = NVS.lock().unwrap();
+    let nvs = nvs
+        .as_mut()
+        .ok_or_else(|| anyhow::anyhow!("No secrets partition"))?;
+    nvs.set_raw(key, value)?;
+    Ok(())
+}
+
+/// Moves a secret that an older firmware kept under `old_key` in another
+/// namespace over to `key`, once.
+pub fn migrate<T: NvsPartitionId>(
+    nvs: &mut EspNvs<T>,
+    old_key: &str,
+    key: &str,
+    max_len: usize,
+) -> anyhow::Result<()> {
+    let mut buf = vec![0; max_len];
+    let value = match nvs.get_raw(old_key, &mut buf)? {
+        Some(value) => value.to_vec(),
+        // Strings are an entry type of their own
+        None => match nvs.get_str(old_key, &mut buf)? {
+            Some(value) => value.as_bytes().to_vec(),
+            None => return Ok(()),
+        },
+    };
+    if get(key, max_len)?.is_none() {
+        info!("Moving {} to the secrets partition", key);
+        set(key, &value)?;
+    }
+    nvs.remove(old_key)?;
+    Ok(())
+}
+
+/// The stored WiFi SSID and password, if any.
+pub fn wifi() -> Option<(String, String)> {
+    let nvs = NVS.lock().unwrap();
+    let nvs = nvs.as_ref()?;
+
+    let mut buf = [0; MAX_WIFI_LEN];
+    let ssid = nvs.get_str(SSID_KEY, &mut buf).ok()??.to_string();
+    let password = nvs.get_str(PASSWORD_KEY, &mut buf).ok()??.to_string();
+    Some((ssid, password))
+}
+
+/// Stores the WiFi SSID and password.
+pub fn set_wifi(ssid: &str, password: &str) -> anyhow::Result<()> {
+    let mut nvs = NVS.lock().unwrap();
+    let nvs = nvs
+        .as_mut()
+        .ok_or_else(|| anyhow::anyhow!("No secrets partition"))?;
+    nvs.set_str(SSID_KEY, ssid)?;
+    nvs.set_str(PASSWORD_KEY, password)?;
+    Ok(())
+}
+
+/// Forgets the WiFi SSID and password.
+#[cfg_attr(not(feature = "ble"), allow(dead_code))]
+pub fn clear_wifi() -> anyhow::Result<()> {
+    if let Some(nvs) = NVS.lock().unwrap().as_mut() {
+        nvs.remove(SSID_KEY)?;
+        nvs.remove(PASSWORD_KEY)?;
+    }
+    Ok(())
+}
+
+/// Keeps the WiFi driver from saving the credentials to the default NVS
+/// partition in plain text. Call after the driver is initialized.
+pub fn keep_wifi_in_ram() -> anyhow::Result<()> {
+    // SAFETY: only changes where the driver keeps its configuration
+    esp!(unsafe { sys::esp_wifi_set_storage(sys::wifi_storage_t_WIFI_STORAGE_RAM) })?;
+    Ok(())
+}
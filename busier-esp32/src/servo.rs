@@ -0,0 +1,83 @@
+//! Servo-driven flag.
+//!
+//! A hobby servo raises a physical "BUSY" flag. Each status has its own angle
+//! and the servo sweeps there slowly instead of snapping, which is quieter
+//! and easier on the flag. Once in place the pulses stop, so the servo does
+//! not hum or jitter while idle.
+
+use std::time::Duration;
+
+use esp_idf_svc::hal::ledc::LedcDriver;
+use log::warn;
+
+use crate::config;
+use crate::status::{self, Status};
+
+const SERVO_STACK_SIZE: usize = 4096;
+const STEP_INTERVAL: Duration = Duration::from_millis(20);
+// Steps to keep driving after arriving, so the servo settles
+const HOLD_STEPS: u32 = 25;
+// Pulse widths for 0 and 180 degrees within the 20 ms period
+const MIN_PULSE_US: u32 = 500;
+const MAX_PULSE_US: u32 = 2500;
+const PERIOD_US: u32 = 20_000;
+
+/// Spawns the thread driving the servo. The LEDC timer must run at 50 Hz.
+pub fn start(mut pwm: LedcDriver<'static>) -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("servo".into())
+        .stack_size(SERVO_STACK_SIZE)
+        .spawn(move || {
+            // Unknown until the first move
+            let mut angle: Option<f32> = None;
+            let mut held = HOLD_STEPS;
+
+            loop {
+                std::thread::sleep(STEP_INTERVAL);
+
+                let config = config::get().servo;
+                if !config.enabled {
+                    continue;
+                }
+
+                let target = f32::from(match status::current() {
+                    Status::Free => config.free_angle,
+                    Status::Dnd => config.dnd_angle,
+                    Status::Away => config.away_angle,
+                })
+                .min(180.0);
+                let step = config.degrees_per_sec * STEP_INTERVAL.as_secs_f32();
+
+                let next = match angle {
+                    // Jump on the first move, the current position is unknown
+                    None => target,
+                    Some(angle) if (target - angle).abs() <= step => target,
+                    Some(angle) => angle + step.copysign(target - angle),
+                };
+
+                if angle != Some(next) {
+                    held = 0;
+                } else if held >= HOLD_STEPS {
+                    continue;
+                }
+                held += 1;
+                angle = Some(next);
+
+                let duty = if held >= HOLD_STEPS {
+                    0
+                } else {
+                    duty_for(&pwm, next)
+                };
+                if let Err(e) = pwm.set_duty(duty) {
+                    warn!("Failed to drive servo: {:?}", e);
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+fn duty_for(pwm: &LedcDriver<'static>, angle: f32) -> u32 {
+    let pulse_us = MIN_PULSE_US as f32 + (MAX_PULSE_US - MIN_PULSE_US) as f32 * angle / 180.0;
+    (pwm.get_max_duty() as f32 * pulse_us / PERIOD_US as f32) as u32
+}
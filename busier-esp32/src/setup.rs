@@ -0,0 +1,261 @@
+//! First-boot setup wizard.
+//!
+//! A device without a stored configuration serves a setup page at `/setup`
+//! that asks for the admin password, the device name, the timezone, the
+//! language and the board.
+//! Until it has been completed, the guest page redirects there, and the
+//! admin routes, CoAP and BLE writes and HomeKit pairing refuse every
+//! request, so nobody on the network can change the device before it has a
+//! password.
+
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::{Read, Write};
+use esp_idf_svc::http::server::EspHttpServer;
+use log::info;
+use serde::Deserialize;
+
+use crate::board::Board;
+use crate::clock;
+use crate::config;
+use crate::http_util::Routes;
+use crate::i18n::{self, Language};
+
+pub const SETUP_PATH: &str = "/setup";
+// Max payload length for the setup form
+const MAX_SETUP_LEN: usize = 512;
+
+static SETUP_HTML: &str = r#"<!DOCTYPE html>
+<html lang="{{lang}}">
+<head>
+    <meta charset="utf-8">
+    <title>{{web.setup_title}}</title>
+    <meta name="viewport" content="width=device-width, initial-scale=1">
+    <style>
+        body {
+            font-family: Arial, sans-serif;
+            margin: 0;
+            padding: 20px;
+            background-color: #f5f5f5;
+        }
+        .container {
+            max-width: 600px;
+            margin: 0 auto;
+            background-color: white;
+            padding: 30px;
+            border-radius: 8px;
+            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
+        }
+        .step {
+            margin: 20px 0;
+        }
+        label {
+            display: block;
+            font-weight: bold;
+            margin-bottom: 5px;
+        }
+        input, select {
+            width: 100%;
+            box-sizing: border-box;
+            padding: 8px;
+        }
+        small {
+            color: #666;
+        }
+        button {
+            background-color: #4CAF50;
+            color: white;
+            padding: 12px 25px;
+            border: none;
+            border-radius: 4px;
+            cursor: pointer;
+            font-size: 16px;
+        }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>{{web.welcome}}</h1>
+        <p>{{web.setup_intro}}</p>
+
+        <div class="step">
+            <label for="password">{{web.admin_password}}</label>
+            <input id="password" type="password" autocomplete="new-password">
+            <input id="confirm" type="password" autocomplete="new-password" placeholder="{{web.repeat_password}}">
+            <small>{{web.password_hint}}</small>
+        </div>
+
+        <div class="step">
+            <label for="device-name">{{web.device_name}}</label>
+            <input id="device-name" placeholder="busier">
+            <small>{{web.device_name_hint}}</small>
+        </div>
+
+        <div class="step">
+            <label for="timezone">{{web.timezone}}</label>
+            <input id="timezone" list="timezones" placeholder="UTC">
+            <datalist id="timezones">
+                <option value="UTC">
+                <option value="Europe/London">
+                <option value="Europe/Berlin">
+                <option value="Europe/Paris">
+                <option value="Europe/Athens">
+                <option value="Europe/Helsinki">
+                <option value="America/New_York">
+                <option value="America/Chicago">
+                <option value="America/Los_Angeles">
+                <option value="Asia/Tokyo">
+                <option value="Australia/Sydney">
+            </datalist>
+            <small>{{web.timezone_hint}}</small>
+        </div>
+
+        <div class="step">
+            <label for="language">{{web.language}}</label>
+            <select id="language">
+                <option value="en">English</option>
+                <option value="de">Deutsch</option>
+                <option value="el">Ελληνικά</option>
+            </select>
+            <small>{{web.language_hint}}</small>
+        </div>
+
+        <div class="step">
+            <label for="board">{{web.board}}</label>
+            <select id="board">
+                <option value="custom">{{web.board_custom}}</option>
+                <option value="heltec-wifi-kit-32">Heltec WiFi Kit 32</option>
+                <option value="ttgo-lora32">LilyGO TTGO LoRa32</option>
+                <option value="ttgo-t-display">LilyGO TTGO T-Display</option>
+                <option value="waveshare-epaper-esp32">Waveshare e-Paper ESP32 Driver Board</option>
+            </select>
+            <small>{{web.board_hint}}</small>
+        </div>
+
+        <button onclick="finish()">{{web.finish_setup}}</button>
+        <p id="result"></p>
+    </div>
+
+    <script>
+        document.getElementById('language').value = '{{lang}}';
+
+        function finish() {
+            const password = document.getElementById('password').value;
+            if (password !== document.getElementById('confirm').value) {
+                document.getElementById('result').textContent = '{{web.passwords_mismatch}}';
+                return;
+            }
+            fetch('/api/setup', {
+                method: 'POST',
+                headers: {
+                    'Content-Type': 'application/json',
+                },
+                body: JSON.stringify({
+                    password: password,
+                    device_name: document.getElementById('device-name').value,
+                    timezone: document.getElementById('timezone').value,
+                    language: document.getElementById('language').value,
+                    board: document.getElementById('board').value,
+                }),
+            })
+            .then(response => {
+                if (response.ok) {
+                    window.location = '/admin';
+                    return;
+                }
+                return response.text().then(text => {
+                    document.getElementById('result').textContent = text;
+                });
+            })
+            .catch(error => {
+                console.error('Error finishing setup:', error);
+            });
+        }
+    </script>
+</body>
+</html>"#;
+
+/// Whether the wizard still has to run.
+pub fn is_pending() -> bool {
+    !config::is_stored()
+}
+
+pub fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    server.route(SETUP_PATH, Method::Get, |req| {
+        if !is_pending() {
+            req.into_response(302, None, &[("Location", "/admin")])?;
+            return Ok(());
+        }
+
+        req.into_ok_response()?
+            .write_all(i18n::localize(SETUP_HTML).as_bytes())?;
+        Ok(())
+    })?;
+
+    server.route("/api/setup", Method::Post, |mut req| {
+        #[derive(Deserialize)]
+        struct SetupData {
+            password: String,
+            #[serde(default)]
+            device_name: String,
+            #[serde(default)]
+            timezone: String,
+            #[serde(default)]
+            language: Language,
+            #[serde(default)]
+            board: Board,
+        }
+
+        if !is_pending() {
+            req.into_status_response(409)?
+                .write_all("Setup already done".as_bytes())?;
+            return Ok(());
+        }
+
+        let len = req.content_len().unwrap_or(0) as usize;
+        if len > MAX_SETUP_LEN {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        }
+
+        let mut buf = vec![0; len];
+        req.read_exact(&mut buf)?;
+
+        let data = match serde_json::from_slice::<SetupData>(&buf) {
+            Ok(data) if !data.password.is_empty() => data,
+            Ok(_) => {
+                req.into_status_response(400)?
+                    .write_all("An admin password is required".as_bytes())?;
+                return Ok(());
+            }
+            Err(_) => {
+                req.into_status_response(400)?
+                    .write_all("JSON error".as_bytes())?;
+                return Ok(());
+            }
+        };
+
+        let config = config::get();
+        let updated = config::update(serde_json::json!({
+            "admin": { "username": config.admin.username, "password": data.password },
+            "device_name": data.device_name.trim(),
+            "timezone": data.timezone.trim(),
+            "language": data.language,
+            "board": data.board,
+        }));
+        // Boards whose pins clash with the drivers of this build are refused
+        if let Err(e) = updated {
+            req.into_status_response(400)?
+                .write_all(e.to_string().as_bytes())?;
+            return Ok(());
+        }
+        clock::apply_timezone();
+        info!("Setup complete");
+
+        req.into_ok_response()?
+            .write_all("Setup complete".as_bytes())?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
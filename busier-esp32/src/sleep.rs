@@ -0,0 +1,156 @@
+//! Deep sleep for battery builds.
+//!
+//! With `sleep.enabled` the device stays awake for `sleep.awake_secs` after
+//! each wake-up, then deep sleeps until the timer, the BOOT button or an
+//! optional touch pad wakes it (the timer alone on the C3). The selected
+//! status and any "back at" time are kept in RTC memory, which survives deep
+//! sleep, so the device resumes with the same status and the display keeps
+//! showing it. Door signs, see `door_sign`, sleep the same way.
+
+use std::sync::atomic::{AtomicU16, AtomicU64, AtomicU8, Ordering};
+use std::time::{Duration, SystemTime};
+
+use esp_idf_svc::sys;
+use log::{info, warn};
+
+use crate::config::{self, SleepConfig};
+use crate::door_sign;
+use crate::pomodoro;
+use crate::status::{self, BackAt, Status};
+
+// Marks the RTC slots as empty, as on a cold boot
+const NONE: u8 = u8::MAX;
+
+#[link_section = ".rtc.data"]
+static SAVED_STATUS: AtomicU8 = AtomicU8::new(NONE);
+// "Back at" as local minute of day and Unix time; minute u16::MAX when unset
+#[link_section = ".rtc.data"]
+static SAVED_BACK_AT_MINUTE: AtomicU16 = AtomicU16::new(u16::MAX);
+#[link_section = ".rtc.data"]
+static SAVED_BACK_AT_UNIX: AtomicU64 = AtomicU64::new(0);
+
+/// Whether this boot is a wake-up from deep sleep.
+pub fn woke_up() -> bool {
+    // SAFETY: only reads the wake-up cause recorded at boot
+    let cause = unsafe { sys::esp_sleep_get_wakeup_cause() };
+    cause != sys::esp_sleep_source_t_ESP_SLEEP_WAKEUP_UNDEFINED
+}
+
+/// Whether this boot is a timer wake-up from deep sleep.
+pub fn woke_by_timer() -> bool {
+    // SAFETY: only reads the wake-up cause recorded at boot
+    let cause = unsafe { sys::esp_sleep_get_wakeup_cause() };
+    cause == sys::esp_sleep_source_t_ESP_SLEEP_WAKEUP_TIMER
+}
+
+/// Restores the state saved before deep sleep. Call early during startup.
+pub fn restore() {
+    // SAFETY: only reads the wake-up cause recorded at boot
+    let cause = unsafe { sys::esp_sleep_get_wakeup_cause() };
+    let reason = match cause {
+        sys::esp_sleep_source_t_ESP_SLEEP_WAKEUP_UNDEFINED => return,
+        sys::esp_sleep_source_t_ESP_SLEEP_WAKEUP_TIMER => "timer",
+        sys::esp_sleep_source_t_ESP_SLEEP_WAKEUP_EXT0 => "button",
+        sys::esp_sleep_source_t_ESP_SLEEP_WAKEUP_TOUCHPAD => "touch",
+        _ => "other",
+    };
+
+    let saved = SAVED_STATUS.load(Ordering::SeqCst);
+    if saved == NONE {
+        return;
+    }
+    let status = Status::from_u8(saved);
+
+    let minute = SAVED_BACK_AT_MINUTE.load(Ordering::SeqCst);
+    let back_at = (minute != u16::MAX).then(|| BackAt {
+        time: format!("{:02}:{:02}", minute / 60, minute % 60),
+        at: SystemTime::UNIX_EPOCH + Duration::from_secs(SAVED_BACK_AT_UNIX.load(Ordering::SeqCst)),
+        meeting: false,
+    });
+
+    info!("Woke up ({}), restoring status {}", reason, status.as_str());
+    status::restore(status, back_at);
+}
+
+/// Whether it is time to go to sleep, given how long the device has been
+/// awake. A running pomodoro keeps it awake. A door sign always sleeps, and
+/// right away after a timer wake-up.
+pub fn is_due(awake: Duration) -> bool {
+    let config = config::get();
+    let awake_secs = if door_sign::sleeps_after_sync() {
+        0
+    } else {
+        config.sleep.awake_secs
+    };
+    (config.sleep.enabled || config.door_sign.enabled)
+        && awake >= Duration::from_secs(awake_secs.into())
+        && pomodoro::state().is_none()
+}
+
+/// Saves the state and enters deep sleep. Only returns if a wake-up source
+/// could not be configured.
+pub fn enter() {
+    let config = config::get().sleep;
+
+    SAVED_STATUS.store(status::selected() as u8, Ordering::SeqCst);
+    match status::back_at() {
+        Some(back_at) => {
+            let minute = config::parse_hhmm(&back_at.time).unwrap_or(u16::MAX);
+            let unix = back_at
+                .at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            SAVED_BACK_AT_MINUTE.store(minute, Ordering::SeqCst);
+            SAVED_BACK_AT_UNIX.store(unix, Ordering::SeqCst);
+        }
+        None => SAVED_BACK_AT_MINUTE.store(u16::MAX, Ordering::SeqCst),
+    }
+
+    // Wake up in time to flip a "back at" status
+    let mut interval = Duration::from_secs(config.interval_secs.into());
+    if let Some(back_at) = status::back_at() {
+        interval = interval.min(back_at.remaining());
+    }
+
+    if let Err(e) = enable_wakeups(interval, &config) {
+        warn!("Cannot configure wake-up, staying awake: {:?}", e);
+        return;
+    }
+
+    info!("Deep sleeping for {} s", interval.as_secs());
+    // SAFETY: does not return; the chip restarts on wake-up
+    unsafe { sys::esp_deep_sleep_start() };
+}
+
+// The C3 has neither ext0 nor touch wake-ups and the S3's touch driver works
+// differently, so only the ESP32 wakes on all three
+fn enable_wakeups(interval: Duration, config: &SleepConfig) -> Result<(), sys::EspError> {
+    // SAFETY: plain configuration call on the sleep driver
+    sys::esp!(unsafe { sys::esp_sleep_enable_timer_wakeup(interval.as_micros() as u64) })?;
+
+    // SAFETY: as above; GPIO0 is an RTC pin and the BOOT button pulls it low
+    #[cfg(any(esp32, esp32s3))]
+    sys::esp!(unsafe { sys::esp_sleep_enable_ext0_wakeup(sys::gpio_num_t_GPIO_NUM_0, 0) })?;
+
+    #[cfg(esp32)]
+    if let Some(pad) = config.touch_pad {
+        // SAFETY: plain configuration calls on the touch driver
+        unsafe {
+            sys::esp!(sys::touch_pad_init())?;
+            sys::esp!(sys::touch_pad_set_fsm_mode(
+                sys::touch_fsm_mode_t_TOUCH_FSM_MODE_TIMER
+            ))?;
+            sys::esp!(sys::touch_pad_config(
+                pad as sys::touch_pad_t,
+                config.touch_threshold
+            ))?;
+            sys::esp!(sys::esp_sleep_enable_touchpad_wakeup())?;
+        }
+    }
+    #[cfg(not(esp32))]
+    if config.touch_pad.is_some() {
+        warn!("Touch wake-up needs an ESP32, waking on the timer only");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,386 @@
+//! Read-only SNMP v2c agent.
+//!
+//! Answers Get, GetNext and GetBulk on UDP port 161 for the community
+//! configured in `snmp.community`. Besides the standard system group it
+//! exposes the device under a private enterprise subtree:
+//!
+//! | OID | Type | Value |
+//! | --- | --- | --- |
+//! | `1.3.6.1.4.1.63500.1.1.0` | INTEGER | status (0 Free, 1 DND, 2 Away) |
+//! | `1.3.6.1.4.1.63500.1.2.0` | OCTET STRING | status name |
+//! | `1.3.6.1.4.1.63500.1.3.0` | Counter32 | HTTP page requests |
+//! | `1.3.6.1.4.1.63500.1.4.0` | INTEGER | WiFi RSSI in dBm |
+//! | `1.3.6.1.4.1.63500.1.5.0` | TimeTicks | uptime |
+
+use std::net::UdpSocket;
+use std::sync::atomic::Ordering;
+
+use log::{info, warn};
+
+use crate::config;
+use crate::device;
+use crate::status;
+
+const SNMP_PORT: u16 = 161;
+const SNMP_STACK_SIZE: usize = 6144;
+const MAX_MESSAGE_LEN: usize = 484;
+const SNMP_V2C: i64 = 1;
+const MAX_BULK_REPETITIONS: usize = 10;
+
+// BER tags
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+// PDU types
+const GET_REQUEST: u8 = 0xA0;
+const GET_NEXT_REQUEST: u8 = 0xA1;
+const RESPONSE: u8 = 0xA2;
+const SET_REQUEST: u8 = 0xA3;
+const GET_BULK_REQUEST: u8 = 0xA5;
+
+const ERROR_TOO_BIG: i64 = 1;
+const ERROR_NOT_WRITABLE: i64 = 17;
+
+enum Value {
+    Integer(i64),
+    OctetString(String),
+    Counter32(u32),
+    TimeTicks(u32),
+    Null,
+    NoSuchObject,
+    EndOfMibView,
+}
+
+type Oid = Vec<u32>;
+
+/// Spawns the agent thread.
+pub fn start() -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", SNMP_PORT))?;
+
+    std::thread::Builder::new()
+        .name("snmp".into())
+        .stack_size(SNMP_STACK_SIZE)
+        .spawn(move || {
+            let mut buf = [0; MAX_MESSAGE_LEN];
+            loop {
+                let Ok((len, sender)) = socket.recv_from(&mut buf) else {
+                    continue;
+                };
+                if let Some(response) = handle(&buf[..len]) {
+                    if let Err(e) = socket.send_to(&response, sender) {
+                        warn!("SNMP response failed: {:?}", e);
+                    }
+                }
+            }
+        })?;
+
+    info!("SNMP agent listening on port {}", SNMP_PORT);
+    Ok(())
+}
+
+/// The MIB, sorted by OID.
+fn mib() -> Vec<(Oid, Value)> {
+    let status = status::current();
+    let uptime_ticks = (device::uptime().as_millis() / 10) as u32;
+
+    vec![
+        (
+            vec![1, 3, 6, 1, 2, 1, 1, 1, 0],
+            Value::OctetString(format!("busier {}", env!("CARGO_PKG_VERSION"))),
+        ),
+        (
+            vec![1, 3, 6, 1, 2, 1, 1, 3, 0],
+            Value::TimeTicks(uptime_ticks),
+        ),
+        (
+            vec![1, 3, 6, 1, 2, 1, 1, 5, 0],
+            Value::OctetString(config::get().device_name().to_string()),
+        ),
+        (
+            vec![1, 3, 6, 1, 4, 1, 63500, 1, 1, 0],
+            Value::Integer(status as i64),
+        ),
+        (
+            vec![1, 3, 6, 1, 4, 1, 63500, 1, 2, 0],
+            Value::OctetString(status.as_str().to_string()),
+        ),
+        (
+            vec![1, 3, 6, 1, 4, 1, 63500, 1, 3, 0],
+            Value::Counter32(crate::REQUEST_COUNTER.load(Ordering::SeqCst)),
+        ),
+        (
+            vec![1, 3, 6, 1, 4, 1, 63500, 1, 4, 0],
+            Value::Integer(device::rssi().unwrap_or(0) as i64),
+        ),
+        (
+            vec![1, 3, 6, 1, 4, 1, 63500, 1, 5, 0],
+            Value::TimeTicks(uptime_ticks),
+        ),
+    ]
+}
+
+fn handle(data: &[u8]) -> Option<Vec<u8>> {
+    let snmp = config::get().snmp;
+    if !snmp.enabled {
+        return None;
+    }
+
+    let mut reader = Reader::new(data);
+    let mut message = reader.read(TAG_SEQUENCE)?;
+    if message.read_integer()? != SNMP_V2C {
+        return None;
+    }
+    let community = message.read(TAG_OCTET_STRING)?.rest();
+    if community != snmp.community.as_bytes() {
+        return None;
+    }
+
+    let (pdu_type, mut pdu) = message.read_any()?;
+    let request_id = pdu.read_integer()?;
+    // For GetBulk these are non-repeaters and max-repetitions
+    let non_repeaters = pdu.read_integer()?.max(0) as usize;
+    let max_repetitions = (pdu.read_integer()?.max(0) as usize).min(MAX_BULK_REPETITIONS);
+
+    let mut requested = Vec::new();
+    let mut varbinds = pdu.read(TAG_SEQUENCE)?;
+    while !varbinds.is_empty() {
+        let mut varbind = varbinds.read(TAG_SEQUENCE)?;
+        requested.push(decode_oid(varbind.read(TAG_OID)?.rest())?);
+    }
+
+    let mib = mib();
+    let mut error_status = 0;
+    let mut error_index = 0;
+    let mut results: Vec<(Oid, &Value)> = Vec::new();
+    // Leading results that must all fit, the rest may be cut off
+    let mut required = requested.len();
+    let null = Value::Null;
+    let no_such_object = Value::NoSuchObject;
+    let end_of_mib_view = Value::EndOfMibView;
+
+    match pdu_type {
+        GET_REQUEST => {
+            for oid in requested {
+                let value = mib
+                    .iter()
+                    .find(|(o, _)| *o == oid)
+                    .map_or(&no_such_object, |(_, v)| v);
+                results.push((oid, value));
+            }
+        }
+        GET_NEXT_REQUEST => {
+            for oid in requested {
+                results.push(next(&mib, &oid, &end_of_mib_view));
+            }
+        }
+        GET_BULK_REQUEST => {
+            required = non_repeaters.min(requested.len());
+            for (i, oid) in requested.into_iter().enumerate() {
+                let repetitions = if i < non_repeaters {
+                    1
+                } else {
+                    max_repetitions
+                };
+                let mut oid = oid;
+                for _ in 0..repetitions {
+                    let (next_oid, value) = next(&mib, &oid, &end_of_mib_view);
+                    oid = next_oid.clone();
+                    results.push((next_oid, value));
+                }
+            }
+        }
+        SET_REQUEST => {
+            error_status = ERROR_NOT_WRITABLE;
+            error_index = 1;
+            results.extend(requested.into_iter().map(|oid| (oid, &null)));
+        }
+        _ => return None,
+    }
+
+    let respond = |error_status, error_index, varbind_list: &[u8]| {
+        encode_response(
+            snmp.community.as_bytes(),
+            request_id,
+            error_status,
+            error_index,
+            varbind_list,
+        )
+    };
+    let mut varbind_list = Vec::new();
+    let mut response = respond(error_status, error_index, &[]);
+    for (i, (oid, value)) in results.iter().enumerate() {
+        let mut varbind = encode_tlv(TAG_OID, &encode_oid(oid));
+        varbind.extend(encode_value(value));
+        varbind_list.extend(encode_tlv(TAG_SEQUENCE, &varbind));
+
+        let candidate = respond(error_status, error_index, &varbind_list);
+        if candidate.len() <= MAX_MESSAGE_LEN {
+            response = candidate;
+        } else if i < required {
+            return Some(respond(ERROR_TOO_BIG, 0, &[]));
+        } else {
+            // GetBulk answers with as many repetitions as fit (RFC 3416 4.2.3)
+            break;
+        }
+    }
+    Some(response)
+}
+
+fn encode_response(
+    community: &[u8],
+    request_id: i64,
+    error_status: i64,
+    error_index: i64,
+    varbind_list: &[u8],
+) -> Vec<u8> {
+    let mut pdu = encode_integer(request_id);
+    pdu.extend(encode_integer(error_status));
+    pdu.extend(encode_integer(error_index));
+    pdu.extend(encode_tlv(TAG_SEQUENCE, varbind_list));
+
+    let mut message = encode_integer(SNMP_V2C);
+    message.extend(encode_tlv(TAG_OCTET_STRING, community));
+    message.extend(encode_tlv(RESPONSE, &pdu));
+    encode_tlv(TAG_SEQUENCE, &message)
+}
+
+fn next<'a>(mib: &'a [(Oid, Value)], oid: &Oid, end: &'a Value) -> (Oid, &'a Value) {
+    mib.iter()
+        .find(|(o, _)| o > oid)
+        .map_or((oid.clone(), end), |(o, v)| (o.clone(), v))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Reads the next TLV, returning its tag and a reader over its value.
+    fn read_any(&mut self) -> Option<(u8, Reader<'a>)> {
+        let tag = *self.data.first()?;
+        let first_len = *self.data.get(1)?;
+        let (len, header_len) = match first_len {
+            0x00..=0x7F => (first_len as usize, 2),
+            0x81 => (*self.data.get(2)? as usize, 3),
+            0x82 => (
+                u16::from_be_bytes([*self.data.get(2)?, *self.data.get(3)?]) as usize,
+                4,
+            ),
+            _ => return None,
+        };
+        let value = self.data.get(header_len..header_len + len)?;
+        self.data = &self.data[header_len + len..];
+        Some((tag, Reader::new(value)))
+    }
+
+    fn read(&mut self, expected_tag: u8) -> Option<Reader<'a>> {
+        let (tag, reader) = self.read_any()?;
+        (tag == expected_tag).then_some(reader)
+    }
+
+    fn read_integer(&mut self) -> Option<i64> {
+        let bytes = self.read(TAG_INTEGER)?.rest();
+        if bytes.is_empty() || bytes.len() > 8 {
+            return None;
+        }
+        // Sign-extend from the first byte
+        let initial = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+        Some(bytes.iter().fold(initial, |acc, b| (acc << 8) | *b as i64))
+    }
+}
+
+fn decode_oid(bytes: &[u8]) -> Option<Oid> {
+    let (first, rest) = bytes.split_first()?;
+    let mut oid = vec![(*first / 40) as u32, (*first % 40) as u32];
+    let mut value: u32 = 0;
+    for byte in rest {
+        value = value.checked_mul(128)? | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            oid.push(value);
+            value = 0;
+        }
+    }
+    Some(oid)
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut bytes =
+        vec![(oid.first().copied().unwrap_or(0) * 40 + oid.get(1).copied().unwrap_or(0)) as u8];
+    for &arc in oid.iter().skip(2) {
+        let mut chunk = vec![(arc & 0x7F) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7F) as u8);
+            rest >>= 7;
+        }
+        bytes.extend(chunk.iter().rev());
+    }
+    bytes
+}
+
+fn encode_length(len: usize) -> Vec<u8> {
+    match len {
+        0..=0x7F => vec![len as u8],
+        0x80..=0xFF => vec![0x81, len as u8],
+        _ => {
+            let bytes = (len as u16).to_be_bytes();
+            vec![0x82, bytes[0], bytes[1]]
+        }
+    }
+}
+
+fn encode_tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut tlv = vec![tag];
+    tlv.extend(encode_length(value.len()));
+    tlv.extend_from_slice(value);
+    tlv
+}
+
+// Minimal two's complement encoding
+fn encode_signed(tag: u8, value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    encode_tlv(tag, &bytes[start..])
+}
+
+fn encode_integer(value: i64) -> Vec<u8> {
+    encode_signed(TAG_INTEGER, value)
+}
+
+fn encode_value(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Integer(v) => encode_integer(*v),
+        Value::OctetString(s) => encode_tlv(TAG_OCTET_STRING, s.as_bytes()),
+        // Unsigned types are encoded like non-negative integers
+        Value::Counter32(v) => encode_signed(TAG_COUNTER32, *v as i64),
+        Value::TimeTicks(v) => encode_signed(TAG_TIMETICKS, *v as i64),
+        Value::Null => encode_tlv(TAG_NULL, &[]),
+        Value::NoSuchObject => encode_tlv(TAG_NO_SUCH_OBJECT, &[]),
+        Value::EndOfMibView => encode_tlv(TAG_END_OF_MIB_VIEW, &[]),
+    }
+}
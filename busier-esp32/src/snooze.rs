@@ -0,0 +1,36 @@
+//! Notification snooze.
+//!
+//! While snoozed, knocks and status changes neither beep nor reach the
+//! outbound integrations. The visible status is left alone.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::info;
+
+static UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Snoozes for the given duration; zero cancels a running snooze.
+pub fn start(duration: Duration) {
+    let mut until = UNTIL.lock().unwrap();
+    if duration.is_zero() {
+        info!("Snooze cancelled");
+        *until = None;
+    } else {
+        info!("Snoozed for {} minutes", duration.as_secs() / 60);
+        *until = Some(Instant::now() + duration);
+    }
+}
+
+/// Time left until notifications resume, if snoozed.
+pub fn remaining() -> Option<Duration> {
+    UNTIL
+        .lock()
+        .unwrap()
+        .and_then(|until| until.checked_duration_since(Instant::now()))
+        .filter(|remaining| !remaining.is_zero())
+}
+
+pub fn is_active() -> bool {
+    remaining().is_some()
+}
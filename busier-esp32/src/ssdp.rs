@@ -0,0 +1,225 @@
+//! SSDP announcements and UPnP device description.
+//!
+//! Makes the device show up in Windows network discovery and UPnP-aware
+//! dashboards as a basic device whose presentation URL is the web interface.
+//! With `alexa.enabled` set it presents itself as a Philips Hue bridge
+//! instead, see [`crate::hue_emulation`].
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::config;
+use crate::device;
+use crate::hue_emulation;
+
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+const SSDP_STACK_SIZE: usize = 6144;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Announce well within the advertised max-age
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(300);
+const MAX_AGE_SECS: u32 = 1800;
+const DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:Basic:1";
+pub const DESCRIPTION_PATH: &str = "/description.xml";
+
+/// Spawns the SSDP responder and announcer thread.
+pub fn start(ip: Ipv4Addr) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", SSDP_PORT))?;
+    socket.join_multicast_v4(&SSDP_ADDR, &ip)?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    std::thread::Builder::new()
+        .name("ssdp".into())
+        .stack_size(SSDP_STACK_SIZE)
+        .spawn(move || {
+            let multicast = SocketAddr::from((SSDP_ADDR, SSDP_PORT));
+            let mut last_notify: Option<Instant> = None;
+            let mut buf = [0; 512];
+
+            loop {
+                if let Ok((len, sender)) = socket.recv_from(&mut buf) {
+                    let request = String::from_utf8_lossy(&buf[..len]);
+                    if let Some(search_target) = search_target(&request) {
+                        for target in matching_targets(&search_target) {
+                            let response = search_response(ip, &target);
+                            if let Err(e) = socket.send_to(response.as_bytes(), sender) {
+                                warn!("SSDP response failed: {:?}", e);
+                            }
+                        }
+                    }
+                }
+
+                if last_notify.map_or(true, |at| at.elapsed() >= NOTIFY_INTERVAL) {
+                    for target in notification_targets() {
+                        let notify = notify_alive(ip, &target);
+                        if let Err(e) = socket.send_to(notify.as_bytes(), multicast) {
+                            warn!("SSDP notify failed: {:?}", e);
+                        }
+                    }
+                    last_notify = Some(Instant::now());
+                }
+            }
+        })?;
+
+    info!("SSDP announcing {}", udn());
+    Ok(())
+}
+
+/// UPnP device description served at [`DESCRIPTION_PATH`].
+pub fn description_xml(ip: Ipv4Addr) -> String {
+    let config = config::get();
+    // Echo devices only talk to descriptions that look like a Hue bridge
+    let (manufacturer, model_name, model_number) = if config.alexa.enabled {
+        (
+            "Royal Philips Electronics",
+            "Philips hue bridge 2012",
+            "929000226503",
+        )
+    } else {
+        ("busier", "busier", env!("CARGO_PKG_VERSION"))
+    };
+    let mac = device::mac();
+
+    format!(
+        r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <URLBase>http://{ip}/</URLBase>
+  <device>
+    <deviceType>{device_type}</deviceType>
+    <friendlyName>{name}</friendlyName>
+    <manufacturer>{manufacturer}</manufacturer>
+    <manufacturerURL>https://github.com/charmitro/busier</manufacturerURL>
+    <modelDescription>ESP32 status sign</modelDescription>
+    <modelName>{model_name}</modelName>
+    <modelNumber>{model_number}</modelNumber>
+    <serialNumber>{serial}</serialNumber>
+    <UDN>{udn}</UDN>
+    <presentationURL>http://{ip}/</presentationURL>
+  </device>
+</root>
+"#,
+        ip = ip,
+        device_type = DEVICE_TYPE,
+        name = xml_escape(config.device_name()),
+        manufacturer = manufacturer,
+        model_name = model_name,
+        model_number = model_number,
+        serial = mac.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+        udn = udn(),
+    )
+}
+
+// Returns the ST header of an M-SEARCH request
+fn search_target(request: &str) -> Option<String> {
+    let mut lines = request.lines();
+    if !lines.next()?.starts_with("M-SEARCH") {
+        return None;
+    }
+    lines.find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("ST")
+            .then(|| value.trim().to_string())
+    })
+}
+
+fn notification_targets() -> Vec<String> {
+    vec![
+        "upnp:rootdevice".to_string(),
+        udn(),
+        DEVICE_TYPE.to_string(),
+    ]
+}
+
+// Echo devices search for "device:basic:1", so targets match ignoring case
+fn matching_targets(search_target: &str) -> Vec<String> {
+    if search_target == "ssdp:all" {
+        notification_targets()
+    } else {
+        notification_targets()
+            .into_iter()
+            .filter(|target| target.eq_ignore_ascii_case(search_target))
+            .map(|_| search_target.to_string())
+            .collect()
+    }
+}
+
+fn usn(target: &str) -> String {
+    if target.eq_ignore_ascii_case(&udn()) {
+        udn()
+    } else {
+        format!("{}::{}", udn(), target)
+    }
+}
+
+fn search_response(ip: Ipv4Addr, target: &str) -> String {
+    let (server, bridge_header) = if config::get().alexa.enabled {
+        (
+            "Linux/3.14.0 UPnP/1.0 IpBridge/1.17.0".to_string(),
+            format!("hue-bridgeid: {}\r\n", hue_emulation::bridge_id()),
+        )
+    } else {
+        (
+            format!("ESP-IDF/5 UPnP/1.0 busier/{}", env!("CARGO_PKG_VERSION")),
+            String::new(),
+        )
+    };
+
+    format!(
+        "HTTP/1.1 200 OK\r\n\
+         CACHE-CONTROL: max-age={}\r\n\
+         EXT:\r\n\
+         LOCATION: http://{}{}\r\n\
+         SERVER: {}\r\n\
+         {}\
+         ST: {}\r\n\
+         USN: {}\r\n\r\n",
+        MAX_AGE_SECS,
+        ip,
+        DESCRIPTION_PATH,
+        server,
+        bridge_header,
+        target,
+        usn(target)
+    )
+}
+
+fn notify_alive(ip: Ipv4Addr, target: &str) -> String {
+    format!(
+        "NOTIFY * HTTP/1.1\r\n\
+         HOST: {}:{}\r\n\
+         CACHE-CONTROL: max-age={}\r\n\
+         LOCATION: http://{}{}\r\n\
+         NT: {}\r\n\
+         NTS: ssdp:alive\r\n\
+         SERVER: ESP-IDF/5 UPnP/1.0 busier/{}\r\n\
+         USN: {}\r\n\r\n",
+        SSDP_ADDR,
+        SSDP_PORT,
+        MAX_AGE_SECS,
+        ip,
+        DESCRIPTION_PATH,
+        target,
+        env!("CARGO_PKG_VERSION"),
+        usn(target)
+    )
+}
+
+// Stable per device: derived from the WiFi MAC address
+fn udn() -> String {
+    let mac = device::mac();
+    format!(
+        "uuid:2f402f80-da50-11e1-9b23-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
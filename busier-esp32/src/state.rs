@@ -0,0 +1,111 @@
+//! Runtime state that survives power loss.
+//!
+//! The selected status and the request counter are checkpointed to NVS: the
+//! status within seconds of a change, the counter at most every few minutes
+//! to spare the flash. Orderly restarts save both through a shutdown handler.
+//!
+//! There is no last-gasp write on brownout. ESP-IDF resets the chip from its
+//! own brownout interrupt without a hook, and flash writes at brownout
+//! voltage are exactly what corrupts data. NVS itself is safe against power
+//! loss mid-write, so a checkpoint is either fully written or ignored.
+
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys;
+use log::{info, warn};
+
+use crate::status::{self, Status};
+use crate::REQUEST_COUNTER;
+
+const NAMESPACE: &str = "state";
+const STATUS_KEY: &str = "status";
+const REQUESTS_KEY: &str = "requests";
+const STATE_STACK_SIZE: usize = 4096;
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const COUNTER_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+static NVS: Mutex<Option<EspNvs<NvsDefault>>> = Mutex::new(None);
+
+/// Restores the last checkpoint. Call before anything changes the status.
+pub fn init(partition: EspDefaultNvsPartition) -> anyhow::Result<()> {
+    let nvs = EspNvs::new(partition, NAMESPACE, true)?;
+
+    // SAFETY: only reads the reason recorded at boot
+    let reason = unsafe { sys::esp_reset_reason() };
+    if reason == sys::esp_reset_reason_t_ESP_RST_BROWNOUT {
+        warn!("Restarted after a brownout");
+    }
+
+    if let Some(saved) = nvs.get_u8(STATUS_KEY)? {
+        let status = Status::from_u8(saved);
+        info!("Restoring status {}", status.as_str());
+        status::restore(status, None);
+    }
+    if let Some(requests) = nvs.get_u32(REQUESTS_KEY)? {
+        REQUEST_COUNTER.store(requests, Ordering::SeqCst);
+    }
+
+    *NVS.lock().unwrap() = Some(nvs);
+
+    // SAFETY: the handler is a plain function that lives forever
+    unsafe { sys::esp!(sys::esp_register_shutdown_handler(Some(on_shutdown)))? };
+
+    Ok(())
+}
+
+/// Spawns the checkpoint thread.
+pub fn start() -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("state".into())
+        .stack_size(STATE_STACK_SIZE)
+        .spawn(|| {
+            let mut saved_status = status::selected();
+            let mut saved_requests = REQUEST_COUNTER.load(Ordering::SeqCst);
+            let mut requests_saved_at = Instant::now();
+
+            loop {
+                std::thread::sleep(CHECK_INTERVAL);
+
+                let status = status::selected();
+                let requests = REQUEST_COUNTER.load(Ordering::SeqCst);
+                let save_status = status != saved_status;
+                let save_requests =
+                    requests != saved_requests && requests_saved_at.elapsed() >= COUNTER_INTERVAL;
+                if !save_status && !save_requests {
+                    continue;
+                }
+
+                if let Err(e) = save(status, requests) {
+                    warn!("Failed to save state: {:?}", e);
+                    continue;
+                }
+                saved_status = status;
+                saved_requests = requests;
+                requests_saved_at = Instant::now();
+            }
+        })?;
+
+    Ok(())
+}
+
+fn save(status: Status, requests: u32) -> anyhow::Result<()> {
+    if let Some(nvs) = NVS.lock().unwrap().as_mut() {
+        nvs.set_u8(STATUS_KEY, status as u8)?;
+        nvs.set_u32(REQUESTS_KEY, requests)?;
+    }
+    Ok(())
+}
+
+extern "C" fn on_shutdown() {
+    // A restart from the checkpoint thread itself must not deadlock
+    let Ok(mut nvs) = NVS.try_lock() else {
+        return;
+    };
+    if let Some(nvs) = nvs.as_mut() {
+        let _ = nvs.set_u8(STATUS_KEY, status::selected() as u8);
+        let _ = nvs.set_u32(REQUESTS_KEY, REQUEST_COUNTER.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,108 @@
+//! Time spent in each status and requests served, per hour and per day.
+//!
+//! A thread records the selected status every minute once the clock is set.
+//! The buckets are saved to NVS every quarter of an hour and on orderly
+//! restarts, so a power cut loses at most the minutes since the last save.
+
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys;
+use log::warn;
+
+use busier_core::stats::Stats;
+
+use crate::clock;
+use crate::status;
+use crate::REQUEST_COUNTER;
+
+const NAMESPACE: &str = "stats";
+const KEY: &str = "buckets";
+const STATS_STACK_SIZE: usize = 6144;
+const RECORD_INTERVAL: Duration = Duration::from_secs(60);
+const SAVE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+// Upper bound for the serialized buckets
+const MAX_STATS_LEN: usize = 8192;
+
+static STATS: Mutex<Stats> = Mutex::new(Stats::new());
+static NVS: Mutex<Option<EspNvs<NvsDefault>>> = Mutex::new(None);
+
+/// Restores the saved buckets.
+pub fn init(partition: EspDefaultNvsPartition) -> anyhow::Result<()> {
+    let nvs = EspNvs::new(partition, NAMESPACE, true)?;
+
+    let mut buf = vec![0; MAX_STATS_LEN];
+    if let Some(data) = nvs.get_raw(KEY, &mut buf)? {
+        match serde_json::from_slice(data) {
+            Ok(stats) => *STATS.lock().unwrap() = stats,
+            Err(e) => warn!("Stored statistics are invalid, starting over: {:?}", e),
+        }
+    }
+
+    *NVS.lock().unwrap() = Some(nvs);
+
+    // SAFETY: the handler is a plain function that lives forever
+    unsafe { sys::esp!(sys::esp_register_shutdown_handler(Some(on_shutdown)))? };
+
+    Ok(())
+}
+
+/// Spawns the recording thread.
+pub fn start() -> anyhow::Result<()> {
+    std::thread::Builder::new()
+        .name("stats".into())
+        .stack_size(STATS_STACK_SIZE)
+        .spawn(|| {
+            let mut counted = REQUEST_COUNTER.load(Ordering::SeqCst);
+            let mut saved_at = Instant::now();
+
+            loop {
+                std::thread::sleep(RECORD_INTERVAL);
+
+                let requests = REQUEST_COUNTER.load(Ordering::SeqCst);
+                let Some(now) = clock::local_stamp() else {
+                    continue;
+                };
+                {
+                    let mut stats = STATS.lock().unwrap();
+                    stats.record_minute(now, status::selected());
+                    stats.record_requests(now, requests.wrapping_sub(counted));
+                }
+                counted = requests;
+
+                if saved_at.elapsed() >= SAVE_INTERVAL {
+                    if let Err(e) = save() {
+                        warn!("Failed to save statistics: {:?}", e);
+                    }
+                    saved_at = Instant::now();
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// A copy of the buckets.
+pub fn get() -> Stats {
+    STATS.lock().unwrap().clone()
+}
+
+fn save() -> anyhow::Result<()> {
+    let data = serde_json::to_vec(&*STATS.lock().unwrap())?;
+    if let Some(nvs) = NVS.lock().unwrap().as_mut() {
+        nvs.set_raw(KEY, &data)?;
+    }
+    Ok(())
+}
+
+extern "C" fn on_shutdown() {
+    // A restart while the thread holds either lock must not deadlock
+    let (Ok(stats), Ok(mut nvs)) = (STATS.try_lock(), NVS.try_lock()) else {
+        return;
+    };
+    if let (Ok(data), Some(nvs)) = (serde_json::to_vec(&*stats), nvs.as_mut()) {
+        let _ = nvs.set_raw(KEY, &data);
+    }
+}
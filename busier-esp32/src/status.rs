@@ -0,0 +1,548 @@
+//! Current availability status shared between the HTTP handlers,
+//! integrations and the display loop.
+//!
+//! When a timed status ends, the transition configured for it, if any, runs
+//! its steps before the claim turns into Free. The status stays the same
+//! during the steps; any new claim or change of status ends them early.
+//!
+//! Automated sources may claim with a lease and renew it; a lease that runs
+//! out withdraws its claim, and with no other claim left the status returns
+//! to Free.
+//!
+//! Every change of the selected status goes into an audit log in RAM, with
+//! the source and the origin of the claim behind it; see [`attributed`].
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::info;
+
+use crate::clock;
+use crate::config::{self, parse_hhmm};
+use crate::device;
+use crate::hal::{Clock, SystemClock};
+use crate::i18n;
+use crate::notify::{self, Event};
+use crate::output::{self, Signal};
+use crate::peer_sync;
+use crate::schedule;
+
+pub use busier_core::arbiter::Source;
+pub use busier_core::audit::{Entry as AuditEntry, Origin};
+pub use busier_core::status::Status;
+
+use busier_core::arbiter::{Arbiter, Claim};
+use busier_core::audit::{AuditLog, Cause, Entry};
+use busier_core::transitions::{Chain, Progress, Step, Transition};
+
+/// Names of the statuses shown to people.
+pub trait StatusLabel {
+    /// Name shown to people, in the configured language.
+    fn label(self) -> &'static str;
+}
+
+impl StatusLabel for Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Free => i18n::text("status.free"),
+            Status::Dnd => i18n::text("status.dnd"),
+            Status::Away => i18n::text("status.away"),
+        }
+    }
+}
+
+/// When the user expects to be back, given as local "HH:MM".
+#[derive(Clone, Debug)]
+pub struct BackAt {
+    pub time: String,
+    pub at: SystemTime,
+    /// The end of a calendar event, which is counted down as a meeting and
+    /// followed by the grace period in `calendar.grace_mins`.
+    pub meeting: bool,
+}
+
+impl BackAt {
+    /// Parses "HH:MM" into the next such moment. Needs a synchronized clock.
+    pub fn parse(time: &str) -> Option<BackAt> {
+        Self::parse_at(time, &SystemClock)
+    }
+
+    /// Like [`BackAt::parse`], with the next moment taken from `clock`.
+    pub fn parse_at(time: &str, clock: &impl Clock) -> Option<BackAt> {
+        let at = UNIX_EPOCH + Duration::from_secs(clock.next_occurrence(parse_hhmm(time)?)?);
+        Some(BackAt {
+            time: time.to_string(),
+            at,
+            meeting: false,
+        })
+    }
+
+    /// The minute `minutes` from now, less than a day ahead. Needs a
+    /// synchronized clock.
+    pub fn in_minutes(minutes: u32) -> Option<BackAt> {
+        const DAY: u32 = 24 * 60;
+        if minutes == 0 || minutes >= DAY {
+            return None;
+        }
+        let minute = (u32::from(SystemClock.local_now()?.minute_of_day()) + minutes) % DAY;
+        Self::parse(&format!("{:02}:{:02}", minute / 60, minute % 60))
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.at
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Whether the status is due to return to Free, after the grace period
+    /// for a meeting.
+    pub fn is_due(&self) -> bool {
+        let grace = if self.meeting {
+            Duration::from_secs(u64::from(config::get().calendar.grace_mins) * 60)
+        } else {
+            Duration::ZERO
+        };
+        SystemTime::now()
+            .duration_since(self.at)
+            .is_ok_and(|overdue| overdue >= grace)
+    }
+
+    /// Text for the display, e.g. "Back at 15:30 (in 42 min)" or "Meeting
+    /// ends in 18 min".
+    pub fn display_text(&self) -> String {
+        // Round up so the countdown never shows 0 before the flip
+        let minutes = self.remaining().as_secs().div_ceil(60);
+        match self.meeting {
+            true if minutes == 0 => i18n::text("display.meeting_over").to_string(),
+            true => i18n::format("display.meeting_ends", &[&minutes]),
+            false => i18n::format("display.back_at", &[&self.time, &minutes]),
+        }
+    }
+}
+
+// Status of the winning claim
+static SELECTED: AtomicU8 = AtomicU8::new(Status::Free as u8);
+// Pending automatic return to Free
+static BACK_AT: Mutex<Option<BackAt>> = Mutex::new(None);
+// Source of the winning claim; None before any claim and after a restore
+static SOURCE: Mutex<Option<Source>> = Mutex::new(None);
+// Latest claim of each source
+static ARBITER: Mutex<Arbiter<BackAt>> = Mutex::new(Arbiter::new());
+// Set while Do Not Disturb was selected by closing the door
+static FROM_DOOR: AtomicBool = AtomicBool::new(false);
+// Transition in progress and the source whose claim it ends
+static CHAIN: Mutex<Option<(Source, Chain)>> = Mutex::new(None);
+
+// Counts the changes of the shown status and its "back at" time, with
+// what was last counted
+static VERSION: Mutex<(u64, Option<(Status, Option<String>)>)> = Mutex::new((0, None));
+static VERSION_CHANGED: Condvar = Condvar::new();
+// Held from the version check of a conditional change to its claim
+static CONDITIONAL: Mutex<()> = Mutex::new(());
+// Who made each source's latest claim
+static ORIGINS: Mutex<Vec<(Source, Origin)>> = Mutex::new(Vec::new());
+// The recent changes of the selected status
+static AUDIT: Mutex<AuditLog> = Mutex::new(AuditLog::new());
+
+thread_local! {
+    // Who the claims made on this thread come from, see `attributed`
+    static ORIGIN: RefCell<Option<Origin>> = const { RefCell::new(None) };
+}
+
+/// Status selected by the winning source, ignoring working hours.
+pub fn selected() -> Status {
+    Status::from_u8(SELECTED.load(Ordering::SeqCst))
+}
+
+/// Status to show: Away outside working hours, the selected one otherwise.
+pub fn current() -> Status {
+    busier_core::status::shown(selected(), schedule::in_working_hours())
+}
+
+/// Source of the selected status, if it was set since startup.
+pub fn source() -> Option<Source> {
+    *SOURCE.lock().unwrap()
+}
+
+/// Runs `f` with the claims it makes attributed to `origin` in the audit
+/// log. The HTTP handlers that change the status run inside it.
+pub fn attributed<R>(origin: Origin, f: impl FnOnce() -> R) -> R {
+    let previous = ORIGIN.with(|current| current.replace(Some(origin)));
+    let result = f();
+    ORIGIN.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// The changes of the selected status since startup, the most recent
+/// first, at most `limit`.
+pub fn audit(limit: usize) -> Vec<AuditEntry> {
+    AUDIT.lock().unwrap().latest().take(limit).cloned().collect()
+}
+
+/// Claims a status for a source and notifies integrations if the status
+/// changed. A claim loses against sources of higher priority still holding
+/// theirs, see [`busier_core::arbiter`]. Returns whether the status changed.
+pub fn set(source: Source, status: Status) -> bool {
+    set_with_back_at(source, status, None)
+}
+
+/// Like [`set`], additionally flipping back to Free at `back_at`. The
+/// calendar's `back_at` is the end of the current event.
+pub fn set_with_back_at(source: Source, status: Status, back_at: Option<BackAt>) -> bool {
+    set_with_lease(source, status, back_at, None)
+}
+
+/// Like [`set_with_back_at`], with the claim withdrawn once `lease` runs
+/// out. Claiming the same status again before then renews the lease
+/// without counting as a new claim.
+pub fn set_with_lease(
+    source: Source,
+    status: Status,
+    back_at: Option<BackAt>,
+    lease: Option<Duration>,
+) -> bool {
+    FROM_DOOR.store(false, Ordering::SeqCst);
+    *CHAIN.lock().unwrap() = None;
+    let back_at = back_at.map(|back_at| BackAt {
+        meeting: source == Source::Calendar,
+        ..back_at
+    });
+    claim(source, status, back_at, lease);
+    resolve(true, Cause::Claim)
+}
+
+/// Like [`set_with_lease`], only if `matches` accepts the version, as
+/// with an `If-Match` header. Two conditional changes made from the same
+/// version cannot both pass. Returns the version instead if it did not
+/// match.
+pub fn set_if(
+    source: Source,
+    status: Status,
+    back_at: Option<BackAt>,
+    lease: Option<Duration>,
+    matches: impl FnOnce(u64) -> bool,
+) -> Result<bool, u64> {
+    let _conditional = CONDITIONAL.lock().unwrap();
+    let version = version();
+    if !matches(version) {
+        return Err(version);
+    }
+    Ok(set_with_lease(source, status, back_at, lease))
+}
+
+/// Switches between Do Not Disturb and Free; returns the status now
+/// selected, which stays the same if the claim lost.
+pub fn toggle_dnd(source: Source) -> Status {
+    let new_status = if selected() == Status::Dnd {
+        Status::Free
+    } else {
+        Status::Dnd
+    };
+    set(source, new_status);
+    selected()
+}
+
+/// Mirrors a status received from a paired device, as an integration. The
+/// originating device already notified the integrations, so this one stays
+/// quiet.
+pub fn apply_from_peer(status: Status) {
+    FROM_DOOR.store(false, Ordering::SeqCst);
+    *CHAIN.lock().unwrap() = None;
+    claim(Source::Integration, status, None, None);
+    resolve(false, Cause::Claim);
+}
+
+/// Follows the door sensor when `door.auto_dnd` is set. Closing the door
+/// during working hours turns Free into Do Not Disturb; opening it returns to
+/// Free only if the door selected Do Not Disturb. Any other selection in
+/// between takes priority and is left alone.
+pub fn door_changed(open: bool) {
+    if !config::get().door.auto_dnd {
+        return;
+    }
+
+    if !open && selected() == Status::Free && schedule::in_working_hours() {
+        set(Source::Presence, Status::Dnd);
+        FROM_DOOR.store(true, Ordering::SeqCst);
+    } else if open && FROM_DOOR.load(Ordering::SeqCst) {
+        set(Source::Presence, Status::Free);
+    }
+}
+
+/// Withdraws a source's claim, letting the other claims decide.
+pub fn withdraw(source: Source) {
+    ARBITER.lock().unwrap().withdraw(source);
+    resolve(true, Cause::Claim);
+}
+
+/// The latest claim of each source that made one since startup.
+pub fn claims() -> Vec<(Source, Status)> {
+    let arbiter = ARBITER.lock().unwrap();
+    Source::ALL
+        .into_iter()
+        .filter_map(|source| Some((source, arbiter.get(source)?.status)))
+        .collect()
+}
+
+/// Sets the status without notifying anyone, when restoring it after deep
+/// sleep or mirroring it on a door sign. Forgets the claims made so far.
+pub fn restore(status: Status, back_at: Option<BackAt>) {
+    ARBITER.lock().unwrap().clear();
+    *CHAIN.lock().unwrap() = None;
+    *SOURCE.lock().unwrap() = None;
+    *BACK_AT.lock().unwrap() = back_at;
+    SELECTED.store(status as u8, Ordering::SeqCst);
+    update_version();
+}
+
+pub fn back_at() -> Option<BackAt> {
+    BACK_AT.lock().unwrap().clone()
+}
+
+/// Time left on the lease of the winning claim, if it has one.
+pub fn lease_remaining() -> Option<Duration> {
+    let source = source()?;
+    let until = ARBITER.lock().unwrap().get(source)?.until?;
+    let now = device::uptime().as_secs();
+    Some(Duration::from_secs(until.saturating_sub(now)))
+}
+
+/// Goes up whenever the shown status or its "back at" time changes,
+/// including at the start and end of working hours.
+pub fn version() -> u64 {
+    VERSION.lock().unwrap().0
+}
+
+/// Waits until the version is no longer `since`, or for `timeout`; returns
+/// the version then.
+pub fn wait_for_change(since: u64, timeout: Duration) -> u64 {
+    let version = VERSION.lock().unwrap();
+    let (version, _) = VERSION_CHANGED
+        .wait_timeout_while(version, timeout, |(version, _)| *version == since)
+        .unwrap();
+    version.0
+}
+
+/// The step of the transition in progress, its final status and the
+/// seconds left until then.
+pub fn transition() -> Option<(Step, Status, u64)> {
+    let now = device::uptime().as_secs();
+    CHAIN
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|(_, chain)| (chain.step().clone(), chain.to(), chain.remaining(now)))
+}
+
+/// Returns to Free once the "back at" time has passed, through the steps of
+/// a configured transition, and lets other claims win once a hold ends.
+pub fn tick() {
+    let now = device::uptime().as_secs();
+
+    // A source that stopped renewing its lease no longer claims anything
+    let expired = ARBITER.lock().unwrap().expire(now);
+    for source in &expired {
+        info!("Lease of the {} claim ran out", source.as_str());
+    }
+    if source().is_some_and(|source| expired.contains(&source)) {
+        let sources = config::get().sources;
+        if ARBITER.lock().unwrap().winner(&sources, now).is_none() {
+            // Nothing left to fall back on
+            *CHAIN.lock().unwrap() = None;
+            *SOURCE.lock().unwrap() = None;
+            return_to_free(Cause::LeaseExpired);
+            return;
+        }
+    }
+
+    let due = BACK_AT.lock().unwrap().as_ref().is_some_and(BackAt::is_due);
+
+    if due {
+        match source() {
+            // The claim turns into Free, still holding for as long as before,
+            // or keeps its status without the "back at" through the steps
+            Some(source) => {
+                let transitions = config::get().transitions;
+                let chain = Transition::find(&transitions, selected(), Status::Free)
+                    .and_then(|transition| Chain::start(transition, now));
+                match chain {
+                    Some(chain) => {
+                        end_claim(source, selected());
+                        begin_step(chain.step());
+                        *CHAIN.lock().unwrap() = Some((source, chain));
+                    }
+                    None => end_claim(source, Status::Free),
+                }
+            }
+            // Restored after deep sleep, without a claim behind it
+            None => {
+                return_to_free(Cause::BackAt);
+                return;
+            }
+        }
+    }
+
+    let progress = CHAIN.lock().unwrap().as_mut().map(|(source, chain)| {
+        let progress = chain.advance(now);
+        (*source, progress, chain.step().clone(), chain.to())
+    });
+    let done = match progress {
+        Some((_, Progress::Next, step, _)) => {
+            begin_step(&step);
+            false
+        }
+        Some((source, Progress::Done, _, to)) => {
+            *CHAIN.lock().unwrap() = None;
+            end_claim(source, to);
+            true
+        }
+        _ => false,
+    };
+
+    // Otherwise a hold ended and another claim won, if anything changed
+    let cause = if due || done { Cause::BackAt } else { Cause::Claim };
+    resolve(true, cause);
+    // Notices the working hours beginning or ending, even without claims
+    update_version();
+}
+
+// Selects Free without a claim behind it
+fn return_to_free(cause: Cause) {
+    *BACK_AT.lock().unwrap() = None;
+    let previous = Status::from_u8(SELECTED.swap(Status::Free as u8, Ordering::SeqCst));
+    if previous != Status::Free {
+        record(previous, Status::Free, cause, None);
+        notify::send(Event::StatusChanged {
+            status: Status::Free,
+            user: None,
+        });
+        peer_sync::publish(Status::Free);
+    }
+    update_version();
+}
+
+// Replaces the source's claim with one for `status` without a "back at",
+// still holding for as long as before
+fn end_claim(source: Source, status: Status) {
+    let mut arbiter = ARBITER.lock().unwrap();
+    if let Some(claim) = arbiter.get(source).cloned() {
+        let claim = Claim {
+            status,
+            back_at: None,
+            ..claim
+        };
+        arbiter.claim(source, claim);
+    }
+}
+
+fn begin_step(step: &Step) {
+    info!("Transition step '{}' for {} min", step.label, step.mins);
+    if step.sound {
+        output::signal(Signal::StatusChanged);
+    }
+}
+
+fn claim(source: Source, status: Status, back_at: Option<BackAt>, lease: Option<Duration>) {
+    let now = device::uptime().as_secs();
+    let until = lease.map(|lease| now + lease.as_secs());
+    let origin = ORIGIN.with(|origin| origin.borrow().clone()).unwrap_or_default();
+    let mut origins = ORIGINS.lock().unwrap();
+    origins.retain(|(from, _)| *from != source);
+    origins.push((source, origin));
+    drop(origins);
+
+    let mut arbiter = ARBITER.lock().unwrap();
+    // Renewing a lease keeps the claim's age, so it wins no more than before
+    let since = match arbiter.get(source) {
+        Some(claim)
+            if until.is_some()
+                && claim.status == status
+                && claim.until.is_some_and(|until| now < until) =>
+        {
+            claim.since
+        }
+        _ => now,
+    };
+    arbiter.claim(
+        source,
+        Claim {
+            status,
+            since,
+            back_at,
+            until,
+        },
+    );
+}
+
+// Selects the winning claim's status; returns whether it changed
+fn resolve(notify: bool, cause: Cause) -> bool {
+    let config = config::get().sources;
+    let (source, status, back_at) = {
+        let arbiter = ARBITER.lock().unwrap();
+        let Some((source, claim)) = arbiter.winner(&config, device::uptime().as_secs()) else {
+            return false;
+        };
+        (source, claim.status, claim.back_at.clone())
+    };
+
+    *SOURCE.lock().unwrap() = Some(source);
+    *BACK_AT.lock().unwrap() = back_at;
+    let previous = Status::from_u8(SELECTED.swap(status as u8, Ordering::SeqCst));
+    let changed = previous != status;
+    if changed {
+        record(previous, status, cause, Some(source));
+    }
+
+    // Another source winning or the status changing ends a transition
+    let mut chain = CHAIN.lock().unwrap();
+    if changed || chain.as_ref().is_some_and(|(from, _)| *from != source) {
+        *chain = None;
+    }
+    drop(chain);
+
+    if changed && notify {
+        notify::send(Event::StatusChanged { status, user: None });
+        peer_sync::publish(status);
+    }
+    update_version();
+    changed
+}
+
+// Adds a change of the selected status to the audit log
+fn record(from: Status, to: Status, cause: Cause, source: Option<Source>) {
+    let origin = source
+        .and_then(|source| {
+            let origins = ORIGINS.lock().unwrap();
+            origins
+                .iter()
+                .find(|(from, _)| *from == source)
+                .map(|(_, origin)| origin.clone())
+        })
+        .unwrap_or_default();
+    let time = clock::is_synced()
+        .then(|| SystemTime::now().duration_since(UNIX_EPOCH).ok())
+        .flatten()
+        .map(|since_epoch| since_epoch.as_secs());
+    AUDIT.lock().unwrap().push(Entry {
+        time,
+        uptime_secs: device::uptime().as_secs(),
+        from,
+        to,
+        cause,
+        source,
+        origin,
+    });
+}
+
+// Counts a new version if the shown status or "back at" time changed
+fn update_version() {
+    let shown = Some((current(), back_at().map(|back_at| back_at.time)));
+    let mut version = VERSION.lock().unwrap();
+    if version.1 != shown {
+        version.0 += 1;
+        version.1 = shown;
+        VERSION_CHANGED.notify_all();
+    }
+}
@@ -0,0 +1,80 @@
+//! The FreeRTOS tasks, as the kernel lists them.
+//!
+//! `GET /api/tasks` shows what runs on the device: every thread of the
+//! firmware, the web server's, WiFi's and the other ESP-IDF tasks, with
+//! their state, priority and the least unused stack since they started.
+//! The list needs `CONFIG_FREERTOS_USE_TRACE_FACILITY`.
+
+use std::ffi::CStr;
+
+use embedded_svc::http::Method;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::EspHttpServer;
+use esp_idf_svc::sys;
+use serde::Serialize;
+
+use crate::http_util::Routes;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Task {
+    pub name: String,
+    /// `running`, `ready`, `blocked`, `suspended` or `deleted`.
+    pub state: &'static str,
+    pub priority: u32,
+    /// The priority without one inherited through a mutex.
+    pub base_priority: u32,
+    /// Least unused stack in bytes since the task started; stacks are
+    /// arrays of bytes on ESP-IDF.
+    pub stack_headroom: u32,
+}
+
+/// The tasks in the order the kernel lists them.
+pub fn list() -> Vec<Task> {
+    // SAFETY: the buffer has room for the tasks counted, plus two that may
+    // start in between; the kernel fills in at most that many, and their
+    // names are copied before anything else runs on this task
+    unsafe {
+        let room = sys::uxTaskGetNumberOfTasks() as usize + 2;
+        let mut statuses: Vec<sys::TaskStatus_t> = Vec::with_capacity(room);
+        let count =
+            sys::uxTaskGetSystemState(statuses.as_mut_ptr(), room as _, std::ptr::null_mut());
+        statuses.set_len(count as usize);
+        statuses
+            .iter()
+            .map(|status| Task {
+                name: CStr::from_ptr(status.pcTaskName)
+                    .to_string_lossy()
+                    .into_owned(),
+                state: state_name(status.eCurrentState),
+                priority: status.uxCurrentPriority as u32,
+                base_priority: status.uxBasePriority as u32,
+                stack_headroom: status.usStackHighWaterMark as u32,
+            })
+            .collect()
+    }
+}
+
+/// Registers `GET /api/tasks`.
+pub fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    server.route("/api/tasks", Method::Get, |req| {
+        let mut tasks = list();
+        tasks.sort_by(|a, b| a.name.cmp(&b.name));
+        let body = serde_json::json!({ "tasks": tasks });
+        req.into_response(200, None, &[("Content-Type", "application/json")])?
+            .write_all(body.to_string().as_bytes())?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+fn state_name(state: sys::eTaskState) -> &'static str {
+    match state {
+        sys::eTaskState_eRunning => "running",
+        sys::eTaskState_eReady => "ready",
+        sys::eTaskState_eBlocked => "blocked",
+        sys::eTaskState_eSuspended => "suspended",
+        sys::eTaskState_eDeleted => "deleted",
+        _ => "invalid",
+    }
+}
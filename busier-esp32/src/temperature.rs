@@ -0,0 +1,111 @@
+//! Chip temperature.
+//!
+//! The ESP32-C3 and ESP32-S3 have a temperature sensor on the die; a thread
+//! samples it so that a device in a closed case in the sun warns before the
+//! chip gets too hot. From `temperature.high_celsius` the device counts as
+//! hot until it cools down a few degrees. The ESP32 has no sensor that
+//! ESP-IDF supports, so there the temperature stays unknown.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static CELSIUS: Mutex<Option<f32>> = Mutex::new(None);
+static HIGH: AtomicBool = AtomicBool::new(false);
+
+/// The last measured chip temperature in degrees Celsius; None until
+/// measured or without a sensor.
+pub fn celsius() -> Option<f32> {
+    *CELSIUS.lock().unwrap()
+}
+
+/// Whether the chip is at or above `temperature.high_celsius`.
+pub fn is_high() -> bool {
+    HIGH.load(Ordering::SeqCst)
+}
+
+/// Spawns the sampling thread where the chip has a sensor.
+pub fn start() -> anyhow::Result<()> {
+    #[cfg(not(esp32))]
+    sensor::start()?;
+    Ok(())
+}
+
+#[cfg(not(esp32))]
+mod sensor {
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use esp_idf_svc::sys;
+    use log::{info, warn};
+
+    use super::{CELSIUS, HIGH};
+    use crate::config;
+
+    const TEMPERATURE_STACK_SIZE: usize = 3072;
+    const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+    // Degrees below the threshold needed to stop warning
+    const HYSTERESIS: f32 = 5.0;
+    // Range the sensor is set up for; the narrower, the more accurate
+    const RANGE: (i32, i32) = (20, 100);
+
+    // The driver's handle, moved into the sampling thread
+    struct Sensor(sys::temperature_sensor_handle_t);
+
+    // SAFETY: only the sampling thread uses the handle
+    unsafe impl Send for Sensor {}
+
+    pub fn start() -> anyhow::Result<()> {
+        let mut handle = std::ptr::null_mut();
+        let sensor_config = sys::temperature_sensor_config_t {
+            range_min: RANGE.0,
+            range_max: RANGE.1,
+            clk_src:
+                sys::soc_periph_temperature_sensor_clk_src_t_TEMPERATURE_SENSOR_CLK_SRC_DEFAULT,
+            ..Default::default()
+        };
+        // SAFETY: the driver writes the handle, which is then kept for good
+        unsafe {
+            sys::esp!(sys::temperature_sensor_install(&sensor_config, &mut handle))?;
+            sys::esp!(sys::temperature_sensor_enable(handle))?;
+        }
+        let sensor = Sensor(handle);
+
+        std::thread::Builder::new()
+            .name("temperature".into())
+            .stack_size(TEMPERATURE_STACK_SIZE)
+            .spawn(move || loop {
+                let mut celsius = 0.0;
+                // SAFETY: the sensor was installed and enabled above
+                let result = unsafe {
+                    sys::esp!(sys::temperature_sensor_get_celsius(sensor.0, &mut celsius))
+                };
+                match result {
+                    Ok(()) => update(celsius, config::get().temperature.high_celsius),
+                    Err(e) => warn!("Failed to read the chip temperature: {:?}", e),
+                }
+
+                std::thread::sleep(SAMPLE_INTERVAL);
+            })?;
+
+        Ok(())
+    }
+
+    fn update(celsius: f32, high_celsius: f32) {
+        *CELSIUS.lock().unwrap() = Some(celsius);
+
+        let was_high = HIGH.load(Ordering::SeqCst);
+        let high = if was_high {
+            celsius > high_celsius - HYSTERESIS
+        } else {
+            celsius >= high_celsius
+        };
+        if high != was_high {
+            if high {
+                warn!("Chip temperature high at {:.1} °C", celsius);
+            } else {
+                info!("Chip temperature back to {:.1} °C", celsius);
+            }
+            HIGH.store(high, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! ST7789 TFT panel, as on the LilyGO TTGO T-Display.
+//!
+//! Drives the 240x135 panel in landscape as another display panel, with the
+//! same coloured status screens as the HUB75 panel. Frames are drawn into a
+//! buffer of 3-bit colours, two pixels per byte, since a full RGB565 frame
+//! would take 64 KB of RAM, and widened to RGB565 one row at a time while
+//! sent.
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use embedded_graphics::{
+    mono_font::MonoTextStyle,
+    pixelcolor::Rgb565,
+    prelude::*,
+    text::{Alignment, Text},
+};
+use esp_idf_svc::hal::gpio::{AnyOutputPin, Output, PinDriver};
+use esp_idf_svc::hal::spi::{SpiDeviceDriver, SpiDriver};
+
+use crate::config::DisplayLayout;
+use crate::display::{self, Frame, Panel};
+use crate::i18n::{self, FontSize};
+
+const WIDTH: usize = 240;
+const HEIGHT: usize = 135;
+// The 135 visible rows and columns sit inside the controller's 240x320 RAM
+const X_OFFSET: u16 = 40;
+const Y_OFFSET: u16 = 53;
+
+// ST7789 commands
+const SWRESET: u8 = 0x01;
+const SLPOUT: u8 = 0x11;
+const NORON: u8 = 0x13;
+const INVON: u8 = 0x21;
+const DISPON: u8 = 0x29;
+const CASET: u8 = 0x2A;
+const RASET: u8 = 0x2B;
+const RAMWR: u8 = 0x2C;
+const MADCTL: u8 = 0x36;
+const COLMOD: u8 = 0x3A;
+
+// Row and column exchange plus column mirroring turn the panel to landscape
+const MADCTL_LANDSCAPE: u8 = 0x60;
+// 16 bits per pixel
+const COLMOD_RGB565: u8 = 0x55;
+
+const RED: u8 = 0b001;
+const GREEN: u8 = 0b010;
+const BLUE: u8 = 0b100;
+
+type Spi = SpiDeviceDriver<'static, SpiDriver<'static>>;
+
+/// The panel as a display panel.
+pub struct Tft {
+    spi: Spi,
+    dc: PinDriver<'static, AnyOutputPin, Output>,
+    reset: PinDriver<'static, AnyOutputPin, Output>,
+    backlight: PinDriver<'static, AnyOutputPin, Output>,
+    pixels: Box<[u8]>,
+}
+
+impl Tft {
+    pub fn new(
+        spi: Spi,
+        dc: AnyOutputPin,
+        reset: AnyOutputPin,
+        backlight: AnyOutputPin,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            spi,
+            dc: PinDriver::output(dc)?,
+            reset: PinDriver::output(reset)?,
+            backlight: PinDriver::output(backlight)?,
+            pixels: vec![0; WIDTH * HEIGHT / 2].into_boxed_slice(),
+        })
+    }
+
+    fn command(&mut self, command: u8, data: &[u8]) -> anyhow::Result<()> {
+        self.dc.set_low()?;
+        self.spi.write(&[command])?;
+        if !data.is_empty() {
+            self.dc.set_high()?;
+            self.spi.write(data)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        let [x0, x1] = [X_OFFSET, X_OFFSET + WIDTH as u16 - 1].map(u16::to_be_bytes);
+        let [y0, y1] = [Y_OFFSET, Y_OFFSET + HEIGHT as u16 - 1].map(u16::to_be_bytes);
+        self.command(CASET, &[x0[0], x0[1], x1[0], x1[1]])?;
+        self.command(RASET, &[y0[0], y0[1], y1[0], y1[1]])?;
+        self.command(RAMWR, &[])?;
+
+        self.dc.set_high()?;
+        let mut row = [0; WIDTH * 2];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let color = rgb565(self.pixel(x, y));
+                row[x * 2..x * 2 + 2].copy_from_slice(&color.to_be_bytes());
+            }
+            self.spi.write(&row)?;
+        }
+        Ok(())
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        let i = y * WIDTH + x;
+        (self.pixels[i / 2] >> (i % 2 * 4)) & 0x0F
+    }
+}
+
+// Each channel fully on or off
+fn rgb565(color: u8) -> u16 {
+    u16::from(color & RED != 0) * 0xF800
+        | u16::from(color & GREEN != 0) * 0x07E0
+        | u16::from(color & BLUE != 0) * 0x001F
+}
+
+impl Panel for Tft {
+    fn init_panel(&mut self) -> anyhow::Result<()> {
+        self.reset.set_low()?;
+        std::thread::sleep(Duration::from_millis(10));
+        self.reset.set_high()?;
+        std::thread::sleep(Duration::from_millis(120));
+
+        self.command(SWRESET, &[])?;
+        std::thread::sleep(Duration::from_millis(150));
+        self.command(SLPOUT, &[])?;
+        std::thread::sleep(Duration::from_millis(10));
+        self.command(COLMOD, &[COLMOD_RGB565])?;
+        self.command(MADCTL, &[MADCTL_LANDSCAPE])?;
+        // The T-Display's panel has inverted colours
+        self.command(INVON, &[])?;
+        self.command(NORON, &[])?;
+        self.clear(Rgb565::BLACK).unwrap();
+        self.flush()?;
+        self.command(DISPON, &[])?;
+
+        self.backlight.set_high()?;
+        Ok(())
+    }
+
+    fn show(&mut self, layout: DisplayLayout, frame: &Frame) -> anyhow::Result<()> {
+        self.clear(Rgb565::BLACK).unwrap();
+
+        if layout == DisplayLayout::Status && !frame.users.is_empty() {
+            let style = MonoTextStyle::new(i18n::font(FontSize::Small), Rgb565::WHITE);
+            for (line, y) in display::user_lines(&frame.users).iter().zip((14..).step_by(12)) {
+                Text::new(line, Point::new(4, y), style).draw(self).unwrap();
+            }
+            return self.flush();
+        }
+
+        let color = display::headline_color(frame);
+        let headline_y = match layout {
+            DisplayLayout::Detail => 56,
+            DisplayLayout::Status => 74,
+        };
+        Text::with_alignment(
+            display::frame_headline(frame),
+            Point::new(WIDTH as i32 / 2, headline_y),
+            MonoTextStyle::new(i18n::font(FontSize::Large), color),
+            Alignment::Center,
+        )
+        .draw(self)
+        .unwrap();
+        if frame.step.is_none() {
+            let icon_center = Point::new(20, headline_y - 8);
+            display::draw_icon(self, frame.status, icon_center, 20, color);
+        }
+
+        if layout == DisplayLayout::Detail {
+            let style = MonoTextStyle::new(i18n::font(FontSize::Small), Rgb565::WHITE);
+            Text::with_alignment(
+                &frame.detail,
+                Point::new(WIDTH as i32 / 2, 90),
+                style,
+                Alignment::Center,
+            )
+            .draw(self)
+            .unwrap();
+            Text::with_alignment(
+                &frame.ip.to_string(),
+                Point::new(WIDTH as i32 / 2, 124),
+                style,
+                Alignment::Center,
+            )
+            .draw(self)
+            .unwrap();
+        }
+
+        self.flush()
+    }
+
+    fn message(&mut self, text: &str) -> anyhow::Result<()> {
+        self.clear(Rgb565::BLACK).unwrap();
+        Text::new(
+            text,
+            Point::new(4, 14),
+            MonoTextStyle::new(i18n::font(FontSize::Small), Rgb565::WHITE),
+        )
+        .draw(self)
+        .unwrap();
+        self.flush()
+    }
+}
+
+impl OriginDimensions for Tft {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Tft {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) else {
+                continue;
+            };
+            if x >= WIDTH || y >= HEIGHT {
+                continue;
+            }
+            // Each channel is on from half intensity up
+            let color = u8::from(color.r() > 15) * RED
+                | u8::from(color.g() > 31) * GREEN
+                | u8::from(color.b() > 15) * BLUE;
+            let i = y * WIDTH + x;
+            let shift = i % 2 * 4;
+            self.pixels[i / 2] = (self.pixels[i / 2] & !(0x0F << shift)) | (color << shift);
+        }
+        Ok(())
+    }
+}
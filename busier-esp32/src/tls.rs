@@ -0,0 +1,187 @@
+//! HTTPS listener with optional client certificates.
+//!
+//! The server certificate, its private key and the CA that signs client
+//! certificates are PEM files uploaded through `POST /api/tls/<file>`. The
+//! certificates are kept in their own NVS namespace, the private key with
+//! [`crate::secrets`]. Without an uploaded certificate, the
+//! first start generates a self-signed one and the displays show its
+//! fingerprint for a few minutes. With `https.enabled` the same routes are
+//! served over HTTPS as well; with `https.client_certs` and a stored CA the
+//! listener only completes handshakes with clients presenting a certificate
+//! signed by that CA, and the admin routes are refused over plain HTTP.
+//!
+//! esp-idf-svc does not expose the client CA of the HTTPS server, so
+//! build.rs wraps `httpd_ssl_start` at link time and the wrapper puts the CA
+//! into the `httpd_ssl_config_t` the server starts with. The server checks
+//! client certificates from its first handshake on.
+
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use esp_idf_svc::http::server::{Configuration as HttpConfiguration, EspHttpServer};
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_svc::sys;
+use esp_idf_svc::tls::X509;
+use log::{info, warn};
+
+use crate::cert;
+use crate::config;
+use crate::http_util;
+use crate::secrets;
+
+const NAMESPACE: &str = "tls";
+/// Files that can be uploaded, by name.
+pub const FILES: [&str; 3] = ["cert", "key", "ca"];
+// The private key's file name, and its key in the secrets partition
+const KEY_FILE: &str = "key";
+const KEY_SECRET: &str = "tls_key";
+pub const MAX_PEM_LEN: usize = 4096;
+// The plain HTTP server uses the default control port
+const CTRL_PORT: u16 = 32769;
+
+static NVS: Mutex<Option<EspNvs<NvsDefault>>> = Mutex::new(None);
+static CLIENT_CERTS: AtomicBool = AtomicBool::new(false);
+static GENERATED: Mutex<Option<[u8; 32]>> = Mutex::new(None);
+// Client CA for the HTTPS server being started, NUL-terminated
+static CLIENT_CA: Mutex<Option<&'static [u8]>> = Mutex::new(None);
+
+extern "C" {
+    fn __real_httpd_ssl_start(
+        handle: *mut sys::httpd_handle_t,
+        config: *mut sys::httpd_ssl_config_t,
+    ) -> sys::esp_err_t;
+}
+
+/// Called instead of `httpd_ssl_start`, see build.rs. Adds the client CA,
+/// if one is set, to the configuration before the server starts.
+#[no_mangle]
+unsafe extern "C" fn __wrap_httpd_ssl_start(
+    handle: *mut sys::httpd_handle_t,
+    config: *mut sys::httpd_ssl_config_t,
+) -> sys::esp_err_t {
+    if let (Some(ca), Some(config)) = (*CLIENT_CA.lock().unwrap(), config.as_mut()) {
+        config.cacert_pem = ca.as_ptr();
+        config.cacert_len = ca.len() as _;
+    }
+    __real_httpd_ssl_start(handle, config)
+}
+
+/// Opens the certificate store. Call after [`crate::secrets::init`]; a
+/// private key that an older firmware kept next to the certificates is
+/// moved to the secrets partition.
+pub fn init(partition: EspDefaultNvsPartition) -> anyhow::Result<()> {
+    let mut nvs = EspNvs::new(partition, NAMESPACE, true)?;
+    secrets::migrate(&mut nvs, KEY_FILE, KEY_SECRET, MAX_PEM_LEN)?;
+    *NVS.lock().unwrap() = Some(nvs);
+    Ok(())
+}
+
+/// Stores one of the PEM files; used on the next start.
+pub fn store(file: &str, pem: &[u8]) -> anyhow::Result<()> {
+    if !FILES.contains(&file) {
+        anyhow::bail!("unknown file {:?}", file);
+    }
+    if !pem.starts_with(b"-----BEGIN ") {
+        anyhow::bail!("not a PEM file");
+    }
+    if file == KEY_FILE {
+        return secrets::set(KEY_SECRET, pem);
+    }
+
+    let mut nvs = NVS.lock().unwrap();
+    let nvs = nvs
+        .as_mut()
+        .ok_or_else(|| anyhow::anyhow!("TLS storage not initialized"))?;
+    nvs.set_raw(file, pem)?;
+    Ok(())
+}
+
+/// Whether the HTTPS listener is up and checking client certificates.
+pub fn requires_client_certs() -> bool {
+    CLIENT_CERTS.load(Ordering::Relaxed)
+}
+
+/// SHA-256 fingerprint of the certificate generated on this boot, if any.
+pub fn generated_fingerprint() -> Option<[u8; 32]> {
+    *GENERATED.lock().unwrap()
+}
+
+/// Starts the HTTPS listener if enabled, generating a self-signed
+/// certificate for `ip` if none is stored. Register the routes on the
+/// returned server.
+pub fn start(ip: Ipv4Addr) -> anyhow::Result<Option<EspHttpServer<'static>>> {
+    let config = config::get();
+    let https = config.https.clone();
+    if !https.enabled {
+        return Ok(None);
+    }
+
+    if load("cert")?.is_none() || load(KEY_FILE)?.is_none() {
+        info!("No HTTPS certificate stored, generating a self-signed one");
+        let generated = cert::self_signed(config.device_name(), ip)?;
+        store(KEY_FILE, generated.key_pem.as_bytes())?;
+        store("cert", generated.cert_pem.as_bytes())?;
+        info!(
+            "HTTPS certificate SHA-256 fingerprint {}",
+            cert::format_fingerprint(&generated.fingerprint)
+        );
+        *GENERATED.lock().unwrap() = Some(generated.fingerprint);
+    }
+
+    let (Some(cert), Some(key)) = (load("cert")?, load(KEY_FILE)?) else {
+        anyhow::bail!("HTTPS certificate missing after storing it");
+    };
+    let ca = match (https.client_certs, load("ca")?) {
+        (true, Some(ca)) => Some(ca),
+        // Rather no HTTPS than HTTPS letting in any client
+        (true, None) => {
+            warn!("Client certificates required but no CA stored, HTTPS stays off");
+            return Ok(None);
+        }
+        (false, _) => None,
+    };
+
+    let server_config = HttpConfiguration {
+        https_port: https.port,
+        ctrl_port: CTRL_PORT,
+        server_certificate: Some(X509::pem_until_nul(cert)),
+        private_key: Some(X509::pem_until_nul(key)),
+        ..http_util::server_configuration()
+    };
+    // The CA is leaked, so it outlives the server
+    *CLIENT_CA.lock().unwrap() = ca;
+    let server = EspHttpServer::new(&server_config);
+    *CLIENT_CA.lock().unwrap() = None;
+    let server = server?;
+
+    if ca.is_some() {
+        CLIENT_CERTS.store(true, Ordering::Relaxed);
+        info!("HTTPS on port {} with client certificates", https.port);
+    } else {
+        info!("HTTPS on port {}", https.port);
+    }
+
+    Ok(Some(server))
+}
+
+// Reads a PEM file, NUL-terminated as mbedTLS expects. It is kept for as
+// long as the server runs.
+fn load(file: &str) -> anyhow::Result<Option<&'static [u8]>> {
+    let pem = if file == KEY_FILE {
+        secrets::get(KEY_SECRET, MAX_PEM_LEN)?
+    } else {
+        let nvs = NVS.lock().unwrap();
+        let nvs = nvs
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("TLS storage not initialized"))?;
+        let mut buf = vec![0; MAX_PEM_LEN];
+        nvs.get_raw(file, &mut buf)?.map(<[u8]>::to_vec)
+    };
+
+    let Some(mut pem) = pem else {
+        return Ok(None);
+    };
+    pem.push(0);
+    Ok(Some(Vec::leak(pem)))
+}
@@ -0,0 +1,56 @@
+//! Per-person statuses.
+//!
+//! A device on a shared office door can track one status per person named
+//! in `users`, next to the device's own status. People start out Free and
+//! their statuses are kept only until restart. Changes are announced like
+//! the device's own, with the person's name in the message.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::config;
+use crate::notify::{self, Event};
+use crate::status::Status;
+
+static STATUSES: Mutex<BTreeMap<String, Status>> = Mutex::new(BTreeMap::new());
+
+/// The configured people and their statuses, in configuration order.
+pub fn list() -> Vec<(String, Status)> {
+    let statuses = STATUSES.lock().unwrap();
+    config::get()
+        .users
+        .into_iter()
+        .map(|name| {
+            let status = statuses.get(&name).copied().unwrap_or(Status::Free);
+            (name, status)
+        })
+        .collect()
+}
+
+/// A person's status; None for names not in `users`.
+pub fn get(name: &str) -> Option<Status> {
+    list()
+        .into_iter()
+        .find(|(user, _)| user == name)
+        .map(|(_, status)| status)
+}
+
+/// Sets a person's status and notifies integrations if it changed. Returns
+/// None for names not in `users`, otherwise whether the status changed.
+pub fn set(name: &str, status: Status) -> Option<bool> {
+    get(name)?;
+
+    let previous = STATUSES
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), status)
+        .unwrap_or(Status::Free);
+    let changed = previous != status;
+    if changed {
+        notify::send(Event::StatusChanged {
+            status,
+            user: Some(name.to_string()),
+        });
+    }
+    Some(changed)
+}
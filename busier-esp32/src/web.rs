@@ -0,0 +1,120 @@
+//! Web pages and their assets.
+//!
+//! The pages, scripts, style sheet and icons live in `web/` and are embedded
+//! at build time. Pages, scripts and the SVG icon are templates: besides the
+//! translated texts, their `{{key}}` placeholders take the values below,
+//! filled in when served, so the first paint shows the device's name,
+//! status and firmware version, and hides the panels of drivers the build
+//! does not include, without a request back to the device. The SVG icon is
+//! drawn in the color of the current status, so a browser tab shows it at a
+//! glance.
+//!
+//! The pages link their assets with the firmware version and the language
+//! in the query, so browsers may cache them for good: an update or a change
+//! of language loads them afresh.
+
+use embedded_svc::http::Method;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::server::EspHttpServer;
+
+use crate::board;
+use crate::config;
+use crate::http_util::Routes;
+use crate::i18n;
+use crate::output;
+use crate::status::{self, StatusLabel};
+
+pub static INDEX_HTML: &str = include_str!("../web/index.html");
+pub static ADMIN_HTML: &str = include_str!("../web/admin.html");
+pub static API_INDEX_HTML: &str = include_str!("../web/api.html");
+pub static ERROR_HTML: &str = include_str!("../web/error.html");
+pub static STATUS_HTML: &str = include_str!("../web/status.html");
+static INDEX_JS: &str = include_str!("../web/index.js");
+static ADMIN_JS: &str = include_str!("../web/admin.js");
+static STYLE_CSS: &[u8] = include_bytes!("../web/style.css");
+static FAVICON_SVG: &str = include_str!("../web/favicon.svg");
+static FAVICON_ICO: &[u8] = include_bytes!("../web/favicon.ico");
+static TOUCH_ICON_PNG: &[u8] = include_bytes!("../web/apple-touch-icon.png");
+
+pub const HTML: (&str, &str) = ("Content-Type", "text/html; charset=utf-8");
+const JAVASCRIPT: (&str, &str) = ("Content-Type", "text/javascript; charset=utf-8");
+const CSS: (&str, &str) = ("Content-Type", "text/css; charset=utf-8");
+const CACHE_FOREVER: (&str, &str) = ("Cache-Control", "public, max-age=31536000, immutable");
+const CACHE_DAY: (&str, &str) = ("Cache-Control", "public, max-age=86400");
+const NO_CACHE: (&str, &str) = ("Cache-Control", "no-cache");
+
+/// Registers the routes of the scripts, the style sheet and the icons.
+pub fn register(server: &mut EspHttpServer<'static>) -> anyhow::Result<()> {
+    server.route("/static/style.css", Method::Get, |req| {
+        req.into_response(200, None, &[CSS, CACHE_FOREVER])?
+            .write_all(STYLE_CSS)?;
+        Ok(())
+    })?;
+
+    for (path, script) in [
+        ("/static/index.js", INDEX_JS),
+        ("/static/admin.js", ADMIN_JS),
+    ] {
+        server.route(path, Method::Get, move |req| {
+            req.into_response(200, None, &[JAVASCRIPT, CACHE_FOREVER])?
+                .write_all(render(script).as_bytes())?;
+            Ok(())
+        })?;
+    }
+
+    // The plain icons, which browsers ask for whether the pages link them
+    // or not
+    for (path, content_type, icon) in [
+        ("/favicon.ico", "image/x-icon", FAVICON_ICO),
+        ("/apple-touch-icon.png", "image/png", TOUCH_ICON_PNG),
+    ] {
+        server.route(path, Method::Get, move |req| {
+            req.into_response(200, None, &[("Content-Type", content_type), CACHE_DAY])?
+                .write_all(icon)?;
+            Ok(())
+        })?;
+    }
+
+    // The icon in the color of the current status
+    server.route("/favicon.svg", Method::Get, |req| {
+        req.into_response(200, None, &[("Content-Type", "image/svg+xml"), NO_CACHE])?
+            .write_all(render(FAVICON_SVG).as_bytes())?;
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+/// Fills in a page's or script's texts and values.
+pub fn render(page: &str) -> String {
+    i18n::localize_with(page, &values())
+}
+
+/// Like [`render`], with more values for this page.
+pub fn render_with(page: &str, extra: Vec<(&'static str, String)>) -> String {
+    let mut values = values();
+    values.extend(extra);
+    i18n::localize_with(page, &values)
+}
+
+fn values() -> Vec<(&'static str, String)> {
+    let config = config::get();
+    let status = status::current();
+    let back_at_text = status::back_at()
+        .map(|back_at| format!("{} {}", i18n::text("web.back_at"), back_at.time))
+        .unwrap_or_default();
+
+    vec![
+        ("device_name", config.device_name().to_string()),
+        ("status", status.as_str().to_string()),
+        ("status_label", status.label().to_string()),
+        ("status_color", output::style(status).color),
+        ("status_version", status::version().to_string()),
+        ("back_at_text", back_at_text),
+        ("version", env!("CARGO_PKG_VERSION").to_string()),
+        // Space-separated, for the `~=` attribute selector
+        ("drivers", board::drivers().join(" ")),
+        ("knock_ringtone", config.buzzer.knock),
+        ("status_ringtone", config.buzzer.status_changed),
+    ]
+}
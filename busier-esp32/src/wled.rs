@@ -0,0 +1,39 @@
+//! WLED controller mirroring the status.
+//!
+//! Pushes the status to a WLED device's JSON API, either by loading a preset
+//! configured for the status or as the solid color of the status in
+//! `styles`, turned off for a status without `light`: red while busy, green
+//! while free and off while away by default.
+
+use embedded_svc::http::Method;
+use serde_json::json;
+
+use crate::config::{StatusStyle, WledConfig};
+use crate::http_client;
+use crate::mirror;
+use crate::output;
+use crate::status::Status;
+
+/// Spawns the thread that keeps the controller in sync with the status.
+pub fn start() -> anyhow::Result<()> {
+    mirror::start(
+        "wled",
+        |config| {
+            let wled = config.wled;
+            (wled.enabled && !wled.host.is_empty()).then_some(wled)
+        },
+        apply,
+    )
+}
+
+fn apply(wled: &WledConfig, status: Status, style: &StatusStyle) -> anyhow::Result<()> {
+    let url = format!("http://{}/json/state", wled.host);
+
+    let body = match wled.presets.get(status.as_str()) {
+        Some(preset) => json!({ "ps": preset }),
+        None if style.light => json!({ "on": true, "seg": [{ "col": [output::color(style)] }] }),
+        None => json!({ "on": false }),
+    };
+
+    http_client::send_json(Method::Post, &url, &[], &body)
+}
@@ -0,0 +1,149 @@
+//! On-target tests for the I2C bus and the display path.
+//!
+//! Needs a board with an SSD1306 at 0x3C on the chip's default I2C pins,
+//! as listed in the README. `cargo test --test display` flashes the binary through the
+//! configured runner and prints one line per case, then a summary; the
+//! cases run in order, as later ones need the panel set up by earlier ones.
+
+use busier_core::pins::Chip;
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::Text,
+};
+use embedded_hal::i2c::I2c;
+use esp_idf_svc::hal::gpio::AnyIOPin;
+use esp_idf_svc::hal::i2c::{I2cConfig, I2cDriver};
+use esp_idf_svc::hal::prelude::*;
+use esp_idf_svc::log::EspLogger;
+use log::{error, info};
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, I2CInterface, Ssd1306};
+
+const DISPLAY_ADDRESS: u8 = 0x3C;
+
+// The chip cfgs set by esp-idf-sys, as in src/board.rs
+#[cfg(esp32)]
+const CHIP: Chip = Chip::Esp32;
+#[cfg(esp32c3)]
+const CHIP: Chip = Chip::Esp32C3;
+#[cfg(esp32s3)]
+const CHIP: Chip = Chip::Esp32S3;
+
+type Oled<'d> = Ssd1306<
+    I2CInterface<I2cDriver<'d>>,
+    DisplaySize128x32,
+    BufferedGraphicsMode<DisplaySize128x32>,
+>;
+
+type Case = fn(&mut Fixture) -> anyhow::Result<()>;
+
+struct Fixture {
+    i2c: Option<I2cDriver<'static>>,
+    oled: Option<Oled<'static>>,
+}
+
+const CASES: &[(&str, Case)] = &[
+    ("bus_finds_display", bus_finds_display),
+    ("panel_initializes", panel_initializes),
+    ("text_flushes", text_flushes),
+    ("fill_flushes", fill_flushes),
+    ("panel_clears", panel_clears),
+];
+
+fn main() {
+    esp_idf_svc::sys::link_patches();
+    EspLogger::initialize_default();
+
+    let peripherals = Peripherals::take().expect("peripherals");
+    let pins = CHIP.default_pins();
+    let i2c = I2cDriver::new(
+        peripherals.i2c0,
+        // SAFETY: the default pins exist on the chip and nothing else takes
+        // them
+        unsafe { AnyIOPin::new(pins.sda.into()) },
+        unsafe { AnyIOPin::new(pins.scl.into()) },
+        &I2cConfig::new().baudrate(400.kHz().into()),
+    )
+    .expect("I2C driver");
+
+    let mut fixture = Fixture {
+        i2c: Some(i2c),
+        oled: None,
+    };
+
+    let mut failed = 0;
+    for (name, case) in CASES {
+        match case(&mut fixture) {
+            Ok(()) => info!("test {} ... ok", name),
+            Err(e) => {
+                error!("test {} ... FAILED: {:?}", name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    info!(
+        "test result: {}. {} passed; {} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        CASES.len() - failed,
+        failed
+    );
+}
+
+// A zero-length write is acknowledged only by a device at the address
+fn bus_finds_display(fixture: &mut Fixture) -> anyhow::Result<()> {
+    let i2c = fixture.i2c.as_mut().ok_or_else(|| anyhow::anyhow!("no bus"))?;
+    i2c.write(DISPLAY_ADDRESS, &[])
+        .map_err(|e| anyhow::anyhow!("no ACK at {:#04x}: {:?}", DISPLAY_ADDRESS, e))
+}
+
+fn panel_initializes(fixture: &mut Fixture) -> anyhow::Result<()> {
+    let i2c = fixture.i2c.take().ok_or_else(|| anyhow::anyhow!("no bus"))?;
+    let interface = I2CInterface::new(i2c, DISPLAY_ADDRESS, 0x40);
+    let mut oled = Ssd1306::new(interface, DisplaySize128x32, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+    oled.init().map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    fixture.oled = Some(oled);
+    Ok(())
+}
+
+fn text_flushes(fixture: &mut Fixture) -> anyhow::Result<()> {
+    let oled = panel(fixture)?;
+    oled.clear(BinaryColor::Off).unwrap();
+    Text::new(
+        "busier self-test",
+        Point::new(0, 10),
+        MonoTextStyle::new(&FONT_6X10, BinaryColor::On),
+    )
+    .draw(oled)
+    .unwrap();
+    oled.flush().map_err(|e| anyhow::anyhow!("{:?}", e))
+}
+
+fn fill_flushes(fixture: &mut Fixture) -> anyhow::Result<()> {
+    let oled = panel(fixture)?;
+    let size = oled.bounding_box().size;
+    if size != Size::new(128, 32) {
+        anyhow::bail!("panel reports {:?}", size);
+    }
+    Rectangle::new(Point::zero(), size)
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(oled)
+        .unwrap();
+    oled.flush().map_err(|e| anyhow::anyhow!("{:?}", e))
+}
+
+fn panel_clears(fixture: &mut Fixture) -> anyhow::Result<()> {
+    let oled = panel(fixture)?;
+    oled.clear(BinaryColor::Off).unwrap();
+    oled.flush().map_err(|e| anyhow::anyhow!("{:?}", e))
+}
+
+fn panel(fixture: &mut Fixture) -> anyhow::Result<&mut Oled<'static>> {
+    fixture
+        .oled
+        .as_mut()
+        .ok_or_else(|| anyhow::anyhow!("panel not initialized"))
+}
@@ -0,0 +1,241 @@
+const STATUS_LABELS = { free: '{{status.free}}', dnd: '{{status.dnd}}', away: '{{status.away}}' };
+
+const DRIVERS = document.body.dataset.drivers.split(' ');
+
+// The status and ringtones are filled in when served; start the
+// timers and poll only the drivers this build includes
+window.onload = function() {
+    fetchUsers();
+    fetchPomodoro();
+    setInterval(fetchPomodoro, 1000);
+    if (DRIVERS.includes('rfid')) {
+        setInterval(fetchBadge, 2000);
+    }
+    setInterval(fetchUsers, 5000);
+    fetchTokens();
+    setInterval(fetchTokens, 5000);
+    if (DRIVERS.includes('ir')) {
+        setInterval(fetchIr, 2000);
+    }
+};
+
+// Version of the status last shown, so a change does not overwrite one
+// made from another browser in the meantime
+let statusEtag = '"' + document.getElementById('current-status').dataset.version + '"';
+
+// Fetch the current status from the server
+function fetchCurrentStatus() {
+    fetch('/status')
+        .then(response => {
+            statusEtag = response.headers.get('ETag') || statusEtag;
+            return response.text();
+        })
+        .then(status => {
+            document.getElementById('current-status').textContent =
+                STATUS_LABELS[status] || status;
+            // A new URL makes the browser load the icon in the status color
+            document.querySelector('link[rel=icon]').href = '/favicon.svg?' + status;
+        })
+        .catch(error => {
+            console.error('Error fetching status:', error);
+        });
+}
+
+// Set a new status
+function setStatus(status) {
+    fetch('/status', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+            'If-Match': statusEtag,
+        },
+        body: JSON.stringify({ status: status }),
+    })
+    .then(response => response.text())
+    // On a conflict this shows the status someone else set
+    .then(result => {
+        fetchCurrentStatus();
+    })
+    .catch(error => {
+        console.error('Error setting status:', error);
+    });
+}
+
+// List the people sharing the device with buttons for each
+function fetchUsers() {
+    fetch('/api/users')
+        .then(response => response.json())
+        .then(users => {
+            const list = document.getElementById('users');
+            list.innerHTML = '';
+            users.forEach(user => {
+                const row = document.createElement('div');
+                const name = document.createElement('p');
+                name.textContent = user.name + ': ' + (STATUS_LABELS[user.status] || user.status);
+                row.appendChild(name);
+                [['dnd', 'dnd-button'], ['free', 'free-button']].forEach(([status, style]) => {
+                    const button = document.createElement('button');
+                    button.className = style;
+                    button.textContent = STATUS_LABELS[status];
+                    button.onclick = () => setUserStatus(user.name, status);
+                    row.appendChild(button);
+                });
+                list.appendChild(row);
+            });
+            document.getElementById('users-panel').style.display = users.length ? '' : 'none';
+        })
+        .catch(error => {
+            console.error('Error fetching users:', error);
+        });
+}
+
+// Set one person's status
+function setUserStatus(user, status) {
+    fetch('/status', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ status: status, user: user }),
+    })
+    .then(fetchUsers)
+    .catch(error => {
+        console.error('Error setting status:', error);
+    });
+}
+
+// List the API tokens with their usage since startup
+function fetchTokens() {
+    fetch('/api/tokens')
+        .then(response => response.json())
+        .then(tokens => {
+            const list = document.getElementById('tokens');
+            list.innerHTML = '';
+            tokens.forEach(token => {
+                const row = document.createElement('p');
+                row.textContent = token.name + ': ' + '{{web.token_usage}}'
+                    .replace('{requests}', token.requests)
+                    .replace('{throttled}', token.throttled)
+                    .replace('{limit}', token.rate_per_min || '{{web.no_limit}}');
+                list.appendChild(row);
+            });
+            document.getElementById('tokens-panel').style.display = tokens.length ? '' : 'none';
+        })
+        .catch(error => {
+            console.error('Error fetching tokens:', error);
+        });
+}
+
+// Show the pomodoro countdown
+function fetchPomodoro() {
+    fetch('/api/pomodoro')
+        .then(response => response.json())
+        .then(state => {
+            let text = '{{web.stopped}}';
+            if (state.running) {
+                const mins = Math.floor(state.remaining_secs / 60);
+                const secs = String(state.remaining_secs % 60).padStart(2, '0');
+                const label = state.phase === 'work' ? '{{pomodoro.focus}}' : '{{pomodoro.break}}';
+                text = label + ' ' + mins + ':' + secs + ' ({{web.cycle}} ' + state.cycle + ')';
+            }
+            document.getElementById('pomodoro-state').textContent = text;
+        })
+        .catch(error => {
+            console.error('Error fetching pomodoro:', error);
+        });
+}
+
+// Start or stop the pomodoro timer
+function setPomodoro(action) {
+    fetch('/api/pomodoro', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ action: action }),
+    })
+    .then(() => {
+        fetchPomodoro();
+        fetchCurrentStatus();
+    })
+    .catch(error => {
+        console.error('Error setting pomodoro:', error);
+    });
+}
+
+// Show the UID of the last tapped badge
+function fetchBadge() {
+    fetch('/api/rfid')
+        .then(response => response.json())
+        .then(state => {
+            document.getElementById('last-badge').textContent = state.last_uid || '{{web.none}}';
+        })
+        .catch(error => {
+            console.error('Error fetching badge:', error);
+        });
+}
+
+// Map the last tapped badge to an action
+function addBadge(action) {
+    const uid = document.getElementById('last-badge').textContent;
+    if (uid === '{{web.none}}') {
+        return;
+    }
+    fetch('/api/config')
+        .then(response => response.json())
+        .then(config => {
+            const cards = config.rfid.cards.filter(card => card.uid !== uid);
+            cards.push({ uid: uid, action: action });
+            return fetch('/api/config', {
+                method: 'POST',
+                headers: {
+                    'Content-Type': 'application/json',
+                },
+                body: JSON.stringify({ rfid: { cards: cards } }),
+            });
+        })
+        .catch(error => {
+            console.error('Error saving badge:', error);
+        });
+}
+
+// Show whether a remote button is being learned
+function fetchIr() {
+    fetch('/api/ir')
+        .then(response => response.json())
+        .then(state => {
+            document.getElementById('ir-state').textContent =
+                state.learning ? '{{web.press_button}}' : (state.last_code || '{{web.none}}');
+        })
+        .catch(error => {
+            console.error('Error fetching IR state:', error);
+        });
+}
+
+// Use the next remote button pressed to toggle Do Not Disturb
+function learnIr() {
+    fetch('/api/ir/learn', { method: 'POST' })
+        .then(fetchIr)
+        .catch(error => {
+            console.error('Error starting IR learn mode:', error);
+        });
+}
+
+// Save the ringtones pasted into the fields
+function saveRingtones() {
+    fetch('/api/config', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({
+            buzzer: {
+                knock: document.getElementById('knock-ringtone').value,
+                status_changed: document.getElementById('status-ringtone').value,
+            },
+        }),
+    })
+    .catch(error => {
+        console.error('Error saving ringtones:', error);
+    });
+}
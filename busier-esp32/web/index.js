@@ -0,0 +1,59 @@
+const STATUS_LABELS = { free: '{{status.free}}', dnd: '{{status.dnd}}', away: '{{status.away}}' };
+
+// The status is filled in when served; refresh it from then on
+window.onload = function() {
+    setInterval(fetchStatus, 5000);
+};
+
+// Show the status and when they are back
+function fetchStatus() {
+    fetch('/api/status')
+        .then(response => response.json())
+        .then(state => {
+            document.getElementById('current-status').textContent =
+                STATUS_LABELS[state.status] || state.status;
+            document.getElementById('back-at').textContent =
+                state.back_at ? '{{web.back_at}} ' + state.back_at : '';
+            showFavicon(state.status);
+        })
+        .catch(error => {
+            console.error('Error fetching status:', error);
+        });
+}
+
+// The icon is drawn in the status color; a new URL makes the browser load it
+function showFavicon(status) {
+    document.querySelector('link[rel=icon]').href = '/favicon.svg?' + status;
+}
+
+function knock() {
+    fetch('/knock', { method: 'POST' })
+        .then(response => response.text())
+        .then(showResult)
+        .catch(error => {
+            console.error('Error knocking:', error);
+        });
+}
+
+function leaveMessage() {
+    const text = document.getElementById('message').value;
+    fetch('/message', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ text: text }),
+    })
+    .then(response => response.text())
+    .then(result => {
+        document.getElementById('message').value = '';
+        showResult(result);
+    })
+    .catch(error => {
+        console.error('Error leaving message:', error);
+    });
+}
+
+function showResult(text) {
+    document.getElementById('result').textContent = text;
+}